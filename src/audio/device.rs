@@ -0,0 +1,199 @@
+//! オーディオ出力デバイスの列挙と選択
+//!
+//! cpalのホスト（ALSA、JACKなど）と出力デバイスを列挙し、
+//! ID（0始まり）または名前で指定されたデバイスを開く。
+//!
+//! # ビジネスルール
+//! - ホストを指定しない場合は既定のホストを使う
+//! - デバイスIDはホストごとの出力デバイスの列挙順（`audio list`の表示と同じ）
+//! - 名前で指定した場合は完全一致を優先し、なければ部分一致する最初のデバイスを選ぶ
+
+use cpal::traits::{DeviceTrait, HostTrait};
+
+use super::error::AudioError;
+
+/// オーディオ出力デバイスの情報
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioDeviceInfo {
+    /// デバイスID（0始まり）
+    pub id: usize,
+    /// デバイス名
+    pub name: String,
+    /// ホストの既定の出力デバイスかどうか
+    pub is_default: bool,
+}
+
+/// 利用可能なオーディオホストの名前を返す
+#[must_use]
+pub fn list_audio_hosts() -> Vec<&'static str> {
+    cpal::available_hosts()
+        .iter()
+        .map(cpal::HostId::name)
+        .collect()
+}
+
+/// 既定のオーディオホストの名前を返す
+#[must_use]
+pub fn default_host_name() -> &'static str {
+    cpal::default_host().id().name()
+}
+
+/// ホスト名を`hosts`の中のインデックスに解決する（大文字小文字は区別しない）
+///
+/// # Errors
+/// 一致するホストがない場合は`AudioError::UnknownHost`
+pub fn resolve_host(name: &str, hosts: &[&str]) -> Result<usize, AudioError> {
+    hosts
+        .iter()
+        .position(|host| host.eq_ignore_ascii_case(name))
+        .ok_or_else(|| AudioError::UnknownHost {
+            name: name.to_string(),
+            available: hosts.join(", "),
+        })
+}
+
+/// デバイスの指定（IDまたは名前）を`devices`の中のインデックスに解決する
+///
+/// # Errors
+/// - 数値のIDが範囲外の場合は`AudioError::InvalidDeviceId`
+/// - 名前に一致するデバイスがない場合は`AudioError::DeviceNameNotFound`
+pub fn resolve_device_id(name_or_id: &str, devices: &[String]) -> Result<usize, AudioError> {
+    if let Ok(id) = name_or_id.parse::<usize>() {
+        if id >= devices.len() {
+            return Err(AudioError::InvalidDeviceId { id });
+        }
+        return Ok(id);
+    }
+
+    devices
+        .iter()
+        .position(|device| device == name_or_id)
+        .or_else(|| {
+            devices
+                .iter()
+                .position(|device| device.contains(name_or_id))
+        })
+        .ok_or_else(|| AudioError::DeviceNameNotFound {
+            name: name_or_id.to_string(),
+        })
+}
+
+/// 出力デバイスを列挙する
+///
+/// # Errors
+/// ホストが見つからない、またはデバイスを列挙できない場合
+pub fn list_audio_devices(host: Option<&str>) -> Result<Vec<AudioDeviceInfo>, AudioError> {
+    let host = open_host(host)?;
+    let default_name = host
+        .default_output_device()
+        .and_then(|device| device.name().ok());
+    Ok(output_devices(&host)?
+        .into_iter()
+        .enumerate()
+        .map(|(id, (_, name))| AudioDeviceInfo {
+            id,
+            is_default: default_name.as_deref() == Some(name.as_str()),
+            name,
+        })
+        .collect())
+}
+
+/// 出力デバイスを開く
+///
+/// `device`を指定しない場合はホストの既定の出力デバイスを返す。
+///
+/// # Errors
+/// ホストまたはデバイスが見つからない場合
+pub fn open_output_device(
+    host: Option<&str>,
+    device: Option<&str>,
+) -> Result<cpal::Device, AudioError> {
+    let host = open_host(host)?;
+    let Some(device) = device else {
+        return host
+            .default_output_device()
+            .ok_or(AudioError::DeviceNotFound);
+    };
+
+    let mut devices = output_devices(&host)?;
+    let names: Vec<String> = devices.iter().map(|(_, name)| name.clone()).collect();
+    let id = resolve_device_id(device, &names)?;
+    Ok(devices.swap_remove(id).0)
+}
+
+fn open_host(name: Option<&str>) -> Result<cpal::Host, AudioError> {
+    let Some(name) = name else {
+        return Ok(cpal::default_host());
+    };
+    let hosts = cpal::available_hosts();
+    let names: Vec<&str> = hosts.iter().map(cpal::HostId::name).collect();
+    let index = resolve_host(name, &names)?;
+    cpal::host_from_id(hosts[index]).map_err(|_| AudioError::UnknownHost {
+        name: name.to_string(),
+        available: names.join(", "),
+    })
+}
+
+fn output_devices(host: &cpal::Host) -> Result<Vec<(cpal::Device, String)>, AudioError> {
+    let devices = host
+        .output_devices()
+        .map_err(|e| AudioError::StreamCreationError(e.to_string()))?;
+    Ok(devices
+        .filter_map(|device| {
+            let name = device.name().ok()?;
+            Some((device, name))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn devices() -> Vec<String> {
+        ["default", "pulse", "hw:CARD=PCH,DEV=0", "hw:CARD=USB,DEV=0"]
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn resolve_device_by_id() {
+        assert_eq!(resolve_device_id("0", &devices()).unwrap(), 0);
+        assert_eq!(resolve_device_id("3", &devices()).unwrap(), 3);
+        assert!(matches!(
+            resolve_device_id("4", &devices()),
+            Err(AudioError::InvalidDeviceId { id: 4 })
+        ));
+    }
+
+    #[test]
+    fn resolve_device_by_name() {
+        assert_eq!(resolve_device_id("pulse", &devices()).unwrap(), 1);
+        assert_eq!(resolve_device_id("USB", &devices()).unwrap(), 3);
+        // 部分一致より完全一致を優先する
+        let devices = vec!["default:USB".to_string(), "USB".to_string()];
+        assert_eq!(resolve_device_id("USB", &devices).unwrap(), 1);
+    }
+
+    #[test]
+    fn resolve_device_not_found() {
+        let err = resolve_device_id("jack", &devices()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[AUD-E007] オーディオデバイス 'jack' が見つかりません"
+        );
+        assert!(resolve_device_id("0", &[]).is_err());
+    }
+
+    #[test]
+    fn resolve_host_ignores_case() {
+        let hosts = ["ALSA", "JACK"];
+        assert_eq!(resolve_host("jack", &hosts).unwrap(), 1);
+        let err = resolve_host("CoreAudio", &hosts).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[AUD-E008] オーディオホスト 'CoreAudio' は利用できません（利用可能: ALSA, JACK）"
+        );
+    }
+}
@@ -12,4 +12,10 @@ pub enum AudioError {
     WavWriteError(String),
     #[error("[AUD-E005] 無効なパラメータ '{parameter}': {reason}")]
     InvalidParameter { parameter: String, reason: String },
+    #[error("[AUD-E006] 無効なオーディオデバイスIDです: {id}")]
+    InvalidDeviceId { id: usize },
+    #[error("[AUD-E007] オーディオデバイス '{name}' が見つかりません")]
+    DeviceNameNotFound { name: String },
+    #[error("[AUD-E008] オーディオホスト '{name}' は利用できません（利用可能: {available}）")]
+    UnknownHost { name: String, available: String },
 }
@@ -1,7 +1,22 @@
 use crate::audio::AudioError;
 use hound::WavSpec;
+use std::io::Write;
 use std::path::Path;
 
+/// Sample rate of exported audio.
+const SAMPLE_RATE: u32 = 44100;
+
+/// Output format for [`write_pcm`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcmFormat {
+    /// 16-bit mono WAV (header followed by s16le data)
+    Wav,
+    /// Raw 32-bit float little-endian mono samples
+    F32le,
+    /// Raw 16-bit signed little-endian mono samples
+    S16le,
+}
+
 /// Export PCM samples to a WAV file.
 ///
 /// # Arguments
@@ -18,7 +33,7 @@ use std::path::Path;
 pub fn export_wav<P: AsRef<Path>>(samples: &[f32], path: P) -> Result<(), AudioError> {
     let spec = WavSpec {
         channels: 1,
-        sample_rate: 44100,
+        sample_rate: SAMPLE_RATE,
         bits_per_sample: 16,
         sample_format: hound::SampleFormat::Int,
     };
@@ -26,18 +41,89 @@ pub fn export_wav<P: AsRef<Path>>(samples: &[f32], path: P) -> Result<(), AudioE
     let mut writer = hound::WavWriter::create(path, spec)?;
 
     for &sample in samples {
-        // f32 -> i16 conversion: scale by 32767.0 and clamp to i16 range
-        let amplitude = f32::from(i16::MAX);
-        let s = (sample * amplitude).clamp(f32::from(i16::MIN), f32::from(i16::MAX));
-
-        #[allow(clippy::cast_possible_truncation)]
-        writer.write_sample(s as i16)?;
+        writer.write_sample(to_i16(sample))?;
     }
 
     writer.finalize()?;
     Ok(())
 }
 
+/// Write PCM samples to a non-seekable writer (e.g. stdout) in the given format.
+///
+/// Unlike [`export_wav`], the WAV header is written up front from the known sample count,
+/// so the output can be piped. If the data does not fit in a WAV header, the size fields
+/// are set to `0xFFFFFFFF` (the usual convention for streamed WAV).
+///
+/// # Errors
+/// * Returns `AudioError::IoError` if writing fails.
+pub fn write_pcm<W: Write>(
+    samples: &[f32],
+    format: PcmFormat,
+    mut writer: W,
+) -> Result<(), AudioError> {
+    match format {
+        PcmFormat::Wav => {
+            write_wav_header(&mut writer, samples.len())?;
+            write_s16le(samples, &mut writer)?;
+        }
+        PcmFormat::S16le => write_s16le(samples, &mut writer)?,
+        PcmFormat::F32le => {
+            for chunk in samples.chunks(4096) {
+                let bytes: Vec<u8> = chunk.iter().flat_map(|s| s.to_le_bytes()).collect();
+                writer.write_all(&bytes)?;
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_wav_header<W: Write>(writer: &mut W, sample_count: usize) -> std::io::Result<()> {
+    const CHANNELS: u16 = 1;
+    const BITS_PER_SAMPLE: u16 = 16;
+    const BLOCK_ALIGN: u16 = CHANNELS * BITS_PER_SAMPLE / 8;
+
+    let data_size = sample_count
+        .checked_mul(usize::from(BLOCK_ALIGN))
+        .and_then(|size| u32::try_from(size).ok())
+        .filter(|size| size.checked_add(36).is_some());
+    let (riff_size, data_size) = data_size.map_or((u32::MAX, u32::MAX), |size| (size + 36, size));
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&riff_size.to_le_bytes())?;
+    writer.write_all(b"WAVEfmt ")?;
+    writer.write_all(&16_u32.to_le_bytes())?;
+    writer.write_all(&1_u16.to_le_bytes())?; // PCM
+    writer.write_all(&CHANNELS.to_le_bytes())?;
+    writer.write_all(&SAMPLE_RATE.to_le_bytes())?;
+    writer.write_all(&(SAMPLE_RATE * u32::from(BLOCK_ALIGN)).to_le_bytes())?;
+    writer.write_all(&BLOCK_ALIGN.to_le_bytes())?;
+    writer.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+    writer.write_all(b"data")?;
+    writer.write_all(&data_size.to_le_bytes())
+}
+
+fn write_s16le<W: Write>(samples: &[f32], writer: &mut W) -> std::io::Result<()> {
+    for chunk in samples.chunks(4096) {
+        let bytes: Vec<u8> = chunk
+            .iter()
+            .flat_map(|&s| to_i16(s).to_le_bytes())
+            .collect();
+        writer.write_all(&bytes)?;
+    }
+    Ok(())
+}
+
+/// f32 -> i16 conversion: scale by 32767.0 and clamp to i16 range
+fn to_i16(sample: f32) -> i16 {
+    let amplitude = f32::from(i16::MAX);
+    let s = (sample * amplitude).clamp(f32::from(i16::MIN), f32::from(i16::MAX));
+
+    #[allow(clippy::cast_possible_truncation)]
+    let s = s as i16;
+    s
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -77,4 +163,39 @@ mod tests {
         // Cleanup
         let _ = std::fs::remove_file(path);
     }
+
+    #[test]
+    fn test_write_pcm_raw_formats() {
+        let samples = [0.0, 0.5, -1.0, 2.0];
+
+        let mut f32le = Vec::new();
+        write_pcm(&samples, PcmFormat::F32le, &mut f32le).unwrap();
+        let expected: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        assert_eq!(f32le, expected);
+
+        let mut s16le = Vec::new();
+        write_pcm(&samples, PcmFormat::S16le, &mut s16le).unwrap();
+        let expected: Vec<u8> = [0_i16, 16383, -32767, 32767]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        assert_eq!(s16le, expected);
+    }
+
+    #[test]
+    fn test_write_pcm_wav_matches_export_wav() {
+        let samples: Vec<f32> = (0..1000).map(|i| (i as f32 * 0.01).sin()).collect();
+
+        let mut streamed = Vec::new();
+        write_pcm(&samples, PcmFormat::Wav, &mut streamed).unwrap();
+
+        let path = std::env::temp_dir().join("test_write_pcm_wav.wav");
+        export_wav(&samples, &path).unwrap();
+        let exported = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(streamed, exported);
+        let reader = WavReader::new(std::io::Cursor::new(streamed)).unwrap();
+        assert_eq!(reader.duration(), 1000);
+    }
 }
@@ -1,6 +1,8 @@
+pub mod device;
 pub mod error;
 pub mod exporter;
 pub mod player;
+pub mod sink;
 pub mod synthesizer;
 pub mod waveform;
 
@@ -1,39 +1,163 @@
 use crate::audio::error::AudioError;
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use crate::audio::sink::{AudioSink, CpalSink};
 use std::sync::{Arc, Mutex};
 
-struct PlaybackState {
+/// 再生中のサンプル列と再生位置
+///
+/// オーディオコールバックと操作側（一時停止・シーク・停止キーなど）で共有する。
+pub struct PlaybackState {
     samples: Vec<f32>,
     position: usize,
     loop_enabled: bool,
+    /// ループ時に戻る位置（カウントインを繰り返さないため）
+    loop_start: usize,
+    paused: bool,
+    /// フェードアウトの残りサンプル数と全体のサンプル数
+    fade: Option<(usize, usize)>,
+    /// フェードアウトを終えて停止した
+    stopped: bool,
+}
+
+impl PlaybackState {
+    #[must_use]
+    pub fn new(samples: Vec<f32>, loop_enabled: bool, loop_start: usize) -> Self {
+        let loop_start = loop_start.min(samples.len());
+        Self {
+            samples,
+            position: 0,
+            loop_enabled,
+            loop_start,
+            paused: false,
+            fade: None,
+            stopped: false,
+        }
+    }
+
+    /// 現在の再生位置（サンプル）
+    #[must_use]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// 全体のサンプル数
+    #[must_use]
+    pub fn total_samples(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// 再生位置を移動する（末尾を超える位置は末尾に丸める）
+    pub fn seek(&mut self, position: usize) {
+        self.position = position.min(self.samples.len());
+    }
+
+    /// サンプル列を差し替える（再生位置・ループ位置は新しい末尾に丸める）
+    pub fn set_samples(&mut self, samples: Vec<f32>) {
+        self.samples = samples;
+        self.position = self.position.min(self.samples.len());
+        self.loop_start = self.loop_start.min(self.samples.len());
+    }
+
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    #[must_use]
+    pub fn is_loop_enabled(&self) -> bool {
+        self.loop_enabled
+    }
+
+    pub fn set_loop_enabled(&mut self, loop_enabled: bool) {
+        self.loop_enabled = loop_enabled;
+    }
+
+    /// `samples`サンプルかけてフェードアウトし、停止する
+    ///
+    /// 一時停止中は音が出ていないため、すぐに停止する。
+    pub fn fade_out(&mut self, samples: usize) {
+        if self.paused || samples == 0 {
+            self.stopped = true;
+        } else if self.fade.is_none() {
+            self.fade = Some((samples, samples));
+        }
+    }
+
+    /// 再生を終えたかどうか（停止した、またはループなしで末尾に達した）
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.stopped
+            || (!self.loop_enabled && self.fade.is_none() && self.position >= self.samples.len())
+    }
+
+    /// 次に出力するサンプルを返し、再生位置を進める
+    pub fn next_sample(&mut self) -> f32 {
+        if self.stopped || self.paused {
+            return 0.0;
+        }
+        if self.position >= self.samples.len() && self.loop_enabled {
+            self.position = self.loop_start;
+        }
+        let sample = match self.samples.get(self.position) {
+            Some(&s) => {
+                self.position += 1;
+                s
+            }
+            None => 0.0,
+        };
+
+        match self.fade {
+            Some((remaining, total)) => {
+                self.fade = (remaining > 1).then_some((remaining - 1, total));
+                self.stopped = self.fade.is_none();
+                #[allow(clippy::cast_precision_loss)]
+                let gain = remaining as f32 / total as f32;
+                sample * gain
+            }
+            None => sample,
+        }
+    }
+
+    /// 出力バッファを埋める（各フレームの全チャンネルに同じサンプルを書き込む）
+    pub fn fill(&mut self, data: &mut [f32], channels: usize) {
+        for frame in data.chunks_mut(channels.max(1)) {
+            let sample = self.next_sample();
+            frame.fill(sample);
+        }
+    }
 }
 
 pub struct AudioPlayer {
-    device: cpal::Device,
-    config: cpal::StreamConfig,
-    stream: Option<cpal::Stream>,
+    sink: Box<dyn AudioSink>,
+    state: Option<Arc<Mutex<PlaybackState>>>,
 }
 
 impl AudioPlayer {
-    /// Creates a new `AudioPlayer`.
+    /// Creates a new `AudioPlayer` on the default output device.
     ///
     /// # Errors
     /// Returns `AudioError` if no device is found or stream creation fails.
     pub fn new() -> Result<Self, AudioError> {
-        let host = cpal::default_host();
-        let device = host
-            .default_output_device()
-            .ok_or(AudioError::DeviceNotFound)?;
+        Self::with_device(None, None)
+    }
 
-        let config = device
-            .default_output_config()
-            .map_err(|e| AudioError::StreamCreationError(e.to_string()))?;
+    /// Creates a new `AudioPlayer` on the given host and output device (ID or name).
+    ///
+    /// `None` selects the default host or the host's default output device.
+    ///
+    /// # Errors
+    /// Returns `AudioError` if the host or device is not found or stream creation fails.
+    pub fn with_device(host: Option<&str>, device: Option<&str>) -> Result<Self, AudioError> {
+        Ok(Self::with_sink(Box::new(CpalSink::open(host, device)?)))
+    }
 
-        Ok(Self {
-            device,
-            config: config.into(),
-            stream: None,
-        })
+    /// Creates a new `AudioPlayer` that plays into the given sink.
+    #[must_use]
+    pub fn with_sink(sink: Box<dyn AudioSink>) -> Self {
+        Self { sink, state: None }
     }
 
     /// Starts audio playback.
@@ -41,70 +165,55 @@ impl AudioPlayer {
     /// # Errors
     /// Returns `AudioError` if stream creation or playback fails.
     pub fn play(&mut self, samples: &[f32], loop_enabled: bool) -> Result<(), AudioError> {
+        self.play_with_loop_start(samples, loop_enabled, 0)
+    }
+
+    /// Starts audio playback, looping back to `loop_start` instead of the beginning.
+    ///
+    /// # Errors
+    /// Returns `AudioError` if stream creation or playback fails.
+    pub fn play_with_loop_start(
+        &mut self,
+        samples: &[f32],
+        loop_enabled: bool,
+        loop_start: usize,
+    ) -> Result<(), AudioError> {
+        self.play_shared(Arc::new(Mutex::new(PlaybackState::new(
+            samples.to_vec(),
+            loop_enabled,
+            loop_start,
+        ))))
+    }
+
+    /// Starts audio playback from a prepared shared state (e.g. already seeked or paused).
+    ///
+    /// # Errors
+    /// Returns `AudioError` if stream creation or playback fails.
+    pub fn play_shared(&mut self, state: Arc<Mutex<PlaybackState>>) -> Result<(), AudioError> {
         // Stop current playback if any
         self.stop();
 
-        let state = Arc::new(Mutex::new(PlaybackState {
-            samples: samples.to_vec(),
-            position: 0,
-            loop_enabled,
-        }));
-
-        let state_clone = state.clone();
-        let channels = self.config.channels as usize;
-
-        let err_fn = |err| eprintln!("Audio stream error: {err}");
-
-        let stream = self
-            .device
-            .build_output_stream(
-                &self.config,
-                move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                    if let Ok(mut state) = state_clone.lock() {
-                        for frame in data.chunks_mut(channels) {
-                            let sample = if state.position < state.samples.len() {
-                                let s = state.samples[state.position];
-                                state.position += 1;
-                                s
-                            } else if state.loop_enabled {
-                                state.position = 0;
-                                if state.samples.is_empty() {
-                                    0.0
-                                } else {
-                                    let s = state.samples[0];
-                                    state.position = 1;
-                                    s
-                                }
-                            } else {
-                                0.0
-                            };
-
-                            for sample_out in frame.iter_mut() {
-                                *sample_out = sample;
-                            }
-                        }
-                    }
-                },
-                err_fn,
-                None,
-            )
-            .map_err(|e| AudioError::StreamCreationError(e.to_string()))?;
-
-        stream
-            .play()
-            .map_err(|e| AudioError::PlaybackError(e.to_string()))?;
-
-        self.stream = Some(stream);
+        self.sink.start(Arc::clone(&state))?;
+        self.state = Some(state);
         Ok(())
     }
 
     pub fn stop(&mut self) {
-        self.stream = None; // Dropping the stream stops it
+        self.sink.stop();
+        self.state = None;
+    }
+
+    /// 再生中の状態（再生位置・一時停止・ループなど）を返す
+    ///
+    /// 再生中にこの状態を変更すると、出力先に即座に反映される。
+    #[must_use]
+    pub fn playback_state(&self) -> Option<Arc<Mutex<PlaybackState>>> {
+        self.state.clone()
     }
 
     #[must_use]
     pub fn is_playing(&self) -> bool {
-        self.stream.is_some()
+        self.state.is_some()
     }
 }
 
@@ -139,4 +248,70 @@ mod tests {
             Err(e) => panic!("Unexpected error: {:?}", e),
         }
     }
+
+    fn drain(state: &mut PlaybackState, count: usize) -> Vec<f32> {
+        (0..count).map(|_| state.next_sample()).collect()
+    }
+
+    #[test]
+    fn test_playback_state_pause_keeps_position() {
+        let mut state = PlaybackState::new(vec![1.0, 2.0, 3.0], false, 0);
+        assert_eq!(drain(&mut state, 1), vec![1.0]);
+        state.set_paused(true);
+        assert_eq!(drain(&mut state, 2), vec![0.0, 0.0]);
+        assert_eq!(state.position(), 1);
+        state.set_paused(false);
+        assert_eq!(drain(&mut state, 2), vec![2.0, 3.0]);
+        assert!(state.is_finished());
+    }
+
+    #[test]
+    fn test_playback_state_seek_and_loop() {
+        let mut state = PlaybackState::new(vec![1.0, 2.0, 3.0], true, 1);
+        state.seek(2);
+        assert_eq!(drain(&mut state, 3), vec![3.0, 2.0, 3.0]);
+        assert!(!state.is_finished());
+
+        state.set_loop_enabled(false);
+        state.seek(10);
+        assert_eq!(state.position(), 3);
+        assert!(state.is_finished());
+    }
+
+    #[test]
+    fn test_playback_state_set_samples_clamps_position() {
+        let mut state = PlaybackState::new(vec![1.0; 4], false, 0);
+        state.seek(3);
+        state.set_samples(vec![2.0; 2]);
+        assert_eq!(state.position(), 2);
+        assert!(state.is_finished());
+        state.seek(1);
+        assert_eq!(drain(&mut state, 2), vec![2.0, 0.0]);
+    }
+
+    #[test]
+    fn test_playback_state_fade_out() {
+        let mut state = PlaybackState::new(vec![1.0; 10], false, 0);
+        state.fade_out(4);
+        assert_eq!(drain(&mut state, 6), vec![1.0, 0.75, 0.5, 0.25, 0.0, 0.0]);
+        assert!(state.is_finished());
+        assert_eq!(state.position(), 4);
+    }
+
+    #[test]
+    fn test_playback_state_fill_copies_sample_to_all_channels() {
+        let mut state = PlaybackState::new(vec![1.0, 2.0], false, 0);
+        let mut data = [9.0; 6];
+        state.fill(&mut data, 2);
+        assert_eq!(data, [1.0, 1.0, 2.0, 2.0, 0.0, 0.0]);
+        assert!(state.is_finished());
+    }
+
+    #[test]
+    fn test_playback_state_fade_out_while_paused_stops_immediately() {
+        let mut state = PlaybackState::new(vec![1.0; 10], false, 0);
+        state.set_paused(true);
+        state.fade_out(4);
+        assert!(state.is_finished());
+    }
 }
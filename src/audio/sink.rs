@@ -0,0 +1,207 @@
+//! オーディオ出力先（シンク）モジュール
+//!
+//! `AudioPlayer`は再生状態（`PlaybackState`）をシンクに渡し、シンクがそこからサンプルを
+//! 取り出して出力する。
+//!
+//! # ビジネスルール
+//! - `CpalSink`: オーディオデバイスに出力する（デバイスのコールバックでサンプルを取り出す）
+//! - `StreamSink`: 実時間のペースでサンプルを取り出し、32bit浮動小数点リトルエンディアン
+//!   （f32le）・モノラルの生PCMとして書き込む。書き込み先を捨てればヌルシンクになる
+//! - `StreamSink`は再生を終えた後は何も書き込まない（一時停止中は無音を書き込む）
+//! - 書き込みに失敗した場合（パイプが閉じられた等）は再生を停止する
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use cpal::traits::{DeviceTrait, StreamTrait};
+
+use super::error::AudioError;
+use super::player::PlaybackState;
+
+/// `StreamSink`が一度に取り出すサンプル数
+const BLOCK_FRAMES: usize = 512;
+
+/// オーディオの出力先
+pub trait AudioSink {
+    /// `state`からサンプルを取り出して出力を始める（出力中の場合は止めてから始める）
+    ///
+    /// # Errors
+    /// 出力を開始できない場合
+    fn start(&mut self, state: Arc<Mutex<PlaybackState>>) -> Result<(), AudioError>;
+
+    /// 出力を止める
+    fn stop(&mut self);
+}
+
+/// オーディオデバイス（cpal）への出力
+pub struct CpalSink {
+    device: cpal::Device,
+    config: cpal::StreamConfig,
+    stream: Option<cpal::Stream>,
+}
+
+impl CpalSink {
+    /// 指定したホスト・出力デバイス（IDまたは名前）を開く
+    ///
+    /// `None`の場合は既定のホスト、またはホストの既定の出力デバイスを使う。
+    ///
+    /// # Errors
+    /// ホスト・デバイスが見つからない、または出力設定を取得できない場合
+    pub fn open(host: Option<&str>, device: Option<&str>) -> Result<Self, AudioError> {
+        let device = super::device::open_output_device(host, device)?;
+        let config = device
+            .default_output_config()
+            .map_err(|e| AudioError::StreamCreationError(e.to_string()))?;
+
+        Ok(Self {
+            device,
+            config: config.into(),
+            stream: None,
+        })
+    }
+}
+
+impl AudioSink for CpalSink {
+    fn start(&mut self, state: Arc<Mutex<PlaybackState>>) -> Result<(), AudioError> {
+        self.stop();
+
+        let channels = self.config.channels as usize;
+        let err_fn = |err| eprintln!("Audio stream error: {err}");
+
+        let stream = self
+            .device
+            .build_output_stream(
+                &self.config,
+                move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                    if let Ok(mut state) = state.lock() {
+                        state.fill(data, channels);
+                    }
+                },
+                err_fn,
+                None,
+            )
+            .map_err(|e| AudioError::StreamCreationError(e.to_string()))?;
+
+        stream
+            .play()
+            .map_err(|e| AudioError::PlaybackError(e.to_string()))?;
+
+        self.stream = Some(stream);
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.stream = None; // Dropping the stream stops it
+    }
+}
+
+type Writer = Box<dyn Write + Send>;
+
+/// 実時間のペースで生PCMを書き込む出力（ヌル・ファイル・標準出力）
+pub struct StreamSink {
+    writer: Option<Writer>,
+    sample_rate: u32,
+    /// 出力中のスレッドと停止フラグ（スレッドは終了時に書き込み先を返す）
+    worker: Option<(Arc<AtomicBool>, JoinHandle<Writer>)>,
+}
+
+impl StreamSink {
+    /// `writer`に書き込むシンクを作成する
+    #[must_use]
+    pub fn new(writer: impl Write + Send + 'static, sample_rate: u32) -> Self {
+        Self {
+            writer: Some(Box::new(writer)),
+            sample_rate,
+            worker: None,
+        }
+    }
+
+    /// 出力を捨てるシンク（実時間で再生が進む）
+    #[must_use]
+    pub fn null(sample_rate: u32) -> Self {
+        Self::new(io::sink(), sample_rate)
+    }
+
+    /// ファイルに書き込むシンク
+    ///
+    /// # Errors
+    /// ファイルを作成できない場合
+    pub fn file(path: impl AsRef<Path>, sample_rate: u32) -> Result<Self, AudioError> {
+        let file = File::create(path).map_err(|e| AudioError::PlaybackError(e.to_string()))?;
+        Ok(Self::new(BufWriter::new(file), sample_rate))
+    }
+
+    /// 標準出力に書き込むシンク
+    #[must_use]
+    pub fn stdout(sample_rate: u32) -> Self {
+        Self::new(io::stdout(), sample_rate)
+    }
+}
+
+impl AudioSink for StreamSink {
+    fn start(&mut self, state: Arc<Mutex<PlaybackState>>) -> Result<(), AudioError> {
+        self.stop();
+        let mut writer = self
+            .writer
+            .take()
+            .ok_or_else(|| AudioError::PlaybackError("出力先が失われました".to_string()))?;
+
+        let halt = Arc::new(AtomicBool::new(false));
+        let halt_clone = Arc::clone(&halt);
+        let sample_rate = f64::from(self.sample_rate);
+        let worker = thread::spawn(move || {
+            let start = Instant::now();
+            let mut written = 0_usize;
+            let mut block = Vec::with_capacity(BLOCK_FRAMES);
+            while !halt_clone.load(Ordering::Relaxed) {
+                block.clear();
+                if let Ok(mut state) = state.lock() {
+                    while block.len() < BLOCK_FRAMES && !state.is_finished() {
+                        block.push(state.next_sample());
+                    }
+                }
+                if block.is_empty() {
+                    thread::sleep(Duration::from_millis(10));
+                    continue;
+                }
+
+                let bytes: Vec<u8> = block.iter().flat_map(|s| s.to_le_bytes()).collect();
+                if let Err(err) = writer.write_all(&bytes).and_then(|()| writer.flush()) {
+                    eprintln!("Audio stream error: {err}");
+                    if let Ok(mut state) = state.lock() {
+                        state.fade_out(0);
+                    }
+                    break;
+                }
+
+                written += block.len();
+                let due = start + Duration::from_secs_f64(written as f64 / sample_rate);
+                thread::sleep(due.saturating_duration_since(Instant::now()));
+            }
+            writer
+        });
+
+        self.worker = Some((halt, worker));
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        if let Some((halt, worker)) = self.worker.take() {
+            halt.store(true, Ordering::Relaxed);
+            if let Ok(writer) = worker.join() {
+                self.writer = Some(writer);
+            }
+        }
+    }
+}
+
+impl Drop for StreamSink {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -1,8 +1,13 @@
 use crate::audio::waveform::{create_node, midi_to_frequency, WaveformType};
-use crate::mml::{Command, Mml, Note, TempoEvent, VolumeValue};
+use crate::mml::{
+    Command, Dynamics, GateValue, Meter, Mml, Note, TempoEvent, TempoState, VolumeValue,
+};
 use fundsp::hacker::{highpass_hz, noise};
 use std::error::Error;
 
+/// 小節の頭のクリックの音量倍率
+const ACCENT_GAIN: f32 = 2.0;
+
 pub struct Synthesizer {
     pub sample_rate: u32,
     pub volume: u8,
@@ -23,38 +28,75 @@ impl Synthesizer {
     ///
     /// # Errors
     /// Returns an error if synthesis fails (though currently it mostly succeeds).
-    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
     pub fn synthesize(&mut self, mml: &Mml) -> Result<Vec<f32>, Box<dyn Error>> {
+        Ok(self.render(mml, None))
+    }
+
+    /// MML中のテンポ指定（`T`、`Tn>m:b`）を無視し、固定テンポ`bpm`で合成する
+    ///
+    /// 外部MIDIクロックに同期して再生する場合に使う。スウィングは反映する。
+    ///
+    /// # Errors
+    /// Returns an error if synthesis fails (though currently it mostly succeeds).
+    pub fn synthesize_at_tempo(&mut self, mml: &Mml, bpm: f64) -> Result<Vec<f32>, Box<dyn Error>> {
+        Ok(self.render(mml, Some(bpm)))
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn render(&mut self, mml: &Mml, fixed_bpm: Option<f64>) -> Vec<f32> {
         let mut samples = Vec::new();
         let mut octave = 4;
         let mut default_length = 4;
-        let mut bpm = 120;
+        let mut tempo = TempoState::default();
+        if let Some(bpm) = fixed_bpm {
+            tempo.set_fixed(bpm);
+        }
         // デフォルト値V10（BR-074準拠）
         let mut current_velocity: u8 = 10;
+        // デフォルト値Q8（音長いっぱいに発音）
+        let mut gate = GateValue::default().ratio();
+        // @vによるベロシティ指定・変化（拍位置で補間）
+        let mut dynamics = Dynamics::default();
+        let mut beat_position = 0.0_f64;
 
-        for command in &mml.commands {
+        for command in mml {
             match command {
                 Command::Note(note) => {
+                    let level = dynamics.level_at(current_velocity, beat_position);
                     let note_samples = self.generate_note_samples(
                         note,
                         octave,
-                        bpm,
+                        &tempo,
+                        beat_position,
                         default_length,
-                        current_velocity,
+                        level,
+                        gate,
                     );
                     samples.extend(note_samples);
+                    beat_position += note.total_beats(default_length);
                 }
                 Command::Rest(rest) => {
-                    let duration = rest.duration_in_seconds(bpm, default_length);
-                    let num_samples = (f64::from(duration) * f64::from(self.sample_rate)) as usize;
+                    let beats = rest.total_beats(default_length);
+                    let duration = tempo.seconds(beat_position, beats);
+                    let num_samples = (duration * f64::from(self.sample_rate)) as usize;
                     samples.extend(vec![0.0; num_samples]);
+                    beat_position += beats;
                 }
                 Command::Octave(o) => octave = o.value,
                 Command::OctaveUp => octave = octave.saturating_add(1).min(8),
                 Command::OctaveDown => octave = octave.saturating_sub(1).max(1),
-                Command::Tempo(t) => bpm = t.value,
+                Command::Tempo(_) | Command::TempoRamp(_) if fixed_bpm.is_some() => {}
+                Command::Tempo(t) => tempo.set(t.value),
+                Command::TempoRamp(r) => tempo.apply_ramp(r, beat_position),
+                Command::Swing(swing) => tempo.set_swing(*swing),
+                Command::TimeSignature(_)
+                | Command::BarLine
+                | Command::Marker(_)
+                | Command::Channel(_)
+                | Command::MidiControl(_) => {}
                 Command::DefaultLength(l) => default_length = l.value,
                 Command::Volume(v) => {
+                    dynamics.clear();
                     current_velocity = match v.value {
                         VolumeValue::Absolute(val) => val,
                         VolumeValue::Relative(delta) => {
@@ -68,8 +110,10 @@ impl Synthesizer {
                         }
                     };
                 }
+                Command::Gate(g) => gate = g.value.ratio(),
+                Command::Velocity(v) => dynamics.apply(v.value, current_velocity, beat_position),
                 Command::Loop { .. } => {
-                    unreachable!("Loop commands should be expanded before synthesis")
+                    unreachable!("Loop commands are expanded lazily by Mml::iter")
                 }
                 Command::Tuplet {
                     commands: tuplet_commands,
@@ -81,9 +125,14 @@ impl Synthesizer {
                         *count,
                         *base_duration,
                         &mut octave,
-                        bpm,
+                        &tempo,
                         default_length,
-                        current_velocity,
+                        &NoteLevel {
+                            volume: current_velocity,
+                            dynamics: &dynamics,
+                        },
+                        gate,
+                        &mut beat_position,
                     );
                     samples.extend(tuplet_samples);
                 }
@@ -93,40 +142,59 @@ impl Synthesizer {
         // Normalization (F-019)
         normalize_samples(&mut samples);
 
-        Ok(samples)
+        samples
     }
 
     #[allow(
         clippy::cast_precision_loss,
         clippy::cast_possible_truncation,
-        clippy::cast_sign_loss
+        clippy::cast_sign_loss,
+        clippy::too_many_arguments
     )]
     fn generate_note_samples(
         &self,
         note: &Note,
         octave: u8,
-        bpm: u16,
+        tempo: &TempoState,
+        beat_position: f64,
         default_length: u8,
-        velocity: u8,
+        level: f32,
+        gate: f32,
     ) -> Vec<f32> {
         let midi_note = note.to_midi_note(octave);
+        let duration = tempo.seconds(beat_position, note.total_beats(default_length));
+        let num_samples = (duration * f64::from(self.sample_rate)) as usize;
+
+        self.render_tone(midi_note, num_samples, level, gate)
+    }
+
+    /// 指定サンプル数の音を生成する
+    ///
+    /// `level`は音量係数（0.0〜1.0）。
+    /// 先頭から`gate`の割合だけ発音し、残りは無音で埋める。
+    /// エンベロープは発音部分にのみ適用される。
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn render_tone(&self, midi_note: u8, num_samples: usize, level: f32, gate: f32) -> Vec<f32> {
         let frequency = midi_to_frequency(midi_note);
-        let duration = note.duration_in_seconds(bpm, default_length);
-        let num_samples = (f64::from(duration) * f64::from(self.sample_rate)) as usize;
+        let sounding_samples = ((num_samples as f32) * gate.clamp(0.0, 1.0)) as usize;
 
         let mut audio_node = create_node(self.waveform_type, frequency);
         audio_node.set_sample_rate(f64::from(self.sample_rate));
 
-        // velocityは0-15の範囲、15で最大音量
-        let master_gain = (f32::from(self.volume) / 100.0) * (f32::from(velocity) / 15.0);
+        let master_gain = (f32::from(self.volume) / 100.0) * level;
 
         let mut samples = Vec::with_capacity(num_samples);
-        for _ in 0..num_samples {
-            let sample = audio_node.get_mono() as f32;
+        for _ in 0..sounding_samples {
+            let sample = audio_node.get_mono();
             samples.push(sample * master_gain);
         }
 
         self.apply_envelope(&mut samples);
+        samples.resize(num_samples, 0.0);
 
         samples
     }
@@ -235,6 +303,9 @@ impl Synthesizer {
     }
 
     /// テンポ変更に追従するメトロノームをミックス
+    ///
+    /// テンポイベントに拍子（`meter`）が含まれる場合は、小節の頭で
+    /// 音色の異なるクリック（[`generate_accent_click`]）を鳴らす。
     #[allow(
         clippy::cast_precision_loss,
         clippy::cast_possible_truncation,
@@ -253,6 +324,7 @@ impl Synthesizer {
         }
 
         let click_samples = generate_noise_click(sample_rate, volume);
+        let accent_samples = generate_accent_click(sample_rate, (volume * ACCENT_GAIN).min(1.0));
         let total_len = samples.len();
 
         for (event_idx, event) in tempo_events.iter().enumerate() {
@@ -265,27 +337,88 @@ impl Synthesizer {
                 break;
             }
 
-            let interval_sec = beat_interval_seconds(event.bpm, beat);
-            let interval_samples = (interval_sec * sample_rate as f32) as usize;
-
-            if interval_samples == 0 {
-                continue;
-            }
+            // テンポ変化中もクリック位置が拍に一致するよう、区間先頭からの拍数で計算
+            let tempo = event.tempo_state();
+            let interval_beats = beat_interval_beats(beat);
+            let mut click_beat = 0.0;
+            let mut elapsed_sec = 0.0;
 
             let mut position = section_start;
             while position < section_end && position < total_len {
-                for (i, &click_sample) in click_samples.iter().enumerate() {
+                let is_downbeat = event
+                    .meter
+                    .is_some_and(|meter| meter.is_downbeat(click_beat));
+                let click = if is_downbeat {
+                    &accent_samples
+                } else {
+                    &click_samples
+                };
+                for (i, &click_sample) in click.iter().enumerate() {
                     let sample_index = position + i;
                     if sample_index >= total_len {
                         break;
                     }
                     samples[sample_index] += click_sample;
                 }
-                position += interval_samples;
+                elapsed_sec += tempo.seconds(click_beat, interval_beats);
+                click_beat += interval_beats;
+                position = section_start + (elapsed_sec * sample_rate) as usize;
             }
         }
     }
 
+    /// 演奏前のカウントイン（指定小節数のクリック）を生成
+    ///
+    /// 最初のテンポイベントのテンポと拍子に従い、小節の頭を強調する。
+    /// テンポ変化で始まる曲では開始テンポを使用する。
+    ///
+    /// # Arguments
+    /// * `sample_rate` - サンプリングレート（Hz）
+    /// * `tempo_events` - 演奏のテンポイベント
+    /// * `bars` - カウントインの小節数（0の場合は空）
+    /// * `beat` - ビート値（4, 8, 16）
+    /// * `volume` - メトロノーム音量（0.0〜1.0）
+    #[must_use]
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn generate_count_in(
+        &self,
+        sample_rate: f64,
+        tempo_events: &[TempoEvent],
+        bars: u8,
+        beat: u8,
+        volume: f32,
+    ) -> Vec<f32> {
+        if bars == 0 {
+            return Vec::new();
+        }
+
+        let first = tempo_events.first();
+        let bpm = first.map_or(120, |event| event.bpm);
+        let time_signature = first
+            .and_then(|event| event.meter)
+            .map(|meter| meter.time_signature)
+            .unwrap_or_default();
+
+        let beats = f64::from(bars) * time_signature.beats_per_bar();
+        let num_samples = (beats * 60.0 / f64::from(bpm) * sample_rate) as usize;
+        let mut samples = vec![0.0; num_samples];
+        let event = TempoEvent {
+            sample_position: 0,
+            bpm,
+            curve: None,
+            meter: Some(Meter {
+                time_signature,
+                beat_in_bar: 0.0,
+            }),
+        };
+        self.mix_metronome_with_tempo_events(&mut samples, sample_rate, &[event], beat, volume);
+        samples
+    }
+
     #[allow(
         clippy::cast_precision_loss,
         clippy::cast_possible_truncation,
@@ -298,57 +431,47 @@ impl Synthesizer {
         count: u8,
         base_duration: Option<u8>,
         octave: &mut u8,
-        bpm: u16,
+        tempo: &TempoState,
         default_length: u8,
-        velocity: u8,
+        level: &NoteLevel,
+        gate: f32,
+        beat_position: &mut f64,
     ) -> Vec<f32> {
         let mut samples = Vec::new();
 
         let base = base_duration.unwrap_or(default_length);
-        let base_seconds = 60.0 / f32::from(bpm) * 4.0 / f32::from(base);
-        let tuplet_duration = base_seconds / f32::from(count);
+        let tuplet_beats = 4.0 / f64::from(base) / f64::from(count);
 
         for cmd in commands {
             match cmd {
                 Command::Note(note) => {
-                    let note_duration =
+                    let note_beats =
                         if note.duration.base.value.is_some() || note.duration.has_ties() {
-                            note.duration_in_seconds(bpm, default_length) / f32::from(count)
+                            note.total_beats(default_length) / f64::from(count)
                         } else {
-                            tuplet_duration
+                            tuplet_beats
                         };
 
                     let midi_note = note.to_midi_note(*octave);
-                    let frequency = midi_to_frequency(midi_note);
-                    let num_samples =
-                        (f64::from(note_duration) * f64::from(self.sample_rate)) as usize;
-
-                    let mut audio_node = create_node(self.waveform_type, frequency);
-                    audio_node.set_sample_rate(f64::from(self.sample_rate));
-
-                    let master_gain =
-                        (f32::from(self.volume) / 100.0) * (f32::from(velocity) / 15.0);
+                    let note_duration = tempo.seconds(*beat_position, note_beats);
+                    let num_samples = (note_duration * f64::from(self.sample_rate)) as usize;
 
-                    let mut note_samples = Vec::with_capacity(num_samples);
-                    for _ in 0..num_samples {
-                        let sample = audio_node.get_mono() as f32;
-                        note_samples.push(sample * master_gain);
-                    }
-
-                    self.apply_envelope(&mut note_samples);
-                    samples.extend(note_samples);
+                    let note_level = level.at(*beat_position);
+                    samples.extend(self.render_tone(midi_note, num_samples, note_level, gate));
+                    *beat_position += note_beats;
                 }
                 Command::Rest(rest) => {
-                    let rest_duration =
+                    let rest_beats =
                         if rest.duration.base.value.is_some() || rest.duration.has_ties() {
-                            rest.duration_in_seconds(bpm, default_length) / f32::from(count)
+                            rest.total_beats(default_length) / f64::from(count)
                         } else {
-                            tuplet_duration
+                            tuplet_beats
                         };
 
-                    let num_samples =
-                        (f64::from(rest_duration) * f64::from(self.sample_rate)) as usize;
+                    let rest_duration = tempo.seconds(*beat_position, rest_beats);
+                    let num_samples = (rest_duration * f64::from(self.sample_rate)) as usize;
                     samples.extend(vec![0.0; num_samples]);
+                    *beat_position += rest_beats;
                 }
                 Command::Octave(o) => *octave = o.value,
                 Command::OctaveUp => *octave = octave.saturating_add(1).min(8),
@@ -364,21 +487,23 @@ impl Synthesizer {
                         Some(default_length)
                     };
 
+                    let mut nested_beat_position = *beat_position;
                     let nested_samples = self.synthesize_tuplet(
                         inner_commands,
                         *inner_count,
                         nested_base,
                         octave,
-                        bpm,
+                        tempo,
                         default_length,
-                        velocity,
+                        level,
+                        gate,
+                        &mut nested_beat_position,
                     );
+                    let slot_duration = tempo.seconds(*beat_position, tuplet_beats);
+                    *beat_position += tuplet_beats;
 
-                    let nested_total_duration =
-                        nested_samples.len() as f32 / self.sample_rate as f32;
-                    if nested_total_duration > 0.0 {
-                        let target_samples =
-                            (f64::from(tuplet_duration) * f64::from(self.sample_rate)) as usize;
+                    if !nested_samples.is_empty() {
+                        let target_samples = (slot_duration * f64::from(self.sample_rate)) as usize;
                         let resampled = resample_linear(&nested_samples, target_samples);
                         samples.extend(resampled);
                     }
@@ -394,6 +519,18 @@ impl Synthesizer {
     }
 }
 
+/// 連符内の各音符の音量係数を求めるための音量状態
+struct NoteLevel<'a> {
+    volume: u8,
+    dynamics: &'a Dynamics,
+}
+
+impl NoteLevel<'_> {
+    fn at(&self, beat: f64) -> f32 {
+        self.dynamics.level_at(self.volume, beat)
+    }
+}
+
 /// Linearly resample audio samples to a target length.
 #[must_use]
 pub fn resample_linear(samples: &[f32], target_len: usize) -> Vec<f32> {
@@ -466,6 +603,55 @@ pub fn beat_interval_seconds(bpm: u16, beat: u8) -> f32 {
     }
 }
 
+/// メトロノームのクリック間隔を拍数（4分音符 = 1拍）で取得
+///
+/// テンポ変化中のクリック位置計算に使用する。
+///
+/// # Panics
+/// `beat`が4, 8, 16以外の場合にパニックします（clapによりバリデーション済み）。
+#[must_use]
+pub fn beat_interval_beats(beat: u8) -> f64 {
+    match beat {
+        4 => 1.0,
+        8 => 0.5,
+        16 => 0.25,
+        _ => unreachable!("beat value is validated by clap"),
+    }
+}
+
+/// 小節の頭用のクリックサンプルを生成
+///
+/// 通常のクリック（[`generate_noise_click`]）と聞き分けられるよう、
+/// 1.5kHzの正弦波に指数減衰エンベロープを適用したウッドブロック風の音にする。
+///
+/// # Arguments
+/// * `sample_rate` - サンプリングレート（Hz）通常は44100.0
+/// * `volume` - 音量係数（0.0〜1.0）
+///
+/// # Returns
+/// 30msのクリック音サンプル配列（約1323サンプル @44100Hz）
+#[must_use]
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+pub fn generate_accent_click(sample_rate: f64, volume: f32) -> Vec<f32> {
+    const CLICK_DURATION: f64 = 0.03; // 30ms
+    const DECAY_RATE: f64 = -8.0;
+    const FREQUENCY: f64 = 1500.0;
+
+    let num_samples = (sample_rate * CLICK_DURATION) as usize;
+    (0..num_samples)
+        .map(|i| {
+            let t = (i as f64) / sample_rate;
+            let tone = (std::f64::consts::TAU * FREQUENCY * t).sin();
+            let envelope = (DECAY_RATE * t / CLICK_DURATION).exp();
+            (tone * envelope) as f32 * volume
+        })
+        .collect()
+}
+
 /// ノイズベースのクリックサンプルを生成
 ///
 /// fundspの`noise()`関数によりホワイトノイズを生成し、
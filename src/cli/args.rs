@@ -1,3 +1,4 @@
+use crate::mml::{PlayPosition, DEFAULT_MAX_DURATION_SECS};
 use clap::{Args, Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Debug)]
@@ -16,9 +17,41 @@ pub enum Command {
     Export(ExportArgs),
     /// Clear all playback history
     ClearHistory,
+    /// Interactive MML shell (each line is played immediately)
+    Repl(ReplArgs),
+    /// Audio device management
+    Audio(AudioArgs),
     /// MIDI device management
     #[cfg(feature = "midi-output")]
     Midi(MidiArgs),
+    /// Record notes from a MIDI input device into MML
+    #[cfg(feature = "midi-output")]
+    Record(RecordArgs),
+}
+
+/// Record subcommand arguments
+#[cfg(feature = "midi-output")]
+#[derive(Args, Debug)]
+pub struct RecordArgs {
+    /// MIDI input device ID or name (see `midi list`)
+    #[arg(long, value_name = "DEVICE")]
+    pub midi_in: String,
+
+    /// クオンタイズのテンポ（30-300）
+    #[arg(long, short = 't', default_value_t = 120, value_parser = clap::value_parser!(u16).range(30..=300))]
+    pub tempo: u16,
+
+    /// クオンタイズの単位（4, 8, 16, 32分音符）
+    #[arg(long, value_parser = validate_grid, default_value_t = 16)]
+    pub grid: u8,
+
+    /// 書き出すMMLファイル（.mml、省略時は履歴に保存）
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<String>,
+
+    /// 履歴にメモを付与（最大500文字、UTF-8対応）
+    #[arg(long, conflicts_with = "output")]
+    pub note: Option<String>,
 }
 
 /// MIDI subcommand arguments
@@ -37,12 +70,31 @@ pub enum MidiSubcommand {
     List,
 }
 
+/// Audio subcommand arguments
+#[derive(Args, Debug)]
+pub struct AudioArgs {
+    #[command(subcommand)]
+    pub command: AudioSubcommand,
+}
+
+/// Audio subcommands
+#[derive(Subcommand, Debug)]
+pub enum AudioSubcommand {
+    /// List available audio output devices
+    List {
+        /// 一覧を表示するオーディオホスト（省略時はすべてのホスト）
+        #[arg(long, value_name = "HOST")]
+        audio_host: Option<String>,
+    },
+}
+
 #[derive(Args, Debug)]
 #[command(group(
     clap::ArgGroup::new("input")
         .required(true)
         .args(["mml", "history_id", "file"]),
 ))]
+#[allow(clippy::struct_excessive_bools)]
 pub struct PlayArgs {
     /// MML string to play
     pub mml: Option<String>,
@@ -55,6 +107,10 @@ pub struct PlayArgs {
     #[arg(long, short = 'f', value_name = "FILE")]
     pub file: Option<String>,
 
+    /// ファイル内の指定したセクションだけを再生（--file と併用）
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["mml", "history_id"])]
+    pub section: Option<String>,
+
     #[arg(short, long, default_value = "sine")]
     pub waveform: Waveform,
 
@@ -73,23 +129,104 @@ pub struct PlayArgs {
     #[arg(long, value_parser = validate_volume, default_value_t = 0.3)]
     pub metronome_volume: f32,
 
+    /// 演奏前にカウントインする小節数（0-16）
+    #[arg(long, value_name = "BARS", default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=16))]
+    pub count_in: u8,
+
+    /// 演奏時間の上限（秒、0で無制限）
+    #[arg(long, value_name = "SECONDS", default_value_t = DEFAULT_MAX_DURATION_SECS)]
+    pub max_duration: u32,
+
+    /// 再生の開始位置（小節番号、マーカー名、または m:ss）
+    #[arg(long, value_name = "POS")]
+    pub from: Option<PlayPosition>,
+
+    /// 再生の終了位置（小節番号の場合はその小節の終わりまで）
+    #[arg(long, value_name = "POS")]
+    pub to: Option<PlayPosition>,
+
     /// 履歴にメモを付与（最大500文字、UTF-8対応）
     #[arg(long)]
     pub note: Option<String>,
 
+    /// オーディオ出力デバイスのIDまたは名前（`audio list`で確認、省略時は既定のデバイス）
+    #[arg(long, value_name = "DEVICE")]
+    pub audio_device: Option<String>,
+
+    /// オーディオホスト（ALSA、JACKなど。省略時は既定のホスト）
+    #[arg(long, value_name = "HOST")]
+    pub audio_host: Option<String>,
+
+    /// 音声の出力先
+    #[arg(long, value_name = "SINK", default_value = "device")]
+    pub audio_sink: AudioSinkKind,
+
+    /// --audio-sink file の書き込み先
+    #[arg(long, value_name = "FILE", required_if_eq("audio_sink", "file"))]
+    pub pcm_file: Option<String>,
+
     /// MIDI output device ID or name (enables MIDI mode)
     #[cfg(feature = "midi-output")]
-    #[arg(long, value_name = "DEVICE")]
+    #[arg(long, value_name = "DEVICE", group = "midi_output")]
     pub midi_out: Option<String>,
 
-    /// MIDI channel (1-16, default: 1)
+    /// Create a virtual MIDI output port with this name and play to it (Linux/macOS; enables MIDI mode)
+    #[cfg(feature = "midi-output")]
+    #[arg(long, value_name = "NAME", group = "midi_output")]
+    pub midi_virtual: Option<String>,
+
+    /// MIDI channel (1-16, default: 1; `@C` in MML switches channels)
     #[cfg(feature = "midi-output")]
     #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=16))]
     pub midi_channel: u8,
 
+    /// Send MIDI clock (24 PPQN) and Start/Stop/Continue to the MIDI output
+    #[cfg(feature = "midi-output")]
+    #[arg(long, default_value_t = false, requires = "midi_output")]
+    pub midi_clock: bool,
+
+    /// Follow MIDI clock and Start/Stop/Continue from this MIDI input device (ID or name)
+    #[cfg(feature = "midi-output")]
+    #[arg(
+        long,
+        value_name = "DEVICE",
+        conflicts_with_all = ["from", "to", "loop_play", "watch", "count_in", "metronome", "midi_clock"]
+    )]
+    pub midi_clock_in: Option<String>,
+
+    /// Play audio together with the MIDI output (--midi-out / --midi-virtual)
+    #[cfg(feature = "midi-output")]
+    #[arg(
+        long,
+        default_value_t = false,
+        requires = "midi_output",
+        conflicts_with_all = ["count_in", "watch", "midi_clock_in"]
+    )]
+    pub with_audio: bool,
+
+    /// Shift MIDI output against audio in milliseconds (-1000 to 1000; negative sends MIDI earlier)
+    #[cfg(feature = "midi-output")]
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = 0,
+        allow_negative_numbers = true,
+        requires = "with_audio",
+        value_parser = clap::value_parser!(i32).range(-1000..=1000)
+    )]
+    pub midi_offset_ms: i32,
+
     /// 履歴に保存しない
     #[arg(long, short = 'N', default_value_t = false)]
     pub no_history: bool,
+
+    /// ファイル（とインクルードしたファイル）の保存を監視し、再パースして再生し直す（--file と併用）
+    #[arg(long, default_value_t = false, conflicts_with_all = ["mml", "history_id"])]
+    pub watch: bool,
+
+    /// --watch で再読み込みした時、現在位置ではなく先頭から再生し直す
+    #[arg(long, default_value_t = false, requires = "watch")]
+    pub watch_from_start: bool,
 }
 
 #[cfg(test)]
@@ -108,16 +245,32 @@ impl PlayArgs {
             mml,
             history_id,
             file,
+            section: None,
             waveform,
             volume,
             loop_play: false,
             metronome: false,
             metronome_beat: 4,
             metronome_volume: 0.3,
+            count_in: 0,
+            max_duration: DEFAULT_MAX_DURATION_SECS,
+            from: None,
+            to: None,
             note,
+            audio_device: None,
+            audio_host: None,
+            audio_sink: AudioSinkKind::Device,
+            pcm_file: None,
             midi_out: None,
+            midi_virtual: None,
             midi_channel: 1,
+            midi_clock: false,
+            midi_clock_in: None,
+            with_audio: false,
+            midi_offset_ms: 0,
             no_history: false,
+            watch: false,
+            watch_from_start: false,
         }
     }
 
@@ -135,14 +288,25 @@ impl PlayArgs {
             mml,
             history_id,
             file,
+            section: None,
             waveform,
             volume,
             loop_play: false,
             metronome: false,
             metronome_beat: 4,
             metronome_volume: 0.3,
+            count_in: 0,
+            max_duration: DEFAULT_MAX_DURATION_SECS,
+            from: None,
+            to: None,
             note,
+            audio_device: None,
+            audio_host: None,
+            audio_sink: AudioSinkKind::Device,
+            pcm_file: None,
             no_history: false,
+            watch: false,
+            watch_from_start: false,
         }
     }
 
@@ -162,16 +326,32 @@ impl PlayArgs {
             mml,
             history_id,
             file,
+            section: None,
             waveform,
             volume,
             loop_play: false,
             metronome: false,
             metronome_beat: 4,
             metronome_volume: 0.3,
+            count_in: 0,
+            max_duration: DEFAULT_MAX_DURATION_SECS,
+            from: None,
+            to: None,
             note,
+            audio_device: None,
+            audio_host: None,
+            audio_sink: AudioSinkKind::Device,
+            pcm_file: None,
             midi_out: None,
+            midi_virtual: None,
             midi_channel: 1,
+            midi_clock: false,
+            midi_clock_in: None,
+            with_audio: false,
+            midi_offset_ms: 0,
             no_history,
+            watch: false,
+            watch_from_start: false,
         }
     }
 
@@ -191,25 +371,123 @@ impl PlayArgs {
             mml,
             history_id,
             file,
+            section: None,
             waveform,
             volume,
             loop_play: false,
             metronome: false,
             metronome_beat: 4,
             metronome_volume: 0.3,
+            count_in: 0,
+            max_duration: DEFAULT_MAX_DURATION_SECS,
+            from: None,
+            to: None,
             note,
+            audio_device: None,
+            audio_host: None,
+            audio_sink: AudioSinkKind::Device,
+            pcm_file: None,
             no_history,
+            watch: false,
+            watch_from_start: false,
         }
     }
 }
 
 #[derive(Args, Debug)]
+#[command(group(
+    clap::ArgGroup::new("input")
+        .required(true)
+        .args(["history_id", "file"]),
+))]
 pub struct ExportArgs {
     #[arg(long)]
-    pub history_id: i64,
+    pub history_id: Option<i64>,
+
+    /// Read MML from file (.mml extension required)
+    #[arg(long, short = 'f', value_name = "FILE")]
+    pub file: Option<String>,
 
+    /// ファイル内の指定したセクションだけを書き出す（--file と併用）
+    #[arg(long, value_name = "NAME", conflicts_with = "history_id")]
+    pub section: Option<String>,
+
+    /// 出力ファイル（`-`で標準出力）
     #[arg(short, long)]
     pub output: String,
+
+    /// 出力形式（wav: 16bit WAV、f32le / s16le: ヘッダーなしの生PCM）
+    #[arg(long, value_name = "FORMAT", default_value = "wav")]
+    pub format: ExportFormat,
+
+    /// 波形（省略時は履歴の波形、ファイルの場合はsine）
+    #[arg(short, long)]
+    pub waveform: Option<Waveform>,
+
+    /// 音量（省略時は履歴の音量、ファイルの場合は1.0）
+    #[arg(short, long, value_parser = validate_volume)]
+    pub volume: Option<f32>,
+
+    /// メトロノームのクリックを書き出す
+    #[arg(long, default_value_t = false)]
+    pub metronome: bool,
+
+    #[arg(long, value_parser = validate_metronome_beat, default_value_t = 4)]
+    pub metronome_beat: u8,
+
+    #[arg(long, value_parser = validate_volume, default_value_t = 0.3)]
+    pub metronome_volume: f32,
+
+    /// 演奏前にカウントインする小節数（0-16）
+    #[arg(long, value_name = "BARS", default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=16))]
+    pub count_in: u8,
+
+    /// 演奏時間の上限（秒、0で無制限）
+    #[arg(long, value_name = "SECONDS", default_value_t = DEFAULT_MAX_DURATION_SECS)]
+    pub max_duration: u32,
+
+    /// 書き出しの開始位置（小節番号、マーカー名、または m:ss）
+    #[arg(long, value_name = "POS")]
+    pub from: Option<PlayPosition>,
+
+    /// 書き出しの終了位置（小節番号の場合はその小節の終わりまで）
+    #[arg(long, value_name = "POS")]
+    pub to: Option<PlayPosition>,
+}
+
+#[cfg(test)]
+impl ExportArgs {
+    /// テスト用のファクトリーメソッド（メトロノームなし）
+    #[must_use]
+    pub fn for_test(history_id: i64, output: String) -> Self {
+        Self {
+            history_id: Some(history_id),
+            file: None,
+            section: None,
+            output,
+            format: ExportFormat::Wav,
+            waveform: None,
+            volume: None,
+            metronome: false,
+            metronome_beat: 4,
+            metronome_volume: 0.3,
+            count_in: 0,
+            max_duration: DEFAULT_MAX_DURATION_SECS,
+            from: None,
+            to: None,
+        }
+    }
+}
+
+/// replサブコマンドの引数
+#[derive(Args, Debug)]
+pub struct ReplArgs {
+    /// 波形（`:waveform`で変更可能）
+    #[arg(short, long, default_value = "sine")]
+    pub waveform: Waveform,
+
+    #[arg(short, long, default_value_t = 1.0, value_parser = validate_volume)]
+    pub volume: f32,
 }
 
 #[derive(ValueEnum, Clone, Debug, PartialEq)]
@@ -219,6 +497,32 @@ pub enum Waveform {
     Square,
 }
 
+/// `export`の出力形式（いずれもモノラル・44100Hz）
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    /// 16bit WAV
+    #[default]
+    Wav,
+    /// 32bit浮動小数点リトルエンディアンの生PCM
+    F32le,
+    /// 16bit整数リトルエンディアンの生PCM
+    S16le,
+}
+
+/// 音声の出力先
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum AudioSinkKind {
+    /// オーディオデバイス
+    #[default]
+    Device,
+    /// 出力しない（実時間で再生が進む）
+    Null,
+    /// 生PCM（f32le・モノラル）をファイルに書き込む（--pcm-file で指定）
+    File,
+    /// 生PCM（f32le・モノラル）を標準出力に書き込む
+    Stdout,
+}
+
 // Validation functions
 
 /// Validates that the volume is between 0.0 and 1.0.
@@ -248,6 +552,21 @@ pub fn validate_metronome_beat(v: &str) -> Result<u8, String> {
     }
 }
 
+/// Validates quantize grid (4, 8, 16, 32).
+///
+/// # Errors
+/// Returns an error message if the value is not a valid grid.
+pub fn validate_grid(v: &str) -> Result<u8, String> {
+    let val: u8 = v.parse().map_err(|_| "Invalid number".to_string())?;
+    if crate::mml::transcribe::GRIDS.contains(&val) {
+        Ok(val)
+    } else {
+        Err(format!(
+            "グリッドは 4, 8, 16, 32 のいずれかを指定してください（指定値: {val}）"
+        ))
+    }
+}
+
 /// Maximum allowed length for note field (in characters, not bytes).
 pub const MAX_NOTE_LENGTH: usize = 500;
 
@@ -1,14 +1,23 @@
-use crate::cli::args::{validate_note, ExportArgs, PlayArgs, Waveform};
-use crate::cli::output;
+use crate::cli::args::{
+    validate_note, AudioArgs, AudioSinkKind, AudioSubcommand, ExportArgs, ExportFormat, PlayArgs,
+    ReplArgs, Waveform,
+};
+use crate::cli::{output, repl, transport, watch};
 use crate::{audio, db, mml};
 use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
 use comfy_table::Table;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "midi-output")]
-use crate::cli::args::{MidiArgs, MidiSubcommand};
+use crate::cli::args::{MidiArgs, MidiSubcommand, RecordArgs};
 #[cfg(feature = "midi-output")]
 use crate::midi;
 
+/// 合成・再生のサンプリングレート
+const SAMPLE_RATE: u32 = 44100;
+
 fn determine_should_save(args: &PlayArgs) -> bool {
     // no_historyが指定されている場合は保存しない
     if args.no_history {
@@ -33,6 +42,18 @@ fn handle_midi_list() -> Result<()> {
             output::message_indent(&format!("{i}: {name}"));
         }
     }
+
+    let inputs = midi::list_midi_input_devices()?;
+    if !inputs.is_empty() {
+        output::message("MIDI入力デバイス（--midi-clock-in）:");
+        for (i, name) in inputs.iter().enumerate() {
+            if midi::is_virtual_port(name) {
+                output::message_indent(&format!("{i}: {name}（sine-mmlの仮想ポート）"));
+            } else {
+                output::message_indent(&format!("{i}: {name}"));
+            }
+        }
+    }
     Ok(())
 }
 
@@ -44,19 +65,177 @@ pub fn midi_handler(args: MidiArgs) -> Result<()> {
     }
 }
 
+/// `record`で受信メッセージを確認する間隔
+#[cfg(feature = "midi-output")]
+const RECORD_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// `record`: MIDI入力デバイスの演奏を録音し、MMLに変換して履歴またはファイルに保存する
+#[cfg(feature = "midi-output")]
+#[allow(clippy::needless_pass_by_value)]
+pub fn record_handler(args: RecordArgs) -> Result<()> {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    if let Some(ref note) = args.note {
+        validate_note(note).map_err(|e| anyhow::anyhow!("[CLI-E010] {e}"))?;
+    }
+    if let Some(ref path) = args.output {
+        validate_mml_output(path)?;
+    }
+
+    let mut port = midi::MidiInputPort::open(&args.midi_in).map_err(|e| anyhow::anyhow!("{e}"))?;
+    let interrupt = Arc::new(AtomicBool::new(false));
+    let interrupt_clone = Arc::clone(&interrupt);
+    ctrlc::set_handler(move || {
+        interrupt_clone.store(true, Ordering::SeqCst);
+    })
+    .context("Ctrl+Cハンドラーの設定に失敗しました")?;
+
+    output::info("録音中...");
+    output::message_indent(&format!("デバイス: {}", args.midi_in));
+    output::message_indent(&format!(
+        "テンポ: {}  グリッド: {}分音符",
+        args.tempo, args.grid
+    ));
+    let keys = transport::Transport::stop_only();
+    let mut recorder = midi::NoteRecorder::new();
+    while !keys.stop_requested() && !interrupt.load(Ordering::Relaxed) {
+        recorder.pump(&mut port);
+        std::thread::sleep(RECORD_POLL_INTERVAL);
+    }
+    recorder.pump(&mut port);
+    let notes = recorder.finish(port.origin().elapsed());
+    drop(keys);
+
+    if notes.is_empty() {
+        output::warning("ノートが録音されませんでした");
+        return Ok(());
+    }
+    let mml_string = mml::transcribe::transcribe(
+        &notes,
+        mml::transcribe::TranscribeOptions {
+            bpm: args.tempo,
+            grid: args.grid,
+        },
+    );
+    output::message(&mml_string);
+    save_recording(&args, &mml_string)
+}
+
+/// 録音したMMLの書き出し先（`.mml`ファイル）を確認する
+#[cfg(feature = "midi-output")]
+fn validate_mml_output(path: &str) -> Result<()> {
+    if std::path::Path::new(path)
+        .extension()
+        .and_then(|s| s.to_str())
+        != Some("mml")
+    {
+        bail!("[CLI-E013] 出力ファイルの拡張子は .mml である必要があります: {path}");
+    }
+    Ok(())
+}
+
+/// 録音したMMLを`--output`のファイル、または履歴に保存する
+#[cfg(feature = "midi-output")]
+fn save_recording(args: &RecordArgs, mml_string: &str) -> Result<()> {
+    if let Some(ref path) = args.output {
+        std::fs::write(path, format!("{mml_string}\n"))
+            .with_context(|| format!("{path} に書き込めませんでした"))?;
+        output::success(&format!("✓ {path} に保存しました"));
+        return Ok(());
+    }
+
+    let db = db::Database::init()?;
+    let entry = db::HistoryEntry::new(
+        mml_string.to_string(),
+        db::history::Waveform::Sine,
+        1.0,
+        args.tempo,
+        args.note.clone(),
+    );
+    let id = db.save(&entry).context("履歴の保存に失敗しました")?;
+    output::success(&format!("✓ 履歴に保存しました（履歴ID: {id}）"));
+    Ok(())
+}
+
+fn handle_audio_list(host: Option<&str>) -> Result<()> {
+    let default_host = audio::device::default_host_name();
+    let hosts = match host {
+        Some(name) => {
+            let hosts = audio::device::list_audio_hosts();
+            let index = audio::device::resolve_host(name, &hosts)?;
+            vec![hosts[index]]
+        }
+        None => audio::device::list_audio_hosts(),
+    };
+    if hosts.is_empty() {
+        output::message("オーディオホストが見つかりません");
+        return Ok(());
+    }
+
+    for host in hosts {
+        let label = if host == default_host {
+            format!("{host}（既定）")
+        } else {
+            host.to_string()
+        };
+        output::message(&format!("オーディオホスト: {label}"));
+        let devices = audio::device::list_audio_devices(Some(host))?;
+        if devices.is_empty() {
+            output::message_indent("出力デバイスが見つかりません");
+        }
+        for device in devices {
+            let default = if device.is_default {
+                "（既定）"
+            } else {
+                ""
+            };
+            output::message_indent(&format!("{}: {}{default}", device.id, device.name));
+        }
+    }
+    Ok(())
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn audio_handler(args: AudioArgs) -> Result<()> {
+    match args.command {
+        AudioSubcommand::List { audio_host } => handle_audio_list(audio_host.as_deref()),
+    }
+}
+
+/// `--midi-out`のデバイスに接続する、または`--midi-virtual`の仮想ポートを作成する
+///
+/// 接続と表示用の出力先の名前を返す。MIDI出力の指定がない場合は`None`。
+/// 仮想ポートは接続を閉じるまで他のアプリケーションから購読できる。
+#[cfg(feature = "midi-output")]
+fn open_midi_output(args: &PlayArgs) -> Result<Option<(midi::MidiOutputPort, String)>> {
+    let (conn, device) = if let Some(ref name) = args.midi_virtual {
+        (
+            midi::MidiOutputPort::open_virtual(name),
+            format!("仮想ポート {}:{name}", midi::VIRTUAL_CLIENT_NAME),
+        )
+    } else if let Some(ref device) = args.midi_out {
+        (midi::MidiOutputPort::open(device), device.clone())
+    } else {
+        return Ok(None);
+    };
+    let conn = conn.map_err(|e| anyhow::anyhow!("{e}"))?;
+    Ok(Some((conn, device)))
+}
+
 #[cfg(feature = "midi-output")]
 fn handle_midi_output(
+    conn: midi::MidiOutputPort,
     device: &str,
     channel: u8,
-    _mml_string: &str,
+    options: midi::PlayOptions,
     ast: &mml::Mml,
+    range: &mml::PlayRange,
     loop_play: bool,
 ) -> Result<()> {
     use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::Arc;
 
-    let mut conn = midi::connect_midi_device(device).map_err(|e| anyhow::anyhow!("{e}"))?;
-
     let interrupt = Arc::new(AtomicBool::new(false));
     let interrupt_clone = Arc::clone(&interrupt);
 
@@ -65,85 +244,271 @@ fn handle_midi_output(
     })
     .context("Ctrl+Cハンドラーの設定に失敗しました")?;
 
-    output::info("MIDI再生中... (Ctrl+Cで停止)");
+    output::info("MIDI再生中...");
     output::message_indent(&format!("デバイス: {device}"));
     output::message_indent(&format!("チャンネル: {channel}"));
+    if options.send_clock {
+        output::message_indent("MIDIクロック: 送信");
+    }
 
-    if loop_play {
-        // ループ再生: プログレスバーなし
-        loop {
-            if interrupt.load(Ordering::Relaxed) {
-                break;
-            }
-            midi::play_midi_stream_interruptible(&mut conn, &ast.commands, channel, &interrupt)
-                .map_err(|e| anyhow::anyhow!("{e}"))?;
-        }
+    let mut playback = transport::MidiPlayback::start(
+        conn,
+        &ast.commands,
+        channel,
+        range,
+        options,
+        loop_play,
+        Arc::clone(&interrupt),
+    )?;
+    let timeline = transport::Timeline::from_range(ast, range, SAMPLE_RATE);
+    let completion = transport::run(&mut playback, &timeline)?;
+
+    if completion == transport::Completion::Stopped || interrupt.load(Ordering::Relaxed) {
+        output::success("✓ MIDI再生を中断しました");
     } else {
-        // 単発再生: プログレスバー付き
-        let total_duration_ms = midi::calculate_total_duration_ms(&ast.commands);
+        output::success("✓ MIDI再生完了");
+    }
 
-        // プログレスバースレッドを起動
-        let interrupt_for_progress = Arc::clone(&interrupt);
-        let progress_handle = std::thread::spawn(move || {
-            output::display_midi_progress(total_duration_ms, &interrupt_for_progress);
-        });
+    Ok(())
+}
+
+/// `--with-audio`: 音声とMIDIを同じ位置から同時に再生する
+///
+/// MIDIは`--midi-offset-ms`だけ送信時刻をずらし、音声の出力遅延や音源の発音遅延との差を
+/// 補正する。音声デバイスがない場合はMIDIだけを再生する。
+#[cfg(feature = "midi-output")]
+fn handle_layered_playback(
+    args: &PlayArgs,
+    mml_string: &str,
+    conn: midi::MidiOutputPort,
+    device: &str,
+    ast: &mml::Mml,
+    range: &mml::PlayRange,
+) -> Result<()> {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    let (buffer, count_in_samples) = render_audio(args, ast, range)?;
+    let Some(mut player) = open_audio_player(args)? else {
+        return handle_midi_output(
+            conn,
+            device,
+            args.midi_channel,
+            midi_play_options(args),
+            ast,
+            range,
+            args.loop_play,
+        );
+    };
+
+    let interrupt = Arc::new(AtomicBool::new(false));
+    let interrupt_clone = Arc::clone(&interrupt);
+    ctrlc::set_handler(move || {
+        interrupt_clone.store(true, Ordering::SeqCst);
+    })
+    .context("Ctrl+Cハンドラーの設定に失敗しました")?;
 
-        // MIDI再生実行
-        midi::play_midi_stream_interruptible(&mut conn, &ast.commands, channel, &interrupt)
-            .map_err(|e| anyhow::anyhow!("{e}"))?;
+    let midi_playback = transport::MidiPlayback::new(
+        conn,
+        &ast.commands,
+        args.midi_channel,
+        range,
+        midi_play_options(args),
+        Arc::clone(&interrupt),
+    )?;
+    let history_id_opt = save_history_if_needed(args, mml_string)?;
 
-        // プログレスバースレッドの終了を待機
-        interrupt.store(true, Ordering::SeqCst);
-        let _ = progress_handle.join();
+    output::info("音声とMIDIを再生中...");
+    output::message_indent(&format!("デバイス: {device}"));
+    output::message_indent(&format!("チャンネル: {}", args.midi_channel));
+    if args.midi_offset_ms != 0 {
+        output::message_indent(&format!("MIDIのずれ: {:+}ms", args.midi_offset_ms));
     }
 
-    if interrupt.load(Ordering::Relaxed) && !loop_play {
-        output::success("✓ MIDI再生を中断しました");
-    } else if !loop_play {
-        output::success("✓ MIDI再生完了");
+    player
+        .play_with_loop_start(&buffer, args.loop_play, count_in_samples)
+        .context("音声再生に失敗しました")?;
+    let Some(state) = player.playback_state() else {
+        return Ok(());
+    };
+    let audio_playback = transport::AudioPlayback::new(state, SAMPLE_RATE, range, count_in_samples);
+    let mut playback = transport::LayeredPlayback::start(audio_playback, midi_playback)?;
+    let timeline = transport::Timeline::from_range(ast, range, SAMPLE_RATE);
+    let completion = transport::run(&mut playback, &timeline)?;
+
+    if completion == transport::Completion::Stopped || interrupt.load(Ordering::Relaxed) {
+        output::success("✓ 再生を中断しました");
     } else {
-        output::success("✓ MIDI再生を中断しました");
+        print_completion_message(history_id_opt, args.note.as_ref());
     }
-
     Ok(())
 }
 
-fn handle_audio_playback(args: &PlayArgs, mml_string: &str, ast: &mml::Mml) -> Result<()> {
-    let waveform_type = match args.waveform {
-        Waveform::Sine => audio::waveform::WaveformType::Sine,
-        Waveform::Sawtooth => audio::waveform::WaveformType::Sawtooth,
-        Waveform::Square => audio::waveform::WaveformType::Square,
-    };
+#[cfg(feature = "midi-output")]
+fn midi_play_options(args: &PlayArgs) -> midi::PlayOptions {
+    midi::PlayOptions {
+        send_clock: args.midi_clock,
+        follow: None,
+        offset_ms: args.midi_offset_ms,
+    }
+}
 
-    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
-    let volume_u8 = (args.volume * 100.0) as u8;
-    let sample_rate = 44100;
+/// `--midi-clock-in`: 外部MIDIクロックをテンポの基準として音声またはMIDIを再生する
+#[cfg(feature = "midi-output")]
+fn handle_external_clock(
+    args: &PlayArgs,
+    mml_string: &str,
+    clock_device: &str,
+    ast: &mml::Mml,
+) -> Result<()> {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
 
-    let mut synth = audio::synthesizer::Synthesizer::new(sample_rate, volume_u8, waveform_type);
-    let mut buffer = synth
-        .synthesize(ast)
-        .map_err(|e| anyhow::anyhow!("{e}"))
-        .context("音声合成に失敗しました")?;
+    use crate::cli::sync;
 
-    if args.metronome {
-        let tempo_events = ast.get_tempo_events(sample_rate);
-        synth.mix_metronome_with_tempo_events(
-            &mut buffer,
-            f64::from(sample_rate),
-            &tempo_events,
-            args.metronome_beat,
-            args.metronome_volume,
-        );
-        audio::synthesizer::normalize_samples(&mut buffer);
+    let mut port = midi::MidiInputPort::open(clock_device).map_err(|e| anyhow::anyhow!("{e}"))?;
+    let clock = midi::ExternalClock::new(port.origin());
+
+    let interrupt = Arc::new(AtomicBool::new(false));
+    let interrupt_clone = Arc::clone(&interrupt);
+    ctrlc::set_handler(move || {
+        interrupt_clone.store(true, Ordering::SeqCst);
+    })
+    .context("Ctrl+Cハンドラーの設定に失敗しました")?;
+
+    let fallback_bpm = f64::from(ast.get_tempo());
+
+    if let Some((conn, device)) = open_midi_output(args)? {
+        let options = midi::PlayOptions {
+            follow: Some(clock.clone()),
+            ..midi::PlayOptions::default()
+        };
+        let mut playback = transport::MidiPlayback::new(
+            conn,
+            &ast.commands,
+            args.midi_channel,
+            &sync::beat_range(ast),
+            options,
+            Arc::clone(&interrupt),
+        )?;
+
+        output::info("外部MIDIクロックを待機中...");
+        output::message_indent(&format!("クロック入力: {clock_device}"));
+        output::message_indent(&format!("デバイス: {device}"));
+        output::message_indent(&format!("チャンネル: {}", args.midi_channel));
+        let keys = transport::Transport::stop_only();
+        sync::follow(&mut port, &clock, &mut playback, fallback_bpm, || {
+            keys.stop_requested() || interrupt.load(Ordering::Relaxed)
+        })?;
+        output::success("✓ MIDI再生を終了しました");
+        return Ok(());
     }
 
+    let Some(mut player) = open_audio_player(args)? else {
+        return Ok(());
+    };
+    let mut state = audio::player::PlaybackState::new(Vec::new(), false, 0);
+    state.set_paused(true);
+    let state = Arc::new(Mutex::new(state));
+    player
+        .play_shared(Arc::clone(&state))
+        .context("音声再生に失敗しました")?;
+
+    let mut synth = new_synthesizer(&args.waveform, args.volume);
+    let song = ast.clone();
+    let render = move |bpm: f64| {
+        synth
+            .synthesize_at_tempo(&song, bpm)
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .context("音声合成に失敗しました")
+    };
+    let mut target = sync::AudioSyncTarget::new(render, state, SAMPLE_RATE);
+
     let history_id_opt = save_history_if_needed(args, mml_string)?;
-    play_audio_buffer(&buffer, mml_string, args.loop_play)?;
+    output::info("外部MIDIクロックを待機中...");
+    output::message_indent(&format!("クロック入力: {clock_device}"));
+    let keys = transport::Transport::stop_only();
+    sync::follow(&mut port, &clock, &mut target, fallback_bpm, || {
+        keys.stop_requested() || interrupt.load(Ordering::Relaxed)
+    })?;
+    print_completion_message(history_id_opt, args.note.as_ref());
+    Ok(())
+}
+
+fn handle_audio_playback(
+    args: &PlayArgs,
+    mml_string: &str,
+    ast: &mml::Mml,
+    range: &mml::PlayRange,
+) -> Result<()> {
+    let (buffer, count_in_samples) = render_audio(args, ast, range)?;
+    let player = open_audio_player(args)?;
+
+    let history_id_opt = save_history_if_needed(args, mml_string)?;
+    let timeline = transport::Timeline::from_range(ast, range, SAMPLE_RATE);
+    if let Some(player) = player {
+        play_audio_buffer(
+            player,
+            &buffer,
+            range,
+            &timeline,
+            args.loop_play,
+            count_in_samples,
+        )?;
+    }
     print_completion_message(history_id_opt, args.note.as_ref());
 
     Ok(())
 }
 
+/// 再生範囲の音声を合成し、メトロノーム・カウントインを加える
+///
+/// 合成したサンプルとカウントインのサンプル数を返す。
+fn render_audio(
+    args: &PlayArgs,
+    ast: &mml::Mml,
+    range: &mml::PlayRange,
+) -> Result<(Vec<f32>, usize)> {
+    let (synth, mut buffer) = synthesize(ast, &args.waveform, args.volume)?;
+
+    let click_track = ClickTrack {
+        metronome: args.metronome,
+        beat: args.metronome_beat,
+        volume: args.metronome_volume,
+        count_in: args.count_in,
+    };
+    let count_in_samples = click_track.mix(&synth, ast, &mut buffer, range);
+    Ok((buffer, count_in_samples))
+}
+
+/// 波形・音量を指定してMML全体を合成する
+fn synthesize(
+    ast: &mml::Mml,
+    waveform: &Waveform,
+    volume: f32,
+) -> Result<(audio::synthesizer::Synthesizer, Vec<f32>)> {
+    let mut synth = new_synthesizer(waveform, volume);
+    let buffer = synth
+        .synthesize(ast)
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .context("音声合成に失敗しました")?;
+    Ok((synth, buffer))
+}
+
+/// 波形・音量を指定して合成器を作成する
+fn new_synthesizer(waveform: &Waveform, volume: f32) -> audio::synthesizer::Synthesizer {
+    let waveform_type = match waveform {
+        Waveform::Sine => audio::waveform::WaveformType::Sine,
+        Waveform::Sawtooth => audio::waveform::WaveformType::Sawtooth,
+        Waveform::Square => audio::waveform::WaveformType::Square,
+    };
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let volume_u8 = (volume * 100.0) as u8;
+
+    audio::synthesizer::Synthesizer::new(SAMPLE_RATE, volume_u8, waveform_type)
+}
+
 fn save_history_if_needed(args: &PlayArgs, mml_string: &str) -> Result<Option<i64>> {
     if !determine_should_save(args) {
         return Ok(None);
@@ -173,17 +538,129 @@ fn save_history_if_needed(args: &PlayArgs, mml_string: &str) -> Result<Option<i6
     }
 }
 
-fn play_audio_buffer(buffer: &[f32], mml_string: &str, loop_play: bool) -> Result<()> {
-    match audio::player::AudioPlayer::new() {
-        Ok(mut player) => {
-            player
-                .play(buffer, loop_play)
-                .context("音声再生に失敗しました")?;
-            output::display_play_progress(mml_string, buffer, loop_play)?;
+/// メトロノームとカウントインの設定
+struct ClickTrack {
+    metronome: bool,
+    beat: u8,
+    volume: f32,
+    count_in: u8,
+}
+
+impl ClickTrack {
+    /// 演奏サンプルにメトロノームをミックスし、再生範囲を切り出して先頭にカウントインを追加する
+    ///
+    /// 曲全体を合成・正規化してから切り出すため、範囲の音は曲中で聴くのと同じになる。
+    /// カウントインは範囲の開始位置のテンポと拍子で刻む。
+    /// 追加したカウントインのサンプル数を返す（ループ再生時の戻り位置）。
+    fn mix(
+        &self,
+        synth: &audio::synthesizer::Synthesizer,
+        ast: &mml::Mml,
+        buffer: &mut Vec<f32>,
+        range: &mml::PlayRange,
+    ) -> usize {
+        let sample_rate = f64::from(synth.sample_rate);
+        if self.metronome {
+            let tempo_events = ast.get_tempo_events(synth.sample_rate);
+            synth.mix_metronome_with_tempo_events(
+                buffer,
+                sample_rate,
+                &tempo_events,
+                self.beat,
+                self.volume,
+            );
+            audio::synthesizer::normalize_samples(buffer);
         }
-        Err(_) => {
-            eprintln!("Warning: Audio device not found. Skipping playback.");
+
+        buffer.truncate(range.end.sample);
+        buffer.drain(..range.start.sample.min(buffer.len()));
+
+        if self.count_in == 0 {
+            return 0;
+        }
+
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let start_event = mml::TempoEvent {
+            sample_position: 0,
+            bpm: range.start.bpm.round() as u16,
+            curve: None,
+            meter: Some(mml::Meter {
+                time_signature: range.start.time_signature,
+                beat_in_bar: 0.0,
+            }),
+        };
+        let count_in = synth.generate_count_in(
+            sample_rate,
+            &[start_event],
+            self.count_in,
+            self.beat,
+            self.volume,
+        );
+        let count_in_samples = count_in.len();
+        buffer.splice(0..0, count_in);
+        audio::synthesizer::normalize_samples(buffer);
+        count_in_samples
+    }
+}
+
+/// 出力先（`--audio-sink`）を開く
+///
+/// オーディオデバイスに出力する場合、デバイス・ホストを指定していなければ、既定のデバイスが
+/// 使えない時に警告して`None`を返す（指定したデバイスが開けない場合はエラー）。
+fn open_audio_player(args: &PlayArgs) -> Result<Option<audio::player::AudioPlayer>> {
+    use audio::player::AudioPlayer;
+    use audio::sink::StreamSink;
+
+    let device_selected = args.audio_device.is_some() || args.audio_host.is_some();
+    if device_selected && args.audio_sink != AudioSinkKind::Device {
+        bail!("[CLI-E012] --audio-device / --audio-host は --audio-sink device の場合のみ指定できます");
+    }
+
+    let sink = match args.audio_sink {
+        AudioSinkKind::Device => {
+            return match AudioPlayer::with_device(
+                args.audio_host.as_deref(),
+                args.audio_device.as_deref(),
+            ) {
+                Ok(player) => Ok(Some(player)),
+                Err(_) if !device_selected => {
+                    output::warning("Warning: Audio device not found. Skipping playback.");
+                    Ok(None)
+                }
+                Err(e) => Err(e.into()),
+            };
+        }
+        AudioSinkKind::Null => StreamSink::null(SAMPLE_RATE),
+        AudioSinkKind::File => {
+            let path = args
+                .pcm_file
+                .as_deref()
+                .context("--audio-sink file には --pcm-file を指定してください")?;
+            StreamSink::file(path, SAMPLE_RATE)?
         }
+        AudioSinkKind::Stdout => {
+            output::use_stderr();
+            StreamSink::stdout(SAMPLE_RATE)
+        }
+    };
+    Ok(Some(AudioPlayer::with_sink(Box::new(sink))))
+}
+
+fn play_audio_buffer(
+    mut player: audio::player::AudioPlayer,
+    buffer: &[f32],
+    range: &mml::PlayRange,
+    timeline: &transport::Timeline,
+    loop_play: bool,
+    count_in_samples: usize,
+) -> Result<()> {
+    player
+        .play_with_loop_start(buffer, loop_play, count_in_samples)
+        .context("音声再生に失敗しました")?;
+    if let Some(state) = player.playback_state() {
+        let mut playback =
+            transport::AudioPlayback::new(state, SAMPLE_RATE, range, count_in_samples);
+        transport::run(&mut playback, timeline)?;
     }
     Ok(())
 }
@@ -207,20 +684,313 @@ pub fn play_handler(args: PlayArgs) -> Result<()> {
         output::warning("Warning: --note is ignored when --no-history is specified");
     }
 
-    let mml_string = resolve_mml_input(&args)?;
-
     if let Some(ref note) = args.note {
         validate_note(note).map_err(|e| anyhow::anyhow!("[CLI-E010] {e}"))?;
     }
 
-    let ast = mml::parse(&mml_string).map_err(|e| anyhow::anyhow!("MML parse error: {e:?}"))?;
+    if args.watch {
+        let Some(path) = args.file.as_deref() else {
+            bail!("[CLI-E011] --watch は --file と併用してください");
+        };
+        return watch_handler(&args, path);
+    }
+
+    let mml_string = resolve_mml_input(&args)?;
+
+    let ast = parse_mml(&mml_string, args.max_duration)?;
+    let range = resolve_play_range(&ast, args.from.as_ref(), args.to.as_ref())?;
+
+    #[cfg(feature = "midi-output")]
+    if let Some(ref clock_device) = args.midi_clock_in {
+        return handle_external_clock(&args, &mml_string, clock_device, &ast);
+    }
+
+    #[cfg(feature = "midi-output")]
+    if let Some((conn, device)) = open_midi_output(&args)? {
+        if args.with_audio {
+            return handle_layered_playback(&args, &mml_string, conn, &device, &ast, &range);
+        }
+        return handle_midi_output(
+            conn,
+            &device,
+            args.midi_channel,
+            midi_play_options(&args),
+            &ast,
+            &range,
+            args.loop_play,
+        );
+    }
+
+    handle_audio_playback(&args, &mml_string, &ast, &range)
+}
+
+/// `--watch`で履歴に保存するまでの待ち時間（内容がこの間変わらなければ保存する）
+const WATCH_HISTORY_DELAY: Duration = Duration::from_secs(10);
 
+/// 再読み込みの前後で引き継ぐ再生状態
+struct Resume {
+    /// 再生位置（`None`の場合は再生範囲の先頭から）
+    position: Option<f64>,
+    paused: bool,
+    looping: bool,
+}
+
+impl Resume {
+    /// 再生に反映する。位置が新しい再生範囲の外にある場合は先頭から再生する
+    fn apply(
+        &self,
+        playback: &mut impl transport::Playback,
+        timeline: &transport::Timeline,
+    ) -> Result<()> {
+        playback.set_looping(self.looping);
+        if let Some(position) = self.position.filter(|&p| timeline.contains(p)) {
+            playback.seek(position)?;
+        }
+        playback.set_paused(self.paused)
+    }
+}
+
+/// `--watch`での再生先
+enum WatchOutput {
+    Audio,
     #[cfg(feature = "midi-output")]
-    if let Some(ref device) = args.midi_out {
-        return handle_midi_output(device, args.midi_channel, &mml_string, &ast, args.loop_play);
+    Midi {
+        conn: Option<midi::MidiOutputPort>,
+        channel: u8,
+        options: midi::PlayOptions,
+        interrupt: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    },
+}
+
+impl WatchOutput {
+    fn open(args: &PlayArgs) -> Result<Self> {
+        #[cfg(feature = "midi-output")]
+        if let Some((conn, _)) = open_midi_output(args)? {
+            use std::sync::atomic::{AtomicBool, Ordering};
+            use std::sync::Arc;
+
+            let interrupt = Arc::new(AtomicBool::new(false));
+            let interrupt_clone = Arc::clone(&interrupt);
+            ctrlc::set_handler(move || {
+                interrupt_clone.store(true, Ordering::SeqCst);
+            })
+            .context("Ctrl+Cハンドラーの設定に失敗しました")?;
+            return Ok(Self::Midi {
+                conn: Some(conn),
+                channel: args.midi_channel,
+                options: midi_play_options(args),
+                interrupt,
+            });
+        }
+        let _ = args;
+        Ok(Self::Audio)
+    }
+
+    /// 再生し、終わり方と再読み込み後に引き継ぐ状態を返す
+    fn play(
+        &mut self,
+        args: &PlayArgs,
+        ast: &mml::Mml,
+        range: &mml::PlayRange,
+        resume: &Resume,
+        transport: &transport::Transport,
+        changed: impl FnMut() -> bool,
+    ) -> Result<(transport::Completion, Resume)> {
+        use transport::Playback;
+
+        let timeline = transport::Timeline::from_range(ast, range, SAMPLE_RATE);
+        match self {
+            Self::Audio => {
+                let (buffer, count_in_samples) = render_audio(args, ast, range)?;
+                let Some(mut player) = open_audio_player(args)? else {
+                    return Ok((
+                        transport::Completion::Finished,
+                        Resume {
+                            position: None,
+                            ..*resume
+                        },
+                    ));
+                };
+                let state = std::sync::Arc::new(std::sync::Mutex::new(
+                    audio::player::PlaybackState::new(buffer, false, count_in_samples),
+                ));
+                let mut playback = transport::AudioPlayback::new(
+                    std::sync::Arc::clone(&state),
+                    SAMPLE_RATE,
+                    range,
+                    count_in_samples,
+                );
+                resume.apply(&mut playback, &timeline)?;
+                player
+                    .play_shared(state)
+                    .context("音声再生に失敗しました")?;
+                let completion = transport.run_until(&mut playback, &timeline, changed)?;
+                let next = Resume {
+                    position: Some(playback.position()),
+                    paused: playback.is_paused(),
+                    looping: playback.is_looping(),
+                };
+                Ok((completion, next))
+            }
+            #[cfg(feature = "midi-output")]
+            Self::Midi {
+                conn,
+                channel,
+                options,
+                interrupt,
+            } => {
+                let connection = conn
+                    .take()
+                    .ok_or_else(|| anyhow::anyhow!("MIDI接続が失われました"))?;
+                let mut playback = transport::MidiPlayback::new(
+                    connection,
+                    &ast.commands,
+                    *channel,
+                    range,
+                    options.clone(),
+                    std::sync::Arc::clone(interrupt),
+                )?;
+                resume.apply(&mut playback, &timeline)?;
+                let mut completion = transport.run_until(&mut playback, &timeline, changed)?;
+                if interrupt.load(std::sync::atomic::Ordering::Relaxed) {
+                    completion = transport::Completion::Stopped;
+                }
+                let next = Resume {
+                    position: Some(playback.position()),
+                    paused: playback.is_paused(),
+                    looping: playback.is_looping(),
+                };
+                *conn = Some(playback.into_connection()?);
+                Ok((completion, next))
+            }
+        }
     }
+}
 
-    handle_audio_playback(&args, &mml_string, &ast)
+/// `play --watch`: ファイルの保存を監視し、保存のたびに再パースして再生し直す
+///
+/// パースに失敗した場合は診断を表示して次の保存を待つ。再生を最後まで終えた後も
+/// 終了せずに待機し、`q`で終了する。履歴への保存は間引く（[`watch::HistoryDebouncer`]）。
+fn watch_handler(args: &PlayArgs, path: &str) -> Result<()> {
+    let mut watcher = watch::FileWatcher::new(vec![PathBuf::from(path)]);
+    let mut history = watch::HistoryDebouncer::new(WATCH_HISTORY_DELAY);
+    let mut output = WatchOutput::open(args)?;
+
+    output::info(&format!("監視中: {path}（保存すると再生し直します）"));
+    let transport = transport::Transport::start();
+    let mut resume = Resume {
+        position: None,
+        paused: false,
+        looping: args.loop_play,
+    };
+
+    loop {
+        let completion = match load_watched(args, path, &mut watcher) {
+            Ok((mml_string, ast, range)) => {
+                if determine_should_save(args) {
+                    history.update(&mml_string, Instant::now());
+                }
+                let changed = || {
+                    if let Some(mml) = history.due(Instant::now()) {
+                        save_watched_history(args, &mml);
+                    }
+                    watcher.changed()
+                };
+                let (completion, next) =
+                    output.play(args, &ast, &range, &resume, &transport, changed)?;
+                resume = next;
+                completion
+            }
+            Err(e) => {
+                output::error(&format!("{e:#}"));
+                transport::Completion::Finished
+            }
+        };
+
+        match completion {
+            transport::Completion::Stopped => break,
+            transport::Completion::Interrupted => {
+                output::info("変更を検出しました。再読み込みします");
+                if args.watch_from_start {
+                    resume.position = None;
+                }
+            }
+            transport::Completion::Finished => {
+                output::message("保存を待っています...（qで終了）");
+                resume.position = None;
+                loop {
+                    if transport.stop_requested() {
+                        if let Some(mml) = history.flush() {
+                            save_watched_history(args, &mml);
+                        }
+                        return Ok(());
+                    }
+                    if let Some(mml) = history.due(Instant::now()) {
+                        save_watched_history(args, &mml);
+                    }
+                    if watcher.changed() {
+                        break;
+                    }
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+            }
+        }
+    }
+
+    if let Some(mml) = history.flush() {
+        save_watched_history(args, &mml);
+    }
+    Ok(())
+}
+
+/// 監視中のファイルを読み込み、パースして再生範囲を求める
+///
+/// インクルードしたファイルも監視対象に加える。ファイルは1回だけ読み込み、
+/// 監視対象とパースするMMLが同じ内容から得られるようにする。
+fn load_watched(
+    args: &PlayArgs,
+    path: &str,
+    watcher: &mut watch::FileWatcher,
+) -> Result<(String, mml::Mml, mml::PlayRange)> {
+    let source = mml::load_mml_source(path)?;
+    let mml_string = source.section(args.section.as_deref());
+    watcher.watch(source.files);
+
+    let mml_string = mml_string?;
+    let ast = parse_mml(&mml_string, args.max_duration)?;
+    let range = resolve_play_range(&ast, args.from.as_ref(), args.to.as_ref())?;
+    Ok((mml_string, ast, range))
+}
+
+fn save_watched_history(args: &PlayArgs, mml: &str) {
+    if let Ok(Some(id)) = save_history_if_needed(args, mml) {
+        output::info(&format!("履歴に保存しました（履歴ID: {id}）"));
+    }
+}
+
+/// `--from` / `--to`の指定から再生範囲を求める
+fn resolve_play_range(
+    ast: &mml::Mml,
+    from: Option<&mml::PlayPosition>,
+    to: Option<&mml::PlayPosition>,
+) -> Result<mml::PlayRange> {
+    ast.play_range(from, to, SAMPLE_RATE)
+        .map_err(|e| anyhow::anyhow!("{e}"))
+}
+
+/// MMLをパースし、警告（小節の拍数の不一致など）を表示する
+///
+/// `max_duration`は演奏時間の上限（秒）。0の場合は制限しない。
+fn parse_mml(mml_string: &str, max_duration: u32) -> Result<mml::Mml> {
+    let options = mml::ParseOptions {
+        max_duration_secs: (max_duration > 0).then_some(max_duration),
+    };
+    let (ast, warnings) = mml::parse_with_options(mml_string, &options)
+        .map_err(|e| anyhow::anyhow!("MML parse error: {e:?}"))?;
+    for warning in &warnings {
+        output::warning(&format!("Warning: {warning}"));
+    }
+    Ok(ast)
 }
 
 fn resolve_mml_input(args: &PlayArgs) -> Result<String> {
@@ -233,7 +1003,9 @@ fn resolve_mml_input(args: &PlayArgs) -> Result<String> {
                 .with_context(|| format!("[CLI-E002] 履歴ID {id} が見つかりません"))?;
             Ok(entry.mml)
         }
-        (None, None, Some(file_path)) => mml::read_mml_file(file_path),
+        (None, None, Some(file_path)) => {
+            mml::read_mml_file_section(file_path, args.section.as_deref())
+        }
         (None, None, None) => {
             bail!("[CLI-E001] play コマンドでは、MML文字列、--history-id、または --file のいずれか一方を指定してください");
         }
@@ -292,6 +1064,7 @@ fn history_logic(db: &db::Database) -> Result<()> {
 /// # Errors
 /// Returns `anyhow::Result` if:
 /// - History ID not found
+/// - MML file or section not found
 /// - Path traversal detected
 /// - WAV export fails
 #[allow(clippy::needless_pass_by_value)]
@@ -304,34 +1077,242 @@ pub fn export_handler(args: ExportArgs) -> Result<()> {
 }
 
 fn export_logic(db: &db::Database, args: &ExportArgs) -> Result<()> {
-    let entry = db
-        .get_by_id(args.history_id)
-        .context(format!("履歴ID {} が見つかりません", args.history_id))?;
+    let (mml_string, waveform, volume) = match (args.history_id, &args.file) {
+        (Some(id), None) => {
+            let entry = db
+                .get_by_id(id)
+                .context(format!("履歴ID {id} が見つかりません"))?;
+            let waveform = match entry.waveform {
+                db::Waveform::Sine => Waveform::Sine,
+                db::Waveform::Sawtooth => Waveform::Sawtooth,
+                db::Waveform::Square => Waveform::Square,
+            };
+            (entry.mml, waveform, entry.volume)
+        }
+        (None, Some(file_path)) => (
+            mml::read_mml_file_section(file_path, args.section.as_deref())?,
+            Waveform::Sine,
+            1.0,
+        ),
+        _ => unreachable!("clap should prevent this"),
+    };
+    let waveform = args.waveform.clone().unwrap_or(waveform);
+    let volume = args.volume.unwrap_or(volume);
 
-    let ast = mml::parse(&entry.mml).map_err(|e| anyhow::anyhow!("MML parse error: {e:?}"))?;
+    let ast = parse_mml(&mml_string, args.max_duration)?;
+    let range = resolve_play_range(&ast, args.from.as_ref(), args.to.as_ref())?;
 
-    let waveform_type = match entry.waveform {
-        db::Waveform::Sine => audio::waveform::WaveformType::Sine,
-        db::Waveform::Sawtooth => audio::waveform::WaveformType::Sawtooth,
-        db::Waveform::Square => audio::waveform::WaveformType::Square,
+    let (synth, mut buffer) = synthesize(&ast, &waveform, volume)?;
+
+    let click_track = ClickTrack {
+        metronome: args.metronome,
+        beat: args.metronome_beat,
+        volume: args.metronome_volume,
+        count_in: args.count_in,
     };
+    click_track.mix(&synth, &ast, &mut buffer, &range);
 
-    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
-    let volume_u8 = (entry.volume * 100.0) as u8;
+    write_export(&buffer, &args.output, args.format)?;
 
-    let sample_rate = 44100;
-    let mut synth = audio::synthesizer::Synthesizer::new(sample_rate, volume_u8, waveform_type);
-    let buffer = synth
-        .synthesize(&ast)
-        .map_err(|e| anyhow::anyhow!("{e}"))
-        .context("音声合成に失敗しました")?;
+    if args.output == STDOUT_PATH {
+        output::success("✓ エクスポート完了: 標準出力");
+    } else {
+        output::success(&format!("✓ エクスポート完了: {}", args.output));
+    }
+
+    Ok(())
+}
+
+/// 標準出力を表す出力先
+const STDOUT_PATH: &str = "-";
+
+/// 合成したサンプルを出力先（ファイルまたは標準出力）に指定の形式で書き出す
+fn write_export(buffer: &[f32], output_path: &str, format: ExportFormat) -> Result<()> {
+    use audio::exporter::PcmFormat;
+
+    let format = match format {
+        ExportFormat::Wav => PcmFormat::Wav,
+        ExportFormat::F32le => PcmFormat::F32le,
+        ExportFormat::S16le => PcmFormat::S16le,
+    };
+    if output_path == STDOUT_PATH {
+        output::use_stderr();
+        audio::exporter::write_pcm(buffer, format, std::io::stdout().lock())
+            .context("標準出力への書き出しに失敗しました")?;
+    } else if format == PcmFormat::Wav {
+        audio::exporter::export_wav(buffer, output_path)
+            .context("WAVファイルの書き出しに失敗しました")?;
+    } else {
+        let file = std::fs::File::create(output_path)
+            .with_context(|| format!("ファイルを作成できません: {output_path}"))?;
+        audio::exporter::write_pcm(buffer, format, std::io::BufWriter::new(file))
+            .context("PCMファイルの書き出しに失敗しました")?;
+    }
+    Ok(())
+}
+
+/// replサブコマンドのハンドラー
+///
+/// # Errors
+/// Returns `anyhow::Result` if DB initialization or terminal I/O fails.
+#[allow(clippy::needless_pass_by_value)]
+pub fn repl_handler(args: ReplArgs) -> Result<()> {
+    let db = db::Database::init()?;
+    let mut session = repl::ReplSession::new(args.waveform.clone(), args.volume, SAMPLE_RATE);
+    output::info("MML対話モード（:help でコマンド一覧、:quit で終了）");
+    repl_logic(
+        &db,
+        &mut session,
+        &mut std::io::stdin().lock(),
+        &mut std::io::stdout(),
+        &mut play_repl_audio,
+    )
+}
+
+/// 1行ずつ読み込み、MMLは新しい行の部分を再生し、`:`で始まる行はコマンドとして実行する
+///
+/// 行やコマンドのエラーは表示して入力を続ける。
+fn repl_logic<R: std::io::BufRead, W: std::io::Write>(
+    db: &db::Database,
+    session: &mut repl::ReplSession,
+    stdin: &mut R,
+    stdout: &mut W,
+    play: &mut impl FnMut(&[f32], &mml::PlayRange) -> Result<()>,
+) -> Result<()> {
+    loop {
+        write!(stdout, "mml> ")?;
+        stdout.flush()?;
+
+        let mut line = String::new();
+        if stdin.read_line(&mut line)? == 0 {
+            writeln!(stdout)?;
+            return Ok(());
+        }
+
+        let result = repl::ReplInput::parse(&line).and_then(|input| match input {
+            repl::ReplInput::Empty => Ok(true),
+            repl::ReplInput::Mml(text) => {
+                repl_append(session, &text, stdout, play)?;
+                Ok(true)
+            }
+            repl::ReplInput::Command(command) => repl_command(db, session, command, stdout),
+        });
+        match result {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(e) => writeln!(stdout, "{e:#}")?,
+        }
+    }
+}
 
-    let output_path = std::path::Path::new(&args.output);
-    audio::exporter::export_wav(&buffer, output_path)
-        .context("WAVファイルの書き出しに失敗しました")?;
+/// 行をセッションに加え、新しい行の部分を再生する
+fn repl_append<W: std::io::Write>(
+    session: &mut repl::ReplSession,
+    text: &str,
+    stdout: &mut W,
+    play: &mut impl FnMut(&[f32], &mml::PlayRange) -> Result<()>,
+) -> Result<()> {
+    let appended = session.append(text)?;
+    for warning in &appended.warnings {
+        writeln!(stdout, "Warning: {warning}")?;
+    }
+    let Some(range) = appended.range else {
+        return Ok(());
+    };
 
-    output::success(&format!("✓ エクスポート完了: {}", args.output));
+    // 全体を合成してから新しい行の部分を切り出す（前の行の余韻・正規化を揃えるため）
+    let (_, mut buffer) = synthesize(&appended.ast, &session.waveform, session.volume)?;
+    buffer.truncate(range.end.sample);
+    buffer.drain(..range.start.sample.min(buffer.len()));
+    play(&buffer, &range)
+}
+
+/// コマンドを実行する。終了する場合は`false`を返す
+fn repl_command<W: std::io::Write>(
+    db: &db::Database,
+    session: &mut repl::ReplSession,
+    command: repl::ReplCommand,
+    stdout: &mut W,
+) -> Result<bool> {
+    match command {
+        repl::ReplCommand::Waveform(waveform) => {
+            if let Some(name) = waveform.to_possible_value() {
+                writeln!(stdout, "波形: {}", name.get_name())?;
+            }
+            session.waveform = waveform;
+        }
+        repl::ReplCommand::Save(note) => {
+            if session.lines().is_empty() {
+                bail!("保存するMMLがありません");
+            }
+            if let Some(ref note) = note {
+                validate_note(note).map_err(|e| anyhow::anyhow!("[CLI-E010] {e}"))?;
+            }
+            let db_waveform = match session.waveform {
+                Waveform::Sine => db::history::Waveform::Sine,
+                Waveform::Sawtooth => db::history::Waveform::Sawtooth,
+                Waveform::Square => db::history::Waveform::Square,
+            };
+            let entry =
+                db::HistoryEntry::new(session.mml(), db_waveform, session.volume, 120, note);
+            let id = db.save(&entry).context("履歴の保存に失敗しました")?;
+            writeln!(stdout, "✓ 履歴に保存しました（履歴ID: {id}）")?;
+        }
+        repl::ReplCommand::Undo => match session.undo() {
+            Some(line) => writeln!(stdout, "取り消しました: {line}")?,
+            None => writeln!(stdout, "取り消す行がありません")?,
+        },
+        repl::ReplCommand::Export(path) => {
+            if path.contains("..") {
+                bail!("Path traversal detected: '..' is not allowed in output path");
+            }
+            if session.lines().is_empty() {
+                bail!("書き出すMMLがありません");
+            }
+            let ast = session.parse()?;
+            let (_, buffer) = synthesize(&ast, &session.waveform, session.volume)?;
+            audio::exporter::export_wav(&buffer, &path)
+                .context("WAVファイルの書き出しに失敗しました")?;
+            writeln!(stdout, "✓ エクスポート完了: {path}")?;
+        }
+        repl::ReplCommand::Show => {
+            if session.lines().is_empty() {
+                writeln!(stdout, "（入力なし）")?;
+            }
+            for (i, line) in session.lines().iter().enumerate() {
+                writeln!(stdout, "{:>3}: {line}", i + 1)?;
+            }
+        }
+        repl::ReplCommand::Help => {
+            for help in [
+                "MML            入力した行を再生（オクターブ・テンポなどは前の行から引き継ぐ）",
+                ":waveform <波形>  波形を変更（sine / sawtooth / square）",
+                ":save [メモ]      入力したMMLを履歴に保存",
+                ":undo             最後の行を取り消す",
+                ":export <ファイル> 入力したMMLをWAVファイルに書き出す",
+                ":show             入力したMMLを表示",
+                ":quit             終了",
+            ] {
+                writeln!(stdout, "  {help}")?;
+            }
+        }
+        repl::ReplCommand::Quit => return Ok(false),
+    }
+    Ok(true)
+}
 
+/// 対話モードで切り出した音声を再生し、終わるまで待つ
+fn play_repl_audio(buffer: &[f32], range: &mml::PlayRange) -> Result<()> {
+    let mut player = audio::player::AudioPlayer::new()
+        .map_err(|_| anyhow::anyhow!("Warning: Audio device not found. Skipping playback."))?;
+    player
+        .play(buffer, false)
+        .context("音声再生に失敗しました")?;
+    if let Some(state) = player.playback_state() {
+        let mut playback = transport::AudioPlayback::new(state, SAMPLE_RATE, range, 0);
+        let timeline = transport::Timeline::new(range.start.seconds, range.end.seconds, Vec::new());
+        transport::Transport::passive().run(&mut playback, &timeline)?;
+    }
     Ok(())
 }
 
@@ -440,10 +1421,7 @@ mod tests {
 
     #[test]
     fn test_export_handler_path_traversal() {
-        let args = ExportArgs {
-            history_id: 1,
-            output: "../unsafe.wav".to_string(),
-        };
+        let args = ExportArgs::for_test(1, "../unsafe.wav".to_string());
         let result = export_handler(args);
         assert!(result.is_err());
         assert!(result
@@ -483,10 +1461,7 @@ mod tests {
     #[test]
     fn test_export_logic_not_found() {
         let db = db::Database::open_in_memory().unwrap();
-        let args = ExportArgs {
-            history_id: 999,
-            output: "test.wav".to_string(),
-        };
+        let args = ExportArgs::for_test(999, "test.wav".to_string());
         let result = export_logic(&db, &args);
         assert!(result.is_err());
         assert!(result
@@ -505,10 +1480,7 @@ mod tests {
         let path = dir.join("test_export.wav");
         let path_str = path.to_string_lossy().to_string();
 
-        let args = ExportArgs {
-            history_id: id,
-            output: path_str,
-        };
+        let args = ExportArgs::for_test(id, path_str);
 
         let result = export_logic(&db, &args);
         assert!(result.is_ok(), "export_logic failed: {:?}", result.err());
@@ -517,6 +1489,149 @@ mod tests {
         let _ = std::fs::remove_file(path);
     }
 
+    #[test]
+    fn test_export_logic_raw_pcm_formats() {
+        let db = db::Database::open_in_memory().unwrap();
+        let entry = db::HistoryEntry::new("T120 C".to_string(), db::Waveform::Sine, 0.5, 120, None);
+        let id = db.save(&entry).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+
+        // 4分音符（0.5秒）= 22050サンプル
+        for (format, bytes_per_sample) in [(ExportFormat::S16le, 2), (ExportFormat::F32le, 4)] {
+            let path = dir.path().join(format!("out.{format:?}"));
+            let mut args = ExportArgs::for_test(id, path.to_string_lossy().to_string());
+            args.format = format;
+            export_logic(&db, &args).unwrap();
+            assert_eq!(
+                std::fs::metadata(&path).unwrap().len(),
+                22050 * bytes_per_sample
+            );
+        }
+    }
+
+    #[test]
+    fn test_export_logic_with_metronome_and_count_in() {
+        let db = db::Database::open_in_memory().unwrap();
+        let entry =
+            db::HistoryEntry::new("T120 C1".to_string(), db::Waveform::Sine, 0.5, 120, None);
+        let id = db.save(&entry).unwrap();
+
+        let path = std::env::temp_dir().join("test_export_metronome.wav");
+        let mut args = ExportArgs::for_test(id, path.to_string_lossy().to_string());
+        args.metronome = true;
+        args.count_in = 1;
+
+        let result = export_logic(&db, &args);
+        assert!(result.is_ok(), "export_logic failed: {:?}", result.err());
+
+        // 1小節のカウントイン（2秒）+ 全音符（2秒）
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.len(), 44100 * 4);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_export_logic_file_section() {
+        let db = db::Database::open_in_memory().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let mml_path = dir.path().join("song.mml");
+        std::fs::write(
+            &mml_path,
+            "T120\n#section intro\nC1\n#section verse\nC1 C1\n#song intro verse\n",
+        )
+        .unwrap();
+        let wav_path = dir.path().join("verse.wav");
+
+        let mut args = ExportArgs::for_test(0, wav_path.to_string_lossy().to_string());
+        args.history_id = None;
+        args.file = Some(mml_path.to_string_lossy().to_string());
+        args.section = Some("verse".to_string());
+
+        let result = export_logic(&db, &args);
+        assert!(result.is_ok(), "export_logic failed: {:?}", result.err());
+
+        // verse（全音符2つ = 4秒）のみ
+        let reader = hound::WavReader::open(&wav_path).unwrap();
+        assert_eq!(reader.len(), 44100 * 4);
+    }
+
+    #[test]
+    fn test_export_logic_range_matches_full_render() {
+        let db = db::Database::open_in_memory().unwrap();
+        let entry = db::HistoryEntry::new(
+            r#"T120 O5 C1 @"b" V5 D1 E1 T60 F1"#.to_string(),
+            db::Waveform::Sine,
+            0.5,
+            120,
+            None,
+        );
+        let id = db.save(&entry).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+
+        let full_path = dir.path().join("full.wav");
+        let args = ExportArgs::for_test(id, full_path.to_string_lossy().to_string());
+        export_logic(&db, &args).unwrap();
+
+        // マーカー b（2小節目）から3小節目の終わりまで
+        let part_path = dir.path().join("part.wav");
+        let mut args = ExportArgs::for_test(id, part_path.to_string_lossy().to_string());
+        args.from = Some("b".parse().unwrap());
+        args.to = Some("3".parse().unwrap());
+        export_logic(&db, &args).unwrap();
+
+        let read = |path: &std::path::Path| -> Vec<i16> {
+            hound::WavReader::open(path)
+                .unwrap()
+                .samples::<i16>()
+                .map(Result::unwrap)
+                .collect()
+        };
+        let full = read(&full_path);
+        let part = read(&part_path);
+        assert_eq!(part.len(), 44100 * 4);
+        assert_eq!(part, full[44100 * 2..44100 * 6]);
+    }
+
+    #[test]
+    fn test_export_logic_range_errors() {
+        let db = db::Database::open_in_memory().unwrap();
+        let entry =
+            db::HistoryEntry::new("T120 C1 D1".to_string(), db::Waveform::Sine, 0.5, 120, None);
+        let id = db.save(&entry).unwrap();
+
+        let mut args = ExportArgs::for_test(id, "out.wav".to_string());
+        args.from = Some("chorus".parse().unwrap());
+        let err = export_logic(&db, &args).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("マーカー 'chorus' が見つかりません"));
+
+        let mut args = ExportArgs::for_test(id, "out.wav".to_string());
+        args.from = Some("2".parse().unwrap());
+        args.to = Some("0:01".parse().unwrap());
+        let err = export_logic(&db, &args).unwrap_err();
+        assert!(err.to_string().contains("再生範囲が空です"));
+    }
+
+    #[test]
+    fn test_export_logic_unknown_section() {
+        let db = db::Database::open_in_memory().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let mml_path = dir.path().join("song.mml");
+        std::fs::write(&mml_path, "#section intro\nC\n").unwrap();
+
+        let mut args = ExportArgs::for_test(0, "out.wav".to_string());
+        args.history_id = None;
+        args.file = Some(mml_path.to_string_lossy().to_string());
+        args.section = Some("chorus".to_string());
+
+        let err = export_logic(&db, &args).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("セクション 'chorus' が定義されていません"));
+    }
+
     #[test]
     fn test_should_save_flag_mml_input() {
         let args = PlayArgs::for_test(
@@ -603,6 +1718,61 @@ mod tests {
         assert!(output.contains("履歴がありません"));
     }
 
+    fn run_repl(input: &str, db: &db::Database) -> (String, Vec<usize>) {
+        let mut session = repl::ReplSession::new(Waveform::Sine, 0.5, SAMPLE_RATE);
+        let mut stdin = std::io::Cursor::new(input.as_bytes());
+        let mut stdout = Vec::new();
+        let mut played = Vec::new();
+        repl_logic(
+            db,
+            &mut session,
+            &mut stdin,
+            &mut stdout,
+            &mut |buffer, _| {
+                played.push(buffer.len());
+                Ok(())
+            },
+        )
+        .unwrap();
+        (String::from_utf8(stdout).unwrap(), played)
+    }
+
+    #[test]
+    fn test_repl_logic_plays_each_line_with_carried_state() {
+        let db = db::Database::open_in_memory().unwrap();
+        let (output, played) = run_repl("T60 L2 C\n\nDE\nT120 O5\n[FG\n", &db);
+
+        // 1行目は2秒、2行目はテンポ・音長を引き継いで4秒、音のない行は再生しない
+        assert_eq!(played, vec![88200, 176_400]);
+        assert!(output.contains("MML parse error"), "{output}");
+    }
+
+    #[test]
+    fn test_repl_logic_commands() {
+        let db = db::Database::open_in_memory().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("repl.wav");
+        let input = format!(
+            ":save\nCDE\nFGA\n:undo\n:waveform square\n:save 練習\n:export {}\n:show\n:quit\nC\n",
+            wav.display()
+        );
+        let (output, played) = run_repl(&input, &db);
+
+        assert_eq!(played.len(), 2, "{output}");
+        assert!(output.contains("保存するMMLがありません"), "{output}");
+        assert!(output.contains("取り消しました: FGA"), "{output}");
+        assert!(output.contains("波形: square"), "{output}");
+        assert!(output.contains("  1: CDE"), "{output}");
+
+        let entry = db.get_by_id(1).unwrap();
+        assert_eq!(entry.mml, "CDE");
+        assert_eq!(entry.waveform, db::Waveform::Square);
+        assert_eq!(entry.note.as_deref(), Some("練習"));
+
+        let reader = hound::WavReader::open(&wav).unwrap();
+        assert_eq!(reader.duration(), 66150);
+    }
+
     #[test]
     fn test_clear_history_logic_confirm_yes() {
         let db = db::Database::open_in_memory().unwrap();
@@ -1,3 +1,8 @@
 pub mod args;
 pub mod handlers;
 pub mod output;
+pub mod repl;
+#[cfg(feature = "midi-output")]
+pub mod sync;
+pub mod transport;
+pub mod watch;
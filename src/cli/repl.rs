@@ -0,0 +1,254 @@
+//! 対話モード（`repl`サブコマンド）のセッション管理モジュール
+//!
+//! 入力された行をそれまでの行に続けてパースし、新しい行の部分だけを再生する。
+//!
+//! # ビジネスルール
+//! - 入力済みの行を連結して1つのMMLとして扱うため、オクターブ・テンポ・音長・音量などの
+//!   状態は行をまたいで引き継がれる
+//! - パースに失敗した行はセッションに加えない
+//! - 波形と音量はセッションの設定として保持する（`:waveform`で変更）
+//! - `:`で始まる行はコマンド（`:waveform` / `:save` / `:undo` / `:export` / `:show` / `:help` / `:quit`）
+
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+
+use crate::cli::args::Waveform;
+use crate::mml::{self, Mml, ParseOptions, PlayPosition, PlayRange};
+
+/// 対話モードのコマンド
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplCommand {
+    /// 波形を変更する
+    Waveform(Waveform),
+    /// 入力したMMLを履歴に保存する（メモ付き）
+    Save(Option<String>),
+    /// 最後に入力した行を取り消す
+    Undo,
+    /// 入力したMMLをWAVファイルに書き出す
+    Export(String),
+    /// 入力したMMLを表示する
+    Show,
+    Help,
+    Quit,
+}
+
+/// 入力行の種類
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplInput {
+    /// MML
+    Mml(String),
+    Command(ReplCommand),
+    /// 空行
+    Empty,
+}
+
+impl ReplInput {
+    /// 入力行を解釈する
+    ///
+    /// # Errors
+    /// 未知のコマンド、またはコマンドの引数が不正な場合
+    pub fn parse(line: &str) -> Result<Self> {
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(Self::Empty);
+        }
+        let Some(command) = line.strip_prefix(':') else {
+            return Ok(Self::Mml(line.to_string()));
+        };
+
+        let (name, argument) = command
+            .split_once(char::is_whitespace)
+            .map_or((command, ""), |(name, argument)| (name, argument.trim()));
+        let command = match name {
+            "waveform" | "w" => {
+                let Ok(waveform) = Waveform::from_str(argument, true) else {
+                    bail!("波形は sine / sawtooth / square のいずれかを指定してください");
+                };
+                ReplCommand::Waveform(waveform)
+            }
+            "save" => ReplCommand::Save((!argument.is_empty()).then(|| argument.to_string())),
+            "undo" | "u" => ReplCommand::Undo,
+            "export" => {
+                if argument.is_empty() {
+                    bail!("書き出すファイル名を指定してください（例: :export song.wav）");
+                }
+                ReplCommand::Export(argument.to_string())
+            }
+            "show" => ReplCommand::Show,
+            "help" | "h" => ReplCommand::Help,
+            "quit" | "q" => ReplCommand::Quit,
+            _ => bail!("不明なコマンドです: :{name}（:help で一覧を表示）"),
+        };
+        Ok(Self::Command(command))
+    }
+}
+
+/// 行を加えた結果
+#[derive(Debug)]
+pub struct Appended {
+    /// 入力済みの行全体のAST
+    pub ast: Mml,
+    /// 新しい行の範囲（音を含まない行の場合は`None`）
+    pub range: Option<PlayRange>,
+    /// パース時の警告
+    pub warnings: Vec<String>,
+}
+
+/// 対話モードのセッション
+#[derive(Debug, Clone)]
+pub struct ReplSession {
+    lines: Vec<String>,
+    pub waveform: Waveform,
+    pub volume: f32,
+    sample_rate: u32,
+}
+
+impl ReplSession {
+    #[must_use]
+    pub fn new(waveform: Waveform, volume: f32, sample_rate: u32) -> Self {
+        Self {
+            lines: Vec::new(),
+            waveform,
+            volume,
+            sample_rate,
+        }
+    }
+
+    /// 入力済みの行
+    #[must_use]
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// 入力済みの行を連結したMML
+    #[must_use]
+    pub fn mml(&self) -> String {
+        self.lines.join(" ")
+    }
+
+    /// 入力済みの行全体をパースする
+    ///
+    /// # Errors
+    /// パースに失敗した場合
+    pub fn parse(&self) -> Result<Mml> {
+        parse(&self.mml()).map(|(ast, _)| ast)
+    }
+
+    /// 行を加える
+    ///
+    /// # Errors
+    /// 行を加えたMMLのパースに失敗した場合（セッションは変更しない）
+    pub fn append(&mut self, line: &str) -> Result<Appended> {
+        let previous_end = if self.lines.is_empty() {
+            0.0
+        } else {
+            self.parse()?.end_location(self.sample_rate).seconds
+        };
+
+        let mml = if self.lines.is_empty() {
+            line.to_string()
+        } else {
+            format!("{} {line}", self.mml())
+        };
+        let (ast, warnings) = parse(&mml)?;
+        let from = (previous_end > 0.0).then_some(PlayPosition::Time(previous_end));
+        let range = ast.play_range(from.as_ref(), None, self.sample_rate).ok();
+
+        self.lines.push(line.to_string());
+        Ok(Appended {
+            ast,
+            range,
+            warnings,
+        })
+    }
+
+    /// 最後の行を取り消し、取り消した行を返す
+    pub fn undo(&mut self) -> Option<String> {
+        self.lines.pop()
+    }
+}
+
+fn parse(mml: &str) -> Result<(Mml, Vec<String>)> {
+    let (ast, warnings) = mml::parse_with_options(mml, &ParseOptions::default())
+        .map_err(|e| anyhow::anyhow!("MML parse error: {e}"))?;
+    Ok((ast, warnings.iter().map(ToString::to_string).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 44100;
+
+    #[test]
+    fn parse_input() {
+        assert_eq!(ReplInput::parse("  ").unwrap(), ReplInput::Empty);
+        assert_eq!(
+            ReplInput::parse(" CDE ").unwrap(),
+            ReplInput::Mml("CDE".to_string())
+        );
+        assert_eq!(
+            ReplInput::parse(":waveform Square").unwrap(),
+            ReplInput::Command(ReplCommand::Waveform(Waveform::Square))
+        );
+        assert_eq!(
+            ReplInput::parse(":save 練習 メモ").unwrap(),
+            ReplInput::Command(ReplCommand::Save(Some("練習 メモ".to_string())))
+        );
+        assert_eq!(
+            ReplInput::parse(":save").unwrap(),
+            ReplInput::Command(ReplCommand::Save(None))
+        );
+        assert_eq!(
+            ReplInput::parse(":export out.wav").unwrap(),
+            ReplInput::Command(ReplCommand::Export("out.wav".to_string()))
+        );
+        assert!(ReplInput::parse(":waveform organ").is_err());
+        assert!(ReplInput::parse(":export").is_err());
+        assert!(ReplInput::parse(":unknown").is_err());
+    }
+
+    #[test]
+    fn append_plays_only_new_line_with_carried_state() {
+        let mut session = ReplSession::new(Waveform::Sine, 1.0, SAMPLE_RATE);
+
+        let first = session.append("T60 L2 O5 C").unwrap();
+        let range = first.range.unwrap();
+        assert!(range.start.seconds.abs() < 1e-9);
+        assert!((range.end.seconds - 2.0).abs() < 1e-9);
+
+        // テンポ・音長は前の行から引き継ぐ
+        let second = session.append("DE").unwrap();
+        let range = second.range.unwrap();
+        assert!((range.start.seconds - 2.0).abs() < 1e-9);
+        assert!((range.end.seconds - 6.0).abs() < 1e-9);
+        assert_eq!(session.mml(), "T60 L2 O5 C DE");
+    }
+
+    #[test]
+    fn append_without_notes_has_no_range() {
+        let mut session = ReplSession::new(Waveform::Sine, 1.0, SAMPLE_RATE);
+        session.append("C").unwrap();
+        assert!(session.append("T200 O3").unwrap().range.is_none());
+        assert_eq!(session.lines().len(), 2);
+    }
+
+    #[test]
+    fn append_error_keeps_session() {
+        let mut session = ReplSession::new(Waveform::Sine, 1.0, SAMPLE_RATE);
+        session.append("CDE").unwrap();
+        assert!(session.append("[FG").is_err());
+        assert_eq!(session.mml(), "CDE");
+    }
+
+    #[test]
+    fn undo_removes_last_line() {
+        let mut session = ReplSession::new(Waveform::Sine, 1.0, SAMPLE_RATE);
+        session.append("CDE").unwrap();
+        session.append("FGA").unwrap();
+        assert_eq!(session.undo(), Some("FGA".to_string()));
+        assert_eq!(session.mml(), "CDE");
+        assert_eq!(session.undo(), Some("CDE".to_string()));
+        assert_eq!(session.undo(), None);
+    }
+}
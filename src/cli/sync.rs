@@ -0,0 +1,513 @@
+//! 外部MIDIクロックへの追従（`play --midi-clock-in`）モジュール
+//!
+//! 外部機器から受け取ったMIDIクロックをテンポの基準（マスター）として、音声またはMIDIを
+//! 再生する。マスターのStart/Continue/Stopとテンポは[`SyncTarget`]を通して再生に反映する。
+//!
+//! # ビジネスルール
+//! - Startで曲の先頭から、Continueでソングポジションポインタの位置から再生し、Stopで止める
+//! - 音声はマスターのテンポで合成し直し、マスターの位置（拍）に再生位置を合わせる
+//!   - テンポが[`RETEMPO_THRESHOLD_BPM`]以上変わった場合に合成し直す
+//!   - 合成は別スレッドで行い、終わるまでは前のサンプル列で再生を続ける（メッセージの処理を止めない）
+//!   - 再生位置のずれが[`DRIFT_TOLERANCE_SECONDS`]を超えた場合にシークする
+//! - MIDIは外部クロックの位置（拍）を待ちながら送信する（MML中のテンポ指定は無視する）
+//! - テンポが分からないうちにStartを受け取った場合は曲の最初のテンポで再生を始める
+//! - 曲の終わりに達した後は次のStart/Continueを待つ。停止キー（またはCtrl+C）で終了する
+
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::audio::player::PlaybackState;
+use crate::cli::transport::{MidiPlayback, Playback};
+use crate::midi::{ExternalClock, MidiSource, TransportEvent, EXTERNAL_SYNC_BPM};
+use crate::mml::{Location, Mml, PlayRange, TimeSignature};
+
+/// 音声を合成し直すテンポの変化（BPM）
+pub const RETEMPO_THRESHOLD_BPM: f64 = 0.5;
+/// 音声の再生位置を補正するずれ（秒）
+pub const DRIFT_TOLERANCE_SECONDS: f64 = 0.02;
+/// 受信メッセージの確認間隔
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// 外部クロックに合わせて操作する再生
+pub trait SyncTarget {
+    /// `beat`拍の位置から`bpm`のテンポで再生を始める（再生中の場合は位置を移す）
+    ///
+    /// # Errors
+    /// 再生の開始に失敗した場合
+    fn start(&mut self, beat: f64, bpm: f64) -> Result<()>;
+
+    /// 再生を止める
+    ///
+    /// # Errors
+    /// 停止処理に失敗した場合
+    fn stop(&mut self) -> Result<()>;
+
+    /// 再生中にマスターの現在位置（拍）とテンポに合わせる
+    ///
+    /// # Errors
+    /// 再生の補正に失敗した場合
+    fn follow(&mut self, beat: f64, bpm: f64) -> Result<()>;
+}
+
+/// 停止が要求されるまで外部クロックに合わせて再生する
+///
+/// `fallback_bpm`はマスターのテンポが分からない場合に使うテンポ。
+///
+/// # Errors
+/// 再生の操作に失敗した場合
+pub fn follow(
+    source: &mut impl MidiSource,
+    clock: &ExternalClock,
+    target: &mut impl SyncTarget,
+    fallback_bpm: f64,
+    mut stop_requested: impl FnMut() -> bool,
+) -> Result<()> {
+    let mut bpm = fallback_bpm;
+    while !stop_requested() {
+        pump(source, clock, target, &mut bpm)?;
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    target.stop()
+}
+
+/// 受信したメッセージを処理して再生に反映する
+///
+/// `bpm`は直近のマスターのテンポ（分からない場合に使うテンポで初期化する）。
+///
+/// # Errors
+/// 再生の操作に失敗した場合
+pub fn pump(
+    source: &mut impl MidiSource,
+    clock: &ExternalClock,
+    target: &mut impl SyncTarget,
+    bpm: &mut f64,
+) -> Result<()> {
+    for event in clock.pump(source) {
+        if let Some(master) = clock.bpm() {
+            *bpm = master;
+        }
+        match event {
+            TransportEvent::Start => target.start(0.0, *bpm)?,
+            TransportEvent::Continue { beat } => target.start(beat, *bpm)?,
+            TransportEvent::Stop => target.stop()?,
+        }
+    }
+
+    if clock.is_running() {
+        if let Some(master) = clock.bpm() {
+            *bpm = master;
+            target.follow(clock.beat(), master)?;
+        }
+    }
+    Ok(())
+}
+
+/// 曲全体を拍単位で表した再生範囲（外部クロックに追従するMIDI再生で使う）
+///
+/// 追従中のMIDI再生は[`EXTERNAL_SYNC_BPM`]（1拍1秒）で進むため、秒数が拍数と一致する。
+#[must_use]
+pub fn beat_range(ast: &Mml) -> PlayRange {
+    let location = |beat: f64| Location {
+        seconds: beat,
+        sample: 0,
+        bpm: EXTERNAL_SYNC_BPM,
+        time_signature: TimeSignature::default(),
+    };
+    PlayRange {
+        start: location(0.0),
+        end: location(ast.total_beats()),
+    }
+}
+
+/// 合成したテンポとサンプル列
+type Rendered = Result<(f64, Vec<f32>)>;
+
+/// 外部クロックに合わせた音声再生
+///
+/// 再生状態（`PlaybackState`）のサンプル列をマスターのテンポで合成し直し、再生位置を補正する。
+/// 合成は作成時に起動する合成スレッドで1回ずつ行う。
+pub struct AudioSyncTarget {
+    /// 合成スレッドへの合成要求（テンポ）
+    requests: Sender<f64>,
+    /// 合成スレッドからの合成結果
+    results: Receiver<Rendered>,
+    state: Arc<Mutex<PlaybackState>>,
+    sample_rate: f64,
+    /// 現在のサンプル列を合成したテンポ
+    rendered_bpm: Option<f64>,
+    /// 合成中のテンポ
+    pending_bpm: Option<f64>,
+}
+
+impl AudioSyncTarget {
+    /// `render`（固定テンポで曲全体を合成する）を合成スレッドで実行する再生を作成する
+    #[must_use]
+    pub fn new<R>(mut render: R, state: Arc<Mutex<PlaybackState>>, sample_rate: u32) -> Self
+    where
+        R: FnMut(f64) -> Result<Vec<f32>> + Send + 'static,
+    {
+        let (requests, request_receiver) = mpsc::channel::<f64>();
+        let (result_sender, results) = mpsc::channel();
+        // 再生側が破棄されて要求の送信元がなくなると終了する
+        std::thread::spawn(move || {
+            for bpm in request_receiver {
+                let rendered = render(bpm).map(|samples| (bpm, samples));
+                if result_sender.send(rendered).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            requests,
+            results,
+            state,
+            sample_rate: f64::from(sample_rate),
+            rendered_bpm: None,
+            pending_bpm: None,
+        }
+    }
+
+    /// 現在のサンプル列を合成したテンポ
+    #[must_use]
+    pub fn rendered_bpm(&self) -> Option<f64> {
+        self.rendered_bpm
+    }
+
+    /// 合成中のテンポ
+    #[must_use]
+    pub fn pending_bpm(&self) -> Option<f64> {
+        self.pending_bpm
+    }
+
+    /// 合成中であれば終わるまで待ち、サンプル列を差し替える
+    ///
+    /// # Errors
+    /// 合成に失敗した場合
+    pub fn wait_for_render(&mut self) -> Result<()> {
+        self.receive(true)
+    }
+
+    /// テンポが変わった場合に合成を要求する（合成中も前のサンプル列で再生を続ける）
+    ///
+    /// まだ一度も合成していない場合は、無音で始まらないよう合成が終わるまで待つ。
+    fn retempo(&mut self, bpm: f64) -> Result<()> {
+        self.receive(self.rendered_bpm.is_none())?;
+        // 合成中は終わってから改めて比べる（合成は1回ずつ行う）
+        if self.pending_bpm.is_some()
+            || self
+                .rendered_bpm
+                .is_some_and(|rendered| (rendered - bpm).abs() < RETEMPO_THRESHOLD_BPM)
+        {
+            return Ok(());
+        }
+        self.requests
+            .send(bpm)
+            .map_err(|_| anyhow::anyhow!("音声合成スレッドが終了しています"))?;
+        self.pending_bpm = Some(bpm);
+        if self.rendered_bpm.is_none() {
+            self.receive(true)?;
+        }
+        Ok(())
+    }
+
+    /// 合成が終わっていればサンプル列を差し替える（`wait`の場合は終わるまで待つ）
+    fn receive(&mut self, wait: bool) -> Result<()> {
+        if self.pending_bpm.is_none() {
+            return Ok(());
+        }
+        let rendered = if wait {
+            self.results.recv().map_err(|_| TryRecvError::Disconnected)
+        } else {
+            self.results.try_recv()
+        };
+        let rendered = match rendered {
+            Ok(rendered) => rendered,
+            Err(TryRecvError::Empty) => return Ok(()),
+            Err(TryRecvError::Disconnected) => anyhow::bail!("音声合成スレッドが終了しています"),
+        };
+        self.pending_bpm = None;
+        let (bpm, samples) = rendered?;
+        self.lock().set_samples(samples);
+        self.rendered_bpm = Some(bpm);
+        Ok(())
+    }
+
+    /// `beat`拍の位置のサンプル位置（合成したテンポで計算する）
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn sample_at(&self, beat: f64) -> usize {
+        let bpm = self.rendered_bpm.unwrap_or(EXTERNAL_SYNC_BPM);
+        (beat.max(0.0) * 60.0 / bpm * self.sample_rate) as usize
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PlaybackState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl SyncTarget for AudioSyncTarget {
+    fn start(&mut self, beat: f64, bpm: f64) -> Result<()> {
+        self.retempo(bpm)?;
+        let position = self.sample_at(beat);
+        let mut state = self.lock();
+        state.seek(position);
+        state.set_paused(false);
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.lock().set_paused(true);
+        Ok(())
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn follow(&mut self, beat: f64, bpm: f64) -> Result<()> {
+        self.retempo(bpm)?;
+        let target = self.sample_at(beat);
+        let tolerance = (DRIFT_TOLERANCE_SECONDS * self.sample_rate) as usize;
+        let mut state = self.lock();
+        if !state.is_paused() && state.position().abs_diff(target) > tolerance {
+            state.seek(target);
+        }
+        Ok(())
+    }
+}
+
+/// 外部クロックに合わせたMIDI再生
+///
+/// 再生スレッドが外部クロックの位置を待ちながら送信するため、テンポの補正は不要。
+/// [`beat_range`]の再生範囲と、`follow`に外部クロックを指定した`PlayOptions`で作成する。
+impl SyncTarget for MidiPlayback {
+    fn start(&mut self, beat: f64, _bpm: f64) -> Result<()> {
+        self.set_paused(true)?;
+        self.seek(beat)?;
+        self.set_paused(false)
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.set_paused(true)
+    }
+
+    fn follow(&mut self, beat: f64, _bpm: f64) -> Result<()> {
+        // デバイスの再接続待ちの間は、再接続後に外部クロックの現在位置から再開する
+        if self.is_waiting_for_device() {
+            self.seek(beat)?;
+        }
+        self.poll().map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi::TimedMessage;
+    use std::sync::mpsc::{self, Sender};
+    use std::time::Instant;
+
+    /// 120 BPMのクロック間隔（1/48秒）
+    const TICK: Duration = Duration::from_micros(20_833);
+
+    #[derive(Debug, Default)]
+    struct Recorder {
+        calls: Vec<String>,
+    }
+
+    impl SyncTarget for Recorder {
+        fn start(&mut self, beat: f64, bpm: f64) -> Result<()> {
+            self.calls.push(format!("start {beat:.2} {bpm:.0}"));
+            Ok(())
+        }
+
+        fn stop(&mut self) -> Result<()> {
+            self.calls.push("stop".to_string());
+            Ok(())
+        }
+
+        fn follow(&mut self, beat: f64, bpm: f64) -> Result<()> {
+            self.calls.push(format!("follow {beat:.2} {bpm:.0}"));
+            Ok(())
+        }
+    }
+
+    fn send_ticks(sender: &Sender<TimedMessage>, from: u32, count: u32) {
+        for i in from..from + count {
+            sender.send((TICK * i, vec![0xF8])).unwrap();
+        }
+    }
+
+    #[test]
+    fn start_uses_fallback_tempo_until_clock_arrives() {
+        let (sender, mut receiver) = mpsc::channel();
+        let clock = ExternalClock::new(Instant::now());
+        let mut target = Recorder::default();
+        let mut bpm = 90.0;
+
+        sender.send((Duration::ZERO, vec![0xFA])).unwrap();
+        pump(&mut receiver, &clock, &mut target, &mut bpm).unwrap();
+        assert_eq!(target.calls, ["start 0.00 90"]);
+    }
+
+    #[test]
+    fn follows_master_tempo_and_transport() {
+        let (sender, mut receiver) = mpsc::channel();
+        let clock = ExternalClock::new(Instant::now());
+        let mut target = Recorder::default();
+        let mut bpm = 90.0;
+
+        // 停止中のクロックでテンポだけを測る
+        send_ticks(&sender, 0, 24);
+        pump(&mut receiver, &clock, &mut target, &mut bpm).unwrap();
+        assert!(target.calls.is_empty());
+
+        sender.send((TICK * 24, vec![0xFA])).unwrap();
+        send_ticks(&sender, 24, 25);
+        pump(&mut receiver, &clock, &mut target, &mut bpm).unwrap();
+        assert_eq!(target.calls, ["start 0.00 120", "follow 1.00 120"]);
+
+        // 16分音符で8 = 2拍目からContinue
+        target.calls.clear();
+        sender.send((TICK * 49, vec![0xFC])).unwrap();
+        sender.send((TICK * 49, vec![0xF2, 8, 0])).unwrap();
+        sender.send((TICK * 49, vec![0xFB])).unwrap();
+        pump(&mut receiver, &clock, &mut target, &mut bpm).unwrap();
+        assert_eq!(target.calls, ["stop", "start 2.00 120", "follow 2.00 120"]);
+    }
+
+    #[test]
+    fn follow_stops_target_when_requested() {
+        let (_sender, mut receiver) = mpsc::channel::<TimedMessage>();
+        let clock = ExternalClock::new(Instant::now());
+        let mut target = Recorder::default();
+        follow(&mut receiver, &clock, &mut target, 120.0, || true).unwrap();
+        assert_eq!(target.calls, ["stop"]);
+    }
+
+    #[test]
+    fn beat_range_spans_whole_song_in_beats() {
+        let ast = crate::mml::parse("T200 C4 D2 T60 E1").unwrap();
+        let range = beat_range(&ast);
+        assert!(range.start.seconds.abs() < f64::EPSILON);
+        assert!((range.end.seconds - 7.0).abs() < 1e-9);
+    }
+
+    /// 1秒に100サンプルで合成する音声の追従（合成したテンポを`renders`に記録する）
+    fn audio_target(
+        renders: &Arc<Mutex<Vec<f64>>>,
+        state: &Arc<Mutex<PlaybackState>>,
+    ) -> AudioSyncTarget {
+        let renders = Arc::clone(renders);
+        let render = move |bpm: f64| {
+            renders.lock().unwrap().push(bpm);
+            Ok(vec![0.5; 1000])
+        };
+        AudioSyncTarget::new(render, Arc::clone(state), 100)
+    }
+
+    fn empty_state() -> Arc<Mutex<PlaybackState>> {
+        Arc::new(Mutex::new(PlaybackState::new(Vec::new(), false, 0)))
+    }
+
+    #[test]
+    fn audio_target_starts_at_master_position() {
+        let renders = Arc::new(Mutex::new(Vec::new()));
+        let state = empty_state();
+        let mut target = audio_target(&renders, &state);
+
+        // 120 BPMで2拍目 = 1秒 = 100サンプル目
+        target.start(2.0, 120.0).unwrap();
+        assert_eq!(state.lock().unwrap().position(), 100);
+        assert!(!state.lock().unwrap().is_paused());
+
+        target.stop().unwrap();
+        assert!(state.lock().unwrap().is_paused());
+        assert_eq!(*renders.lock().unwrap(), [120.0]);
+    }
+
+    #[test]
+    fn audio_target_rerenders_only_on_tempo_change() {
+        let renders = Arc::new(Mutex::new(Vec::new()));
+        let state = empty_state();
+        let mut target = audio_target(&renders, &state);
+        target.start(0.0, 120.0).unwrap();
+
+        target.follow(0.0, 120.3).unwrap();
+        assert_eq!(target.rendered_bpm(), Some(120.0));
+
+        // 60 BPMに変わった: 合成が終わるまでは120 BPMのサンプル列で追従する
+        target.follow(4.0, 60.0).unwrap();
+        assert_eq!(target.pending_bpm(), Some(60.0));
+        assert_eq!(state.lock().unwrap().position(), 200);
+
+        // 差し替えた後は4拍目（4秒 = 400サンプル目）にシーク
+        target.wait_for_render().unwrap();
+        assert_eq!(*renders.lock().unwrap(), [120.0, 60.0]);
+        assert_eq!(target.rendered_bpm(), Some(60.0));
+        target.follow(4.0, 60.0).unwrap();
+        assert_eq!(state.lock().unwrap().position(), 400);
+    }
+
+    #[test]
+    fn audio_target_keeps_following_while_rendering() {
+        let renders = Arc::new(Mutex::new(Vec::new()));
+        let state = empty_state();
+        let (release, gate) = mpsc::channel::<()>();
+        let recorded = Arc::clone(&renders);
+        // 2回目以降の合成は`release`を送るまで終わらない
+        let render = move |bpm: f64| {
+            if !recorded.lock().unwrap().is_empty() {
+                gate.recv().unwrap();
+            }
+            recorded.lock().unwrap().push(bpm);
+            Ok(vec![0.5; 1000])
+        };
+        let mut target = AudioSyncTarget::new(render, Arc::clone(&state), 100);
+        target.start(0.0, 60.0).unwrap();
+
+        // 合成中も位置の補正を続け、合成中に揺れたテンポで合成を重ねない
+        target.follow(1.0, 120.0).unwrap();
+        target.follow(2.0, 121.0).unwrap();
+        assert_eq!(target.pending_bpm(), Some(120.0));
+        assert_eq!(target.rendered_bpm(), Some(60.0));
+        assert_eq!(state.lock().unwrap().position(), 200);
+
+        release.send(()).unwrap();
+        target.wait_for_render().unwrap();
+        assert_eq!(*renders.lock().unwrap(), [60.0, 120.0]);
+
+        // 差し替え後のテンポとの差が小さければ合成し直さない
+        target.follow(2.0, 120.4).unwrap();
+        assert_eq!(target.pending_bpm(), None);
+    }
+
+    #[test]
+    fn audio_target_reports_render_errors() {
+        let render = |_bpm: f64| anyhow::bail!("合成失敗");
+        let mut target = AudioSyncTarget::new(render, empty_state(), 100);
+        let err = target.start(0.0, 120.0).unwrap_err();
+        assert!(err.to_string().contains("合成失敗"));
+        assert_eq!(target.rendered_bpm(), None);
+    }
+
+    #[test]
+    fn audio_target_corrects_only_large_drift() {
+        let renders = Arc::new(Mutex::new(Vec::new()));
+        let state = empty_state();
+        let mut target = audio_target(&renders, &state);
+        target.start(0.0, 60.0).unwrap();
+        state.lock().unwrap().seek(101);
+
+        // 許容範囲（0.02秒 = 2サンプル）内のずれは補正しない
+        target.follow(1.0, 60.0).unwrap();
+        assert_eq!(state.lock().unwrap().position(), 101);
+
+        target.follow(1.5, 60.0).unwrap();
+        assert_eq!(state.lock().unwrap().position(), 150);
+
+        // 停止中は補正しない
+        target.stop().unwrap();
+        target.follow(3.0, 60.0).unwrap();
+        assert_eq!(state.lock().unwrap().position(), 150);
+    }
+}
@@ -0,0 +1,1047 @@
+//! 再生中のキー操作（トランスポート）モジュール
+//!
+//! `play`の再生中にキー入力で一時停止・シーク・ループの切り替え・停止を行う。
+//! 音声再生とMIDI再生は[`Playback`]トレイトを通して同じキーで操作する。
+//!
+//! # キー操作
+//! - スペース: 一時停止／再開
+//! - ← / →: 1小節戻る／進む
+//! - ↓ / ↑: 5秒戻る／進む
+//! - l: ループ再生の切り替え
+//! - q（Esc）: 停止（音声は短くフェードアウトする）
+//!
+//! # ビジネスルール
+//! - シーク先は再生範囲（`--from` / `--to`）内に丸める。範囲の終わりへのシークは
+//!   ループ再生中なら先頭へ戻り、そうでなければ再生を終える
+//! - 小節頭から0.5秒以内に「1小節戻る」を押した場合は、さらに前の小節頭へ戻る
+//! - 標準入力が端末でない場合はキー操作を受け付けない（Ctrl+Cで停止）
+
+use std::io::IsTerminal;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use anyhow::Result;
+use console::{Key, Term};
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::audio::player::PlaybackState;
+use crate::cli::output;
+use crate::mml::{Mml, PlayPosition, PlayRange};
+
+/// ↓ / ↑で移動する秒数
+pub const SEEK_SECONDS: f64 = 5.0;
+/// 停止時のフェードアウトの長さ（秒）
+pub const FADE_OUT_SECONDS: f64 = 0.1;
+/// 「1小節戻る」で現在の小節頭ではなく前の小節頭へ戻る猶予（秒）
+const BAR_BACK_GRACE_SECONDS: f64 = 0.5;
+/// 位置比較の許容誤差（秒）
+const EPSILON: f64 = 1e-6;
+/// キー入力と表示の更新間隔
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// 再生中のキー操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportCommand {
+    /// 一時停止／再開
+    TogglePause,
+    /// 1小節戻る
+    PreviousBar,
+    /// 1小節進む
+    NextBar,
+    /// 数秒戻る
+    Rewind,
+    /// 数秒進む
+    FastForward,
+    /// ループ再生の切り替え
+    ToggleLoop,
+    /// 停止
+    Stop,
+}
+
+impl TransportCommand {
+    /// キーに割り当てた操作を返す
+    #[must_use]
+    pub fn from_key(key: &Key) -> Option<Self> {
+        match key {
+            Key::Char(' ') => Some(Self::TogglePause),
+            Key::ArrowLeft => Some(Self::PreviousBar),
+            Key::ArrowRight => Some(Self::NextBar),
+            Key::ArrowDown => Some(Self::Rewind),
+            Key::ArrowUp => Some(Self::FastForward),
+            Key::Char('l' | 'L') => Some(Self::ToggleLoop),
+            Key::Char('q' | 'Q') | Key::Escape => Some(Self::Stop),
+            _ => None,
+        }
+    }
+}
+
+/// 再生範囲と小節頭の位置（シーク先の計算に使用）
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timeline {
+    start: f64,
+    end: f64,
+    /// 小節頭の位置（曲の先頭からの秒数、昇順）
+    bars: Vec<f64>,
+}
+
+impl Timeline {
+    #[must_use]
+    pub fn new(start: f64, end: f64, bars: Vec<f64>) -> Self {
+        Self { start, end, bars }
+    }
+
+    /// MMLの小節線と再生範囲から作成する
+    #[must_use]
+    pub fn from_range(ast: &Mml, range: &PlayRange, sample_rate: u32) -> Self {
+        let bars = ast
+            .bar_lines(sample_rate)
+            .iter()
+            .map(|location| location.seconds)
+            .collect();
+        Self::new(range.start.seconds, range.end.seconds, bars)
+    }
+
+    /// 位置が再生範囲内（終わりを含まない）かどうか
+    #[must_use]
+    pub fn contains(&self, seconds: f64) -> bool {
+        (self.start..self.end).contains(&seconds)
+    }
+
+    /// 範囲の長さ（秒）
+    #[must_use]
+    pub fn seconds(&self) -> f64 {
+        self.end - self.start
+    }
+
+    /// シーク先（曲の先頭からの秒数）。シーク以外の操作は`None`
+    #[must_use]
+    pub fn seek_target(&self, command: TransportCommand, position: f64) -> Option<f64> {
+        let target = match command {
+            TransportCommand::PreviousBar => self
+                .bars
+                .iter()
+                .rev()
+                .find(|&&bar| bar < position - BAR_BACK_GRACE_SECONDS)
+                .copied()
+                .unwrap_or(self.start),
+            TransportCommand::NextBar => self
+                .bars
+                .iter()
+                .find(|&&bar| bar > position + EPSILON)
+                .copied()
+                .unwrap_or(self.end),
+            TransportCommand::Rewind => position - SEEK_SECONDS,
+            TransportCommand::FastForward => position + SEEK_SECONDS,
+            TransportCommand::TogglePause
+            | TransportCommand::ToggleLoop
+            | TransportCommand::Stop => return None,
+        };
+        Some(target.clamp(self.start, self.end))
+    }
+}
+
+/// キー操作の対象となる再生
+pub trait Playback {
+    /// 現在の再生位置（曲の先頭からの秒数）
+    fn position(&self) -> f64;
+    /// 再生位置を移動する
+    ///
+    /// # Errors
+    /// 再生の再開に失敗した場合
+    fn seek(&mut self, seconds: f64) -> Result<()>;
+    fn is_paused(&self) -> bool;
+    /// 一時停止・再開する
+    ///
+    /// # Errors
+    /// 再生の停止・再開に失敗した場合
+    fn set_paused(&mut self, paused: bool) -> Result<()>;
+    fn is_looping(&self) -> bool;
+    fn set_looping(&mut self, looping: bool);
+    /// 再生を止める（音声はフェードアウトしてから止まる）
+    ///
+    /// # Errors
+    /// 停止処理に失敗した場合
+    fn stop(&mut self) -> Result<()>;
+    /// 再生状態を更新し、再生を終えたかどうかを返す
+    ///
+    /// # Errors
+    /// 再生中にエラーが発生した場合
+    fn poll(&mut self) -> Result<bool>;
+    /// 出力先のデバイスの再接続を待っているかどうか
+    fn is_waiting_for_device(&self) -> bool {
+        false
+    }
+}
+
+/// キー操作を再生に反映する
+///
+/// # Errors
+/// 再生の操作に失敗した場合
+pub fn apply(
+    playback: &mut impl Playback,
+    timeline: &Timeline,
+    command: TransportCommand,
+) -> Result<()> {
+    match command {
+        TransportCommand::TogglePause => playback.set_paused(!playback.is_paused()),
+        TransportCommand::ToggleLoop => {
+            playback.set_looping(!playback.is_looping());
+            Ok(())
+        }
+        TransportCommand::Stop => playback.stop(),
+        _ => match timeline.seek_target(command, playback.position()) {
+            Some(target) => playback.seek(target),
+            None => Ok(()),
+        },
+    }
+}
+
+/// 再生の終わり方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Completion {
+    /// 最後まで再生した
+    Finished,
+    /// 停止キーまたはCtrl+Cで止めた
+    Stopped,
+    /// 呼び出し側の要求（ファイルの変更など）で中断した。再生は続いている
+    Interrupted,
+}
+
+/// 再生が終わるまでキー操作を受け付け、再生位置を表示する
+///
+/// # Errors
+/// 再生の操作中にエラーが発生した場合
+pub fn run(playback: &mut impl Playback, timeline: &Timeline) -> Result<Completion> {
+    Transport::start().run(playback, timeline)
+}
+
+/// キー操作の受け付け
+///
+/// 開始するとキー入力の読み取りを始め、破棄すると端末の設定を元に戻す。
+/// 続けて何度も再生する場合（`--watch`での再読み込みなど）は同じインスタンスを使う。
+pub struct Transport {
+    keys: Option<KeyListener>,
+}
+
+impl Transport {
+    /// キー入力の読み取りを開始し、操作方法を表示する
+    #[must_use]
+    pub fn start() -> Self {
+        let keys = KeyListener::spawn();
+        if keys.is_some() {
+            output::message_indent(
+                "スペース: 一時停止/再開  ←→: 1小節  ↓↑: 5秒  l: ループ切替  q: 停止",
+            );
+        } else {
+            output::message_indent("Ctrl+Cで停止");
+        }
+        Self { keys }
+    }
+
+    /// 停止キーだけを受け付ける（外部機器が再生を操作する場合）
+    #[must_use]
+    pub fn stop_only() -> Self {
+        let keys = KeyListener::spawn();
+        if keys.is_some() {
+            output::message_indent("q: 停止");
+        } else {
+            output::message_indent("Ctrl+Cで停止");
+        }
+        Self { keys }
+    }
+
+    /// キー操作を受け付けずに再生位置だけを表示する（標準入力を別の用途に使う場合）
+    #[must_use]
+    pub fn passive() -> Self {
+        Self { keys: None }
+    }
+
+    /// 再生が終わるまでキー操作を受け付け、再生位置を表示する
+    ///
+    /// # Errors
+    /// 再生の操作中にエラーが発生した場合
+    pub fn run(&self, playback: &mut impl Playback, timeline: &Timeline) -> Result<Completion> {
+        self.run_until(playback, timeline, || false)
+    }
+
+    /// [`Transport::run`]と同じだが、`interrupted`が`true`を返した時点で再生を止めずに戻る
+    ///
+    /// # Errors
+    /// 再生の操作中にエラーが発生した場合
+    pub fn run_until(
+        &self,
+        playback: &mut impl Playback,
+        timeline: &Timeline,
+        mut interrupted: impl FnMut() -> bool,
+    ) -> Result<Completion> {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let total_ms = (timeline.seconds() * 1000.0) as u64;
+        let pb = ProgressBar::new(total_ms);
+        if let Ok(style) =
+            ProgressStyle::default_bar().template("{spinner:.green} [{bar:40.cyan/blue}] {msg}")
+        {
+            pb.set_style(style.progress_chars("#>-"));
+        }
+
+        let mut completion = Completion::Finished;
+        loop {
+            for command in self.commands() {
+                if command == TransportCommand::Stop {
+                    completion = Completion::Stopped;
+                }
+                apply(playback, timeline, command)?;
+            }
+            if playback.poll()? {
+                break;
+            }
+            if interrupted() {
+                completion = Completion::Interrupted;
+                break;
+            }
+
+            let position = playback.position();
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            pb.set_position(((position - timeline.start).max(0.0) * 1000.0) as u64);
+            pb.set_message(status_message(playback, position, timeline.end));
+            std::thread::sleep(POLL_INTERVAL);
+        }
+
+        pb.finish_and_clear();
+        Ok(completion)
+    }
+
+    /// 再生していない間の入力を確認し、停止キーが押されたかどうかを返す
+    #[must_use]
+    pub fn stop_requested(&self) -> bool {
+        self.commands().contains(&TransportCommand::Stop)
+    }
+
+    /// 前回の確認以降に入力されたキー操作
+    fn commands(&self) -> Vec<TransportCommand> {
+        self.keys.as_ref().map_or_else(Vec::new, |keys| {
+            keys.receiver
+                .try_iter()
+                .filter_map(|key| TransportCommand::from_key(&key))
+                .collect()
+        })
+    }
+}
+
+/// 再生位置と状態の表示（例: `0:12.50 / 1:00.00 ⏸ 一時停止中`）
+fn status_message(playback: &impl Playback, position: f64, end: f64) -> String {
+    let mut message = format!(
+        "{} / {}",
+        PlayPosition::Time(position),
+        PlayPosition::Time(end)
+    );
+    if playback.is_waiting_for_device() {
+        message.push_str(" ⚠ MIDIデバイスの再接続待ち");
+    } else if playback.is_paused() {
+        message.push_str(" ⏸ 一時停止中");
+    }
+    if playback.is_looping() {
+        message.push_str(" 🔁 ループ");
+    }
+    message
+}
+
+/// キー入力を読み取るスレッド
+///
+/// キーの読み取りは中断できないため、スレッドは再生終了後の最初のキー入力で終了する。
+struct KeyListener {
+    receiver: Receiver<Key>,
+    _terminal: TerminalGuard,
+}
+
+impl KeyListener {
+    /// 標準入力が端末の場合にキー入力の読み取りを開始する
+    fn spawn() -> Option<Self> {
+        let term = Term::stderr();
+        if !std::io::stdin().is_terminal() || !term.is_term() {
+            return None;
+        }
+
+        let terminal = TerminalGuard::save();
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            while let Ok(key) = term.read_key() {
+                if sender.send(key).is_err() {
+                    break;
+                }
+            }
+        });
+        Some(Self {
+            receiver,
+            _terminal: terminal,
+        })
+    }
+}
+
+/// 端末の設定を保存し、破棄時に元に戻す
+///
+/// キー入力スレッドは次のキー入力まで端末をrawモードにしたまま待機するため、
+/// 再生を終えた時点で`stty`により設定を戻す。`stty`がない環境では何もしない。
+struct TerminalGuard {
+    settings: Option<String>,
+}
+
+impl TerminalGuard {
+    fn save() -> Self {
+        let settings = Command::new("stty")
+            .arg("-g")
+            .stdin(Stdio::inherit())
+            .stderr(Stdio::null())
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|settings| settings.trim().to_string());
+        Self { settings }
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        if let Some(settings) = &self.settings {
+            let _ = Command::new("stty")
+                .arg(settings)
+                .stdin(Stdio::inherit())
+                .stderr(Stdio::null())
+                .status();
+        }
+    }
+}
+
+/// 音声再生の操作
+///
+/// 再生バッファは「カウントイン + 再生範囲」で、曲中の位置とバッファの位置を相互に変換する。
+pub struct AudioPlayback {
+    state: Arc<Mutex<PlaybackState>>,
+    sample_rate: f64,
+    /// 再生範囲の開始位置（曲の先頭からのサンプル位置）
+    range_start: usize,
+    /// バッファ先頭のカウントインのサンプル数
+    count_in: usize,
+}
+
+impl AudioPlayback {
+    #[must_use]
+    pub fn new(
+        state: Arc<Mutex<PlaybackState>>,
+        sample_rate: u32,
+        range: &PlayRange,
+        count_in: usize,
+    ) -> Self {
+        Self {
+            state,
+            sample_rate: f64::from(sample_rate),
+            range_start: range.start.sample,
+            count_in,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PlaybackState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Playback for AudioPlayback {
+    #[allow(clippy::cast_precision_loss)]
+    fn position(&self) -> f64 {
+        let position = self.lock().position();
+        (position.saturating_sub(self.count_in) + self.range_start) as f64 / self.sample_rate
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn seek(&mut self, seconds: f64) -> Result<()> {
+        let sample = (seconds * self.sample_rate) as usize;
+        let position = self.count_in + sample.saturating_sub(self.range_start);
+        self.lock().seek(position);
+        Ok(())
+    }
+
+    fn is_paused(&self) -> bool {
+        self.lock().is_paused()
+    }
+
+    fn set_paused(&mut self, paused: bool) -> Result<()> {
+        self.lock().set_paused(paused);
+        Ok(())
+    }
+
+    fn is_looping(&self) -> bool {
+        self.lock().is_loop_enabled()
+    }
+
+    fn set_looping(&mut self, looping: bool) {
+        self.lock().set_loop_enabled(looping);
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn stop(&mut self) -> Result<()> {
+        let fade_samples = (FADE_OUT_SECONDS * self.sample_rate) as usize;
+        self.lock().fade_out(fade_samples);
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Result<bool> {
+        Ok(self.lock().is_finished())
+    }
+}
+
+#[cfg(feature = "midi-output")]
+pub use self::midi_playback::{LayeredPlayback, MidiPlayback};
+
+#[cfg(feature = "midi-output")]
+mod midi_playback {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread::JoinHandle;
+    use std::time::{Duration, Instant};
+
+    use anyhow::Result;
+
+    use super::{AudioPlayback, Playback, EPSILON};
+    use crate::midi::{self, MidiError, MidiOutputPort, PlayOptions};
+    use crate::mml::{Command, Location, PlayRange};
+
+    type Worker = JoinHandle<(MidiOutputPort, Result<(), MidiError>)>;
+
+    /// 切断されたMIDIデバイスへの再接続を試みる間隔
+    const RECONNECT_INTERVAL: Duration = Duration::from_millis(500);
+
+    /// MIDI再生の操作
+    ///
+    /// MIDIは送信済みのイベントを取り消せないため、一時停止・シークのたびに再生スレッドを
+    /// 止め、新しい位置から再生し直す。MIDIクロックを送る場合は、止めるたびにStop、
+    /// 再開のたびにContinue（先頭からはStart）を送る。
+    ///
+    /// 送信に失敗した（デバイスが切断された）場合は送信に失敗した位置で止まり、
+    /// 同じ名前のデバイスに再接続できた時点でその位置から再生を再開する。
+    pub struct MidiPlayback {
+        commands: Arc<Vec<Command>>,
+        channel: u8,
+        range: PlayRange,
+        options: PlayOptions,
+        /// Ctrl+Cによる中断フラグ
+        interrupt: Arc<AtomicBool>,
+        /// 再生中のスレッドの停止フラグ
+        halt: Arc<AtomicBool>,
+        conn: Option<MidiOutputPort>,
+        worker: Option<Worker>,
+        /// デバイスの再接続待ちの場合、最後に再接続を試みた時刻
+        reconnecting: Option<Instant>,
+        /// 再生を開始した位置（秒）と時刻
+        started: (f64, Instant),
+        paused_at: Option<f64>,
+        looping: bool,
+        stopped: bool,
+    }
+
+    impl MidiPlayback {
+        /// 再生範囲の先頭で一時停止した状態で作成する
+        ///
+        /// # Errors
+        /// Returns `MidiError::InvalidChannel` if channel is not in 1-16 range.
+        pub fn new(
+            conn: MidiOutputPort,
+            commands: &[Command],
+            channel: u8,
+            range: &PlayRange,
+            options: PlayOptions,
+            interrupt: Arc<AtomicBool>,
+        ) -> Result<Self> {
+            MidiError::validate_channel(channel)?;
+            Ok(Self {
+                commands: Arc::new(commands.to_vec()),
+                channel,
+                range: *range,
+                options,
+                interrupt,
+                halt: Arc::new(AtomicBool::new(false)),
+                conn: Some(conn),
+                worker: None,
+                reconnecting: None,
+                started: (range.start.seconds, Instant::now()),
+                paused_at: Some(range.start.seconds),
+                looping: false,
+                stopped: false,
+            })
+        }
+
+        /// 再生範囲の先頭から再生を開始する
+        ///
+        /// # Errors
+        /// Returns `MidiError::InvalidChannel` if channel is not in 1-16 range.
+        pub fn start(
+            conn: MidiOutputPort,
+            commands: &[Command],
+            channel: u8,
+            range: &PlayRange,
+            options: PlayOptions,
+            looping: bool,
+            interrupt: Arc<AtomicBool>,
+        ) -> Result<Self> {
+            let mut playback = Self::new(conn, commands, channel, range, options, interrupt)?;
+            playback.set_looping(looping);
+            playback.set_paused(false)?;
+            Ok(playback)
+        }
+
+        /// 再生を止めてMIDI接続を返す（再読み込み後の再生に使う）
+        ///
+        /// # Errors
+        /// 再生スレッドでエラーが発生していた場合
+        pub fn into_connection(mut self) -> Result<MidiOutputPort> {
+            self.halt()?;
+            self.conn
+                .take()
+                .ok_or_else(|| anyhow::anyhow!("MIDI接続が失われました"))
+        }
+
+        /// 指定した位置から再生スレッドを起動する
+        fn play_from(&mut self, seconds: f64) {
+            if self.conn.as_ref().is_some_and(|conn| !conn.is_connected()) {
+                // 再接続してから再生する
+                self.paused_at = Some(seconds);
+                self.reconnecting.get_or_insert_with(Instant::now);
+                return;
+            }
+            let Some(mut conn) = self.conn.take() else {
+                return;
+            };
+            // Clockが使うのは開始位置の秒数のみ
+            let range = PlayRange {
+                start: Location {
+                    seconds,
+                    ..self.range.start
+                },
+                end: self.range.end,
+            };
+            let halt = Arc::new(AtomicBool::new(false));
+            self.halt = Arc::clone(&halt);
+            let commands = Arc::clone(&self.commands);
+            let channel = self.channel;
+            let options = self.options.clone();
+
+            self.started = (seconds, Instant::now());
+            self.worker = Some(std::thread::spawn(move || {
+                let result = midi::play_midi_stream_range(
+                    &mut conn, &commands, channel, &range, &halt, options,
+                );
+                (conn, result)
+            }));
+        }
+
+        /// 再生スレッドを止め、接続を取り戻す
+        fn halt(&mut self) -> Result<()> {
+            self.halt.store(true, Ordering::SeqCst);
+            if let Some(worker) = self.worker.take() {
+                let (conn, result) = worker
+                    .join()
+                    .map_err(|_| anyhow::anyhow!("MIDI再生スレッドが異常終了しました"))?;
+                let disconnected_at = conn.disconnected_at();
+                self.conn = Some(conn);
+                match (result, disconnected_at) {
+                    (Err(MidiError::DeviceDisconnected), Some(at)) => {
+                        // 送信に失敗した位置で止めて再接続を待つ
+                        let (from, started) = self.started;
+                        let position = from + at.saturating_duration_since(started).as_secs_f64();
+                        self.paused_at = Some(position.min(self.range.end.seconds));
+                        self.reconnecting = Some(Instant::now());
+                    }
+                    (result, _) => result?,
+                }
+            }
+            Ok(())
+        }
+
+        /// 停止キーまたはCtrl+Cで止めたかどうか
+        #[must_use]
+        pub fn is_stopped(&self) -> bool {
+            self.stopped
+        }
+
+        /// 一定間隔で再接続を試み、再接続できたら止まった位置から再生を再開する
+        fn reconnect(&mut self) {
+            let Some(attempted) = self.reconnecting else {
+                return;
+            };
+            if attempted.elapsed() < RECONNECT_INTERVAL {
+                return;
+            }
+            self.reconnecting = Some(Instant::now());
+            if !self.conn.as_mut().is_some_and(MidiOutputPort::reconnect) {
+                return;
+            }
+            self.reconnecting = None;
+            if let Some(position) = self.paused_at.take() {
+                self.play_from(position);
+            }
+        }
+    }
+
+    impl Playback for MidiPlayback {
+        fn position(&self) -> f64 {
+            if let Some(position) = self.paused_at {
+                return position;
+            }
+            let (from, at) = self.started;
+            (from + at.elapsed().as_secs_f64()).min(self.range.end.seconds)
+        }
+
+        fn seek(&mut self, seconds: f64) -> Result<()> {
+            if self.paused_at.is_some() {
+                self.paused_at = Some(seconds);
+                return Ok(());
+            }
+            self.halt()?;
+            self.play_from(seconds);
+            Ok(())
+        }
+
+        fn is_paused(&self) -> bool {
+            self.paused_at.is_some()
+        }
+
+        fn set_paused(&mut self, paused: bool) -> Result<()> {
+            match (paused, self.paused_at) {
+                (true, None) => {
+                    let position = self.position();
+                    self.halt()?;
+                    self.paused_at = Some(position);
+                }
+                (false, Some(position)) => {
+                    self.paused_at = None;
+                    self.play_from(position);
+                }
+                _ => {}
+            }
+            Ok(())
+        }
+
+        fn is_looping(&self) -> bool {
+            self.looping
+        }
+
+        fn set_looping(&mut self, looping: bool) {
+            self.looping = looping;
+        }
+
+        fn stop(&mut self) -> Result<()> {
+            self.stopped = true;
+            self.halt()
+        }
+
+        fn poll(&mut self) -> Result<bool> {
+            if self.interrupt.load(Ordering::Relaxed) {
+                self.stop()?;
+            }
+            if self.stopped {
+                return Ok(true);
+            }
+            if self.reconnecting.is_some() {
+                self.reconnect();
+                return Ok(false);
+            }
+            if self.paused_at.is_some()
+                || !self.worker.as_ref().is_some_and(JoinHandle::is_finished)
+            {
+                return Ok(false);
+            }
+
+            self.halt()?;
+            if self.reconnecting.is_some() {
+                return Ok(false);
+            }
+            // 範囲の終わりまで再生した
+            if self.looping {
+                self.play_from(self.range.start.seconds);
+                return Ok(false);
+            }
+            Ok(true)
+        }
+
+        fn is_waiting_for_device(&self) -> bool {
+            self.reconnecting.is_some()
+        }
+    }
+
+    impl Drop for MidiPlayback {
+        fn drop(&mut self) {
+            let _ = self.halt();
+        }
+    }
+
+    /// 音声とMIDIを同じ位置から同時に再生する（`--with-audio`）
+    ///
+    /// 操作は両方に反映し、再生位置は音声の位置を使う。ループ再生は音声側で行い、
+    /// 音声が先頭に戻った時点でMIDIをその位置から再生し直す（MIDIの再開による遅れが
+    /// 周回ごとに積み重ならないようにするため）。
+    pub struct LayeredPlayback {
+        audio: AudioPlayback,
+        midi: MidiPlayback,
+        /// 前回確認した音声の再生位置（ループで先頭に戻ったことの検出に使う）
+        last_position: f64,
+        /// MIDIが再生範囲の終わりまで送り終えた
+        midi_finished: bool,
+        stopped: bool,
+    }
+
+    impl LayeredPlayback {
+        /// 再生中の音声に合わせてMIDIの再生を開始する
+        ///
+        /// `midi`は[`MidiPlayback::new`]で作成した一時停止中のもの。
+        ///
+        /// # Errors
+        /// MIDI再生の開始に失敗した場合
+        pub fn start(audio: AudioPlayback, mut midi: MidiPlayback) -> Result<Self> {
+            let position = audio.position();
+            midi.seek(position)?;
+            midi.set_paused(false)?;
+            Ok(Self {
+                audio,
+                midi,
+                last_position: position,
+                midi_finished: false,
+                stopped: false,
+            })
+        }
+    }
+
+    impl Playback for LayeredPlayback {
+        fn position(&self) -> f64 {
+            self.audio.position()
+        }
+
+        fn seek(&mut self, seconds: f64) -> Result<()> {
+            self.audio.seek(seconds)?;
+            self.midi.seek(seconds)?;
+            self.last_position = seconds;
+            self.midi_finished = false;
+            Ok(())
+        }
+
+        fn is_paused(&self) -> bool {
+            self.audio.is_paused()
+        }
+
+        fn set_paused(&mut self, paused: bool) -> Result<()> {
+            self.audio.set_paused(paused)?;
+            if !paused {
+                self.midi.seek(self.audio.position())?;
+                self.midi_finished = false;
+            }
+            self.midi.set_paused(paused)
+        }
+
+        fn is_looping(&self) -> bool {
+            self.audio.is_looping()
+        }
+
+        fn set_looping(&mut self, looping: bool) {
+            self.audio.set_looping(looping);
+        }
+
+        fn stop(&mut self) -> Result<()> {
+            self.stopped = true;
+            self.audio.stop()?;
+            self.midi.stop()
+        }
+
+        fn poll(&mut self) -> Result<bool> {
+            if self.midi.poll()? {
+                if self.midi.is_stopped() && !self.stopped {
+                    // Ctrl+Cで中断した
+                    self.stop()?;
+                }
+                self.midi_finished = true;
+            }
+
+            let position = self.audio.position();
+            if position + EPSILON < self.last_position && !self.audio.is_paused() {
+                // 音声がループで先頭に戻った
+                self.midi.seek(position)?;
+                self.midi_finished = false;
+            }
+            self.last_position = position;
+
+            let audio_finished = self.audio.poll()?;
+            Ok(audio_finished && (self.midi_finished || self.midi.is_waiting_for_device()))
+        }
+
+        fn is_waiting_for_device(&self) -> bool {
+            self.midi.is_waiting_for_device()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timeline() -> Timeline {
+        // 2秒ごとの小節、再生範囲は1小節目〜4小節目の終わり
+        Timeline::new(0.0, 8.0, vec![0.0, 2.0, 4.0, 6.0, 8.0])
+    }
+
+    /// 操作を記録するだけの再生
+    #[derive(Default)]
+    struct FakePlayback {
+        position: f64,
+        paused: bool,
+        looping: bool,
+        stopped: bool,
+    }
+
+    impl Playback for FakePlayback {
+        fn position(&self) -> f64 {
+            self.position
+        }
+
+        fn seek(&mut self, seconds: f64) -> Result<()> {
+            self.position = seconds;
+            Ok(())
+        }
+
+        fn is_paused(&self) -> bool {
+            self.paused
+        }
+
+        fn set_paused(&mut self, paused: bool) -> Result<()> {
+            self.paused = paused;
+            Ok(())
+        }
+
+        fn is_looping(&self) -> bool {
+            self.looping
+        }
+
+        fn set_looping(&mut self, looping: bool) {
+            self.looping = looping;
+        }
+
+        fn stop(&mut self) -> Result<()> {
+            self.stopped = true;
+            Ok(())
+        }
+
+        fn poll(&mut self) -> Result<bool> {
+            Ok(self.stopped)
+        }
+    }
+
+    fn assert_target(command: TransportCommand, position: f64, expected: f64) {
+        let target = timeline().seek_target(command, position).unwrap();
+        assert!(
+            (target - expected).abs() < 1e-9,
+            "{command:?} at {position}: expected {expected}, got {target}"
+        );
+    }
+
+    #[test]
+    fn key_mapping() {
+        assert_eq!(
+            TransportCommand::from_key(&Key::Char(' ')),
+            Some(TransportCommand::TogglePause)
+        );
+        assert_eq!(
+            TransportCommand::from_key(&Key::ArrowLeft),
+            Some(TransportCommand::PreviousBar)
+        );
+        assert_eq!(
+            TransportCommand::from_key(&Key::ArrowRight),
+            Some(TransportCommand::NextBar)
+        );
+        assert_eq!(
+            TransportCommand::from_key(&Key::ArrowDown),
+            Some(TransportCommand::Rewind)
+        );
+        assert_eq!(
+            TransportCommand::from_key(&Key::ArrowUp),
+            Some(TransportCommand::FastForward)
+        );
+        assert_eq!(
+            TransportCommand::from_key(&Key::Char('l')),
+            Some(TransportCommand::ToggleLoop)
+        );
+        assert_eq!(
+            TransportCommand::from_key(&Key::Char('q')),
+            Some(TransportCommand::Stop)
+        );
+        assert_eq!(TransportCommand::from_key(&Key::Char('x')), None);
+    }
+
+    #[test]
+    fn seek_by_bar() {
+        assert_target(TransportCommand::NextBar, 2.5, 4.0);
+        assert_target(TransportCommand::NextBar, 2.0, 4.0);
+        assert_target(TransportCommand::PreviousBar, 3.0, 2.0);
+        // 小節頭の直後は前の小節へ
+        assert_target(TransportCommand::PreviousBar, 2.2, 0.0);
+        assert_target(TransportCommand::PreviousBar, 0.3, 0.0);
+        assert_target(TransportCommand::NextBar, 7.0, 8.0);
+    }
+
+    #[test]
+    fn seek_by_seconds_is_clamped_to_range() {
+        let timeline = Timeline::new(2.0, 6.0, vec![0.0, 2.0, 4.0, 6.0, 8.0]);
+        let rewind = timeline.seek_target(TransportCommand::Rewind, 3.0).unwrap();
+        let forward = timeline
+            .seek_target(TransportCommand::FastForward, 3.0)
+            .unwrap();
+        let previous = timeline
+            .seek_target(TransportCommand::PreviousBar, 2.1)
+            .unwrap();
+        assert!((rewind - 2.0).abs() < 1e-9);
+        assert!((forward - 6.0).abs() < 1e-9);
+        assert!((previous - 2.0).abs() < 1e-9);
+        assert_eq!(timeline.seek_target(TransportCommand::Stop, 3.0), None);
+    }
+
+    #[test]
+    fn apply_commands() {
+        let timeline = timeline();
+        let mut playback = FakePlayback {
+            position: 3.0,
+            ..FakePlayback::default()
+        };
+
+        apply(&mut playback, &timeline, TransportCommand::TogglePause).unwrap();
+        assert!(playback.paused);
+        apply(&mut playback, &timeline, TransportCommand::NextBar).unwrap();
+        assert!((playback.position - 4.0).abs() < 1e-9);
+        apply(&mut playback, &timeline, TransportCommand::TogglePause).unwrap();
+        assert!(!playback.paused);
+
+        apply(&mut playback, &timeline, TransportCommand::ToggleLoop).unwrap();
+        assert!(playback.looping);
+        apply(&mut playback, &timeline, TransportCommand::Stop).unwrap();
+        assert!(playback.poll().unwrap());
+    }
+
+    #[test]
+    fn audio_playback_maps_song_position_to_buffer() {
+        let mml = crate::mml::parse("T120 C1 D1 E1 F1").unwrap();
+        let range = mml
+            .play_range(Some(&PlayPosition::Bar(2)), None, 100)
+            .unwrap();
+        // カウントイン50サンプル + 2小節目以降（600サンプル）
+        let state = Arc::new(Mutex::new(PlaybackState::new(vec![0.0; 650], false, 50)));
+        let mut playback = AudioPlayback::new(Arc::clone(&state), 100, &range, 50);
+
+        assert!((playback.position() - 2.0).abs() < 1e-9);
+        playback.seek(5.0).unwrap();
+        assert_eq!(state.lock().unwrap().position(), 350);
+        assert!((playback.position() - 5.0).abs() < 1e-9);
+
+        playback.stop().unwrap();
+        assert!(!playback.poll().unwrap());
+        for _ in 0..10 {
+            state.lock().unwrap().next_sample();
+        }
+        assert!(playback.poll().unwrap());
+    }
+}
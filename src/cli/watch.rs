@@ -0,0 +1,199 @@
+//! ファイル監視モジュール（`play --watch`）
+//!
+//! MMLファイル（とインクルードしたファイル）の更新日時・サイズを定期的に確認し、
+//! 保存されたことを検出する。
+//!
+//! # ビジネスルール
+//! - 変更を検出してから`SETTLE`の間ファイルが変わらなくなった時点で変更とみなす
+//!   （エディタが複数回に分けて書き込む場合に、書きかけの内容を読まないため）
+//! - 履歴への保存は、内容が`delay`の間変わらなかった時点で行う（保存のたびには記録しない）
+//! - 直前に保存した内容と同じMMLは記録しない
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+/// 変更を検出してから確定するまでの待ち時間
+const SETTLE: Duration = Duration::from_millis(150);
+
+/// ファイルの状態（存在しない場合は`None`）
+type Stamp = Option<(SystemTime, u64)>;
+
+/// 監視対象ファイルの変更検出
+pub struct FileWatcher {
+    files: Vec<PathBuf>,
+    stamps: Vec<Stamp>,
+    /// 検出した変更（確定待ち）とその検出時刻
+    pending: Option<(Vec<Stamp>, Instant)>,
+}
+
+impl FileWatcher {
+    #[must_use]
+    pub fn new(files: Vec<PathBuf>) -> Self {
+        let stamps = snapshot(&files);
+        Self {
+            files,
+            stamps,
+            pending: None,
+        }
+    }
+
+    /// 監視対象を追加し、現在の状態を基準にする
+    ///
+    /// インクルードの追加・削除に追従するため、読み込みのたびに呼ぶ。
+    /// 以前の監視対象は残す（読み込みに失敗した場合も監視を続けるため）。
+    pub fn watch(&mut self, files: impl IntoIterator<Item = PathBuf>) {
+        for file in files {
+            if !self.files.contains(&file) {
+                self.files.push(file);
+            }
+        }
+        self.stamps = snapshot(&self.files);
+        self.pending = None;
+    }
+
+    /// 前回の確認以降にファイルが変更されたかどうか
+    pub fn changed(&mut self) -> bool {
+        self.changed_at(Instant::now())
+    }
+
+    fn changed_at(&mut self, now: Instant) -> bool {
+        let current = snapshot(&self.files);
+        if current == self.stamps {
+            self.pending = None;
+            return false;
+        }
+        match &self.pending {
+            Some((stamps, since)) if *stamps == current => {
+                if now.duration_since(*since) < SETTLE {
+                    return false;
+                }
+                self.stamps = current;
+                self.pending = None;
+                true
+            }
+            _ => {
+                self.pending = Some((current, now));
+                false
+            }
+        }
+    }
+}
+
+fn snapshot(files: &[PathBuf]) -> Vec<Stamp> {
+    files
+        .iter()
+        .map(|file| {
+            let metadata = fs::metadata(file).ok()?;
+            Some((metadata.modified().ok()?, metadata.len()))
+        })
+        .collect()
+}
+
+/// 履歴への保存の間引き
+pub struct HistoryDebouncer {
+    delay: Duration,
+    /// 保存待ちのMMLと、その内容になった時刻
+    pending: Option<(String, Instant)>,
+    last_saved: Option<String>,
+}
+
+impl HistoryDebouncer {
+    #[must_use]
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            pending: None,
+            last_saved: None,
+        }
+    }
+
+    /// 読み込んだMMLを記録する
+    pub fn update(&mut self, mml: &str, now: Instant) {
+        if self
+            .pending
+            .as_ref()
+            .is_some_and(|(pending, _)| pending == mml)
+        {
+            return;
+        }
+        self.pending = (self.last_saved.as_deref() != Some(mml)).then(|| (mml.to_string(), now));
+    }
+
+    /// 保存すべきMML（内容が`delay`の間変わらなかった場合）
+    pub fn due(&mut self, now: Instant) -> Option<String> {
+        match &self.pending {
+            Some((_, since)) if now.duration_since(*since) >= self.delay => self.flush(),
+            _ => None,
+        }
+    }
+
+    /// 保存待ちのMMLを待ち時間に関係なく取り出す（終了時に使用）
+    pub fn flush(&mut self) -> Option<String> {
+        let (mml, _) = self.pending.take()?;
+        self.last_saved = Some(mml.clone());
+        Some(mml)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn file_watcher_detects_settled_change() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("song.mml");
+        fs::write(&path, "C").unwrap();
+
+        let mut watcher = FileWatcher::new(vec![path.clone()]);
+        let start = Instant::now();
+        assert!(!watcher.changed_at(start));
+
+        fs::write(&path, "CDE").unwrap();
+        assert!(!watcher.changed_at(start));
+        assert!(!watcher.changed_at(start + SETTLE / 2));
+        assert!(watcher.changed_at(start + SETTLE));
+        assert!(!watcher.changed_at(start + SETTLE * 2));
+    }
+
+    #[test]
+    fn file_watcher_follows_added_files() {
+        let dir = tempdir().unwrap();
+        let song = dir.path().join("song.mml");
+        let part = dir.path().join("part.mml");
+        fs::write(&song, "C").unwrap();
+
+        let mut watcher = FileWatcher::new(vec![song]);
+        // 存在しないファイルも監視でき、作成されると変更として検出する
+        watcher.watch([part.clone()]);
+        fs::write(&part, "D").unwrap();
+
+        let start = Instant::now();
+        assert!(!watcher.changed_at(start));
+        assert!(watcher.changed_at(start + SETTLE));
+    }
+
+    #[test]
+    fn history_debouncer_saves_stable_content_once() {
+        let delay = Duration::from_secs(10);
+        let mut history = HistoryDebouncer::new(delay);
+        let start = Instant::now();
+
+        history.update("C", start);
+        history.update("CD", start + Duration::from_secs(5));
+        assert_eq!(history.due(start + delay), None);
+        assert_eq!(
+            history.due(start + Duration::from_secs(15)),
+            Some("CD".to_string())
+        );
+        assert_eq!(history.due(start + Duration::from_secs(30)), None);
+
+        // 保存済みと同じ内容は記録しない
+        history.update("CD", start + Duration::from_secs(40));
+        assert_eq!(history.flush(), None);
+        history.update("CDE", start + Duration::from_secs(41));
+        assert_eq!(history.flush(), Some("CDE".to_string()));
+    }
+}
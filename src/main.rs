@@ -1,12 +1,13 @@
 use clap::Parser;
 use sine_mml::cli::args::{Cli, Command};
 use sine_mml::cli::handlers::{
-    clear_history_handler, export_handler, history_handler, play_handler,
+    audio_handler, clear_history_handler, export_handler, history_handler, play_handler,
+    repl_handler,
 };
 use sine_mml::cli::output;
 
 #[cfg(feature = "midi-output")]
-use sine_mml::cli::handlers::midi_handler;
+use sine_mml::cli::handlers::{midi_handler, record_handler};
 
 fn main() {
     let cli = Cli::parse();
@@ -16,8 +17,12 @@ fn main() {
         Command::History => history_handler(),
         Command::Export(args) => export_handler(args),
         Command::ClearHistory => clear_history_handler(),
+        Command::Repl(args) => repl_handler(args),
+        Command::Audio(args) => audio_handler(args),
         #[cfg(feature = "midi-output")]
         Command::Midi(args) => midi_handler(args),
+        #[cfg(feature = "midi-output")]
+        Command::Record(args) => record_handler(args),
     };
 
     if let Err(e) = result {
@@ -0,0 +1,227 @@
+//! MIDI clock scheduling
+//!
+//! This module computes when to send MIDI Timing Clock (0xF8, 24 per quarter note)
+//! and the transport message that starts the clock. It has no I/O so that the
+//! schedule can be tested without a MIDI device; the player sends the events.
+//!
+//! # Business rules
+//! - Clock ticks are on a straight beat grid (swing does not move them)
+//! - Tempo changes (`T`, `Tn>m:b`) re-anchor the grid at the position of the change
+//! - Playback from the top of the song starts with Start (0xFA)
+//! - Playback from the middle of the song starts with Song Position Pointer and
+//!   Continue (0xFB) at the first sixteenth-note boundary at or after the start
+//!   position; ticks before it are not sent
+
+use crate::mml::{swing_position, TempoState};
+
+/// Timing Clock messages per quarter note
+pub const CLOCKS_PER_QUARTER: u32 = 24;
+
+/// Timing Clock messages per Song Position Pointer unit (a sixteenth note)
+const CLOCKS_PER_SIXTEENTH: u64 = 6;
+
+/// Tolerance for comparing tick times (seconds)
+const EPSILON: f64 = 1e-9;
+
+/// Event to send at a scheduled time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockEvent {
+    /// Start (0xFA) from the top of the song
+    Start,
+    /// Song Position Pointer (0xF2) followed by Continue (0xFB)
+    Continue {
+        /// Position in sixteenth notes from the top of the song
+        position: u16,
+    },
+    /// Timing Clock (0xF8)
+    Tick,
+}
+
+/// Schedule of MIDI clock events for one playback run
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClockScheduler {
+    /// Tempo without swing
+    tempo: TempoState,
+    /// Straight beat position of the latest tempo change
+    anchor_beat: f64,
+    /// Song position (seconds) of the latest tempo change
+    anchor_seconds: f64,
+    /// Index of the next tick from the top of the song
+    next_tick: u64,
+    started: bool,
+}
+
+impl Default for ClockScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClockScheduler {
+    /// Create a schedule at the top of the song with the default tempo.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            tempo: TempoState::default(),
+            anchor_beat: 0.0,
+            anchor_seconds: 0.0,
+            next_tick: 0,
+            started: false,
+        }
+    }
+
+    /// Follow the tempo of the song.
+    ///
+    /// `beat` is the beat position in the score and `seconds` the song position of
+    /// the same point. Does nothing if only the swing changed.
+    pub fn set_tempo(&mut self, tempo: &TempoState, beat: f64, seconds: f64) {
+        let straight = TempoState {
+            swing: None,
+            ..*tempo
+        };
+        if straight == self.tempo {
+            return;
+        }
+        self.anchor_beat = tempo
+            .swing
+            .map_or(beat, |swing| swing_position(swing, beat));
+        self.anchor_seconds = seconds;
+        self.tempo = straight;
+    }
+
+    /// Whether Start or Continue has been sent (Stop is due at the end).
+    #[must_use]
+    pub fn is_started(&self) -> bool {
+        self.started
+    }
+
+    /// Song position (seconds) of the tick `tick` with the current tempo.
+    #[must_use]
+    pub fn tick_seconds(&self, tick: u64) -> f64 {
+        #[allow(clippy::cast_precision_loss)]
+        let beat = tick as f64 / f64::from(CLOCKS_PER_QUARTER);
+        self.anchor_seconds
+            + self
+                .tempo
+                .seconds(self.anchor_beat, beat - self.anchor_beat)
+    }
+
+    /// Next event due at or before `until`, with its song position (seconds).
+    ///
+    /// `from` is the start of playback and `end` the end of the playback range;
+    /// no events are scheduled at or after `end`. Call repeatedly until `None`.
+    pub fn poll(&mut self, from: f64, until: f64, end: Option<f64>) -> Option<(f64, ClockEvent)> {
+        loop {
+            let time = self.tick_seconds(self.next_tick);
+            if time > until + EPSILON || end.is_some_and(|end| time >= end - EPSILON) {
+                return None;
+            }
+
+            if !self.started {
+                if time < from - EPSILON || !self.next_tick.is_multiple_of(CLOCKS_PER_SIXTEENTH) {
+                    self.next_tick += 1;
+                    continue;
+                }
+                self.started = true;
+                let event = match self.next_tick / CLOCKS_PER_SIXTEENTH {
+                    0 => ClockEvent::Start,
+                    position => ClockEvent::Continue {
+                        position: u16::try_from(position).unwrap_or(u16::MAX).min(0x3FFF),
+                    },
+                };
+                return Some((time, event));
+            }
+
+            self.next_tick += 1;
+            return Some((time, ClockEvent::Tick));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mml::{Swing, TempoRamp};
+
+    fn drain(scheduler: &mut ClockScheduler, from: f64, until: f64) -> Vec<(f64, ClockEvent)> {
+        std::iter::from_fn(|| scheduler.poll(from, until, None)).collect()
+    }
+
+    #[test]
+    fn ticks_at_24_ppqn_from_start() {
+        let mut scheduler = ClockScheduler::new();
+        // 120 BPM: 1拍0.5秒、1クロック1/48秒
+        let events = drain(&mut scheduler, 0.0, 0.5);
+        assert_eq!(events[0], (0.0, ClockEvent::Start));
+        assert_eq!(events.len(), 1 + 25);
+        assert!(events[1..]
+            .iter()
+            .all(|(_, event)| *event == ClockEvent::Tick));
+        assert!((events[2].0 - 1.0 / 48.0).abs() < 1e-9);
+        assert!((events[25].0 - 0.5).abs() < 1e-9);
+        assert!(scheduler.is_started());
+    }
+
+    #[test]
+    fn follows_tempo_change() {
+        let mut scheduler = ClockScheduler::new();
+        drain(&mut scheduler, 0.0, 0.5);
+        // 1拍目の位置（0.5秒）でT60に変更
+        scheduler.set_tempo(&TempoState::new(60), 1.0, 0.5);
+        let events = drain(&mut scheduler, 0.0, 1.5);
+        assert_eq!(events.len(), 24);
+        assert!((events[0].0 - (0.5 + 1.0 / 24.0)).abs() < 1e-9);
+        assert!((events[23].0 - 1.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn follows_tempo_ramp() {
+        let mut scheduler = ClockScheduler::new();
+        let mut tempo = TempoState::new(60);
+        let ramp = TempoRamp {
+            from: None,
+            to: 120,
+            beats: 4,
+        };
+        tempo.apply_ramp(&ramp, 0.0);
+        scheduler.set_tempo(&tempo, 0.0, 0.0);
+        let until = tempo.seconds(0.0, 4.0);
+        let events = drain(&mut scheduler, 0.0, until);
+        assert_eq!(events.len(), 1 + 4 * 24 + 1);
+        assert!((events.last().unwrap().0 - until).abs() < 1e-9);
+    }
+
+    #[test]
+    fn swing_does_not_move_ticks() {
+        let mut scheduler = ClockScheduler::new();
+        let mut tempo = TempoState::new(120);
+        tempo.set_swing(Swing {
+            ratio: 66,
+            subdivision: 8,
+        });
+        scheduler.set_tempo(&tempo, 0.0, 0.0);
+        let events = drain(&mut scheduler, 0.0, 1.0);
+        assert_eq!(events.len(), 1 + 49);
+        assert!((events[13].0 - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn continues_from_next_sixteenth_note() {
+        let mut scheduler = ClockScheduler::new();
+        // 1拍目と2拍目の16分音符の間（0.5秒 + 1/48秒）から再生
+        let from = 0.5 + 1.0 / 48.0;
+        assert_eq!(drain(&mut scheduler, from, 0.5), []);
+        let events = drain(&mut scheduler, from, 1.0);
+        // 次の16分音符（5番目、0.625秒）からContinue
+        assert_eq!(events[0], (0.625, ClockEvent::Continue { position: 5 }));
+        assert_eq!(events[1], (0.625, ClockEvent::Tick));
+        assert_eq!(events.len(), 1 + 19);
+    }
+
+    #[test]
+    fn stops_before_range_end() {
+        let mut scheduler = ClockScheduler::new();
+        let events: Vec<_> = std::iter::from_fn(|| scheduler.poll(0.0, 10.0, Some(0.5))).collect();
+        assert_eq!(events.len(), 1 + 24);
+    }
+}
@@ -1,10 +1,16 @@
 //! MIDI device management
 //!
-//! This module handles MIDI device enumeration and connection.
+//! This module handles MIDI device enumeration and connection, and reconnecting to
+//! an output device that was unplugged during playback ([`MidiOutputPort`]).
 
-use midir::{MidiOutput, MidiOutputConnection};
+use std::time::Instant;
+
+use midir::{MidiInput, MidiOutput, MidiOutputConnection};
 
 use super::error::MidiError;
+use super::message::send_note_off;
+use super::player::ChannelTracker;
+use super::sink::MidiSink;
 
 /// Information about a MIDI device.
 #[derive(Debug, Clone)]
@@ -127,6 +133,48 @@ pub fn resolve_device_id(name_or_id: &str) -> Result<usize, MidiError> {
     Err(MidiError::NoDeviceFound)
 }
 
+/// List all available MIDI input devices.
+///
+/// # Returns
+///
+/// A vector of device names on success.
+///
+/// # Errors
+///
+/// Returns `MidiError::NoDeviceFound` if MIDI input cannot be initialized.
+pub fn list_midi_input_devices() -> Result<Vec<String>, MidiError> {
+    let midi_in = MidiInput::new("sine-mml-list").map_err(|_| MidiError::NoDeviceFound)?;
+
+    Ok(midi_in
+        .ports()
+        .iter()
+        .filter_map(|port| midi_in.port_name(port).ok())
+        .collect())
+}
+
+/// Resolve an input device identifier (ID or name) to a device ID.
+///
+/// Works like [`resolve_device_id`] on the list of MIDI input devices.
+///
+/// # Errors
+///
+/// - `MidiError::InvalidDeviceId` if numeric ID is out of range
+/// - `MidiError::NoDeviceFound` if name doesn't match any device
+pub fn resolve_input_device_id(name_or_id: &str) -> Result<usize, MidiError> {
+    let devices = list_midi_input_devices()?;
+    if let Ok(id) = name_or_id.parse::<usize>() {
+        if id >= devices.len() {
+            return Err(MidiError::InvalidDeviceId { id });
+        }
+        return Ok(id);
+    }
+
+    devices
+        .iter()
+        .position(|device_name| device_name.contains(name_or_id))
+        .ok_or(MidiError::NoDeviceFound)
+}
+
 /// Connect to a MIDI output device.
 ///
 /// # Arguments
@@ -152,8 +200,11 @@ pub fn resolve_device_id(name_or_id: &str) -> Result<usize, MidiError> {
 /// // Use conn to send MIDI messages...
 /// ```
 pub fn connect_midi_device(device_id_or_name: &str) -> Result<MidiOutputConnection, MidiError> {
-    let id = resolve_device_id(device_id_or_name)?;
+    connect_port(resolve_device_id(device_id_or_name)?)
+}
 
+/// Connect to the MIDI output device with the given ID.
+fn connect_port(id: usize) -> Result<MidiOutputConnection, MidiError> {
     let midi_out = MidiOutput::new("sine-mml-output").map_err(|_| MidiError::NoDeviceFound)?;
 
     let ports = midi_out.ports();
@@ -166,6 +217,193 @@ pub fn connect_midi_device(device_id_or_name: &str) -> Result<MidiOutputConnecti
         })
 }
 
+/// Client name of the virtual ports created by [`create_virtual_output`].
+///
+/// Device lists show the ports as `sine-mml:<port name>`.
+pub const VIRTUAL_CLIENT_NAME: &str = "sine-mml";
+
+/// Create a named virtual MIDI output port.
+///
+/// Other applications can subscribe to the port while the returned connection is
+/// alive. Subscribers see it among their MIDI inputs, so [`list_midi_input_devices`]
+/// lists it too (see [`is_virtual_port`]).
+///
+/// # Errors
+///
+/// - `MidiError::NoDeviceFound` if MIDI output cannot be initialized
+/// - `MidiError::ConnectionFailed` if the port cannot be created
+/// - `MidiError::VirtualPortUnsupported` on platforms without virtual ports (Windows)
+#[cfg(unix)]
+pub fn create_virtual_output(port_name: &str) -> Result<MidiOutputConnection, MidiError> {
+    use midir::os::unix::VirtualOutput;
+
+    let midi_out = MidiOutput::new(VIRTUAL_CLIENT_NAME).map_err(|_| MidiError::NoDeviceFound)?;
+    midi_out
+        .create_virtual(port_name)
+        .map_err(|e| MidiError::connection_failed(e.to_string()))
+}
+
+/// Create a named virtual MIDI output port.
+///
+/// # Errors
+///
+/// Always returns `MidiError::VirtualPortUnsupported`.
+#[cfg(not(unix))]
+pub fn create_virtual_output(_port_name: &str) -> Result<MidiOutputConnection, MidiError> {
+    Err(MidiError::VirtualPortUnsupported)
+}
+
+/// Check if a device name is a virtual port created by [`create_virtual_output`].
+#[must_use]
+pub fn is_virtual_port(device_name: &str) -> bool {
+    device_name
+        .strip_prefix(VIRTUAL_CLIENT_NAME)
+        .is_some_and(|rest| rest.starts_with(':'))
+}
+
+/// Device name without the trailing ALSA client:port address (e.g. `24:0`).
+///
+/// The address can change when a device is plugged in again, so devices are
+/// compared by this name when reconnecting.
+#[must_use]
+pub fn device_base_name(device_name: &str) -> &str {
+    match device_name.rsplit_once(' ') {
+        Some((base, address))
+            if address.split_once(':').is_some_and(|(client, port)| {
+                [client, port]
+                    .iter()
+                    .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+            }) =>
+        {
+            base
+        }
+        _ => device_name,
+    }
+}
+
+/// MIDI output that can reconnect to the same device after it was unplugged
+///
+/// When sending fails the connection is dropped, and sends return
+/// `MidiError::DeviceDisconnected` until [`MidiOutputPort::reconnect`] finds a device
+/// with the same name again. Notes that were sounding when the device was lost get
+/// Note Off after reconnecting.
+pub struct MidiOutputPort {
+    /// Device name used to find the device again (`None` for virtual ports)
+    name: Option<String>,
+    conn: Option<MidiOutputConnection>,
+    notes: ChannelTracker,
+    disconnected_at: Option<Instant>,
+}
+
+impl MidiOutputPort {
+    /// Connect to a MIDI output device (ID or name).
+    ///
+    /// # Errors
+    ///
+    /// - `MidiError::InvalidDeviceId` if numeric ID is out of range
+    /// - `MidiError::NoDeviceFound` if name doesn't match any device or MIDI init fails
+    /// - `MidiError::ConnectionFailed` if connection to the device fails
+    pub fn open(device_id_or_name: &str) -> Result<Self, MidiError> {
+        let id = resolve_device_id(device_id_or_name)?;
+        let name = list_midi_devices()?
+            .into_iter()
+            .nth(id)
+            .ok_or(MidiError::InvalidDeviceId { id })?;
+        let conn = connect_port(id)?;
+        Ok(Self::new(Some(name), conn))
+    }
+
+    /// Create a named virtual MIDI output port (see [`create_virtual_output`]).
+    ///
+    /// # Errors
+    ///
+    /// Same as [`create_virtual_output`].
+    pub fn open_virtual(port_name: &str) -> Result<Self, MidiError> {
+        Ok(Self::new(None, create_virtual_output(port_name)?))
+    }
+
+    fn new(name: Option<String>, conn: MidiOutputConnection) -> Self {
+        Self {
+            name,
+            conn: Some(conn),
+            notes: ChannelTracker::default(),
+            disconnected_at: None,
+        }
+    }
+
+    #[must_use]
+    pub fn is_connected(&self) -> bool {
+        self.conn.is_some()
+    }
+
+    /// Time when sending failed (`None` while connected).
+    #[must_use]
+    pub fn disconnected_at(&self) -> Option<Instant> {
+        self.disconnected_at
+    }
+
+    /// Reconnect to a device with the same name, if it is available again.
+    ///
+    /// Sends Note Off for the notes that were sounding when the device was lost.
+    /// Returns whether the port is connected.
+    pub fn reconnect(&mut self) -> bool {
+        if self.is_connected() {
+            return true;
+        }
+        let Some(name) = self.name.as_deref() else {
+            return false;
+        };
+        let Some(device) = list_midi_devices_info().ok().and_then(|devices| {
+            devices
+                .into_iter()
+                .find(|device| device_base_name(&device.name) == device_base_name(name))
+        }) else {
+            return false;
+        };
+        let Ok(conn) = connect_port(device.id) else {
+            return false;
+        };
+
+        self.name = Some(device.name);
+        self.conn = Some(conn);
+        for (channel, note) in self.notes.sounding().to_vec() {
+            if send_note_off(self, channel, note).is_err() {
+                return false;
+            }
+        }
+        self.disconnected_at = None;
+        true
+    }
+}
+
+impl MidiSink for MidiOutputPort {
+    fn send(&mut self, bytes: &[u8]) -> Result<(), MidiError> {
+        let conn = self.conn.as_mut().ok_or(MidiError::DeviceDisconnected)?;
+        if conn.send(bytes).is_err() {
+            self.conn = None;
+            self.disconnected_at = Some(Instant::now());
+            return Err(MidiError::DeviceDisconnected);
+        }
+        track_notes(&mut self.notes, bytes);
+        Ok(())
+    }
+}
+
+/// Record sent Note On / Note Off messages (Note On with velocity 0 is Note Off).
+fn track_notes(notes: &mut ChannelTracker, bytes: &[u8]) {
+    let &[status, note, velocity, ..] = bytes else {
+        return;
+    };
+    let channel = (status & 0x0F) + 1;
+    match status & 0xF0 {
+        0x90 if velocity > 0 => notes.note_on(channel, note),
+        0x80 | 0x90 => {
+            notes.note_off(channel, note);
+        }
+        _ => {}
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -194,6 +432,38 @@ mod tests {
         assert!(debug_str.contains("Debug Test"));
     }
 
+    #[test]
+    fn test_is_virtual_port() {
+        assert!(is_virtual_port("sine-mml:DAW 128:0"));
+        assert!(!is_virtual_port("sine-mml-list:port 129:0"));
+        assert!(!is_virtual_port("Midi Through:Midi Through Port-0 14:0"));
+    }
+
+    #[test]
+    fn test_device_base_name() {
+        assert_eq!(
+            device_base_name("USB MIDI:USB MIDI MIDI 1 24:0"),
+            "USB MIDI:USB MIDI MIDI 1"
+        );
+        assert_eq!(device_base_name("IAC Driver Bus 1"), "IAC Driver Bus 1");
+        assert_eq!(device_base_name("Synth 1:"), "Synth 1:");
+        assert_eq!(device_base_name("Device 2:a"), "Device 2:a");
+    }
+
+    #[test]
+    fn test_track_notes() {
+        let mut notes = ChannelTracker::default();
+        track_notes(&mut notes, &[0x90, 60, 100]);
+        track_notes(&mut notes, &[0x91, 64, 100]);
+        track_notes(&mut notes, &[0x99, 36, 100]);
+        track_notes(&mut notes, &[0x80, 60, 0]);
+        // ベロシティ0のNote OnはNote Off
+        track_notes(&mut notes, &[0x99, 36, 0]);
+        track_notes(&mut notes, &[0xB1, 123, 0]);
+        track_notes(&mut notes, &[0xF8]);
+        assert_eq!(notes.sounding(), [(2, 64)]);
+    }
+
     #[test]
     fn test_list_midi_devices_returns_result() {
         // This test verifies that list_midi_devices() doesn't panic
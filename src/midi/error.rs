@@ -42,6 +42,10 @@ pub enum MidiError {
         /// The invalid channel number
         channel: u8,
     },
+
+    /// Virtual MIDI ports are not supported on this platform
+    #[error("[MML-E025] この環境では仮想MIDIポートを作成できません")]
+    VirtualPortUnsupported,
 }
 
 impl MidiError {
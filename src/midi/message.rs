@@ -3,10 +3,9 @@
 //! This module defines MIDI message structures for note and control events,
 //! and provides conversion functions between MML and MIDI formats.
 
-use midir::MidiOutputConnection;
-
 use super::error::MidiError;
-use crate::mml::{Accidental, Pitch};
+use super::sink::MidiSink;
+use crate::mml::{Accidental, MidiControl, Pitch};
 
 /// MIDI channel number (1-16)
 pub type MidiChannel = u8;
@@ -17,7 +16,19 @@ pub type MidiNote = u8;
 /// MIDI velocity (0-127)
 pub type MidiVelocity = u8;
 
-/// MIDI message types
+/// Pitch Bend value range (14-bit, centered at 0)
+pub const PITCH_BEND_MIN: i16 = -8192;
+/// Pitch Bend value range (14-bit, centered at 0)
+pub const PITCH_BEND_MAX: i16 = 8191;
+
+/// Control Change number of All Notes Off
+const CC_ALL_NOTES_OFF: u8 = 123;
+/// Control Change number of Bank Select MSB
+const CC_BANK_SELECT_MSB: u8 = 0;
+/// Control Change number of Bank Select LSB
+const CC_BANK_SELECT_LSB: u8 = 32;
+
+/// MIDI channel voice message types
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MidiMessage {
     /// Note On event
@@ -38,6 +49,56 @@ pub enum MidiMessage {
         /// Velocity (0-127, typically 0)
         velocity: MidiVelocity,
     },
+    /// Polyphonic Key Pressure (per-note aftertouch)
+    PolyPressure {
+        /// Channel (1-16)
+        channel: MidiChannel,
+        /// Note number (0-127)
+        note: MidiNote,
+        /// Pressure (0-127)
+        pressure: u8,
+    },
+    /// Control Change
+    ControlChange {
+        /// Channel (1-16)
+        channel: MidiChannel,
+        /// Controller number (0-127)
+        controller: u8,
+        /// Value (0-127)
+        value: u8,
+    },
+    /// Program Change (2 bytes)
+    ProgramChange {
+        /// Channel (1-16)
+        channel: MidiChannel,
+        /// Program number (0-127)
+        program: u8,
+    },
+    /// Channel Pressure (channel aftertouch, 2 bytes)
+    ChannelPressure {
+        /// Channel (1-16)
+        channel: MidiChannel,
+        /// Pressure (0-127)
+        pressure: u8,
+    },
+    /// Pitch Bend
+    PitchBend {
+        /// Channel (1-16)
+        channel: MidiChannel,
+        /// Bend amount (-8192 to 8191, 0 = center)
+        value: i16,
+    },
+    /// Bank Select sequence (Control Change #0, then #32 if `lsb` is given)
+    ///
+    /// Send a Program Change afterwards to apply the bank.
+    BankSelect {
+        /// Channel (1-16)
+        channel: MidiChannel,
+        /// Bank MSB (0-127)
+        msb: u8,
+        /// Bank LSB (0-127)
+        lsb: Option<u8>,
+    },
     /// All Notes Off (Control Change #123)
     AllNotesOff {
         /// Channel (1-16)
@@ -72,38 +133,175 @@ impl MidiMessage {
         Self::AllNotesOff { channel }
     }
 
+    /// Create a Control Change message.
+    #[must_use]
+    pub fn control_change(channel: MidiChannel, controller: u8, value: u8) -> Self {
+        Self::ControlChange {
+            channel,
+            controller,
+            value,
+        }
+    }
+
+    /// Create a Program Change message.
+    #[must_use]
+    pub fn program_change(channel: MidiChannel, program: u8) -> Self {
+        Self::ProgramChange { channel, program }
+    }
+
+    /// Create a Channel Pressure message.
+    #[must_use]
+    pub fn channel_pressure(channel: MidiChannel, pressure: u8) -> Self {
+        Self::ChannelPressure { channel, pressure }
+    }
+
+    /// Create a Pitch Bend message (`value` is clamped to -8192..=8191).
+    #[must_use]
+    pub fn pitch_bend(channel: MidiChannel, value: i16) -> Self {
+        Self::PitchBend {
+            channel,
+            value: value.clamp(PITCH_BEND_MIN, PITCH_BEND_MAX),
+        }
+    }
+
+    /// Create a Bank Select sequence.
+    #[must_use]
+    pub fn bank_select(channel: MidiChannel, msb: u8, lsb: Option<u8>) -> Self {
+        Self::BankSelect { channel, msb, lsb }
+    }
+
+    /// Create the message for an MML MIDI command (`@P`, `@CC`, `@A`, `@PB`, `@B`).
+    #[must_use]
+    pub fn from_control(channel: MidiChannel, control: MidiControl) -> Self {
+        match control {
+            MidiControl::ProgramChange(program) => Self::program_change(channel, program),
+            MidiControl::ControlChange { controller, value } => {
+                Self::control_change(channel, controller, value)
+            }
+            MidiControl::ChannelPressure(pressure) => Self::channel_pressure(channel, pressure),
+            MidiControl::PitchBend(value) => Self::pitch_bend(channel, value),
+            MidiControl::BankSelect { msb, lsb } => Self::bank_select(channel, msb, lsb),
+        }
+    }
+
+    /// Channel (1-16) of the message.
+    #[must_use]
+    pub fn channel(&self) -> MidiChannel {
+        match *self {
+            Self::NoteOn { channel, .. }
+            | Self::NoteOff { channel, .. }
+            | Self::PolyPressure { channel, .. }
+            | Self::ControlChange { channel, .. }
+            | Self::ProgramChange { channel, .. }
+            | Self::ChannelPressure { channel, .. }
+            | Self::PitchBend { channel, .. }
+            | Self::BankSelect { channel, .. }
+            | Self::AllNotesOff { channel } => channel,
+        }
+    }
+
+    /// Convert the message to raw MIDI packets, one per complete MIDI message.
+    ///
+    /// Every message is a single packet except Bank Select, which is two Control Changes
+    /// (or one if no LSB is given).
+    #[must_use]
+    pub fn to_packets(&self) -> Vec<Vec<u8>> {
+        match *self {
+            Self::BankSelect { channel, msb, lsb } => {
+                let mut packets = vec![control_change_bytes(channel, CC_BANK_SELECT_MSB, msb)];
+                if let Some(lsb) = lsb {
+                    packets.push(control_change_bytes(channel, CC_BANK_SELECT_LSB, lsb));
+                }
+                packets.into_iter().map(|packet| packet.to_vec()).collect()
+            }
+            _ => vec![self.to_bytes()],
+        }
+    }
+
     /// Convert the message to raw MIDI bytes.
+    ///
+    /// Program Change and Channel Pressure are 2 bytes, Bank Select is a sequence of
+    /// Control Changes (3 or 6 bytes), and the others are 3 bytes.
     #[must_use]
-    pub fn to_bytes(&self) -> [u8; 3] {
+    pub fn to_bytes(&self) -> Vec<u8> {
         match *self {
             Self::NoteOn {
                 channel,
                 note,
                 velocity,
-            } => {
-                // Note On status byte: 0x90 | (channel - 1)
-                let status = 0x90 | ((channel.saturating_sub(1)) & 0x0F);
-                [status, note & 0x7F, velocity & 0x7F]
-            }
+            } => vec![status(0x90, channel), note & 0x7F, velocity & 0x7F],
             Self::NoteOff {
                 channel,
                 note,
                 velocity,
-            } => {
-                // Note Off status byte: 0x80 | (channel - 1)
-                let status = 0x80 | ((channel.saturating_sub(1)) & 0x0F);
-                [status, note & 0x7F, velocity & 0x7F]
+            } => vec![status(0x80, channel), note & 0x7F, velocity & 0x7F],
+            Self::PolyPressure {
+                channel,
+                note,
+                pressure,
+            } => vec![status(0xA0, channel), note & 0x7F, pressure & 0x7F],
+            Self::ControlChange {
+                channel,
+                controller,
+                value,
+            } => control_change_bytes(channel, controller, value).to_vec(),
+            Self::ProgramChange { channel, program } => {
+                vec![status(0xC0, channel), program & 0x7F]
+            }
+            Self::ChannelPressure { channel, pressure } => {
+                vec![status(0xD0, channel), pressure & 0x7F]
             }
+            Self::PitchBend { channel, value } => {
+                // 14-bit value with 0x2000 as center, sent LSB first
+                #[allow(clippy::cast_sign_loss)]
+                let bend = (value.clamp(PITCH_BEND_MIN, PITCH_BEND_MAX) + 0x2000) as u16;
+                #[allow(clippy::cast_possible_truncation)]
+                let (lsb, msb) = ((bend & 0x7F) as u8, (bend >> 7) as u8);
+                vec![status(0xE0, channel), lsb, msb]
+            }
+            Self::BankSelect { .. } => self.to_packets().concat(),
             Self::AllNotesOff { channel } => {
-                // Control Change status byte: 0xB0 | (channel - 1)
-                // Controller #123: All Notes Off
-                let status = 0xB0 | ((channel.saturating_sub(1)) & 0x0F);
-                [status, 123, 0]
+                control_change_bytes(channel, CC_ALL_NOTES_OFF, 0).to_vec()
             }
         }
     }
 }
 
+/// System Real-Time messages (1 byte, no channel)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiRealtime {
+    /// Timing Clock (24 per quarter note)
+    TimingClock,
+    /// Start from the top of the song
+    Start,
+    /// Continue from the Song Position Pointer
+    Continue,
+    /// Stop
+    Stop,
+}
+
+impl MidiRealtime {
+    /// Convert the message to its status byte.
+    #[must_use]
+    pub fn to_byte(self) -> u8 {
+        match self {
+            Self::TimingClock => 0xF8,
+            Self::Start => 0xFA,
+            Self::Continue => 0xFB,
+            Self::Stop => 0xFC,
+        }
+    }
+}
+
+/// Status byte: message type in the upper nibble, `channel - 1` in the lower nibble.
+fn status(kind: u8, channel: MidiChannel) -> u8 {
+    kind | (channel.saturating_sub(1) & 0x0F)
+}
+
+fn control_change_bytes(channel: MidiChannel, controller: u8, value: u8) -> [u8; 3] {
+    [status(0xB0, channel), controller & 0x7F, value & 0x7F]
+}
+
 // ============================================================
 // Message Build Functions
 // ============================================================
@@ -119,7 +317,7 @@ impl MidiMessage {
 /// A 3-byte array representing the MIDI message.
 #[must_use]
 pub fn build_note_on_message(channel: u8, note: u8, velocity: u8) -> [u8; 3] {
-    MidiMessage::note_on(channel, note, velocity).to_bytes()
+    [status(0x90, channel), note & 0x7F, velocity & 0x7F]
 }
 
 /// Build a Note Off MIDI message as raw bytes.
@@ -132,7 +330,7 @@ pub fn build_note_on_message(channel: u8, note: u8, velocity: u8) -> [u8; 3] {
 /// A 3-byte array representing the MIDI message.
 #[must_use]
 pub fn build_note_off_message(channel: u8, note: u8) -> [u8; 3] {
-    MidiMessage::note_off(channel, note, 0).to_bytes()
+    [status(0x80, channel), note & 0x7F, 0]
 }
 
 /// Build an All Notes Off MIDI message as raw bytes.
@@ -144,7 +342,21 @@ pub fn build_note_off_message(channel: u8, note: u8) -> [u8; 3] {
 /// A 3-byte array representing the MIDI Control Change #123 message.
 #[must_use]
 pub fn build_all_notes_off_message(channel: u8) -> [u8; 3] {
-    MidiMessage::all_notes_off(channel).to_bytes()
+    control_change_bytes(channel, CC_ALL_NOTES_OFF, 0)
+}
+
+/// Build a Song Position Pointer MIDI message as raw bytes.
+///
+/// # Arguments
+/// * `position` - Position in sixteenth notes from the top of the song (0-16383)
+///
+/// # Returns
+/// A 3-byte array (0xF2, LSB, MSB).
+#[must_use]
+pub fn build_song_position_message(position: u16) -> [u8; 3] {
+    #[allow(clippy::cast_possible_truncation)]
+    let (lsb, msb) = ((position & 0x7F) as u8, ((position >> 7) & 0x7F) as u8);
+    [0xF2, lsb, msb]
 }
 
 // ============================================================
@@ -199,20 +411,35 @@ pub fn mml_to_midi_note(pitch: Pitch, accidental: Accidental, octave: u8) -> u8
 /// - V10 → 84
 /// - V15 → 127
 #[must_use]
-#[allow(clippy::cast_possible_truncation)]
 pub fn mml_volume_to_velocity(volume: u8) -> u8 {
     // Linear interpolation: velocity = (volume * 127) / 15
-    (u16::from(volume) * 127 / 15) as u8
+    crate::mml::volume_to_velocity(volume)
 }
 
 // ============================================================
 // MIDI Send Functions
 // ============================================================
 
+/// Send a MIDI message to the MIDI output.
+///
+/// Multi-packet messages (Bank Select) are sent one complete MIDI message at a time.
+///
+/// # Errors
+/// Returns `MidiError::InvalidChannel` if channel is not in 1-16 range.
+/// Returns `MidiError::SendFailed` if sending fails.
+pub fn send_message(conn: &mut dyn MidiSink, message: &MidiMessage) -> Result<(), MidiError> {
+    MidiError::validate_channel(message.channel())?;
+
+    for packet in message.to_packets() {
+        conn.send(&packet)?;
+    }
+    Ok(())
+}
+
 /// Send a Note On message to the MIDI output.
 ///
 /// # Arguments
-/// * `conn` - MIDI output connection
+/// * `conn` - MIDI output (device connection or recording sink)
 /// * `channel` - MIDI channel (1-16)
 /// * `note` - MIDI note number (0-127)
 /// * `velocity` - MIDI velocity (0-127)
@@ -221,38 +448,26 @@ pub fn mml_volume_to_velocity(volume: u8) -> u8 {
 /// Returns `MidiError::InvalidChannel` if channel is not in 1-16 range.
 /// Returns `MidiError::SendFailed` if sending fails.
 pub fn send_note_on(
-    conn: &mut MidiOutputConnection,
+    conn: &mut dyn MidiSink,
     channel: u8,
     note: u8,
     velocity: u8,
 ) -> Result<(), MidiError> {
-    MidiError::validate_channel(channel)?;
-
-    let msg = build_note_on_message(channel, note, velocity);
-    conn.send(&msg)
-        .map_err(|e| MidiError::send_failed(e.to_string()))
+    send_message(conn, &MidiMessage::note_on(channel, note, velocity))
 }
 
 /// Send a Note Off message to the MIDI output.
 ///
 /// # Arguments
-/// * `conn` - MIDI output connection
+/// * `conn` - MIDI output (device connection or recording sink)
 /// * `channel` - MIDI channel (1-16)
 /// * `note` - MIDI note number (0-127)
 ///
 /// # Errors
 /// Returns `MidiError::InvalidChannel` if channel is not in 1-16 range.
 /// Returns `MidiError::SendFailed` if sending fails.
-pub fn send_note_off(
-    conn: &mut MidiOutputConnection,
-    channel: u8,
-    note: u8,
-) -> Result<(), MidiError> {
-    MidiError::validate_channel(channel)?;
-
-    let msg = build_note_off_message(channel, note);
-    conn.send(&msg)
-        .map_err(|e| MidiError::send_failed(e.to_string()))
+pub fn send_note_off(conn: &mut dyn MidiSink, channel: u8, note: u8) -> Result<(), MidiError> {
+    send_message(conn, &MidiMessage::note_off(channel, note, 0))
 }
 
 /// Send an All Notes Off message to the MIDI output.
@@ -260,16 +475,32 @@ pub fn send_note_off(
 /// This is used for cleanup when stopping playback or on interruption.
 ///
 /// # Arguments
-/// * `conn` - MIDI output connection
+/// * `conn` - MIDI output (device connection or recording sink)
 /// * `channel` - MIDI channel (1-16)
 ///
 /// # Errors
 /// Returns `MidiError::InvalidChannel` if channel is not in 1-16 range.
 /// Returns `MidiError::SendFailed` if sending fails.
-pub fn send_all_notes_off(conn: &mut MidiOutputConnection, channel: u8) -> Result<(), MidiError> {
-    MidiError::validate_channel(channel)?;
+pub fn send_all_notes_off(conn: &mut dyn MidiSink, channel: u8) -> Result<(), MidiError> {
+    send_message(conn, &MidiMessage::all_notes_off(channel))
+}
 
-    let msg = build_all_notes_off_message(channel);
-    conn.send(&msg)
-        .map_err(|e| MidiError::send_failed(e.to_string()))
+/// Send a System Real-Time message to the MIDI output.
+///
+/// # Errors
+/// Returns `MidiError::SendFailed` if sending fails.
+pub fn send_realtime(conn: &mut dyn MidiSink, message: MidiRealtime) -> Result<(), MidiError> {
+    conn.send(&[message.to_byte()])
+}
+
+/// Send a Song Position Pointer message to the MIDI output.
+///
+/// # Arguments
+/// * `conn` - MIDI output (device connection or recording sink)
+/// * `position` - Position in sixteenth notes from the top of the song (0-16383)
+///
+/// # Errors
+/// Returns `MidiError::SendFailed` if sending fails.
+pub fn send_song_position(conn: &mut dyn MidiSink, position: u16) -> Result<(), MidiError> {
+    conn.send(&build_song_position_message(position))
 }
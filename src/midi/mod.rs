@@ -1,14 +1,23 @@
 //! MIDI module for sine-mml
 //!
-//! This module provides MIDI output functionality for the MML synthesizer.
+//! This module provides MIDI output functionality for the MML synthesizer,
+//! and following an external MIDI clock or recording notes from a MIDI input.
 //! It is gated behind the `midi-output` feature flag.
 
+pub mod clock;
 pub mod device;
 pub mod error;
 pub mod message;
 pub mod player;
+pub mod record;
+pub mod sink;
+pub mod sync;
 
+pub use clock::*;
 pub use device::*;
 pub use error::*;
 pub use message::*;
 pub use player::*;
+pub use record::*;
+pub use sink::*;
+pub use sync::*;
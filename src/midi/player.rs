@@ -4,30 +4,48 @@
 //! It uses absolute time calculations to prevent cumulative timing drift during
 //! long playback sessions.
 
+use std::collections::BTreeSet;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use midir::MidiOutputConnection;
-
+use super::clock::{ClockEvent, ClockScheduler};
 use super::error::MidiError;
 use super::message::{
-    mml_to_midi_note, mml_volume_to_velocity, send_all_notes_off, send_note_off, send_note_on,
+    mml_to_midi_note, send_all_notes_off, send_message, send_note_off, send_note_on, send_realtime,
+    send_song_position, MidiMessage, MidiRealtime,
+};
+use super::sink::MidiSink;
+use super::sync::ExternalClock;
+use crate::mml::{
+    loop_pass, Command, Dynamics, GateValue, MidiControl, PlayRange, TempoState, VolumeValue,
 };
-use crate::mml::{Command, VolumeValue};
 
 /// Default values for MIDI playback state
 const DEFAULT_OCTAVE: u8 = 4;
 const DEFAULT_BPM: u16 = 120;
 const DEFAULT_LENGTH: u8 = 4;
 const DEFAULT_VOLUME: u8 = 10;
+/// 外部クロックに同期する場合の内部テンポ（1拍 = 1秒として拍位置で待機する）
+pub const EXTERNAL_SYNC_BPM: f64 = 60.0;
 
 /// MIDI playback state
 pub struct PlaybackState {
     pub octave: u8,
+    /// 指定テンポ（テンポ変化中は終了テンポ）
     pub bpm: u16,
+    /// テンポ変化を含むテンポ状態（時間計算に使用）
+    pub tempo: TempoState,
     pub default_length: u8,
     pub volume: u8,
+    /// ゲートタイム（発音する割合、0.0〜1.0）
+    pub gate: f32,
+    /// `@v`によるベロシティ指定・変化
+    pub dynamics: Dynamics,
+    /// 再生位置（拍、ベロシティ変化の補間に使用）
+    pub beat_position: f64,
+    /// 音符を送るMIDIチャンネル（1-16、`@C`で切り替え）
+    pub channel: u8,
 }
 
 impl Default for PlaybackState {
@@ -35,14 +53,20 @@ impl Default for PlaybackState {
         Self {
             octave: DEFAULT_OCTAVE,
             bpm: DEFAULT_BPM,
+            tempo: TempoState::new(DEFAULT_BPM),
             default_length: DEFAULT_LENGTH,
             volume: DEFAULT_VOLUME,
+            gate: GateValue::default().ratio(),
+            dynamics: Dynamics::default(),
+            beat_position: 0.0,
+            channel: 1,
         }
     }
 }
 
 impl PlaybackState {
     /// Update state with a command
+    #[allow(clippy::collapsible_match)]
     pub fn update_state(&mut self, command: &Command) {
         match command {
             Command::Octave(o) => {
@@ -60,11 +84,20 @@ impl PlaybackState {
             }
             Command::Tempo(t) => {
                 self.bpm = t.value;
+                self.tempo.set(t.value);
+            }
+            Command::TempoRamp(r) => {
+                self.bpm = r.to;
+                self.tempo.apply_ramp(r, self.beat_position);
+            }
+            Command::Swing(swing) => {
+                self.tempo.set_swing(*swing);
             }
             Command::DefaultLength(l) => {
                 self.default_length = l.value;
             }
             Command::Volume(v) => {
+                self.dynamics.clear();
                 self.volume = match v.value {
                     VolumeValue::Absolute(val) => val.min(15),
                     #[allow(clippy::cast_sign_loss)]
@@ -74,9 +107,32 @@ impl PlaybackState {
                     }
                 };
             }
+            Command::Gate(g) => {
+                self.gate = g.value.ratio();
+            }
+            Command::Velocity(v) => {
+                self.dynamics
+                    .apply(v.value, self.volume, self.beat_position);
+            }
+            Command::Channel(c) => {
+                self.channel = c.value;
+            }
             _ => {}
         }
     }
+
+    /// 現在位置でのMIDIベロシティ（0-127）を取得
+    #[must_use]
+    pub fn velocity(&self) -> u8 {
+        self.dynamics.velocity_at(self.volume, self.beat_position)
+    }
+
+    /// 現在位置から`beats`拍進め、その区間の秒数を返す
+    pub fn advance(&mut self, beats: f64) -> f64 {
+        let seconds = self.tempo.seconds(self.beat_position, beats);
+        self.beat_position += beats;
+        seconds
+    }
 }
 
 /// Play MIDI stream from MML commands.
@@ -86,7 +142,7 @@ impl PlaybackState {
 /// cumulative timing drift during long playback sessions.
 ///
 /// # Arguments
-/// * `conn` - MIDI output connection
+/// * `conn` - MIDI output (device connection or [`RecordingSink`](super::sink::RecordingSink))
 /// * `commands` - Slice of MML commands to play
 /// * `channel` - MIDI channel (1-16)
 ///
@@ -100,30 +156,18 @@ impl PlaybackState {
 /// play_midi_stream(&mut conn, &commands.commands, 1)?;
 /// ```
 pub fn play_midi_stream(
-    conn: &mut MidiOutputConnection,
+    conn: &mut impl MidiSink,
     commands: &[Command],
     channel: u8,
 ) -> Result<(), MidiError> {
-    MidiError::validate_channel(channel)?;
-
-    let mut state = PlaybackState::default();
-    let start_time = Instant::now();
-    let mut elapsed_duration = Duration::ZERO;
-
-    play_commands_recursive(
+    play_with_clock(
         conn,
         commands,
         channel,
-        &mut state,
-        start_time,
-        &mut elapsed_duration,
+        Clock::new(Duration::ZERO, None),
         None,
-    )?;
-
-    // Send All Notes Off for cleanup
-    send_all_notes_off(conn, channel)?;
-
-    Ok(())
+        PlayOptions::default(),
+    )
 }
 
 /// Play MIDI stream with interrupt support.
@@ -134,7 +178,7 @@ pub fn play_midi_stream(
 /// When interrupted, the function sends All Notes Off to prevent stuck notes.
 ///
 /// # Arguments
-/// * `conn` - MIDI output connection
+/// * `conn` - MIDI output (device connection or [`RecordingSink`](super::sink::RecordingSink))
 /// * `commands` - Slice of MML commands to play
 /// * `channel` - MIDI channel (1-16)
 /// * `interrupt` - Atomic flag for interrupt signaling
@@ -160,34 +204,307 @@ pub fn play_midi_stream(
 /// play_midi_stream_interruptible(&mut conn, &commands.commands, 1, interrupt)?;
 /// ```
 pub fn play_midi_stream_interruptible(
-    conn: &mut MidiOutputConnection,
+    conn: &mut impl MidiSink,
     commands: &[Command],
     channel: u8,
     interrupt: &Arc<AtomicBool>,
 ) -> Result<(), MidiError> {
-    MidiError::validate_channel(channel)?;
-
-    let mut state = PlaybackState::default();
-    let start_time = Instant::now();
-    let mut elapsed_duration = Duration::ZERO;
-
-    play_commands_recursive(
+    play_with_clock(
         conn,
         commands,
         channel,
-        &mut state,
-        start_time,
-        &mut elapsed_duration,
+        Clock::new(Duration::ZERO, None),
         Some(interrupt),
-    )?;
+        PlayOptions::default(),
+    )
+}
+
+/// MIDI再生のオプション
+#[derive(Debug, Clone, Default)]
+pub struct PlayOptions {
+    /// MIDIクロック（24 PPQN）とStart/Continue/Stopを送る
+    pub send_clock: bool,
+    /// 外部MIDIクロックに同期する（MML中のテンポ指定は無視し、外部クロックの拍位置で発音する）
+    pub follow: Option<ExternalClock>,
+    /// 送信時刻をずらすミリ秒数（正の値で遅らせ、負の値で早める。`follow`の場合は無視する）
+    ///
+    /// 早める場合、開始直後のずらした分のメッセージは開始時にまとめて送る。
+    pub offset_ms: i32,
+}
+
+/// 再生範囲を指定してMIDIを再生する（割り込み対応）
+///
+/// 開始位置より前のコマンドは待機せずに走査し、オクターブ・テンポ・音量などの
+/// 状態だけを反映する。開始位置をまたぐ音は開始位置から残りの長さだけ発音し、
+/// 終了位置で発音中の音は終了位置で止める。
+///
+/// `options.follow`の場合は外部クロックの拍位置に合わせて発音する（再生範囲は拍で指定する）。
+/// `options.send_clock`の場合はMIDIクロックを送る。曲の先頭からの再生はStart、
+/// 途中からの再生はSong Position PointerとContinueで始め、終了・中断時にStopを送る
+/// （[`ClockScheduler`]）。
+///
+/// # Errors
+/// Returns `MidiError::InvalidChannel` if channel is not in 1-16 range.
+/// Returns `MidiError::SendFailed` if sending MIDI messages fails.
+pub fn play_midi_stream_range(
+    conn: &mut impl MidiSink,
+    commands: &[Command],
+    channel: u8,
+    range: &PlayRange,
+    interrupt: &Arc<AtomicBool>,
+    options: PlayOptions,
+) -> Result<(), MidiError> {
+    let clock = Clock::new(
+        Duration::from_secs_f64(range.start.seconds),
+        Some(Duration::from_secs_f64(range.end.seconds)),
+    );
+    play_with_clock(conn, commands, channel, clock, Some(interrupt), options)
+}
+
+fn play_with_clock(
+    conn: &mut dyn MidiSink,
+    commands: &[Command],
+    channel: u8,
+    mut clock: Clock,
+    interrupt: Option<&Arc<AtomicBool>>,
+    options: PlayOptions,
+) -> Result<(), MidiError> {
+    MidiError::validate_channel(channel)?;
+
+    let mut state = PlaybackState {
+        channel,
+        ..PlaybackState::default()
+    };
+    if options.follow.is_some() {
+        state.tempo.set_fixed(EXTERNAL_SYNC_BPM);
+    } else {
+        clock.shift(options.offset_ms);
+    }
+    clock.external = options.follow;
+    clock.halt = interrupt.cloned();
+    let mut output = Output::new(conn, channel);
+    if options.send_clock {
+        output.clock = Some(ClockScheduler::new());
+    }
+    let result = play_commands_recursive(&mut output, commands, &mut state, &mut clock, interrupt);
+
+    // Release sounding notes and send All Notes Off on every used channel for cleanup
+    let released = output.release_all();
+    result?;
+    released
+}
+
+/// 発音中の音と使用したチャンネルの記録
+///
+/// Note Offはチャンネルごとに発音中の音と対応させる。終了時（中断を含む）には
+/// 発音中の音を止め、使用した全チャンネルにAll Notes Offを送る。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChannelTracker {
+    /// 発音中の（チャンネル, ノート番号）
+    sounding: Vec<(u8, u8)>,
+    used: BTreeSet<u8>,
+}
+
+impl ChannelTracker {
+    /// `channel`を使用済みにする（音を送らなくてもAll Notes Offの対象にする）
+    pub fn use_channel(&mut self, channel: u8) {
+        self.used.insert(channel);
+    }
+
+    /// Note Onを記録する
+    pub fn note_on(&mut self, channel: u8, note: u8) {
+        self.used.insert(channel);
+        self.sounding.push((channel, note));
+    }
+
+    /// Note Offを記録し、そのチャンネルで発音中だったかどうかを返す
+    pub fn note_off(&mut self, channel: u8, note: u8) -> bool {
+        match self.sounding.iter().position(|&n| n == (channel, note)) {
+            Some(index) => {
+                self.sounding.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 発音中の（チャンネル, ノート番号）
+    #[must_use]
+    pub fn sounding(&self) -> &[(u8, u8)] {
+        &self.sounding
+    }
+
+    /// 使用したチャンネル（昇順）
+    pub fn used_channels(&self) -> impl Iterator<Item = u8> + '_ {
+        self.used.iter().copied()
+    }
+}
+
+/// MIDI出力と、送った音の記録
+struct Output<'a> {
+    conn: &'a mut dyn MidiSink,
+    notes: ChannelTracker,
+    /// MIDIクロックの送信予定（送らない場合は`None`）
+    clock: Option<ClockScheduler>,
+}
+
+impl<'a> Output<'a> {
+    fn new(conn: &'a mut dyn MidiSink, channel: u8) -> Self {
+        let mut notes = ChannelTracker::default();
+        notes.use_channel(channel);
+        Self {
+            conn,
+            notes,
+            clock: None,
+        }
+    }
+
+    /// 曲中の位置`at`まで待機する（途中のMIDIクロックを送る）
+    fn wait_until(&mut self, clock: &Clock, at: Duration) -> Result<(), MidiError> {
+        if let Some(scheduler) = &mut self.clock {
+            let from = clock.from.as_secs_f64();
+            let end = clock.to.map(|to| to.as_secs_f64());
+            while let Some((time, event)) = scheduler.poll(from, at.as_secs_f64(), end) {
+                clock.wait_until(Duration::from_secs_f64(time));
+                match event {
+                    ClockEvent::Start => send_realtime(self.conn, MidiRealtime::Start)?,
+                    ClockEvent::Continue { position } => {
+                        send_song_position(self.conn, position)?;
+                        send_realtime(self.conn, MidiRealtime::Continue)?;
+                    }
+                    ClockEvent::Tick => send_realtime(self.conn, MidiRealtime::TimingClock)?,
+                }
+            }
+        }
+        clock.wait_until(at);
+        Ok(())
+    }
+
+    /// MIDIクロックを現在位置のテンポに合わせる
+    fn follow_tempo(&mut self, state: &PlaybackState, clock: &Clock) {
+        if let Some(scheduler) = &mut self.clock {
+            scheduler.set_tempo(
+                &state.tempo,
+                state.beat_position,
+                clock.elapsed.as_secs_f64(),
+            );
+        }
+    }
+
+    fn note_on(&mut self, channel: u8, note: u8, velocity: u8) -> Result<(), MidiError> {
+        send_note_on(self.conn, channel, note, velocity)?;
+        self.notes.note_on(channel, note);
+        Ok(())
+    }
+
+    fn note_off(&mut self, channel: u8, note: u8) -> Result<(), MidiError> {
+        self.notes.note_off(channel, note);
+        send_note_off(self.conn, channel, note)
+    }
 
-    send_all_notes_off(conn, channel)?;
+    fn send(&mut self, message: MidiMessage) -> Result<(), MidiError> {
+        send_message(self.conn, &message)?;
+        self.notes.use_channel(message.channel());
+        Ok(())
+    }
 
-    Ok(())
+    /// MIDIクロックを止め、発音中の音を止め、使用した全チャンネルにAll Notes Offを送る
+    fn release_all(&mut self) -> Result<(), MidiError> {
+        if self.clock.take().is_some_and(|clock| clock.is_started()) {
+            send_realtime(self.conn, MidiRealtime::Stop)?;
+        }
+        for (channel, note) in std::mem::take(&mut self.notes.sounding) {
+            send_note_off(self.conn, channel, note)?;
+        }
+        for channel in self.notes.used_channels().collect::<Vec<_>>() {
+            send_all_notes_off(self.conn, channel)?;
+        }
+        Ok(())
+    }
 }
 
-fn is_interrupted(interrupt: Option<&Arc<AtomicBool>>) -> bool {
-    interrupt.is_some_and(|flag| flag.load(Ordering::Relaxed))
+/// 曲中の位置と実時間の対応
+///
+/// `elapsed`は曲の先頭からの位置。再生範囲の開始位置より前は待機せずに進み、
+/// 終了位置に達すると再生を終える。
+struct Clock {
+    start_time: Instant,
+    /// 曲の先頭からの経過時間
+    elapsed: Duration,
+    /// 再生範囲の開始位置
+    from: Duration,
+    /// 再生範囲の終了位置（`None`の場合は曲の終わりまで）
+    to: Option<Duration>,
+    /// 同期する外部クロック（位置は1拍 = 1秒）
+    external: Option<ExternalClock>,
+    /// 外部クロックを待機中に再生を止めるためのフラグ
+    halt: Option<Arc<AtomicBool>>,
+}
+
+impl Clock {
+    fn new(from: Duration, to: Option<Duration>) -> Self {
+        Self {
+            start_time: Instant::now(),
+            elapsed: Duration::ZERO,
+            from,
+            to,
+            external: None,
+            halt: None,
+        }
+    }
+
+    /// 送信時刻を`offset_ms`ミリ秒遅らせる（負の値で早める）
+    fn shift(&mut self, offset_ms: i32) {
+        let offset = Duration::from_millis(u64::from(offset_ms.unsigned_abs()));
+        if offset_ms >= 0 {
+            self.start_time += offset;
+        } else if let Some(start_time) = self.start_time.checked_sub(offset) {
+            self.start_time = start_time;
+        }
+    }
+
+    /// 曲中の位置`at`に対応する時刻まで待機する
+    fn wait_until(&self, at: Duration) {
+        let at = self.to.map_or(at, |to| at.min(to));
+        let Some(offset) = at.checked_sub(self.from) else {
+            return;
+        };
+        match &self.external {
+            Some(external) => wait_for_beat(external, at.as_secs_f64(), self.halt.as_ref()),
+            None => wait_until_target(self.start_time, offset),
+        }
+    }
+
+    /// 区間`[start, end)`のうち再生範囲内の部分
+    fn clip(&self, start: Duration, end: Duration) -> Option<(Duration, Duration)> {
+        let start = start.max(self.from);
+        let end = self.to.map_or(end, |to| end.min(to));
+        (start < end).then_some((start, end))
+    }
+
+    /// 終了位置に達したかどうか
+    fn is_finished(&self) -> bool {
+        self.to.is_some_and(|to| self.elapsed >= to)
+    }
+}
+
+fn is_interrupted(interrupt: Option<&Arc<AtomicBool>>, clock: &Clock) -> bool {
+    interrupt.is_some_and(|flag| flag.load(Ordering::Relaxed)) || clock.is_finished()
+}
+
+/// 外部クロックの位置が`beat`に達するまで待機する（停止フラグが立った場合は待機をやめる）
+fn wait_for_beat(external: &ExternalClock, beat: f64, halt: Option<&Arc<AtomicBool>>) {
+    const POLL_LIMIT: f64 = 0.002;
+    while !halt.is_some_and(|flag| flag.load(Ordering::Relaxed)) {
+        let remaining = beat - external.beat();
+        if remaining <= 0.0 {
+            return;
+        }
+        let seconds = external
+            .bpm()
+            .map_or(POLL_LIMIT, |bpm| remaining * 60.0 / bpm);
+        std::thread::sleep(Duration::from_secs_f64(seconds.min(POLL_LIMIT)));
+    }
 }
 
 fn wait_until_target(start_time: Instant, elapsed: Duration) {
@@ -198,126 +515,175 @@ fn wait_until_target(start_time: Instant, elapsed: Duration) {
     }
 }
 
-fn play_note(
-    conn: &mut MidiOutputConnection,
-    note: &crate::mml::Note,
+/// 1音を送信する（ゲートタイム対応）
+///
+/// Note Offはゲートタイム経過時点で送信し、経過時間は音長いっぱいまで進める。
+/// これによりゲートタイムを変えても全体のタイミングは変わらない。
+/// 再生範囲外の部分は発音しない。
+fn send_gated_note(
+    output: &mut Output,
     channel: u8,
-    state: &PlaybackState,
-    start_time: Instant,
-    elapsed_duration: &mut Duration,
+    midi_note: u8,
+    velocity: u8,
+    duration_secs: f64,
+    gate: f32,
+    clock: &mut Clock,
 ) -> Result<(), MidiError> {
-    let midi_note = mml_to_midi_note(note.pitch, note.accidental, state.octave);
-    let velocity = mml_volume_to_velocity(state.volume);
+    let gate_secs = duration_secs * f64::from(gate.clamp(0.0, 1.0));
+    let sounding = clock.clip(
+        clock.elapsed,
+        clock.elapsed + Duration::from_secs_f64(gate_secs),
+    );
+    if let Some((note_on, note_off)) = sounding {
+        output.wait_until(clock, note_on)?;
+        output.note_on(channel, midi_note, velocity)?;
+        output.wait_until(clock, note_off)?;
+        output.note_off(channel, midi_note)?;
+    }
 
-    send_note_on(conn, channel, midi_note, velocity)?;
+    clock.elapsed += Duration::from_secs_f64(duration_secs);
+    output.wait_until(clock, clock.elapsed)
+}
 
-    let note_duration_secs = note.duration_in_seconds(state.bpm, state.default_length);
-    *elapsed_duration += Duration::from_secs_f32(note_duration_secs);
-    wait_until_target(start_time, *elapsed_duration);
+fn play_note(
+    output: &mut Output,
+    note: &crate::mml::Note,
+    state: &mut PlaybackState,
+    clock: &mut Clock,
+) -> Result<(), MidiError> {
+    let midi_note = mml_to_midi_note(note.pitch, note.accidental, state.octave);
+    let velocity = state.velocity();
+    let note_duration_secs = state.advance(note.total_beats(state.default_length));
+
+    send_gated_note(
+        output,
+        state.channel,
+        midi_note,
+        velocity,
+        note_duration_secs,
+        state.gate,
+        clock,
+    )
+}
 
-    send_note_off(conn, channel, midi_note)
+/// MIDIコントロールコマンドを現在位置で現在のチャンネルに送る
+///
+/// 再生範囲の開始位置より前のものも（待機せずに）送り、音色などを開始位置に反映する。
+fn send_control(
+    output: &mut Output,
+    control: MidiControl,
+    state: &PlaybackState,
+    clock: &Clock,
+) -> Result<(), MidiError> {
+    output.wait_until(clock, clock.elapsed)?;
+    output.send(MidiMessage::from_control(state.channel, control))
 }
 
 fn play_rest(
+    output: &mut Output,
     rest: &crate::mml::Rest,
-    state: &PlaybackState,
-    start_time: Instant,
-    elapsed_duration: &mut Duration,
-) {
-    let rest_duration_secs = rest.duration_in_seconds(state.bpm, state.default_length);
-    *elapsed_duration += Duration::from_secs_f32(rest_duration_secs);
-    wait_until_target(start_time, *elapsed_duration);
+    state: &mut PlaybackState,
+    clock: &mut Clock,
+) -> Result<(), MidiError> {
+    let rest_duration_secs = state.advance(rest.total_beats(state.default_length));
+    clock.elapsed += Duration::from_secs_f64(rest_duration_secs);
+    output.wait_until(clock, clock.elapsed)
+}
+
+/// 発音しないコマンドを再生状態に反映する
+fn apply_state(output: &mut Output, command: &Command, state: &mut PlaybackState, clock: &Clock) {
+    state.update_state(command);
+    if clock.external.is_some() {
+        state.tempo.set_fixed(EXTERNAL_SYNC_BPM);
+    }
+    output.follow_tempo(state, clock);
 }
 
-#[allow(clippy::too_many_arguments)]
 fn play_tuplet(
-    conn: &mut MidiOutputConnection,
+    output: &mut Output,
     tuplet_commands: &[Command],
     count: u8,
     base_duration: Option<u8>,
-    channel: u8,
     state: &mut PlaybackState,
-    start_time: Instant,
-    elapsed_duration: &mut Duration,
+    clock: &mut Clock,
     interrupt: Option<&Arc<AtomicBool>>,
 ) -> Result<bool, MidiError> {
     let base_len = base_duration.unwrap_or(state.default_length);
-    let beats_per_tuplet = 4.0 / f32::from(base_len);
-    let seconds_per_tuplet = beats_per_tuplet * (60.0 / f32::from(state.bpm));
-    let duration_per_note = seconds_per_tuplet / f32::from(count);
+    let beats_per_note = 4.0 / f64::from(base_len) / f64::from(count);
 
     for tuplet_cmd in tuplet_commands {
-        if is_interrupted(interrupt) {
+        if is_interrupted(interrupt, clock) {
             return Ok(false);
         }
 
         match tuplet_cmd {
             Command::Note(note) => {
                 let midi_note = mml_to_midi_note(note.pitch, note.accidental, state.octave);
-                let velocity = mml_volume_to_velocity(state.volume);
-
-                send_note_on(conn, channel, midi_note, velocity)?;
-                *elapsed_duration += Duration::from_secs_f32(duration_per_note);
-                wait_until_target(start_time, *elapsed_duration);
-                send_note_off(conn, channel, midi_note)?;
+                let velocity = state.velocity();
+                let duration_per_note = state.advance(beats_per_note);
+
+                send_gated_note(
+                    output,
+                    state.channel,
+                    midi_note,
+                    velocity,
+                    duration_per_note,
+                    state.gate,
+                    clock,
+                )?;
             }
             Command::Rest(_) => {
-                *elapsed_duration += Duration::from_secs_f32(duration_per_note);
-                wait_until_target(start_time, *elapsed_duration);
+                let duration_per_note = state.advance(beats_per_note);
+                clock.elapsed += Duration::from_secs_f64(duration_per_note);
+                output.wait_until(clock, clock.elapsed)?;
+            }
+            Command::MidiControl(control) => {
+                send_control(output, *control, state, clock)?;
             }
             _ => {
-                state.update_state(tuplet_cmd);
+                apply_state(output, tuplet_cmd, state, clock);
             }
         }
     }
     Ok(true)
 }
 
-#[allow(clippy::too_many_arguments)]
 fn play_commands_recursive(
-    conn: &mut MidiOutputConnection,
+    output: &mut Output,
     commands: &[Command],
-    channel: u8,
     state: &mut PlaybackState,
-    start_time: Instant,
-    elapsed_duration: &mut Duration,
+    clock: &mut Clock,
     interrupt: Option<&Arc<AtomicBool>>,
 ) -> Result<bool, MidiError> {
     for command in commands {
-        if is_interrupted(interrupt) {
+        if is_interrupted(interrupt, clock) {
             return Ok(false);
         }
 
         match command {
             Command::Note(note) => {
-                play_note(conn, note, channel, state, start_time, elapsed_duration)?;
+                play_note(output, note, state, clock)?;
             }
             Command::Rest(rest) => {
-                play_rest(rest, state, start_time, elapsed_duration);
+                play_rest(output, rest, state, clock)?;
+            }
+            Command::MidiControl(control) => {
+                send_control(output, *control, state, clock)?;
             }
             Command::Loop {
                 commands: loop_commands,
                 escape_index,
+                endings,
                 repeat_count,
             } => {
-                for i in 0..*repeat_count {
-                    let is_last = i == repeat_count - 1;
-                    let end_idx = if is_last {
-                        escape_index.map_or(loop_commands.len(), |idx| idx)
-                    } else {
-                        loop_commands.len()
-                    };
-
-                    if !play_commands_recursive(
-                        conn,
-                        &loop_commands[..end_idx],
-                        channel,
-                        state,
-                        start_time,
-                        elapsed_duration,
-                        interrupt,
-                    )? {
-                        return Ok(false);
+                for pass in 0..*repeat_count {
+                    let (body, ending) =
+                        loop_pass(loop_commands, *escape_index, endings, *repeat_count, pass);
+
+                    for part in [body, ending] {
+                        if !play_commands_recursive(output, part, state, clock, interrupt)? {
+                            return Ok(false);
+                        }
                     }
                 }
             }
@@ -327,21 +693,19 @@ fn play_commands_recursive(
                 base_duration,
             } => {
                 if !play_tuplet(
-                    conn,
+                    output,
                     tuplet_commands,
                     *count,
                     *base_duration,
-                    channel,
                     state,
-                    start_time,
-                    elapsed_duration,
+                    clock,
                     interrupt,
                 )? {
                     return Ok(false);
                 }
             }
             _ => {
-                state.update_state(command);
+                apply_state(output, command, state, clock);
             }
         }
     }
@@ -361,13 +725,13 @@ pub fn calculate_total_duration_ms(commands: &[Command]) -> u64 {
     let mut state = PlaybackState::default();
     let total_secs = calculate_duration_recursive(commands, &mut state);
 
-    // f32 -> u64変換: 音声時間は現実的に2^64ms（約584,942,417年）を超えない
+    // f64 -> u64変換: 音声時間は現実的に2^64ms（約584,942,417年）を超えない
     #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
     let total_ms = (total_secs * 1000.0) as u64;
     total_ms
 }
 
-fn calculate_duration_recursive(commands: &[Command], state: &mut PlaybackState) -> f32 {
+fn calculate_duration_recursive(commands: &[Command], state: &mut PlaybackState) -> f64 {
     let mut total = 0.0;
 
     for cmd in commands {
@@ -375,10 +739,10 @@ fn calculate_duration_recursive(commands: &[Command], state: &mut PlaybackState)
 
         match cmd {
             Command::Note(note) => {
-                total += note.duration_in_seconds(state.bpm, state.default_length);
+                total += state.advance(note.total_beats(state.default_length));
             }
             Command::Rest(rest) => {
-                total += rest.duration_in_seconds(state.bpm, state.default_length);
+                total += state.advance(rest.total_beats(state.default_length));
             }
             Command::Tuplet {
                 commands: _,
@@ -386,9 +750,8 @@ fn calculate_duration_recursive(commands: &[Command], state: &mut PlaybackState)
                 base_duration,
             } => {
                 let base_len = base_duration.unwrap_or(state.default_length);
-                let beats_per_tuplet = 4.0 / f32::from(base_len);
-                let seconds_per_tuplet = beats_per_tuplet * (60.0 / f32::from(state.bpm));
-                total += seconds_per_tuplet;
+                let beats_per_tuplet = 4.0 / f64::from(base_len);
+                total += state.advance(beats_per_tuplet);
             }
             // Note: Chordコマンドは現在のMMLパーサーではサポートされていないため処理を省略
             // MMLパーサーが[CEG]構文をChordとしてパースする実装が追加されたら以下を追加:
@@ -401,19 +764,17 @@ fn calculate_duration_recursive(commands: &[Command], state: &mut PlaybackState)
             Command::Loop {
                 commands: loop_commands,
                 escape_index,
+                endings,
                 repeat_count,
             } => {
                 // ループの全回数分の時間を計算
                 // Note: stateはミュータブル参照で渡されるため、ループ内でのTempo/Octave等の
                 // 状態変更は累積的に反映される（例: L[T60 C T120 C]2 で2回目はT120から開始）
-                for i in 0..*repeat_count {
-                    let is_last = i == repeat_count - 1;
-                    let end_idx = if is_last {
-                        escape_index.map_or(loop_commands.len(), |idx| idx)
-                    } else {
-                        loop_commands.len()
-                    };
-                    total += calculate_duration_recursive(&loop_commands[..end_idx], state);
+                for pass in 0..*repeat_count {
+                    let (body, ending) =
+                        loop_pass(loop_commands, *escape_index, endings, *repeat_count, pass);
+                    total += calculate_duration_recursive(body, state);
+                    total += calculate_duration_recursive(ending, state);
                 }
             }
             _ => {}
@@ -422,3 +783,67 @@ fn calculate_duration_recursive(commands: &[Command], state: &mut PlaybackState)
 
     total
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(value: u64) -> Duration {
+        Duration::from_millis(value)
+    }
+
+    #[test]
+    fn clock_clip_without_range() {
+        let clock = Clock::new(Duration::ZERO, None);
+        assert_eq!(clock.clip(ms(100), ms(200)), Some((ms(100), ms(200))));
+    }
+
+    #[test]
+    fn clock_clip_to_range() {
+        let clock = Clock::new(ms(1000), Some(ms(2000)));
+        // 開始位置より前に終わる音は発音しない
+        assert_eq!(clock.clip(ms(0), ms(1000)), None);
+        // 開始位置をまたぐ音は開始位置から残りを発音
+        assert_eq!(clock.clip(ms(500), ms(1500)), Some((ms(1000), ms(1500))));
+        // 終了位置をまたぐ音は終了位置で止める
+        assert_eq!(clock.clip(ms(1800), ms(2500)), Some((ms(1800), ms(2000))));
+        assert_eq!(clock.clip(ms(2000), ms(2500)), None);
+    }
+
+    #[test]
+    fn clock_finishes_at_range_end() {
+        let mut clock = Clock::new(ms(1000), Some(ms(2000)));
+        clock.elapsed = ms(1999);
+        assert!(!clock.is_finished());
+        clock.elapsed = ms(2000);
+        assert!(clock.is_finished());
+        assert!(!Clock::new(Duration::ZERO, None).is_finished());
+    }
+
+    #[test]
+    fn channel_tracker_matches_note_off_per_channel() {
+        let mut notes = ChannelTracker::default();
+        notes.use_channel(1);
+        notes.note_on(1, 60);
+        notes.note_on(10, 60);
+        assert_eq!(notes.sounding(), &[(1, 60), (10, 60)]);
+
+        // 同じノート番号でもチャンネルが異なれば別の音
+        assert!(notes.note_off(10, 60));
+        assert!(!notes.note_off(10, 60));
+        assert_eq!(notes.sounding(), &[(1, 60)]);
+
+        notes.note_on(2, 64);
+        assert_eq!(notes.used_channels().collect::<Vec<_>>(), vec![1, 2, 10]);
+    }
+
+    #[test]
+    fn playback_state_follows_channel_command() {
+        let mut state = PlaybackState {
+            channel: 3,
+            ..PlaybackState::default()
+        };
+        state.update_state(&Command::Channel(crate::mml::Channel { value: 10 }));
+        assert_eq!(state.channel, 10);
+    }
+}
@@ -0,0 +1,146 @@
+//! MIDI note recording
+//!
+//! This module pairs incoming Note On / Note Off messages into [`RecordedNote`]s for
+//! conversion to MML (`mml::transcribe`). Messages are read through [`MidiSource`],
+//! so recording can be tested with synthetic messages.
+//!
+//! # Business rules
+//! - Notes on all channels are recorded
+//! - Note On with velocity 0 is treated as Note Off
+//! - A second Note On for a held note ends the held note at that time
+//! - Notes still held when recording stops end at the stop time
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use super::sync::MidiSource;
+use crate::mml::transcribe::RecordedNote;
+
+/// Collects notes from incoming MIDI messages
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NoteRecorder {
+    /// Start time (seconds) of each held note
+    held: HashMap<u8, f64>,
+    notes: Vec<RecordedNote>,
+}
+
+impl NoteRecorder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Process a received message.
+    pub fn handle(&mut self, at: Duration, bytes: &[u8]) {
+        let &[status, note, velocity, ..] = bytes else {
+            return;
+        };
+        let at = at.as_secs_f64();
+        match status & 0xF0 {
+            0x90 if velocity > 0 => {
+                self.release(note, at);
+                self.held.insert(note, at);
+            }
+            0x80 | 0x90 => self.release(note, at),
+            _ => {}
+        }
+    }
+
+    /// Process all pending messages from `source`.
+    pub fn pump(&mut self, source: &mut impl MidiSource) {
+        while let Some((at, bytes)) = source.try_recv() {
+            self.handle(at, &bytes);
+        }
+    }
+
+    /// Number of notes recorded so far (including held notes).
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.notes.len() + self.held.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Stop recording at `at` and return the notes in order of their start.
+    #[must_use]
+    pub fn finish(mut self, at: Duration) -> Vec<RecordedNote> {
+        let at = at.as_secs_f64();
+        let held: Vec<u8> = self.held.keys().copied().collect();
+        for note in held {
+            self.release(note, at);
+        }
+        self.notes.sort_by(|a, b| a.start.total_cmp(&b.start));
+        self.notes
+    }
+
+    fn release(&mut self, note: u8, at: f64) {
+        if let Some(start) = self.held.remove(&note) {
+            self.notes.push(RecordedNote {
+                note,
+                start,
+                end: at.max(start),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn pairs_note_on_and_off() {
+        let mut recorder = NoteRecorder::new();
+        recorder.handle(ms(100), &[0x90, 60, 100]);
+        recorder.handle(ms(200), &[0x91, 64, 90]);
+        recorder.handle(ms(300), &[0x80, 60, 0]);
+        // ベロシティ0のNote OnはNote Off
+        recorder.handle(ms(400), &[0x91, 64, 0]);
+        assert_eq!(
+            recorder.finish(ms(500)),
+            [
+                RecordedNote {
+                    note: 60,
+                    start: 0.1,
+                    end: 0.3
+                },
+                RecordedNote {
+                    note: 64,
+                    start: 0.2,
+                    end: 0.4
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn ends_held_notes_when_finished() {
+        let mut recorder = NoteRecorder::new();
+        recorder.handle(ms(0), &[0x90, 60, 100]);
+        recorder.handle(ms(100), &[0x90, 60, 100]);
+        assert_eq!(recorder.len(), 2);
+        let notes = recorder.finish(ms(300));
+        assert_eq!(notes.len(), 2);
+        assert!((notes[0].end - 0.1).abs() < 1e-9);
+        assert!((notes[1].end - 0.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ignores_other_messages() {
+        let (sender, mut receiver) = mpsc::channel();
+        sender.send((ms(0), vec![0xF8])).unwrap();
+        sender.send((ms(0), vec![0xB0, 64, 127])).unwrap();
+        sender.send((ms(0), vec![0x80, 60, 0])).unwrap();
+        let mut recorder = NoteRecorder::new();
+        recorder.pump(&mut receiver);
+        assert!(recorder.is_empty());
+    }
+}
@@ -0,0 +1,93 @@
+//! MIDI output sinks
+//!
+//! The player sends complete MIDI messages to a [`MidiSink`]. A `midir` output
+//! connection is the sink for MIDI devices; [`RecordingSink`] keeps the messages in
+//! memory with their send times so that tests can check the order and timing of
+//! the messages without MIDI hardware.
+
+use std::time::Instant;
+
+use midir::MidiOutputConnection;
+
+use super::error::MidiError;
+use super::sync::TimedMessage;
+
+/// Destination of MIDI messages
+pub trait MidiSink {
+    /// Send one complete MIDI message.
+    ///
+    /// # Errors
+    /// Returns `MidiError::SendFailed` if sending fails.
+    fn send(&mut self, bytes: &[u8]) -> Result<(), MidiError>;
+}
+
+impl MidiSink for MidiOutputConnection {
+    fn send(&mut self, bytes: &[u8]) -> Result<(), MidiError> {
+        MidiOutputConnection::send(self, bytes).map_err(|e| MidiError::send_failed(e.to_string()))
+    }
+}
+
+/// Sink that records the sent messages with the time since the sink was created
+#[derive(Debug, Clone)]
+pub struct RecordingSink {
+    origin: Instant,
+    messages: Vec<TimedMessage>,
+}
+
+impl Default for RecordingSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RecordingSink {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            messages: Vec::new(),
+        }
+    }
+
+    /// Sent messages in the order they were sent.
+    #[must_use]
+    pub fn messages(&self) -> &[TimedMessage] {
+        &self.messages
+    }
+
+    /// Sent messages without the send times.
+    #[must_use]
+    pub fn bytes(&self) -> Vec<Vec<u8>> {
+        self.messages
+            .iter()
+            .map(|(_, bytes)| bytes.clone())
+            .collect()
+    }
+
+    #[must_use]
+    pub fn into_messages(self) -> Vec<TimedMessage> {
+        self.messages
+    }
+}
+
+impl MidiSink for RecordingSink {
+    fn send(&mut self, bytes: &[u8]) -> Result<(), MidiError> {
+        self.messages.push((self.origin.elapsed(), bytes.to_vec()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recording_sink_keeps_messages_in_order() {
+        let mut sink = RecordingSink::new();
+        sink.send(&[0x90, 60, 100]).unwrap();
+        sink.send(&[0x80, 60, 0]).unwrap();
+        assert_eq!(sink.bytes(), [vec![0x90, 60, 100], vec![0x80, 60, 0]]);
+        let messages = sink.into_messages();
+        assert!(messages[0].0 <= messages[1].0);
+    }
+}
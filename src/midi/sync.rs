@@ -0,0 +1,413 @@
+//! External MIDI clock following
+//!
+//! This module tracks an incoming MIDI clock (Timing Clock, Start/Stop/Continue and
+//! Song Position Pointer) so that playback can follow another device as tempo master.
+//! Messages are read through the [`MidiSource`] trait; a `midir` input port is one
+//! implementation and an `mpsc::Receiver` (used by tests) is another.
+//!
+//! # Business rules
+//! - Positions are counted in clocks (24 per quarter note) from the top of the song
+//! - After Start or Continue, the first Timing Clock marks the start position
+//! - Song Position Pointer sets the position that the next Continue resumes from
+//! - Tempo is the average of the latest clock intervals; intervals longer than
+//!   `MAX_TICK_INTERVAL` (the master stopped sending clocks) are ignored
+//! - Between clocks the position is interpolated, but never beyond the next clock
+
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use midir::{MidiInput, MidiInputConnection};
+
+use super::clock::CLOCKS_PER_QUARTER;
+use super::device::resolve_input_device_id;
+use super::error::MidiError;
+
+/// Number of clock intervals averaged for the tempo
+const TEMPO_WINDOW: usize = 24;
+
+/// Clock intervals longer than this are not used for the tempo (under 5 BPM)
+const MAX_TICK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Song Position Pointer unit (a sixteenth note) in clocks
+const CLOCKS_PER_SIXTEENTH: u64 = 6;
+
+/// A received MIDI message: time since the source was opened, and raw bytes
+pub type TimedMessage = (Duration, Vec<u8>);
+
+/// Source of incoming MIDI messages
+pub trait MidiSource {
+    /// Next received message, if any (does not block).
+    fn try_recv(&mut self) -> Option<TimedMessage>;
+}
+
+impl MidiSource for Receiver<TimedMessage> {
+    fn try_recv(&mut self) -> Option<TimedMessage> {
+        Receiver::try_recv(self).ok()
+    }
+}
+
+/// Transport change requested by the clock master
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransportEvent {
+    /// Start from the top of the song
+    Start,
+    /// Continue from a position (beats from the top of the song)
+    Continue {
+        /// Position in quarter-note beats
+        beat: f64,
+    },
+    /// Stop
+    Stop,
+}
+
+/// State of an incoming MIDI clock
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClockFollower {
+    running: bool,
+    /// Position (in clocks) of the next Timing Clock
+    next_position: u64,
+    /// Time and position of the latest Timing Clock since Start/Continue
+    last_tick: Option<(Duration, u64)>,
+    /// Time of the latest Timing Clock (also while stopped, for the tempo)
+    previous_tick_at: Option<Duration>,
+    intervals: VecDeque<Duration>,
+}
+
+impl ClockFollower {
+    /// Create a stopped follower at the top of the song.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Process a received message and return the transport change it requests.
+    pub fn handle(&mut self, at: Duration, bytes: &[u8]) -> Option<TransportEvent> {
+        match *bytes.first()? {
+            0xF8 => {
+                self.tick(at);
+                None
+            }
+            0xFA => {
+                self.running = true;
+                self.next_position = 0;
+                self.last_tick = None;
+                Some(TransportEvent::Start)
+            }
+            0xFB => {
+                self.running = true;
+                self.last_tick = None;
+                Some(TransportEvent::Continue {
+                    beat: clocks_to_beats(self.next_position),
+                })
+            }
+            0xFC => {
+                self.running = false;
+                Some(TransportEvent::Stop)
+            }
+            0xF2 if bytes.len() >= 3 => {
+                let sixteenths = u64::from(bytes[1] & 0x7F) | (u64::from(bytes[2] & 0x7F) << 7);
+                self.next_position = sixteenths * CLOCKS_PER_SIXTEENTH;
+                self.last_tick = None;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn tick(&mut self, at: Duration) {
+        if let Some(previous) = self.previous_tick_at {
+            let interval = at.saturating_sub(previous);
+            if interval <= MAX_TICK_INTERVAL {
+                if self.intervals.len() == TEMPO_WINDOW {
+                    self.intervals.pop_front();
+                }
+                self.intervals.push_back(interval);
+            }
+        }
+        self.previous_tick_at = Some(at);
+
+        if self.running {
+            self.last_tick = Some((at, self.next_position));
+            self.next_position += 1;
+        }
+    }
+
+    /// Whether the master is playing (between Start/Continue and Stop).
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Tempo of the master (BPM), once at least one clock interval is known.
+    #[must_use]
+    pub fn bpm(&self) -> Option<f64> {
+        let interval = self.tick_interval()?;
+        Some(60.0 / (interval * f64::from(CLOCKS_PER_QUARTER)))
+    }
+
+    /// Average clock interval (seconds).
+    fn tick_interval(&self) -> Option<f64> {
+        if self.intervals.is_empty() {
+            return None;
+        }
+        let total: Duration = self.intervals.iter().sum();
+        #[allow(clippy::cast_precision_loss)]
+        Some(total.as_secs_f64() / self.intervals.len() as f64)
+    }
+
+    /// Song position (beats) at time `now`.
+    ///
+    /// Before the first clock after Start/Continue (and while stopped) this is the
+    /// position the next clock will mark.
+    #[must_use]
+    pub fn beat_at(&self, now: Duration) -> f64 {
+        let (Some((at, position)), true) = (self.last_tick, self.running) else {
+            return clocks_to_beats(self.next_position);
+        };
+        let fraction = self.tick_interval().map_or(0.0, |interval| {
+            (now.saturating_sub(at).as_secs_f64() / interval).min(1.0)
+        });
+        #[allow(clippy::cast_precision_loss)]
+        let clocks = position as f64 + fraction;
+        clocks / f64::from(CLOCKS_PER_QUARTER)
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn clocks_to_beats(clocks: u64) -> f64 {
+    clocks as f64 / f64::from(CLOCKS_PER_QUARTER)
+}
+
+/// Incoming MIDI clock shared between the message reader and the players
+///
+/// Times are measured from `origin`, which must be the origin of the timestamps of
+/// the messages passed to [`ExternalClock::handle`].
+#[derive(Debug, Clone)]
+pub struct ExternalClock {
+    follower: Arc<Mutex<ClockFollower>>,
+    origin: Instant,
+}
+
+impl ExternalClock {
+    #[must_use]
+    pub fn new(origin: Instant) -> Self {
+        Self {
+            follower: Arc::new(Mutex::new(ClockFollower::new())),
+            origin,
+        }
+    }
+
+    /// Time since `origin`.
+    #[must_use]
+    pub fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+
+    /// Process a received message (see [`ClockFollower::handle`]).
+    #[must_use]
+    pub fn handle(&self, at: Duration, bytes: &[u8]) -> Option<TransportEvent> {
+        self.lock().handle(at, bytes)
+    }
+
+    /// Process all pending messages from `source` and return the transport changes.
+    pub fn pump(&self, source: &mut impl MidiSource) -> Vec<TransportEvent> {
+        std::iter::from_fn(|| source.try_recv())
+            .filter_map(|(at, bytes)| self.handle(at, &bytes))
+            .collect()
+    }
+
+    /// Current song position (beats).
+    #[must_use]
+    pub fn beat(&self) -> f64 {
+        self.lock().beat_at(self.now())
+    }
+
+    /// Current tempo of the master (BPM).
+    #[must_use]
+    pub fn bpm(&self) -> Option<f64> {
+        self.lock().bpm()
+    }
+
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        self.lock().is_running()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ClockFollower> {
+        self.follower.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// MIDI input port delivering received messages as [`TimedMessage`]s
+pub struct MidiInputPort {
+    _conn: MidiInputConnection<()>,
+    receiver: Receiver<TimedMessage>,
+    origin: Instant,
+}
+
+impl MidiInputPort {
+    /// Open a MIDI input device (ID or name) including Timing Clock messages.
+    ///
+    /// # Errors
+    /// - `MidiError::InvalidDeviceId` if numeric ID is out of range
+    /// - `MidiError::NoDeviceFound` if name doesn't match any device or MIDI init fails
+    /// - `MidiError::ConnectionFailed` if connection to the device fails
+    pub fn open(device_id_or_name: &str) -> Result<Self, MidiError> {
+        let id = resolve_input_device_id(device_id_or_name)?;
+
+        let mut midi_in = MidiInput::new("sine-mml-input").map_err(|_| MidiError::NoDeviceFound)?;
+        // Timing Clockなどのシステムリアルタイムメッセージも受け取る
+        midi_in.ignore(midir::Ignore::Sysex | midir::Ignore::ActiveSense);
+
+        let ports = midi_in.ports();
+        let port = ports.get(id).ok_or(MidiError::InvalidDeviceId { id })?;
+
+        let origin = Instant::now();
+        let (sender, receiver) = mpsc::channel();
+        let conn = midi_in
+            .connect(
+                port,
+                "sine-mml-input",
+                move |_, bytes, ()| {
+                    let _ = sender.send((origin.elapsed(), bytes.to_vec()));
+                },
+                (),
+            )
+            .map_err(|e| MidiError::ConnectionFailed {
+                reason: e.to_string(),
+            })?;
+
+        Ok(Self {
+            _conn: conn,
+            receiver,
+            origin,
+        })
+    }
+
+    /// Origin of the message timestamps.
+    #[must_use]
+    pub fn origin(&self) -> Instant {
+        self.origin
+    }
+}
+
+impl MidiSource for MidiInputPort {
+    fn try_recv(&mut self) -> Option<TimedMessage> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 120 BPMのクロック間隔（1/48秒）
+    const TICK: Duration = Duration::from_micros(20_833);
+
+    fn ticks(follower: &mut ClockFollower, from: u32, count: u32) {
+        for i in from..from + count {
+            follower.handle(TICK * i, &[0xF8]);
+        }
+    }
+
+    #[test]
+    fn measures_tempo_from_clock_intervals() {
+        let mut follower = ClockFollower::new();
+        assert_eq!(follower.bpm(), None);
+        ticks(&mut follower, 0, 48);
+        let bpm = follower.bpm().unwrap();
+        assert!((bpm - 120.0).abs() < 0.01, "bpm: {bpm}");
+        // クロックは停止中でも受け付け、位置は進めない
+        assert!(!follower.is_running());
+        assert!(follower.beat_at(TICK * 48).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn ignores_long_gaps_for_tempo() {
+        let mut follower = ClockFollower::new();
+        ticks(&mut follower, 0, 2);
+        follower.handle(Duration::from_secs(10), &[0xF8]);
+        let bpm = follower.bpm().unwrap();
+        assert!((bpm - 120.0).abs() < 0.01, "bpm: {bpm}");
+    }
+
+    #[test]
+    fn start_counts_position_from_first_clock() {
+        let mut follower = ClockFollower::new();
+        assert_eq!(
+            follower.handle(Duration::ZERO, &[0xFA]),
+            Some(TransportEvent::Start)
+        );
+        assert!(follower.is_running());
+        assert!(follower.beat_at(TICK * 5).abs() < f64::EPSILON);
+
+        ticks(&mut follower, 1, 25);
+        // 25個目のクロックが1拍目
+        assert!((follower.beat_at(TICK * 25) - 1.0).abs() < 1e-9);
+        // クロックの間は補間し、次のクロックを越えない
+        let half = TICK * 25 + TICK / 2;
+        assert!((follower.beat_at(half) - (1.0 + 0.5 / 24.0)).abs() < 1e-3);
+        assert!((follower.beat_at(TICK * 40) - (1.0 + 1.0 / 24.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn stop_and_continue_keep_position() {
+        let mut follower = ClockFollower::new();
+        follower.handle(Duration::ZERO, &[0xFA]);
+        ticks(&mut follower, 0, 24);
+        assert_eq!(
+            follower.handle(TICK * 24, &[0xFC]),
+            Some(TransportEvent::Stop)
+        );
+        assert!(!follower.is_running());
+        // 停止中のクロックでは位置は進まない
+        ticks(&mut follower, 24, 10);
+        assert!((follower.beat_at(TICK * 40) - 1.0).abs() < 1e-9);
+        assert_eq!(
+            follower.handle(TICK * 40, &[0xFB]),
+            Some(TransportEvent::Continue { beat: 1.0 })
+        );
+    }
+
+    #[test]
+    fn song_position_pointer_sets_continue_position() {
+        let mut follower = ClockFollower::new();
+        // 16分音符で200 = 50拍
+        assert_eq!(follower.handle(Duration::ZERO, &[0xF2, 72, 1]), None);
+        assert_eq!(
+            follower.handle(Duration::ZERO, &[0xFB]),
+            Some(TransportEvent::Continue { beat: 50.0 })
+        );
+        ticks(&mut follower, 1, 2);
+        assert!((follower.beat_at(TICK * 2) - (50.0 + 1.0 / 24.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ignores_other_messages() {
+        let mut follower = ClockFollower::new();
+        assert_eq!(follower.handle(Duration::ZERO, &[0x90, 60, 100]), None);
+        assert_eq!(follower.handle(Duration::ZERO, &[]), None);
+        assert_eq!(follower, ClockFollower::new());
+    }
+
+    #[test]
+    fn external_clock_pumps_messages_from_source() {
+        let (sender, mut receiver) = mpsc::channel();
+        let clock = ExternalClock::new(Instant::now());
+        sender.send((Duration::ZERO, vec![0xFA])).unwrap();
+        for i in 0..3 {
+            sender.send((TICK * i, vec![0xF8])).unwrap();
+        }
+        sender.send((TICK * 3, vec![0xFC])).unwrap();
+
+        assert_eq!(
+            clock.pump(&mut receiver),
+            [TransportEvent::Start, TransportEvent::Stop]
+        );
+        assert!(!clock.is_running());
+        assert!((clock.beat() - 3.0 / 24.0).abs() < 1e-9);
+        assert!(clock.pump(&mut receiver).is_empty());
+    }
+}
@@ -3,13 +3,53 @@ pub struct Mml {
     pub commands: Vec<Command>,
 }
 
+use super::timing::{TempoCurve, TempoState};
+
 /// テンポ変更イベント（サンプル位置とBPM値のペア）
 #[derive(Debug, Clone, PartialEq)]
 pub struct TempoEvent {
     /// サンプル位置（0始まり）
     pub sample_position: usize,
-    /// BPM値
+    /// BPM値（テンポ変化中の場合は開始テンポ）
     pub bpm: u16,
+    /// このイベントから始まるテンポ変化（イベント位置を0拍目とする）
+    ///
+    /// `None`の場合は次のイベントまで`bpm`で一定。
+    pub curve: Option<TempoCurve>,
+    /// 拍子と小節内の位置（`@M`指定がない場合は4/4）
+    ///
+    /// `None`の場合は小節の頭を強調しない。
+    pub meter: Option<Meter>,
+}
+
+/// 拍子と、テンポイベント位置での小節内の拍位置
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Meter {
+    pub time_signature: TimeSignature,
+    /// 小節内の拍位置（0.0で小節の頭、4分音符 = 1拍）
+    pub beat_in_bar: f64,
+}
+
+impl Meter {
+    /// イベント位置から`beat`拍進んだ位置が小節の頭かどうか
+    #[must_use]
+    pub fn is_downbeat(&self, beat: f64) -> bool {
+        let bar = self.time_signature.beats_per_bar();
+        let phase = (self.beat_in_bar + beat).rem_euclid(bar);
+        phase < 1e-6 || bar - phase < 1e-6
+    }
+}
+
+impl TempoEvent {
+    /// イベント区間のテンポ状態を取得
+    #[must_use]
+    pub fn tempo_state(&self) -> TempoState {
+        TempoState {
+            bpm: self.curve.map_or(f64::from(self.bpm), |curve| curve.to),
+            curve: self.curve,
+            swing: None,
+        }
+    }
 }
 
 impl Mml {
@@ -17,9 +57,13 @@ impl Mml {
     /// Tempoコマンドがない場合はデフォルトの120を返す。
     #[must_use]
     pub fn get_tempo(&self) -> u16 {
-        for command in &self.commands {
-            if let Command::Tempo(tempo) = command {
-                return tempo.value;
+        for command in self {
+            match command {
+                Command::Tempo(tempo) => return tempo.value,
+                Command::TempoRamp(TempoRamp {
+                    from: Some(from), ..
+                }) => return *from,
+                _ => {}
             }
         }
         120
@@ -28,44 +72,64 @@ impl Mml {
     /// MMLコマンドからテンポ変更イベントのリストを取得する。
     /// 各イベントには、テンポ変更が発生するサンプル位置とBPM値が含まれる。
     /// 最初のイベントは常にサンプル位置0で、初期テンポ（最初のTempoコマンドまたはデフォルト120）。
+    /// テンポ変化（`Tn>m:b`）は変化開始位置のイベントに`curve`として含まれる。
     #[must_use]
     #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
     pub fn get_tempo_events(&self, sample_rate: u32) -> Vec<TempoEvent> {
         let mut events = Vec::new();
         let mut default_length: u8 = 4;
         let mut current_sample: usize = 0;
+        let mut beat_position = 0.0_f64;
 
         let initial_bpm = self.get_tempo();
         events.push(TempoEvent {
             sample_position: 0,
             bpm: initial_bpm,
+            curve: None,
+            meter: Some(Meter {
+                time_signature: TimeSignature::default(),
+                beat_in_bar: 0.0,
+            }),
         });
-        let mut current_bpm = initial_bpm;
+        let mut tempo = TempoState::new(initial_bpm);
+        // 拍子と、その拍子が始まった位置（拍）
+        let mut time_signature = (TimeSignature::default(), 0.0);
 
-        for command in &self.commands {
+        for command in self {
             match command {
                 Command::Note(note) => {
-                    let duration_sec = note.duration_in_seconds(current_bpm, default_length);
-                    let num_samples = (f64::from(duration_sec) * f64::from(sample_rate)) as usize;
-                    current_sample += num_samples;
+                    let beats = note.total_beats(default_length);
+                    let duration_sec = tempo.seconds(beat_position, beats);
+                    current_sample += (duration_sec * f64::from(sample_rate)) as usize;
+                    beat_position += beats;
                 }
                 Command::Rest(rest) => {
-                    let duration_sec = rest.duration_in_seconds(current_bpm, default_length);
-                    let num_samples = (f64::from(duration_sec) * f64::from(sample_rate)) as usize;
-                    current_sample += num_samples;
+                    let beats = rest.total_beats(default_length);
+                    let duration_sec = tempo.seconds(beat_position, beats);
+                    current_sample += (duration_sec * f64::from(sample_rate)) as usize;
+                    beat_position += beats;
                 }
                 Command::Tempo(t) => {
-                    // 最初のテンポコマンドは既にevents[0]に含まれているのでスキップ
-                    if current_sample > 0 || t.value != initial_bpm {
-                        // 位置0で初期テンポと同じ場合は重複を避ける
-                        if current_sample > 0 {
-                            events.push(TempoEvent {
-                                sample_position: current_sample,
-                                bpm: t.value,
-                            });
-                        }
-                    }
-                    current_bpm = t.value;
+                    tempo.set(t.value);
+                    // 位置0のイベントは初期テンポとしてevents[0]を置き換える
+                    push_tempo_event(
+                        &mut events,
+                        tempo_event(current_sample, &tempo, beat_position, time_signature),
+                    );
+                }
+                Command::TempoRamp(ramp) => {
+                    tempo.apply_ramp(ramp, beat_position);
+                    push_tempo_event(
+                        &mut events,
+                        tempo_event(current_sample, &tempo, beat_position, time_signature),
+                    );
+                }
+                Command::TimeSignature(sig) => {
+                    time_signature = (*sig, beat_position);
+                    push_tempo_event(
+                        &mut events,
+                        tempo_event(current_sample, &tempo, beat_position, time_signature),
+                    );
                 }
                 Command::DefaultLength(l) => {
                     default_length = l.value;
@@ -79,7 +143,8 @@ impl Mml {
                         tuplet_commands,
                         *count,
                         *base_duration,
-                        current_bpm,
+                        &tempo,
+                        &mut beat_position,
                         default_length,
                         sample_rate,
                     );
@@ -89,7 +154,14 @@ impl Mml {
                 | Command::OctaveUp
                 | Command::OctaveDown
                 | Command::Volume(_)
+                | Command::Gate(_)
+                | Command::Velocity(_)
+                | Command::BarLine
+                | Command::Marker(_)
+                | Command::Channel(_)
+                | Command::MidiControl(_)
                 | Command::Loop { .. } => {}
+                Command::Swing(swing) => tempo.set_swing(*swing),
             }
         }
 
@@ -97,61 +169,80 @@ impl Mml {
     }
 
     /// 連符内のコマンドの合計サンプル数を計算
+    ///
+    /// シンセサイザーと同じく音符ごとにサンプル数を切り捨てて合計する。
+    /// ネストした連符は1音分の長さとして扱う。
     #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
     fn calculate_tuplet_samples(
         commands: &[Command],
         count: u8,
         base_duration: Option<u8>,
-        bpm: u16,
+        tempo: &TempoState,
+        beat_position: &mut f64,
         default_length: u8,
         sample_rate: u32,
     ) -> usize {
         let base = base_duration.unwrap_or(default_length);
-        let base_seconds = 60.0 / f32::from(bpm) * 4.0 / f32::from(base);
-        let tuplet_duration = base_seconds / f32::from(count);
+        let tuplet_beats = 4.0 / f64::from(base) / f64::from(count);
 
         let mut total_samples = 0usize;
         for cmd in commands {
-            match cmd {
+            let beats = match cmd {
                 Command::Note(note) => {
-                    let note_duration =
-                        if note.duration.base.value.is_some() || note.duration.has_ties() {
-                            note.duration_in_seconds(bpm, default_length) / f32::from(count)
-                        } else {
-                            tuplet_duration
-                        };
-                    total_samples += (f64::from(note_duration) * f64::from(sample_rate)) as usize;
+                    if note.duration.base.value.is_some() || note.duration.has_ties() {
+                        note.total_beats(default_length) / f64::from(count)
+                    } else {
+                        tuplet_beats
+                    }
                 }
                 Command::Rest(rest) => {
-                    let rest_duration =
-                        if rest.duration.base.value.is_some() || rest.duration.has_ties() {
-                            rest.duration_in_seconds(bpm, default_length) / f32::from(count)
-                        } else {
-                            tuplet_duration
-                        };
-                    total_samples += (f64::from(rest_duration) * f64::from(sample_rate)) as usize;
-                }
-                Command::Tuplet {
-                    commands: nested_cmds,
-                    count: nested_count,
-                    base_duration: nested_base,
-                } => {
-                    total_samples += Self::calculate_tuplet_samples(
-                        nested_cmds,
-                        *nested_count,
-                        *nested_base,
-                        bpm,
-                        default_length,
-                        sample_rate,
-                    );
+                    if rest.duration.base.value.is_some() || rest.duration.has_ties() {
+                        rest.total_beats(default_length) / f64::from(count)
+                    } else {
+                        tuplet_beats
+                    }
                 }
-                _ => {}
-            }
+                Command::Tuplet { .. } => tuplet_beats,
+                _ => continue,
+            };
+            let seconds = tempo.seconds(*beat_position, beats);
+            total_samples += (seconds * f64::from(sample_rate)) as usize;
+            *beat_position += beats;
         }
         total_samples
     }
 }
 
+/// 現在のテンポ・拍子からテンポイベントを作成する
+///
+/// `time_signature`は拍子と、その拍子が始まった位置（拍）の組。
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn tempo_event(
+    sample_position: usize,
+    tempo: &TempoState,
+    beat_position: f64,
+    (sig, start): (TimeSignature, f64),
+) -> TempoEvent {
+    let rebased = tempo.rebased(beat_position);
+    TempoEvent {
+        sample_position,
+        bpm: rebased.bpm_at(0.0).round() as u16,
+        curve: rebased.curve,
+        meter: Some(Meter {
+            time_signature: sig,
+            beat_in_bar: (beat_position - start).rem_euclid(sig.beats_per_bar()),
+        }),
+    }
+}
+
+/// テンポイベントを追加する（同じ位置のイベントは置き換える）
+fn push_tempo_event(events: &mut Vec<TempoEvent>, event: TempoEvent) {
+    match events.last_mut() {
+        Some(last) if last.sample_position == event.sample_position => *last = event,
+        _ => events.push(event),
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Command {
     Note(Note),
@@ -160,8 +251,26 @@ pub enum Command {
     OctaveUp,
     OctaveDown,
     Tempo(Tempo),
+    /// テンポ変化コマンド: `Tn>m:b` / `T>m:b`
+    TempoRamp(TempoRamp),
     DefaultLength(DefaultLength),
     Volume(Volume),
+    /// ゲートタイムコマンド: `Qn` / `Q%n`
+    Gate(Gate),
+    /// ベロシティコマンド: `@vn` / `@vn>m:b` / `@v>m:b`
+    Velocity(Velocity),
+    /// スウィングコマンド: `@Sn` / `@Sn:d`
+    Swing(Swing),
+    /// 拍子コマンド: `@Mn/d`
+    TimeSignature(TimeSignature),
+    /// 小節線: `|`（発音には影響せず、小節の拍数チェックに使用）
+    BarLine,
+    /// マーカー: `@"name"`（発音には影響せず、再生範囲の指定に使用）
+    Marker(String),
+    /// MIDIチャンネルコマンド: `@Cn`（1-16、以降の音符を送るチャンネル。音声合成では無視）
+    Channel(Channel),
+    /// MIDIコントロールコマンド: `@Pn` / `@CCn:v` / `@An` / `@PBn` / `@Bn:l`（音声合成では無視）
+    MidiControl(MidiControl),
     /// ループコマンド: [commands]n
     ///
     /// # フィールド
@@ -169,12 +278,16 @@ pub enum Command {
     /// - `escape_index`: 脱出ポイントのインデックス（:の位置）
     ///   - `None`: 脱出ポイントなし（全コマンドを繰り返す）
     ///   - `Some(n)`: n番目のコマンドの後で最終回に脱出
+    /// - `endings`: n番カッコ（`:1`, `:2`...）
+    ///   - 空の場合は全回で`commands`のみを演奏
     /// - `repeat_count`: ループ回数（1-99）
     Loop {
-        /// ループ内のコマンド列
+        /// ループ内のコマンド列（n番カッコがある場合は共通部分）
         commands: Vec<Command>,
         /// 脱出ポイントのインデックス（Noneの場合は脱出なし）
         escape_index: Option<usize>,
+        /// n番カッコ（回数の昇順）
+        endings: Vec<VoltaEnding>,
         /// 繰り返し回数（1-99）
         repeat_count: usize,
     },
@@ -388,6 +501,68 @@ pub struct Rest {
     pub duration: TiedDuration,
 }
 
+impl Command {
+    /// コマンドの長さ（4分音符 = 1拍）
+    ///
+    /// 音符・休符・連符以外は0.0。連符内の音価指定のある音符は`音価 / 連符数`、
+    /// 音価指定のない音符とネストした連符は`基準音価 / 連符数`として合計する
+    /// （シンセサイザー・MIDIプレイヤーの時間計算と同じ規則）。
+    #[must_use]
+    pub fn beats(&self, default_length: u8) -> f64 {
+        match self {
+            Self::Note(note) => note.total_beats(default_length),
+            Self::Rest(rest) => rest.total_beats(default_length),
+            Self::Tuplet { .. } => self.tuplet_element_beats(default_length).iter().sum(),
+            _ => 0.0,
+        }
+    }
+
+    /// 連符の各要素（音符・休符・ネストした連符）の拍数
+    ///
+    /// 連符以外の場合は空。要素ごとの規則は[`Command::beats`]と同じ。
+    #[must_use]
+    pub fn tuplet_element_beats(&self, default_length: u8) -> Vec<f64> {
+        let Self::Tuplet {
+            commands,
+            count,
+            base_duration,
+        } = self
+        else {
+            return Vec::new();
+        };
+        let base = base_duration.unwrap_or(default_length);
+        let slot = 4.0 / f64::from(base) / f64::from(*count);
+        commands
+            .iter()
+            .filter_map(|cmd| match cmd {
+                Self::Note(note)
+                    if note.duration.base.value.is_some() || note.duration.has_ties() =>
+                {
+                    Some(note.total_beats(default_length) / f64::from(*count))
+                }
+                Self::Rest(rest)
+                    if rest.duration.base.value.is_some() || rest.duration.has_ties() =>
+                {
+                    Some(rest.total_beats(default_length) / f64::from(*count))
+                }
+                Self::Note(_) | Self::Rest(_) | Self::Tuplet { .. } => Some(slot),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// n番カッコ（ループの特定の回だけ演奏する部分）
+///
+/// `[CDE :1 FG :2 AB]2`の`:1 FG`、`:2 AB`にあたる。
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoltaEnding {
+    /// 演奏する回（1始まり）
+    pub pass: usize,
+    /// 共通部分の後に演奏するコマンド列
+    pub commands: Vec<Command>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Octave {
     pub value: u8,
@@ -398,6 +573,84 @@ pub struct Tempo {
     pub value: u16,
 }
 
+/// MIDIチャンネル（1-16）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Channel {
+    pub value: u8,
+}
+
+/// MIDIのコントロール系メッセージ（現在のチャンネルに送る）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiControl {
+    /// プログラムチェンジ（音色、0-127）: `@Pn`
+    ProgramChange(u8),
+    /// コントロールチェンジ（0-127）: `@CCn:v`
+    ControlChange { controller: u8, value: u8 },
+    /// チャンネルプレッシャー（0-127）: `@An`
+    ChannelPressure(u8),
+    /// ピッチベンド（-8192〜8191、0が中央）: `@PBn`
+    PitchBend(i16),
+    /// バンクセレクト（MSB、LSB、各0-127）: `@Bn` / `@Bn:l`
+    BankSelect { msb: u8, lsb: Option<u8> },
+}
+
+/// スウィング（ハネ）
+///
+/// `subdivision`分音符2つを1組とし、1つ目の長さを組全体の`ratio`%にする。
+/// `ratio`が50の場合はスウィングなし（イーブン）。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Swing {
+    /// 1つ目の音符が占める割合（50-75%）
+    pub ratio: u8,
+    /// スウィングさせる音符の細分（4, 8, 16）
+    pub subdivision: u8,
+}
+
+/// 拍子
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeSignature {
+    /// 1小節あたりの拍数（分子、1-32）
+    pub numerator: u8,
+    /// 1拍とする音符（分母、1, 2, 4, 8, 16, 32）
+    pub denominator: u8,
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        Self {
+            numerator: 4,
+            denominator: 4,
+        }
+    }
+}
+
+impl TimeSignature {
+    /// 1小節の長さ（4分音符 = 1拍）
+    ///
+    /// # Examples
+    /// - 4/4 → 4.0拍
+    /// - 3/4 → 3.0拍
+    /// - 6/8 → 3.0拍
+    #[must_use]
+    pub fn beats_per_bar(&self) -> f64 {
+        f64::from(self.numerator) * 4.0 / f64::from(self.denominator)
+    }
+}
+
+/// テンポ変化（アッチェレランド/リタルダンド）
+///
+/// `from`から`to`へ`beats`拍かけてテンポを直線的に変化させる。
+/// `from`が`None`の場合は現在のテンポから変化する。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TempoRamp {
+    /// 開始テンポ（30-300）
+    pub from: Option<u16>,
+    /// 終了テンポ（30-300）
+    pub to: u16,
+    /// 変化にかける拍数（1-128）
+    pub beats: u8,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DefaultLength {
     pub value: u8,
@@ -434,6 +687,97 @@ pub struct Volume {
     pub value: VolumeValue,
 }
 
+/// ゲートタイム値の種類
+///
+/// 音符の長さのうち、実際に発音する割合を表す。残りは無音になる。
+///
+/// # バリアント
+/// - `Eighths(u8)`: 8分率（1-8、`Q8`で音長いっぱいに発音）
+/// - `Percent(u8)`: 百分率（1-100）
+///
+/// # 例
+/// ```ignore
+/// // Q4 の場合（音長の半分だけ発音）
+/// GateValue::Eighths(4)
+///
+/// // Q%75 の場合
+/// GateValue::Percent(75)
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GateValue {
+    /// 8分率（1-8）
+    Eighths(u8),
+    /// 百分率（1-100）
+    Percent(u8),
+}
+
+impl Default for GateValue {
+    fn default() -> Self {
+        Self::Eighths(8)
+    }
+}
+
+impl GateValue {
+    /// 発音する割合（0.0〜1.0）を取得
+    ///
+    /// # Examples
+    /// - `Q8` → 1.0
+    /// - `Q4` → 0.5
+    /// - `Q%75` → 0.75
+    #[must_use]
+    pub fn ratio(self) -> f32 {
+        match self {
+            Self::Eighths(n) => f32::from(n.min(8)) / 8.0,
+            Self::Percent(p) => f32::from(p.min(100)) / 100.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gate {
+    /// 8分率（1-8）または百分率（1-100）
+    pub value: GateValue,
+}
+
+/// ベロシティ値の種類
+///
+/// # バリアント
+/// - `Absolute(u8)`: 絶対値（0-127）
+/// - `Ramp`: 指定拍数かけて`from`から`to`へ変化（クレッシェンド/デクレッシェンド）
+///   - `from`が`None`の場合は現在の音量から変化を開始する
+///
+/// # 例
+/// ```ignore
+/// // @v100 の場合
+/// VelocityValue::Absolute(100)
+///
+/// // @v40>110:8 の場合（8拍かけて40から110へ）
+/// VelocityValue::Ramp { from: Some(40), to: 110, beats: 8 }
+///
+/// // @v>20:4 の場合（4拍かけて現在の音量から20へ）
+/// VelocityValue::Ramp { from: None, to: 20, beats: 4 }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VelocityValue {
+    /// 絶対値（0-127）
+    Absolute(u8),
+    /// 指定拍数かけて変化
+    Ramp {
+        /// 開始値（0-127、`None`の場合は現在の音量）
+        from: Option<u8>,
+        /// 終了値（0-127）
+        to: u8,
+        /// 変化にかける拍数（1-128）
+        beats: u8,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Velocity {
+    /// 絶対値または変化指定
+    pub value: VelocityValue,
+}
+
 impl Note {
     #[must_use]
     #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
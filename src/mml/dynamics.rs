@@ -0,0 +1,202 @@
+//! 音量変化（ベロシティ）計算モジュール
+//!
+//! `@v`コマンドによる0-127のベロシティ指定と、指定拍数かけて変化する
+//! クレッシェンド/デクレッシェンドを扱う。シンセサイザーとMIDIプレイヤーの
+//! 双方から利用され、音符ごとに同じベロシティが得られることを保証する。
+//!
+//! # ビジネスルール
+//! - `@v`指定がない間は`V`コマンドの音量（0-15）を使用する
+//! - `V`コマンドは`@v`指定と進行中の変化を解除する
+//! - 変化は開始位置からの経過拍数で線形補間し、終了後は終了値を維持する
+
+use super::VelocityValue;
+
+/// Vコマンドの音量（0-15）をベロシティ（0-127）に変換
+///
+/// # Examples
+/// - V0 → 0
+/// - V10 → 84
+/// - V15 → 127
+#[must_use]
+#[allow(clippy::cast_possible_truncation)]
+pub fn volume_to_velocity(volume: u8) -> u8 {
+    (u16::from(volume.min(15)) * 127 / 15) as u8
+}
+
+/// 進行中のベロシティ変化
+///
+/// 開始位置（拍）からの経過拍数に応じて、`from`から`to`へ線形補間する。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VelocityRamp {
+    /// 開始値（0-127）
+    pub from: u8,
+    /// 終了値（0-127）
+    pub to: u8,
+    /// 変化を開始した位置（拍）
+    pub start_beat: f64,
+    /// 変化にかける拍数
+    pub beats: f64,
+}
+
+impl VelocityRamp {
+    /// Creates a new `VelocityRamp`
+    #[must_use]
+    pub const fn new(from: u8, to: u8, start_beat: f64, beats: f64) -> Self {
+        Self {
+            from,
+            to,
+            start_beat,
+            beats,
+        }
+    }
+
+    /// 指定位置（拍）でのベロシティ（0-127）を計算
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn velocity_at(&self, beat: f64) -> u8 {
+        let progress = if self.beats > 0.0 {
+            ((beat - self.start_beat) / self.beats).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        let from = f64::from(self.from);
+        let to = f64::from(self.to);
+        (from + (to - from) * progress).round().clamp(0.0, 127.0) as u8
+    }
+}
+
+/// `@v`コマンドによるベロシティ状態
+///
+/// `V`コマンドの音量は呼び出し側が保持し、各メソッドに渡す。
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Dynamics {
+    /// `@v`で指定された固定ベロシティ（0-127）
+    pub velocity: Option<u8>,
+    /// 進行中のベロシティ変化
+    pub ramp: Option<VelocityRamp>,
+}
+
+impl Dynamics {
+    /// `@v`コマンドを適用する
+    ///
+    /// # Arguments
+    /// * `value` - ベロシティ値
+    /// * `volume` - 現在の`V`音量（0-15）。開始値省略時の変化の起点に使う
+    /// * `beat` - コマンドの位置（拍）
+    pub fn apply(&mut self, value: VelocityValue, volume: u8, beat: f64) {
+        match value {
+            VelocityValue::Absolute(v) => {
+                self.velocity = Some(v.min(127));
+                self.ramp = None;
+            }
+            VelocityValue::Ramp { from, to, beats } => {
+                let from = from.unwrap_or_else(|| self.velocity_at(volume, beat));
+                self.velocity = None;
+                self.ramp = Some(VelocityRamp::new(
+                    from.min(127),
+                    to.min(127),
+                    beat,
+                    f64::from(beats),
+                ));
+            }
+        }
+    }
+
+    /// `@v`指定と進行中の変化を解除する（`V`コマンド用）
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// 指定位置（拍）でのベロシティ（0-127）を取得
+    #[must_use]
+    pub fn velocity_at(&self, volume: u8, beat: f64) -> u8 {
+        if let Some(ramp) = &self.ramp {
+            ramp.velocity_at(beat)
+        } else if let Some(velocity) = self.velocity {
+            velocity
+        } else {
+            volume_to_velocity(volume)
+        }
+    }
+
+    /// 指定位置（拍）での音量係数（0.0〜1.0）を取得
+    ///
+    /// `@v`指定がない場合は従来通り`V`音量/15を返す。
+    #[must_use]
+    pub fn level_at(&self, volume: u8, beat: f64) -> f32 {
+        if self.ramp.is_none() && self.velocity.is_none() {
+            f32::from(volume.min(15)) / 15.0
+        } else {
+            f32::from(self.velocity_at(volume, beat)) / 127.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_volume_to_velocity() {
+        assert_eq!(volume_to_velocity(0), 0);
+        assert_eq!(volume_to_velocity(10), 84);
+        assert_eq!(volume_to_velocity(15), 127);
+        assert_eq!(volume_to_velocity(20), 127);
+    }
+
+    #[test]
+    fn test_ramp_interpolation() {
+        let ramp = VelocityRamp::new(40, 120, 2.0, 4.0);
+        assert_eq!(ramp.velocity_at(0.0), 40);
+        assert_eq!(ramp.velocity_at(2.0), 40);
+        assert_eq!(ramp.velocity_at(4.0), 80);
+        assert_eq!(ramp.velocity_at(6.0), 120);
+        assert_eq!(ramp.velocity_at(100.0), 120);
+    }
+
+    #[test]
+    fn test_ramp_decrescendo() {
+        let ramp = VelocityRamp::new(100, 0, 0.0, 2.0);
+        assert_eq!(ramp.velocity_at(1.0), 50);
+        assert_eq!(ramp.velocity_at(2.0), 0);
+    }
+
+    #[test]
+    fn test_dynamics_default_uses_volume() {
+        let dynamics = Dynamics::default();
+        assert_eq!(dynamics.velocity_at(10, 0.0), 84);
+        assert!((dynamics.level_at(15, 0.0) - 1.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_dynamics_absolute() {
+        let mut dynamics = Dynamics::default();
+        dynamics.apply(VelocityValue::Absolute(100), 10, 0.0);
+        assert_eq!(dynamics.velocity_at(10, 5.0), 100);
+    }
+
+    #[test]
+    fn test_dynamics_ramp_from_current_volume() {
+        let mut dynamics = Dynamics::default();
+        dynamics.apply(
+            VelocityValue::Ramp {
+                from: None,
+                to: 124,
+                beats: 4,
+            },
+            10,
+            8.0,
+        );
+        assert_eq!(dynamics.velocity_at(10, 8.0), 84);
+        assert_eq!(dynamics.velocity_at(10, 10.0), 104);
+        assert_eq!(dynamics.velocity_at(10, 12.0), 124);
+    }
+
+    #[test]
+    fn test_dynamics_clear() {
+        let mut dynamics = Dynamics::default();
+        dynamics.apply(VelocityValue::Absolute(30), 10, 0.0);
+        dynamics.clear();
+        assert_eq!(dynamics.velocity_at(10, 0.0), 84);
+    }
+}
@@ -43,7 +43,38 @@ pub enum ParseError {
         max_depth: usize,
         position: usize,
     },
-    /// ループ展開後のコマンド数が多すぎる（最大10,000）
+    /// MML-E032: n番カッコの回数がループ回数の範囲外
+    ///
+    /// 例: `[CD:1E:3F]2` (2回ループに3番カッコ)
+    VoltaOutOfRange {
+        pass: usize,
+        repeat_count: usize,
+        position: usize,
+    },
+    /// MML-E033: n番カッコの回数が昇順でない、または重複している
+    ///
+    /// 例: `[CD:2E:1F]2`, `[CD:1E:1F]2`
+    InvalidVoltaOrder {
+        pass: usize,
+        position: usize,
+    },
+    /// MML-E034: 脱出ポイント`:`とn番カッコ`:n`が同じループで使われている
+    ///
+    /// 例: `[C:D:1E]2`
+    MixedEscapeAndVolta {
+        position: usize,
+    },
+    /// MML-E035: 演奏時間が上限を超えている
+    ///
+    /// ループ展開後の演奏時間が上限（既定3600秒）を超えている。
+    /// 例: `[[[C]99]99]99`
+    DurationTooLong {
+        max_seconds: u32,
+    },
+    /// MML-E042: ループ展開後のコマンド数が上限を超えている
+    ///
+    /// 演奏時間を持たないコマンドだけのループも上限（1,000,000）で制限する。
+    /// 例: `[[[[[V1]99]99]99]99]99`
     LoopExpandedTooLarge {
         max_commands: usize,
         actual: usize,
@@ -91,6 +122,40 @@ pub enum ParseError {
         max_depth: usize,
         position: usize,
     },
+    /// MML-E030: 無効なスウィングの細分
+    ///
+    /// スウィングの細分は4, 8, 16のいずれか。
+    /// 例: `@S60:32`
+    InvalidSwingSubdivision {
+        value: u16,
+        position: usize,
+    },
+    /// MML-E031: 無効な拍子の分母
+    ///
+    /// 拍子の分母は1, 2, 4, 8, 16, 32のいずれか。
+    /// 例: `@M3/5`
+    InvalidTimeSignature {
+        denominator: u16,
+        position: usize,
+    },
+    /// MML-E036: マーカー名の閉じ引用符がない
+    ///
+    /// 例: `@"chorus CDE`
+    UnterminatedMarker {
+        position: usize,
+    },
+    /// MML-E037: マーカー名が空
+    ///
+    /// 例: `@""`
+    EmptyMarkerName {
+        position: usize,
+    },
+    /// MML-E038: 連符内のマーカー
+    ///
+    /// 例: `{C@"a"DE}3`
+    MarkerInTuplet {
+        position: usize,
+    },
 }
 
 impl ParseError {
@@ -128,6 +193,17 @@ impl ParseError {
                 "位置 {position}: 数値 {value} は範囲 {}-{} を超えています",
                 range.0, range.1
             ),
+            Self::InvalidSwingSubdivision { value, position } => write!(
+                f,
+                "位置 {position}: スウィングの細分 {value} は無効です（4, 8, 16のいずれかを指定してください）"
+            ),
+            Self::InvalidTimeSignature {
+                denominator,
+                position,
+            } => write!(
+                f,
+                "位置 {position}: 拍子の分母 {denominator} は無効です（1, 2, 4, 8, 16, 32のいずれかを指定してください）"
+            ),
             _ => unreachable!(),
         }
     }
@@ -166,6 +242,26 @@ impl ParseError {
                 f,
                 "位置 {position}: ループのネストが深すぎます（最大{max_depth}階層）"
             ),
+            Self::VoltaOutOfRange {
+                pass,
+                repeat_count,
+                position,
+            } => write!(
+                f,
+                "位置 {position}: {pass}番カッコはループ回数（{repeat_count}回）の範囲外です（1-{repeat_count}を指定してください）"
+            ),
+            Self::InvalidVoltaOrder { pass, position } => write!(
+                f,
+                "位置 {position}: {pass}番カッコの順序が不正です（番号は重複なく昇順で指定してください）"
+            ),
+            Self::MixedEscapeAndVolta { position } => write!(
+                f,
+                "位置 {position}: 脱出ポイント ':' とn番カッコ ':n' は同じループで併用できません"
+            ),
+            Self::DurationTooLong { max_seconds } => write!(
+                f,
+                "演奏時間が上限（{max_seconds}秒）を超えています"
+            ),
             Self::LoopExpandedTooLarge {
                 max_commands,
                 actual,
@@ -221,6 +317,22 @@ impl ParseError {
             _ => unreachable!(),
         }
     }
+
+    fn fmt_marker_error(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnterminatedMarker { position } => write!(
+                f,
+                "位置 {position}: マーカー名の閉じ引用符 '\"' がありません"
+            ),
+            Self::EmptyMarkerName { position } => {
+                write!(f, "位置 {position}: マーカー名が空です")
+            }
+            Self::MarkerInTuplet { position } => {
+                write!(f, "位置 {position}: 連符の中にはマーカーを置けません")
+            }
+            _ => unreachable!(),
+        }
+    }
 }
 
 impl std::fmt::Display for ParseError {
@@ -231,7 +343,9 @@ impl std::fmt::Display for ParseError {
             | Self::UnexpectedEof { .. }
             | Self::EmptyInput => self.fmt_token_error(f),
 
-            Self::InvalidNumber { .. } => self.fmt_number_error(f),
+            Self::InvalidNumber { .. }
+            | Self::InvalidSwingSubdivision { .. }
+            | Self::InvalidTimeSignature { .. } => self.fmt_number_error(f),
 
             Self::UnmatchedLoopStart { .. }
             | Self::UnmatchedLoopEnd { .. }
@@ -239,6 +353,10 @@ impl std::fmt::Display for ParseError {
             | Self::LoopEscapeOutsideLoop { .. }
             | Self::MultipleEscapePoints { .. }
             | Self::LoopNestTooDeep { .. }
+            | Self::VoltaOutOfRange { .. }
+            | Self::InvalidVoltaOrder { .. }
+            | Self::MixedEscapeAndVolta { .. }
+            | Self::DurationTooLong { .. }
             | Self::LoopExpandedTooLarge { .. } => self.fmt_loop_error(f),
 
             Self::InvalidTieSequence { .. }
@@ -249,12 +367,48 @@ impl std::fmt::Display for ParseError {
             | Self::TupletCountMissing { .. }
             | Self::InvalidTupletCount { .. }
             | Self::TupletNestTooDeep { .. } => self.fmt_tuplet_error(f),
+
+            Self::UnterminatedMarker { .. }
+            | Self::EmptyMarkerName { .. }
+            | Self::MarkerInTuplet { .. } => self.fmt_marker_error(f),
         }
     }
 }
 
 impl std::error::Error for ParseError {}
 
+/// パースは成功したが注意が必要な箇所
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseWarning {
+    /// MML-W001: 小節線の間の拍数が拍子と一致しない
+    ///
+    /// 小節番号はループ展開後の通し番号。ループ内の小節は最初に一致しなかった回だけ報告する。
+    /// 例: `@M3/4 CDEF|` (3/4拍子の小節に4拍)
+    BarLengthMismatch {
+        /// 小節番号（1始まり）
+        bar: usize,
+        /// 拍子から求めた1小節の拍数（4分音符 = 1拍）
+        expected: f64,
+        /// 実際の拍数（4分音符 = 1拍）
+        actual: f64,
+    },
+}
+
+impl std::fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BarLengthMismatch {
+                bar,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "第{bar}小節の拍数が拍子と一致しません（期待: {expected}拍, 実際: {actual}拍）"
+            ),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -286,6 +440,43 @@ mod tests {
         );
     }
 
+    #[test]
+    fn display_invalid_swing_subdivision() {
+        let err = ParseError::InvalidSwingSubdivision {
+            value: 32,
+            position: 6,
+        };
+        assert_eq!(
+            err.to_string(),
+            "位置 6: スウィングの細分 32 は無効です（4, 8, 16のいずれかを指定してください）"
+        );
+    }
+
+    #[test]
+    fn display_invalid_time_signature() {
+        let err = ParseError::InvalidTimeSignature {
+            denominator: 5,
+            position: 4,
+        };
+        assert_eq!(
+            err.to_string(),
+            "位置 4: 拍子の分母 5 は無効です（1, 2, 4, 8, 16, 32のいずれかを指定してください）"
+        );
+    }
+
+    #[test]
+    fn display_bar_length_mismatch_warning() {
+        let warning = ParseWarning::BarLengthMismatch {
+            bar: 2,
+            expected: 3.0,
+            actual: 3.5,
+        };
+        assert_eq!(
+            warning.to_string(),
+            "第2小節の拍数が拍子と一致しません（期待: 3拍, 実際: 3.5拍）"
+        );
+    }
+
     #[test]
     fn display_unexpected_character() {
         let err = ParseError::UnexpectedCharacter {
@@ -384,4 +575,26 @@ mod tests {
             "位置 5: 連符のネストが深すぎます（最大5階層）"
         );
     }
+
+    #[test]
+    fn display_volta_out_of_range() {
+        let err = ParseError::VoltaOutOfRange {
+            pass: 3,
+            repeat_count: 2,
+            position: 6,
+        };
+        assert_eq!(
+            err.to_string(),
+            "位置 6: 3番カッコはループ回数（2回）の範囲外です（1-2を指定してください）"
+        );
+    }
+
+    #[test]
+    fn display_mixed_escape_and_volta() {
+        let err = ParseError::MixedEscapeAndVolta { position: 4 };
+        assert_eq!(
+            err.to_string(),
+            "位置 4: 脱出ポイント ':' とn番カッコ ':n' は同じループで併用できません"
+        );
+    }
 }
@@ -0,0 +1,334 @@
+//! ループの遅延展開モジュール
+//!
+//! パーサーはループ（`Command::Loop`）を展開せずにASTに保持する。
+//! シンセサイザーやテンポイベント生成などはこのモジュールのイテレータで
+//! コマンドを1つずつ取り出すため、メモリ使用量はMMLの記述量に比例する。
+//!
+//! # ビジネスルール
+//! - 各回で共通部分、続いてその回のn番カッコを演奏する
+//! - 脱出ポイントがある場合、最終回は脱出ポイントまでで終わる
+//! - ネストしたループも同じ規則で再帰的に展開する
+
+use super::{Command, Mml, VoltaEnding};
+
+/// ループを展開したコマンド列を返す
+#[must_use]
+pub fn expand_loop(
+    commands: &[Command],
+    escape_index: Option<usize>,
+    repeat_count: usize,
+) -> Vec<Command> {
+    expand_loop_with_endings(commands, escape_index, &[], repeat_count)
+}
+
+/// n番カッコを含むループを展開したコマンド列を返す
+///
+/// 展開結果をすべてメモリに載せるため、演奏には[`CommandIter`]を使用する。
+#[must_use]
+pub fn expand_loop_with_endings(
+    commands: &[Command],
+    escape_index: Option<usize>,
+    endings: &[VoltaEnding],
+    repeat_count: usize,
+) -> Vec<Command> {
+    let mut iter = CommandIter::new(&[]);
+    iter.push_loop(commands, escape_index, endings, repeat_count);
+    iter.cloned().collect()
+}
+
+/// ループの`pass`回目（0始まり）に演奏するコマンド列を返す
+///
+/// 戻り値は（共通部分, n番カッコ）の組。脱出ポイントがある場合、最終回の
+/// 共通部分は脱出ポイントまでになる。該当するn番カッコがない回は空を返す。
+#[must_use]
+pub fn loop_pass<'a>(
+    commands: &'a [Command],
+    escape_index: Option<usize>,
+    endings: &'a [VoltaEnding],
+    repeat_count: usize,
+    pass: usize,
+) -> (&'a [Command], &'a [Command]) {
+    let is_last_iteration = pass + 1 == repeat_count;
+    let body = match (is_last_iteration, escape_index) {
+        (true, Some(idx)) => &commands[..idx],
+        _ => commands,
+    };
+    let ending = endings
+        .iter()
+        .find(|ending| ending.pass == pass + 1)
+        .map_or(&[][..], |ending| ending.commands.as_slice());
+    (body, ending)
+}
+
+/// ループを展開した後のコマンド数を、展開せずに数える
+///
+/// [`CommandIter`]が返すコマンドの数と一致する（連符は1コマンドとして数える）。
+/// 値が`usize`に収まらない場合は`usize::MAX`を返す。
+#[must_use]
+pub fn expanded_len(commands: &[Command]) -> usize {
+    commands.iter().fold(0, |len, command| {
+        let command_len = match command {
+            Command::Loop {
+                commands,
+                escape_index,
+                endings,
+                repeat_count,
+            } => loop_expanded_len(commands, *escape_index, endings, *repeat_count),
+            _ => 1,
+        };
+        len.saturating_add(command_len)
+    })
+}
+
+/// ループ1つを展開した後のコマンド数
+fn loop_expanded_len(
+    commands: &[Command],
+    escape_index: Option<usize>,
+    endings: &[VoltaEnding],
+    repeat_count: usize,
+) -> usize {
+    if repeat_count == 0 {
+        return 0;
+    }
+    let body = expanded_len(commands);
+    let last_body = escape_index.map_or(body, |idx| expanded_len(&commands[..idx]));
+    let endings = endings
+        .iter()
+        .filter(|ending| ending.pass <= repeat_count)
+        .map(|ending| expanded_len(&ending.commands))
+        .fold(0, usize::saturating_add);
+    body.saturating_mul(repeat_count - 1)
+        .saturating_add(last_body)
+        .saturating_add(endings)
+}
+
+/// 展開中のループ
+struct LoopFrame<'a> {
+    commands: &'a [Command],
+    escape_index: Option<usize>,
+    endings: &'a [VoltaEnding],
+    repeat_count: usize,
+    pass: usize,
+}
+
+/// 走査中のコマンド列（トップレベルまたはループの1回分）
+struct Frame<'a> {
+    /// 共通部分とn番カッコ
+    parts: [&'a [Command]; 2],
+    part: usize,
+    index: usize,
+    /// ループの場合は次の回へ進むための情報
+    looping: Option<LoopFrame<'a>>,
+}
+
+impl<'a> Frame<'a> {
+    fn new(commands: &'a [Command]) -> Self {
+        Self {
+            parts: [commands, &[]],
+            part: 0,
+            index: 0,
+            looping: None,
+        }
+    }
+
+    fn start_pass(&mut self) {
+        if let Some(looping) = &self.looping {
+            let (body, ending) = loop_pass(
+                looping.commands,
+                looping.escape_index,
+                looping.endings,
+                looping.repeat_count,
+                looping.pass,
+            );
+            self.parts = [body, ending];
+            self.part = 0;
+            self.index = 0;
+        }
+    }
+}
+
+/// ループを遅延展開しながらコマンドを返すイテレータ
+///
+/// `Command::Loop`は返さず、その中身を演奏順に返す。連符の中身は展開しない。
+pub struct CommandIter<'a> {
+    stack: Vec<Frame<'a>>,
+}
+
+impl<'a> CommandIter<'a> {
+    /// コマンド列の走査を開始する
+    #[must_use]
+    pub fn new(commands: &'a [Command]) -> Self {
+        Self {
+            stack: vec![Frame::new(commands)],
+        }
+    }
+
+    fn push_loop(
+        &mut self,
+        commands: &'a [Command],
+        escape_index: Option<usize>,
+        endings: &'a [VoltaEnding],
+        repeat_count: usize,
+    ) {
+        if repeat_count == 0 {
+            return;
+        }
+        let mut frame = Frame::new(&[]);
+        frame.looping = Some(LoopFrame {
+            commands,
+            escape_index,
+            endings,
+            repeat_count,
+            pass: 0,
+        });
+        frame.start_pass();
+        self.stack.push(frame);
+    }
+}
+
+impl<'a> Iterator for CommandIter<'a> {
+    type Item = &'a Command;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let frame = self.stack.last_mut()?;
+
+            if let Some(command) = frame.parts[frame.part].get(frame.index) {
+                frame.index += 1;
+                if let Command::Loop {
+                    commands,
+                    escape_index,
+                    endings,
+                    repeat_count,
+                } = command
+                {
+                    self.push_loop(commands, *escape_index, endings, *repeat_count);
+                    continue;
+                }
+                return Some(command);
+            }
+
+            if frame.part + 1 < frame.parts.len() {
+                frame.part += 1;
+                frame.index = 0;
+                continue;
+            }
+
+            match &mut frame.looping {
+                Some(looping) if looping.pass + 1 < looping.repeat_count => {
+                    looping.pass += 1;
+                    frame.start_pass();
+                }
+                _ => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+impl Mml {
+    /// ループを展開した演奏順でコマンドを返すイテレータ
+    #[must_use]
+    pub fn iter(&self) -> CommandIter<'_> {
+        CommandIter::new(&self.commands)
+    }
+}
+
+impl<'a> IntoIterator for &'a Mml {
+    type Item = &'a Command;
+    type IntoIter = CommandIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mml::{parse, parse_with_options, ParseOptions};
+
+    fn pitches<'a>(commands: impl Iterator<Item = &'a Command>) -> String {
+        commands
+            .filter_map(|cmd| match cmd {
+                Command::Note(note) => Some(format!("{:?}", note.pitch)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn iter_expands_nested_loops_in_order() {
+        let mml = parse("C[D[E]2:F]3G").unwrap();
+        assert_eq!(pitches(mml.iter()), "CDEEFDEEFDEEG");
+    }
+
+    #[test]
+    fn iter_expands_volta_endings() {
+        let mml = parse("[C:1D:2E]2").unwrap();
+        assert_eq!(pitches(mml.iter()), "CDCE");
+    }
+
+    #[test]
+    fn iter_expands_volta_endings_after_suffixed_commands() {
+        let mml = parse("[{cde}3 :1 f :2 g]2").unwrap();
+        // 連符は展開しない（1周目: 連符 F、2周目: 連符 G）
+        assert_eq!(pitches(mml.iter()), "FG");
+        assert_eq!(
+            mml.iter()
+                .filter(|cmd| matches!(cmd, Command::Tuplet { .. }))
+                .count(),
+            2
+        );
+        let mml = parse("[c @S60 :1 e :2 f]2").unwrap();
+        assert_eq!(pitches(mml.iter()), "CECF");
+    }
+
+    #[test]
+    fn iter_skips_empty_loops() {
+        let mml = parse("C[]3[[]2]2D").unwrap();
+        assert_eq!(pitches(mml.iter()), "CD");
+    }
+
+    #[test]
+    fn iter_keeps_ast_unexpanded() {
+        let options = ParseOptions {
+            max_duration_secs: None,
+        };
+        let (mml, _) = parse_with_options("[[[C]99]99]99", &options).unwrap();
+        assert_eq!(mml.commands.len(), 1);
+        assert_eq!(mml.iter().take(5).count(), 5);
+    }
+
+    #[test]
+    fn expanded_len_matches_iter() {
+        for input in [
+            "C[D[E]2:F]3G",
+            "[C:1D:2E]2",
+            "[{cde}3 :1 f :2 g]2",
+            "C[]3[[]2]2D",
+            "[[C:D]3 V1 :1 E]2",
+        ] {
+            let mml = parse(input).unwrap();
+            assert_eq!(expanded_len(&mml.commands), mml.iter().count(), "{input}");
+        }
+    }
+
+    #[test]
+    fn expanded_len_saturates() {
+        let inner = Command::Loop {
+            commands: vec![Command::BarLine],
+            escape_index: None,
+            endings: Vec::new(),
+            repeat_count: usize::MAX,
+        };
+        let commands = vec![Command::Loop {
+            commands: vec![inner],
+            escape_index: None,
+            endings: Vec::new(),
+            repeat_count: 2,
+        }];
+        assert_eq!(expanded_len(&commands), usize::MAX);
+    }
+}
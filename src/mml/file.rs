@@ -5,18 +5,30 @@
 //! # ビジネスルール
 //! - BR-067: `.mml`拡張子のみ受け付け
 //! - BR-068: UTF-8エンコーディング必須
-//! - BR-069: 1MB以下のファイルサイズ制限
-//! - BR-070: `#`で始まる行はコメント
+//! - BR-069: 1MB以下のファイルサイズ制限（`#include`を展開した後の内容も1MB以下、
+//!   `#include`の展開は合計1,000回まで）
+//! - BR-070: `#`で始まる行はコメント（`#section`・`#song`ディレクティブを除く）
 //! - BR-071: 空白行は無視
+//! - `#section <名前>`以降の行はその名前のセクションになる
+//! - `#song <名前>...`でセクションの演奏順を指定する（省略時は定義順）
+//! - 最初の`#section`より前の行は前置部として、曲全体・各セクションの先頭に付く
+//! - `#include <パス>`の行は指定したファイルの内容に置き換える（パスは記述したファイルからの
+//!   相対パス。循環するインクルードはエラー）
 
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 
 /// ファイルサイズ上限（1MB）
 const MAX_FILE_SIZE: u64 = 1_000_000;
 
+/// `#include`を展開した後の内容の上限（1MB）
+const MAX_SOURCE_SIZE: usize = 1_000_000;
+
+/// 1つのファイルを読み込む間に展開できる`#include`の回数の上限
+const MAX_INCLUDES: usize = 1_000;
+
 /// MMLファイルを読み込み、コメントと空行を除去してMML文字列を返す
 ///
 /// # 引数
@@ -41,8 +53,126 @@ const MAX_FILE_SIZE: u64 = 1_000_000;
 /// println!("MML: {}", mml);
 /// ```
 pub fn read_mml_file(path: &str) -> Result<String> {
-    let path = Path::new(path);
+    read_mml_file_section(path, None)
+}
+
+/// MMLファイルを読み込み、指定したセクション（`None`の場合は曲全体）のMML文字列を返す
+///
+/// # エラー
+/// [`read_mml_file`]のエラーに加え、以下の場合にエラーを返す
+/// - セクション定義・`#song`行が不正（[`Song::parse`]）
+/// - 指定したセクションが定義されていない
+pub fn read_mml_file_section(path: &str, section: Option<&str>) -> Result<String> {
+    load_mml_source(path)?.section(section)
+}
+
+/// `#include`を展開したMMLファイルの内容
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MmlSource {
+    /// 展開後のファイル内容（コメント・セクションは未処理）
+    pub content: String,
+    /// 読み込んだファイル（指定したファイルと、インクルードしたファイル）
+    pub files: Vec<PathBuf>,
+}
+
+impl MmlSource {
+    /// 指定したセクション（`None`の場合は曲全体）のMML文字列を返す
+    ///
+    /// # エラー
+    /// - セクション定義・`#song`行が不正（[`Song::parse`]）
+    /// - 指定したセクションが定義されていない
+    /// - ファイルにMMLが含まれていない
+    pub fn section(&self, section: Option<&str>) -> Result<String> {
+        let song = Song::parse(&self.content)?;
+        let mml = match section {
+            Some(name) => song.section(name)?,
+            None => song.render(),
+        };
+
+        if mml.is_empty() {
+            let path = self.files.first().map(|path| path.display());
+            match path {
+                Some(path) => anyhow::bail!("ファイルにMMLが含まれていません: {path}"),
+                None => anyhow::bail!("ファイルにMMLが含まれていません"),
+            }
+        }
+
+        Ok(mml)
+    }
+}
+
+/// MMLファイルを読み込み、`#include`を展開する
+///
+/// # エラー
+/// [`read_mml_file`]のファイルに関するエラーに加え、以下の場合にエラーを返す
+/// - `#include`行にパスがない、またはインクルードしたファイルを読み込めない
+/// - インクルードが循環している
+/// - `#include`の展開回数が1,000回、展開後の内容が1MBを超える
+pub fn load_mml_source(path: &str) -> Result<MmlSource> {
+    let mut source = MmlSource::default();
+    let mut stack = Vec::new();
+    let mut includes = 0;
+    load_into(Path::new(path), &mut stack, &mut includes, &mut source)?;
+    Ok(source)
+}
+
+fn load_into(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    includes: &mut usize,
+    source: &mut MmlSource,
+) -> Result<()> {
+    let content = read_checked(path)?;
+    let canonical = fs::canonicalize(path)
+        .with_context(|| format!("ファイルの読み込みに失敗しました: {}", path.display()))?;
+    if stack.contains(&canonical) {
+        anyhow::bail!("インクルードが循環しています: {}", path.display());
+    }
+    if !source.files.contains(&canonical) {
+        source.files.push(canonical.clone());
+    }
+    stack.push(canonical);
+
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    for (index, line) in content.lines().enumerate() {
+        let Some(target) = directive(line.trim(), "#include") else {
+            source.content.push_str(line);
+            source.content.push('\n');
+            // 同じファイルを複数回インクルードすると内容が膨らむため、展開後の合計も制限する (BR-069)
+            if source.content.len() > MAX_SOURCE_SIZE {
+                anyhow::bail!(
+                    "インクルードを展開した内容が大きすぎます（上限: 1MB）: {}",
+                    path.display()
+                );
+            }
+            continue;
+        };
+        let target = target.trim_matches('"');
+        if target.is_empty() {
+            anyhow::bail!(
+                "{}の{}行目: #include にファイルのパスを指定してください",
+                path.display(),
+                index + 1
+            );
+        }
+        *includes += 1;
+        if *includes > MAX_INCLUDES {
+            anyhow::bail!(
+                "{}の{}行目: #include が多すぎます（上限: {MAX_INCLUDES}回）",
+                path.display(),
+                index + 1
+            );
+        }
+        load_into(&dir.join(target), stack, includes, source)
+            .with_context(|| format!("{}の{}行目の #include", path.display(), index + 1))?;
+    }
+
+    stack.pop();
+    Ok(())
+}
 
+/// ファイルの存在・拡張子・サイズを確認して読み込む
+fn read_checked(path: &Path) -> Result<String> {
     // ファイル存在確認
     if !path.exists() {
         anyhow::bail!("ファイルが見つかりません: {}", path.display());
@@ -66,22 +196,158 @@ pub fn read_mml_file(path: &str) -> Result<String> {
     }
 
     // ファイル読み込み (BR-068: UTF-8)
-    let content = fs::read_to_string(path)
-        .with_context(|| format!("ファイルの読み込みに失敗しました: {}", path.display()))?;
+    fs::read_to_string(path)
+        .with_context(|| format!("ファイルの読み込みに失敗しました: {}", path.display()))
+}
 
-    // コメントと空行を除去 (BR-070, BR-071)
-    let mml = content
-        .lines()
-        .map(str::trim)
-        .filter(|line| !line.is_empty() && !line.starts_with('#'))
-        .collect::<Vec<_>>()
-        .join(" ");
+/// セクション定義
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    /// セクション名
+    pub name: String,
+    /// MML文字列（コメント、空行除去済み）
+    pub mml: String,
+}
+
+/// セクションと演奏順（アレンジメント）で構成された曲
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Song {
+    /// 最初の`#section`より前のMML（テンポ・音長などの共通設定）
+    pub preamble: String,
+    /// 定義順のセクション
+    pub sections: Vec<Section>,
+    /// `#song`行で指定された演奏順（`None`の場合は定義順）
+    pub arrangement: Option<Vec<String>>,
+}
+
+impl Song {
+    /// ファイル内容から曲構成を解析する
+    ///
+    /// # エラー
+    /// - セクション名がない、または重複している
+    /// - `#song`行が複数ある、またはセクション名がない
+    /// - `#song`行で未定義のセクションを参照している
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut song = Self::default();
+        let mut preamble = Vec::new();
+        let mut current: Option<(String, Vec<&str>)> = None;
+
+        for (index, line) in content.lines().map(str::trim).enumerate() {
+            let line_number = index + 1;
+
+            if let Some(name) = directive(line, "#section") {
+                if name.is_empty() || name.contains(char::is_whitespace) {
+                    anyhow::bail!("{line_number}行目: セクション名を1つ指定してください");
+                }
+                if let Some((prev, lines)) = current.take() {
+                    song.push_section(prev, &lines);
+                }
+                if song.sections.iter().any(|s| s.name == name) {
+                    anyhow::bail!("{line_number}行目: セクション '{name}' が重複しています");
+                }
+                current = Some((name.to_string(), Vec::new()));
+                continue;
+            }
+
+            if let Some(names) = directive(line, "#song") {
+                if song.arrangement.is_some() {
+                    anyhow::bail!("{line_number}行目: #song 行は1つだけ指定できます");
+                }
+                let names: Vec<String> = names.split_whitespace().map(String::from).collect();
+                if names.is_empty() {
+                    anyhow::bail!("{line_number}行目: #song 行にセクション名がありません");
+                }
+                song.arrangement = Some(names);
+                continue;
+            }
+
+            // コメントと空行を除去 (BR-070, BR-071)
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match &mut current {
+                Some((_, lines)) => lines.push(line),
+                None => preamble.push(line),
+            }
+        }
+
+        if let Some((name, lines)) = current.take() {
+            song.push_section(name, &lines);
+        }
+        song.preamble = preamble.join(" ");
+
+        if let Some(arrangement) = &song.arrangement {
+            if let Some(name) = arrangement
+                .iter()
+                .find(|name| !song.sections.iter().any(|s| &s.name == *name))
+            {
+                anyhow::bail!("#song 行のセクション '{name}' が定義されていません");
+            }
+        }
+
+        Ok(song)
+    }
 
-    if mml.is_empty() {
-        anyhow::bail!("ファイルにMMLが含まれていません: {}", path.display());
+    fn push_section(&mut self, name: String, lines: &[&str]) {
+        self.sections.push(Section {
+            name,
+            mml: lines.join(" "),
+        });
     }
 
-    Ok(mml)
+    /// 曲全体のMML文字列（前置部 + 演奏順に並べたセクション）
+    #[must_use]
+    pub fn render(&self) -> String {
+        let sections: Vec<&str> = match &self.arrangement {
+            Some(arrangement) => arrangement
+                .iter()
+                .filter_map(|name| self.find(name))
+                .map(|section| section.mml.as_str())
+                .collect(),
+            None => self.sections.iter().map(|s| s.mml.as_str()).collect(),
+        };
+        join_mml(&self.preamble, &sections)
+    }
+
+    /// 指定したセクションのMML文字列（前置部 + セクション）
+    ///
+    /// # エラー
+    /// セクションが定義されていない場合にエラーを返す
+    pub fn section(&self, name: &str) -> Result<String> {
+        let Some(section) = self.find(name) else {
+            let names: Vec<&str> = self.sections.iter().map(|s| s.name.as_str()).collect();
+            if names.is_empty() {
+                anyhow::bail!("セクション '{name}' が定義されていません（セクションなし）");
+            }
+            anyhow::bail!(
+                "セクション '{name}' が定義されていません（定義済み: {}）",
+                names.join(", ")
+            );
+        };
+        Ok(join_mml(&self.preamble, &[&section.mml]))
+    }
+
+    fn find(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+}
+
+/// `#section` / `#song` / `#include` ディレクティブであれば引数部分を返す
+fn directive<'a>(line: &'a str, keyword: &str) -> Option<&'a str> {
+    let rest = line.strip_prefix(keyword)?;
+    if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+        Some(rest.trim())
+    } else {
+        None
+    }
+}
+
+fn join_mml(preamble: &str, parts: &[&str]) -> String {
+    std::iter::once(preamble)
+        .chain(parts.iter().copied())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 #[cfg(test)]
@@ -339,4 +605,223 @@ mod tests {
         let result = read_mml_file(file_path.to_str().unwrap());
         assert_eq!(result.unwrap(), "T120 L8 O5 CDEF GAB >C");
     }
+
+    // === セクション ===
+
+    const SONG: &str = "T120 L8\n#section intro\nCDEF\n\n#section verse\n# 主旋律\nGAB\n>C\n#song intro verse intro\n";
+
+    #[test]
+    fn test_song_parse_sections() {
+        let song = Song::parse(SONG).unwrap();
+        assert_eq!(song.preamble, "T120 L8");
+        assert_eq!(
+            song.sections,
+            vec![
+                Section {
+                    name: "intro".to_string(),
+                    mml: "CDEF".to_string(),
+                },
+                Section {
+                    name: "verse".to_string(),
+                    mml: "GAB >C".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            song.arrangement,
+            Some(vec![
+                "intro".to_string(),
+                "verse".to_string(),
+                "intro".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_song_render_follows_arrangement() {
+        let song = Song::parse(SONG).unwrap();
+        assert_eq!(song.render(), "T120 L8 CDEF GAB >C CDEF");
+    }
+
+    #[test]
+    fn test_song_render_without_arrangement_uses_definition_order() {
+        let song = Song::parse("#section a\nC\n#section b\nD\n").unwrap();
+        assert_eq!(song.render(), "C D");
+    }
+
+    #[test]
+    fn test_song_section_includes_preamble() {
+        let song = Song::parse(SONG).unwrap();
+        assert_eq!(song.section("verse").unwrap(), "T120 L8 GAB >C");
+    }
+
+    #[test]
+    fn test_song_section_unknown() {
+        let song = Song::parse(SONG).unwrap();
+        let err = song.section("chorus").unwrap_err().to_string();
+        assert!(err.contains("セクション 'chorus' が定義されていません"));
+        assert!(err.contains("定義済み: intro, verse"));
+
+        let err = Song::parse("CDE").unwrap().section("a").unwrap_err();
+        assert!(err.to_string().contains("セクションなし"));
+    }
+
+    #[test]
+    fn test_song_parse_errors() {
+        let cases = [
+            ("#section\nC", "1行目: セクション名を1つ指定してください"),
+            (
+                "#section a b\nC",
+                "1行目: セクション名を1つ指定してください",
+            ),
+            (
+                "#section a\nC\n#section a\nD",
+                "3行目: セクション 'a' が重複しています",
+            ),
+            (
+                "#section a\n#song a\n#song a",
+                "3行目: #song 行は1つだけ指定できます",
+            ),
+            (
+                "#section a\n#song",
+                "2行目: #song 行にセクション名がありません",
+            ),
+            (
+                "#section a\n#song a b",
+                "#song 行のセクション 'b' が定義されていません",
+            ),
+        ];
+        for (content, expected) in cases {
+            let err = Song::parse(content).unwrap_err().to_string();
+            assert!(err.contains(expected), "{content:?}: {err}");
+        }
+    }
+
+    #[test]
+    fn test_song_directive_prefix_is_comment() {
+        let song = Song::parse("#songwriter: someone\n#sections\nCDE").unwrap();
+        assert!(song.sections.is_empty());
+        assert_eq!(song.arrangement, None);
+        assert_eq!(song.render(), "CDE");
+    }
+
+    #[test]
+    fn test_read_mml_file_section() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("song.mml");
+        std::fs::write(&file_path, SONG).unwrap();
+        let path = file_path.to_str().unwrap();
+
+        assert_eq!(read_mml_file(path).unwrap(), "T120 L8 CDEF GAB >C CDEF");
+        assert_eq!(
+            read_mml_file_section(path, Some("intro")).unwrap(),
+            "T120 L8 CDEF"
+        );
+        assert!(read_mml_file_section(path, Some("outro")).is_err());
+    }
+
+    #[test]
+    fn test_load_mml_source_expands_includes() {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("parts")).unwrap();
+        std::fs::write(dir.path().join("parts/verse.mml"), "#section verse\nGAB\n").unwrap();
+        std::fs::write(
+            dir.path().join("song.mml"),
+            "T120\n#include parts/verse.mml\n#section outro\nC\n",
+        )
+        .unwrap();
+        let path = dir.path().join("song.mml");
+        let path = path.to_str().unwrap();
+
+        let source = load_mml_source(path).unwrap();
+        assert_eq!(source.files.len(), 2);
+        assert!(source.files[1].ends_with("parts/verse.mml"));
+        assert_eq!(read_mml_file(path).unwrap(), "T120 GAB C");
+        assert_eq!(
+            read_mml_file_section(path, Some("verse")).unwrap(),
+            "T120 GAB"
+        );
+    }
+
+    #[test]
+    fn test_load_mml_source_include_errors() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("a.mml"), "C\n#include b.mml\n").unwrap();
+        std::fs::write(dir.path().join("b.mml"), "#include \"a.mml\"\n").unwrap();
+        std::fs::write(dir.path().join("missing.mml"), "#include none.mml\n").unwrap();
+        std::fs::write(dir.path().join("empty.mml"), "#include\n").unwrap();
+
+        let cases = [
+            ("a.mml", "インクルードが循環しています"),
+            ("missing.mml", "ファイルが見つかりません"),
+            (
+                "empty.mml",
+                "1行目: #include にファイルのパスを指定してください",
+            ),
+        ];
+        for (file, expected) in cases {
+            let path = dir.path().join(file);
+            let err = load_mml_source(path.to_str().unwrap()).unwrap_err();
+            assert!(format!("{err:#}").contains(expected), "{file}: {err:#}");
+        }
+    }
+
+    #[test]
+    fn test_load_mml_source_limits_expanded_size() {
+        // 各ファイルは小さいが、2つずつインクルードすると展開後は2^n倍になる
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("l0.mml"), "CDEFGAB\n").unwrap();
+        std::fs::write(
+            dir.path().join("big.mml"),
+            format!("{}\n", "C".repeat(600_000)),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("twice.mml"),
+            "#include big.mml\n#include big.mml\n",
+        )
+        .unwrap();
+        for level in 1..=20 {
+            let prev = level - 1;
+            std::fs::write(
+                dir.path().join(format!("l{level}.mml")),
+                format!("#include l{prev}.mml\n#include l{prev}.mml\n"),
+            )
+            .unwrap();
+        }
+
+        let cases = [
+            ("l20.mml", "#include が多すぎます（上限: 1000回）"),
+            ("twice.mml", "インクルードを展開した内容が大きすぎます"),
+        ];
+        for (file, expected) in cases {
+            let path = dir.path().join(file);
+            let err = load_mml_source(path.to_str().unwrap()).unwrap_err();
+            assert!(format!("{err:#}").contains(expected), "{file}: {err:#}");
+        }
+
+        // l8は510回の展開で収まる
+        let path = dir.path().join("l8.mml");
+        let source = load_mml_source(path.to_str().unwrap()).unwrap();
+        assert_eq!(source.content.len(), 8 * 256);
+        assert_eq!(source.files.len(), 9);
+    }
+
+    #[test]
+    fn test_mml_source_section() {
+        let source = MmlSource {
+            content: "T120\n#section verse\nGAB\n#section empty\n".to_string(),
+            files: Vec::new(),
+        };
+        assert_eq!(source.section(None).unwrap(), "T120 GAB");
+        assert_eq!(source.section(Some("verse")).unwrap(), "T120 GAB");
+        assert!(source.section(Some("outro")).is_err());
+
+        let source = MmlSource::default();
+        assert!(source
+            .section(None)
+            .unwrap_err()
+            .to_string()
+            .contains("ファイルにMMLが含まれていません"));
+    }
 }
@@ -1,13 +1,22 @@
 mod ast;
+pub mod dynamics;
 pub mod error;
+pub mod expand;
 pub mod file;
+pub mod position;
 
 pub use ast::*;
-pub use error::ParseError;
-pub use file::read_mml_file;
+pub use dynamics::{volume_to_velocity, Dynamics, VelocityRamp};
+pub use error::{ParseError, ParseWarning};
+pub use expand::CommandIter;
+pub use file::{load_mml_source, read_mml_file, read_mml_file_section, MmlSource, Section, Song};
+pub use position::{Location, PlayPosition, PlayRange, RangeError};
 
 pub mod parser;
+pub mod timing;
+pub mod transcribe;
 pub use parser::*;
+pub use timing::{swing_position, TempoCurve, TempoState};
 
 use std::iter::Peekable;
 use std::str::Chars;
@@ -38,6 +47,24 @@ pub enum Token {
     TupletStart,
     /// Tuplet end brace `}`
     TupletEnd,
+    /// Gate time command `Q`
+    Gate,
+    /// Percent sign `%` (used for percentage gate time `Q%n`)
+    Percent,
+    /// Extended command prefix `@` (e.g. `@v` fine velocity)
+    At,
+    /// Swing command `S` (used as `@S`)
+    Swing,
+    /// Time signature command `M` (used as `@M`)
+    Meter,
+    /// MIDI program change command `P` (used as `@P` / `@PB`)
+    Program,
+    /// Slash `/` (used in time signature `@M3/4`)
+    Slash,
+    /// Bar line `|`
+    BarLine,
+    /// Quoted marker name `"name"` (used as `@"name"`)
+    MarkerName(String),
     Eof,
 }
 
@@ -45,12 +72,19 @@ pub enum Token {
 pub struct TokenWithPos {
     pub token: Token,
     pub position: usize,
+    /// トークンの直後の位置（空白を挟まずに続くトークンの位置と一致する）
+    pub end: usize,
 }
 
 impl TokenWithPos {
+    /// 1文字のトークンを作成する（`end`は`tokenize`が実際の長さに合わせて設定する）
     #[must_use]
     pub const fn new(token: Token, position: usize) -> Self {
-        Self { token, position }
+        Self {
+            token,
+            position,
+            end: position + 1,
+        }
     }
 }
 
@@ -121,6 +155,36 @@ pub fn tokenize(input: &str) -> Result<Vec<TokenWithPos>, ParseError> {
                 position += 1;
                 tok
             }
+            'S' => {
+                chars.next();
+                let tok = TokenWithPos::new(Token::Swing, position);
+                position += 1;
+                tok
+            }
+            'M' => {
+                chars.next();
+                let tok = TokenWithPos::new(Token::Meter, position);
+                position += 1;
+                tok
+            }
+            'P' => {
+                chars.next();
+                let tok = TokenWithPos::new(Token::Program, position);
+                position += 1;
+                tok
+            }
+            '/' => {
+                chars.next();
+                let tok = TokenWithPos::new(Token::Slash, position);
+                position += 1;
+                tok
+            }
+            '|' => {
+                chars.next();
+                let tok = TokenWithPos::new(Token::BarLine, position);
+                position += 1;
+                tok
+            }
             'R' => {
                 chars.next();
                 let tok = TokenWithPos::new(Token::Rest, position);
@@ -139,6 +203,24 @@ pub fn tokenize(input: &str) -> Result<Vec<TokenWithPos>, ParseError> {
                 position += 1;
                 tok
             }
+            'Q' => {
+                chars.next();
+                let tok = TokenWithPos::new(Token::Gate, position);
+                position += 1;
+                tok
+            }
+            '%' => {
+                chars.next();
+                let tok = TokenWithPos::new(Token::Percent, position);
+                position += 1;
+                tok
+            }
+            '@' => {
+                chars.next();
+                let tok = TokenWithPos::new(Token::At, position);
+                position += 1;
+                tok
+            }
             '[' => {
                 chars.next();
                 let tok = TokenWithPos::new(Token::LoopStart, position);
@@ -163,6 +245,12 @@ pub fn tokenize(input: &str) -> Result<Vec<TokenWithPos>, ParseError> {
                 position += 1;
                 tok
             }
+            '"' => {
+                let start_pos = position;
+                let (name, consumed) = parse_quoted(&mut chars, start_pos)?;
+                position += consumed;
+                TokenWithPos::new(Token::MarkerName(name), start_pos)
+            }
             '{' => {
                 chars.next();
                 let tok = TokenWithPos::new(Token::TupletStart, position);
@@ -188,13 +276,36 @@ pub fn tokenize(input: &str) -> Result<Vec<TokenWithPos>, ParseError> {
                 });
             }
         };
-        tokens.push(token);
+        tokens.push(TokenWithPos {
+            end: position,
+            ..token
+        });
     }
 
     tokens.push(TokenWithPos::new(Token::Eof, position));
     Ok(tokens)
 }
 
+/// `"`で囲まれた文字列を読み取り、（中身, 消費した文字数）を返す
+fn parse_quoted(
+    chars: &mut Peekable<Chars>,
+    position: usize,
+) -> Result<(String, usize), ParseError> {
+    chars.next(); // Consume opening '"'
+    let mut text = String::new();
+    let mut consumed = 1;
+
+    for c in chars.by_ref() {
+        consumed += 1;
+        if c == '"' {
+            return Ok((text, consumed));
+        }
+        text.push(c);
+    }
+
+    Err(ParseError::UnterminatedMarker { position })
+}
+
 fn parse_number(chars: &mut Peekable<Chars>) -> Result<(u16, usize), ParseError> {
     let mut num_str = String::new();
     let mut consumed = 0;
@@ -1,51 +1,58 @@
+use std::collections::HashSet;
+
 use super::{
-    Accidental, Command, DefaultLength, Duration, Mml, Note, Octave, ParseError, Rest, Tempo,
-    TiedDuration, Token, TokenWithPos, Volume, VolumeValue,
+    Accidental, Channel, Command, CommandIter, DefaultLength, Duration, Gate, GateValue,
+    MidiControl, Mml, Note, Octave, ParseError, ParseWarning, Pitch, Rest, Swing, Tempo, TempoRamp,
+    TempoState, TiedDuration, TimeSignature, Token, TokenWithPos, Velocity, VelocityValue,
+    VoltaEnding, Volume, VolumeValue,
 };
 
-const MAX_EXPANDED_COMMANDS: usize = 10_000;
+pub use super::expand::{expand_loop, expand_loop_with_endings, expanded_len, loop_pass};
 
-/// # Errors
-/// Returns `ParseError::LoopExpandedTooLarge` if expanded commands exceed 10,000
-pub fn expand_loop(
-    commands: &[Command],
-    escape_index: Option<usize>,
-    repeat_count: usize,
-) -> Result<Vec<Command>, ParseError> {
-    let mut expanded = Vec::with_capacity(commands.len() * repeat_count);
-
-    for i in 0..repeat_count {
-        let is_last_iteration = i == repeat_count - 1;
-
-        let end_index = if let (true, Some(idx)) = (is_last_iteration, escape_index) {
-            idx
-        } else {
-            commands.len()
-        };
+/// 演奏時間の上限の既定値（秒）
+pub const DEFAULT_MAX_DURATION_SECS: u32 = 3600;
 
-        for cmd in &commands[..end_index] {
-            if let Command::Loop {
-                commands: inner_cmds,
-                escape_index: inner_escape,
-                repeat_count: inner_count,
-            } = cmd
-            {
-                let inner_expanded = expand_loop(inner_cmds, *inner_escape, *inner_count)?;
-                expanded.extend(inner_expanded);
-            } else {
-                expanded.push(cmd.clone());
-            }
+/// ループ展開後のコマンド数の上限
+///
+/// 演奏時間の上限とは別に常に適用する。
+pub const MAX_EXPANDED_COMMANDS: usize = 1_000_000;
+
+/// パースの設定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// 演奏時間の上限（秒）。`None`の場合は制限しない
+    pub max_duration_secs: Option<u32>,
+}
 
-            if expanded.len() > MAX_EXPANDED_COMMANDS {
-                return Err(ParseError::LoopExpandedTooLarge {
-                    max_commands: MAX_EXPANDED_COMMANDS,
-                    actual: expanded.len(),
-                });
-            }
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            max_duration_secs: Some(DEFAULT_MAX_DURATION_SECS),
         }
     }
+}
 
-    Ok(expanded)
+/// n番カッコ`:n`を検証し、新しいn番カッコを開始する
+fn start_volta_ending(
+    endings: &mut Vec<(VoltaEnding, usize)>,
+    pass: usize,
+    has_escape: bool,
+    position: usize,
+) -> Result<(), ParseError> {
+    if has_escape {
+        return Err(ParseError::MixedEscapeAndVolta { position });
+    }
+    if endings.last().is_some_and(|(last, _)| pass <= last.pass) {
+        return Err(ParseError::InvalidVoltaOrder { pass, position });
+    }
+    endings.push((
+        VoltaEnding {
+            pass,
+            commands: Vec::new(),
+        },
+        position,
+    ));
+    Ok(())
 }
 
 /// 最大ループネスト深度
@@ -59,19 +66,35 @@ pub struct Parser {
     current: usize,
     loop_depth: usize,
     tuplet_depth: usize,
+    warnings: Vec<ParseWarning>,
+    options: ParseOptions,
 }
 
 impl Parser {
     #[must_use]
     pub fn new(tokens: Vec<TokenWithPos>) -> Self {
+        Self::with_options(tokens, ParseOptions::default())
+    }
+
+    /// 設定を指定してパーサーを作成
+    #[must_use]
+    pub fn with_options(tokens: Vec<TokenWithPos>, options: ParseOptions) -> Self {
         Self {
             tokens,
             current: 0,
             loop_depth: 0,
             tuplet_depth: 0,
+            warnings: Vec::new(),
+            options,
         }
     }
 
+    /// 直前の`parse`で検出された警告
+    #[must_use]
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
     /// Parses the tokens into an MML AST.
     ///
     /// # Errors
@@ -84,20 +107,20 @@ impl Parser {
         let mut commands = Vec::new();
 
         while !self.is_at_end() {
-            let command = self.parse_command()?;
+            // ループは展開せずに保持する（演奏時に`Mml::iter`で遅延展開）
+            commands.push(self.parse_command()?);
+        }
 
-            if let Command::Loop {
-                commands: loop_commands,
-                escape_index,
-                repeat_count,
-            } = command
-            {
-                let expanded = expand_loop(&loop_commands, escape_index, repeat_count)?;
-                commands.extend(expanded);
-            } else {
-                commands.push(command);
-            }
+        check_expanded_len(&commands)?;
+        if let Some(max_seconds) = self.options.max_duration_secs {
+            check_duration(&commands, max_seconds)?;
         }
+        // 小節線がなければ展開後の走査を省略する
+        self.warnings = if contains_bar_line(&commands) {
+            check_bar_lengths(&commands)
+        } else {
+            Vec::new()
+        };
 
         Ok(Mml { commands })
     }
@@ -116,9 +139,15 @@ impl Parser {
                 self.advance();
                 Ok(Command::OctaveDown)
             }
-            Token::Tempo => Ok(Command::Tempo(self.parse_tempo()?)),
+            Token::Tempo => self.parse_tempo(),
             Token::Length => Ok(Command::DefaultLength(self.parse_length()?)),
             Token::Volume => Ok(Command::Volume(self.parse_volume()?)),
+            Token::Gate => Ok(Command::Gate(self.parse_gate()?)),
+            Token::At => self.parse_extended_command(),
+            Token::BarLine => {
+                self.advance();
+                Ok(Command::BarLine)
+            }
             Token::LoopStart => self.parse_loop(),
             Token::LoopEnd => Err(ParseError::UnmatchedLoopEnd {
                 position: token_with_pos.position,
@@ -161,6 +190,8 @@ impl Parser {
         let mut commands = Vec::new();
         let mut escape_index = None;
         let mut escape_count = 0;
+        // n番カッコ（回, 位置）と、そのコマンド列
+        let mut endings: Vec<(VoltaEnding, usize)> = Vec::new();
 
         while !self.check_loop_end() {
             if self.is_at_end() {
@@ -171,21 +202,44 @@ impl Parser {
             }
 
             if self.check_loop_escape() {
+                let escape_pos = self.peek().position;
+                self.advance();
+
+                // `:n` はn番カッコ、`:` 単独は脱出ポイント
+                if let Some(pass) = self.check_volta_number() {
+                    if let Err(err) =
+                        start_volta_ending(&mut endings, pass, escape_index.is_some(), escape_pos)
+                    {
+                        self.loop_depth -= 1;
+                        return Err(err);
+                    }
+                    self.advance();
+                    continue;
+                }
+
+                if !endings.is_empty() {
+                    self.loop_depth -= 1;
+                    return Err(ParseError::MixedEscapeAndVolta {
+                        position: escape_pos,
+                    });
+                }
                 escape_count += 1;
                 if escape_count > 1 {
                     self.loop_depth -= 1; // エラー時も深度を戻す
                     return Err(ParseError::MultipleEscapePoints {
-                        position: self.peek().position,
+                        position: escape_pos,
                     });
                 }
-                self.advance();
                 escape_index = Some(commands.len());
                 continue;
             }
 
             // ネストしたループを許可（parse_command経由で再帰的にparse_loopが呼ばれる）
             let command = self.parse_command()?;
-            commands.push(command);
+            match endings.last_mut() {
+                Some((ending, _)) => ending.commands.push(command),
+                None => commands.push(command),
+            }
         }
 
         self.advance();
@@ -209,9 +263,21 @@ impl Parser {
             1
         };
 
+        if let Some((ending, position)) = endings
+            .iter()
+            .find(|(ending, _)| ending.pass == 0 || ending.pass > repeat_count)
+        {
+            return Err(ParseError::VoltaOutOfRange {
+                pass: ending.pass,
+                repeat_count,
+                position: *position,
+            });
+        }
+
         Ok(Command::Loop {
             commands,
             escape_index,
+            endings: endings.into_iter().map(|(ending, _)| ending).collect(),
             repeat_count,
         })
     }
@@ -221,6 +287,8 @@ impl Parser {
     /// # 構文
     /// `{<コマンド>...}n[:base_duration]`
     ///
+    /// ループ内では`:base_duration`を空白を挟まずに書く（空白を挟んだ`:`はn番カッコ・脱出ポイント）。
+    ///
     /// # Returns
     /// * `Ok(Command::Tuplet)` - 連符コマンド
     /// * `Err(ParseError)` - エラー
@@ -294,7 +362,7 @@ impl Parser {
         };
 
         // ベース音長の指定（オプション）
-        let base_duration = if self.check_colon() {
+        let base_duration = if self.check_suffix_colon() {
             self.advance(); // Consume ':'
             if !self.check_number() {
                 return Err(ParseError::UnexpectedToken {
@@ -321,11 +389,34 @@ impl Parser {
         matches!(self.peek().token, Token::TupletEnd)
     }
 
+    /// 次のトークンがn番カッコの回数か確認し、その値を返す
+    ///
+    /// 0は範囲外として`VoltaOutOfRange`で検出するため、ここでは数値であれば返す。
+    fn check_volta_number(&self) -> Option<usize> {
+        match self.peek().token {
+            Token::Number(n) => Some(usize::from(n)),
+            _ => None,
+        }
+    }
+
     /// 次のトークンがコロン（LoopEscape）かチェック
     fn check_colon(&self) -> bool {
         matches!(self.peek().token, Token::LoopEscape)
     }
 
+    /// 次のトークンが直前のトークンに空白を挟まずに続いているかチェック
+    fn follows_previous(&self) -> bool {
+        self.current > 0 && self.previous().end == self.peek().position
+    }
+
+    /// 次のトークンが数値の後に続ける省略可能な`:`（連符のベース音長、スウィングの細分）かチェック
+    ///
+    /// ループ内で空白を挟んだ`:`はn番カッコ・脱出ポイントとして扱う
+    /// （例: `[{cde}3 :1 f :2 g]2`）。
+    fn check_suffix_colon(&self) -> bool {
+        self.check_colon() && (self.loop_depth == 0 || self.follows_previous())
+    }
+
     fn check_loop_end(&self) -> bool {
         matches!(self.peek().token, Token::LoopEnd)
     }
@@ -465,10 +556,35 @@ impl Parser {
         Ok(Octave { value })
     }
 
-    fn parse_tempo(&mut self) -> Result<Tempo, ParseError> {
+    /// テンポコマンドを解析（30-300、アッチェレランド/リタルダンド対応）
+    ///
+    /// # 構文
+    /// - `T<30-300>` - テンポ指定
+    /// - `T<30-300>><30-300>:<1-128>` - 開始テンポから終了テンポへ指定拍数かけて変化
+    /// - `T><30-300>:<1-128>` - 現在のテンポから終了テンポへ指定拍数かけて変化
+    ///
+    /// # エラー
+    /// - `InvalidNumber` - 値が範囲外
+    /// - `UnexpectedToken` - 変化指定で`:`や拍数がない
+    fn parse_tempo(&mut self) -> Result<Command, ParseError> {
         self.advance(); // Consume Tempo
-        let value = self.consume_number_in_range(30, 300)?;
-        Ok(Tempo { value })
+
+        let from = if self.check_ramp_arrow() {
+            None
+        } else {
+            Some(self.consume_number_in_range(30, 300)?)
+        };
+
+        if !self.check_ramp_arrow() {
+            let value = from.expect("tempo value parsed above");
+            return Ok(Command::Tempo(Tempo { value }));
+        }
+
+        self.advance(); // Consume '>'
+        let to = self.consume_number_in_range(30, 300)?;
+        #[allow(clippy::cast_possible_truncation)]
+        let beats = self.consume_ramp_beats()? as u8;
+        Ok(Command::TempoRamp(TempoRamp { from, to, beats }))
     }
 
     fn parse_length(&mut self) -> Result<DefaultLength, ParseError> {
@@ -534,6 +650,317 @@ impl Parser {
         Ok(Volume { value })
     }
 
+    /// ゲートタイムコマンドを解析
+    ///
+    /// # 構文
+    /// - `Q<1-8>` - 8分率指定（`Q8`で音長いっぱいに発音）
+    /// - `Q%<1-100>` - 百分率指定
+    ///
+    /// # エラー
+    /// - `InvalidNumber` - 値が範囲外
+    fn parse_gate(&mut self) -> Result<Gate, ParseError> {
+        self.advance(); // Consume 'Q'
+
+        let value = if matches!(self.peek().token, Token::Percent) {
+            self.advance(); // Consume '%'
+            let val = self.consume_number_in_range(1, 100)?;
+            #[allow(clippy::cast_possible_truncation)]
+            GateValue::Percent(val as u8)
+        } else {
+            let val = self.consume_number_in_range(1, 8)?;
+            #[allow(clippy::cast_possible_truncation)]
+            GateValue::Eighths(val as u8)
+        };
+
+        Ok(Gate { value })
+    }
+
+    /// `@`で始まる拡張コマンドを解析
+    ///
+    /// # 構文
+    /// - `@v...` - ベロシティ指定（`parse_velocity`参照）
+    /// - `@P...` / `@CC...` / `@A...` / `@B...` - MIDIコントロール（`parse_midi_control`参照）
+    fn parse_extended_command(&mut self) -> Result<Command, ParseError> {
+        self.advance(); // Consume '@'
+
+        let token_with_pos = self.peek();
+        match token_with_pos.token {
+            Token::Volume => Ok(Command::Velocity(self.parse_velocity()?)),
+            Token::Swing => Ok(Command::Swing(self.parse_swing()?)),
+            Token::Meter => Ok(Command::TimeSignature(self.parse_time_signature()?)),
+            Token::MarkerName(_) => self.parse_marker(),
+            Token::Pitch(Pitch::C) if matches!(self.peek_next().token, Token::Pitch(Pitch::C)) => {
+                Ok(Command::MidiControl(self.parse_midi_control()?))
+            }
+            Token::Pitch(Pitch::C) => Ok(Command::Channel(self.parse_channel()?)),
+            Token::Program | Token::Pitch(Pitch::A | Pitch::B) => {
+                Ok(Command::MidiControl(self.parse_midi_control()?))
+            }
+            _ => Err(ParseError::UnexpectedToken {
+                expected: "V, S, M, C, P, A, B or \"name\"".to_string(),
+                found: token_with_pos.token.clone(),
+                position: token_with_pos.position,
+            }),
+        }
+    }
+
+    /// ベロシティコマンドを解析（0-127、クレッシェンド/デクレッシェンド対応）
+    ///
+    /// # 構文
+    /// - `@v<0-127>` - 絶対値指定
+    /// - `@v<0-127>><0-127>:<1-128>` - 開始値から終了値へ指定拍数かけて変化
+    /// - `@v><0-127>:<1-128>` - 現在の音量から終了値へ指定拍数かけて変化
+    ///
+    /// # エラー
+    /// - `InvalidNumber` - 値が範囲外
+    /// - `UnexpectedToken` - 変化指定で`:`や拍数がない
+    ///
+    /// # 注意
+    /// - `>`は既存の`Token::OctaveUp`を流用（直後が数値の場合のみ変化指定とみなす）
+    /// - `:`は既存の`Token::LoopEscape`を流用
+    fn parse_velocity(&mut self) -> Result<Velocity, ParseError> {
+        self.advance(); // Consume 'V'
+
+        let from = if self.check_ramp_arrow() {
+            None
+        } else {
+            #[allow(clippy::cast_possible_truncation)]
+            Some(self.consume_number_in_range(0, 127)? as u8)
+        };
+
+        let value = if self.check_ramp_arrow() {
+            self.advance(); // Consume '>'
+            #[allow(clippy::cast_possible_truncation)]
+            let to = self.consume_number_in_range(0, 127)? as u8;
+            #[allow(clippy::cast_possible_truncation)]
+            let beats = self.consume_ramp_beats()? as u8;
+            VelocityValue::Ramp { from, to, beats }
+        } else {
+            VelocityValue::Absolute(from.expect("absolute value parsed above"))
+        };
+
+        Ok(Velocity { value })
+    }
+
+    /// スウィングコマンドを解析
+    ///
+    /// # 構文
+    /// - `@S<50-75>` - 8分音符のスウィング（50でスウィングなし）
+    /// - `@S<50-75>:<4|8|16>` - 細分を指定
+    ///
+    /// # エラー
+    /// - `InvalidNumber` - 割合が範囲外
+    /// - `InvalidSwingSubdivision` - 細分が4, 8, 16以外
+    ///
+    /// # 注意
+    /// - `:`の直後が数値でない場合はループの脱出ポイントとみなす
+    /// - ループ内で空白を挟んだ`:`はn番カッコ・脱出ポイントとみなす
+    fn parse_swing(&mut self) -> Result<Swing, ParseError> {
+        self.advance(); // Consume 'S'
+
+        #[allow(clippy::cast_possible_truncation)]
+        let ratio = self.consume_number_in_range(50, 75)? as u8;
+
+        let subdivision =
+            if self.check_suffix_colon() && matches!(self.peek_next().token, Token::Number(_)) {
+                self.advance(); // Consume ':'
+                let position = self.peek().position;
+                match self.consume_number()? {
+                    #[allow(clippy::cast_possible_truncation)]
+                    value @ (4 | 8 | 16) => value as u8,
+                    value => return Err(ParseError::InvalidSwingSubdivision { value, position }),
+                }
+            } else {
+                8
+            };
+
+        Ok(Swing { ratio, subdivision })
+    }
+
+    /// MIDIチャンネルコマンドを解析
+    ///
+    /// # 構文
+    /// - `@C<1-16>` - 以降の音符を送るMIDIチャンネル
+    ///
+    /// # エラー
+    /// - `InvalidNumber` - チャンネルが範囲外
+    fn parse_channel(&mut self) -> Result<Channel, ParseError> {
+        self.advance(); // Consume 'C'
+
+        #[allow(clippy::cast_possible_truncation)]
+        let value = self.consume_number_in_range(1, 16)? as u8;
+        Ok(Channel { value })
+    }
+
+    /// MIDIコントロールコマンドを解析
+    ///
+    /// # 構文
+    /// - `@P<0-127>` - プログラムチェンジ
+    /// - `@PB<-8192..8191>` - ピッチベンド（0が中央、`+`も可）
+    /// - `@CC<0-127>:<0-127>` - コントロールチェンジ（コントローラ番号:値）
+    /// - `@A<0-127>` - チャンネルプレッシャー
+    /// - `@B<0-127>` / `@B<0-127>:<0-127>` - バンクセレクト（MSB / MSB:LSB）
+    ///
+    /// # エラー
+    /// - `InvalidNumber` - 値が範囲外
+    /// - `UnexpectedToken` - `@CC`で`:`や値がない
+    ///
+    /// # 注意
+    /// - `-`/`+`は既存の`Token::Flat`/`Token::Sharp`を流用
+    /// - `@B`の`:`は空白を挟まずに続ける。空白を挟んだ`:`や直後が数値でない`:`は
+    ///   ループの脱出ポイント・n番カッコとみなす
+    #[allow(clippy::cast_possible_truncation)]
+    fn parse_midi_control(&mut self) -> Result<MidiControl, ParseError> {
+        let command = self.advance().token.clone(); // Consume 'P', 'C', 'A' or 'B'
+
+        let control = match command {
+            Token::Program if matches!(self.peek().token, Token::Pitch(Pitch::B)) => {
+                self.advance(); // Consume 'B'
+                MidiControl::PitchBend(self.parse_pitch_bend_value()?)
+            }
+            Token::Program => {
+                MidiControl::ProgramChange(self.consume_number_in_range(0, 127)? as u8)
+            }
+            Token::Pitch(Pitch::C) => {
+                self.advance(); // Consume second 'C'
+                let controller = self.consume_number_in_range(0, 127)? as u8;
+                if !self.check_colon() {
+                    let token_with_pos = self.peek();
+                    return Err(ParseError::UnexpectedToken {
+                        expected: ":".to_string(),
+                        found: token_with_pos.token.clone(),
+                        position: token_with_pos.position,
+                    });
+                }
+                self.advance(); // Consume ':'
+                let value = self.consume_number_in_range(0, 127)? as u8;
+                MidiControl::ControlChange { controller, value }
+            }
+            Token::Pitch(Pitch::A) => {
+                MidiControl::ChannelPressure(self.consume_number_in_range(0, 127)? as u8)
+            }
+            _ => {
+                let msb = self.consume_number_in_range(0, 127)? as u8;
+                let lsb = if self.check_colon()
+                    && self.follows_previous()
+                    && matches!(self.peek_next().token, Token::Number(_))
+                {
+                    self.advance(); // Consume ':'
+                    Some(self.consume_number_in_range(0, 127)? as u8)
+                } else {
+                    None
+                };
+                MidiControl::BankSelect { msb, lsb }
+            }
+        };
+
+        Ok(control)
+    }
+
+    /// ピッチベンドの値（符号付き、-8192〜8191）を解析
+    ///
+    /// 範囲外の場合は符号を除いた値と範囲（`-`なら0-8192、それ以外は0-8191）でエラーにする。
+    fn parse_pitch_bend_value(&mut self) -> Result<i16, ParseError> {
+        if self.check_flat() {
+            self.advance(); // Consume '-'
+            let magnitude = self.consume_number_in_range(0, 8192)?;
+            #[allow(clippy::cast_possible_wrap)]
+            return Ok(-(magnitude as i16));
+        }
+        if self.check_sharp() {
+            self.advance(); // Consume '+'
+        }
+        let value = self.consume_number_in_range(0, 8191)?;
+        #[allow(clippy::cast_possible_wrap)]
+        Ok(value as i16)
+    }
+
+    /// マーカーを解析
+    ///
+    /// # 構文
+    /// - `@"<名前>"` - 例: `@"chorus"`（前後の空白は除去）
+    ///
+    /// # エラー
+    /// - `EmptyMarkerName` - 名前が空
+    /// - `MarkerInTuplet` - 連符の中で使用された
+    fn parse_marker(&mut self) -> Result<Command, ParseError> {
+        let token_with_pos = self.advance();
+        let position = token_with_pos.position;
+        let Token::MarkerName(name) = &token_with_pos.token else {
+            unreachable!("checked by parse_extended_command")
+        };
+        let name = name.trim().to_string();
+
+        if self.tuplet_depth > 0 {
+            return Err(ParseError::MarkerInTuplet { position });
+        }
+        if name.is_empty() {
+            return Err(ParseError::EmptyMarkerName { position });
+        }
+        Ok(Command::Marker(name))
+    }
+
+    /// 拍子コマンドを解析
+    ///
+    /// # 構文
+    /// - `@M<1-32>/<1|2|4|8|16|32>` - 例: `@M3/4`, `@M6/8`
+    ///
+    /// # エラー
+    /// - `InvalidNumber` - 分子が範囲外
+    /// - `InvalidTimeSignature` - 分母が2の累乗（1-32）以外
+    /// - `UnexpectedToken` - `/`がない
+    fn parse_time_signature(&mut self) -> Result<TimeSignature, ParseError> {
+        self.advance(); // Consume 'M'
+
+        #[allow(clippy::cast_possible_truncation)]
+        let numerator = self.consume_number_in_range(1, 32)? as u8;
+
+        if !matches!(self.peek().token, Token::Slash) {
+            return Err(ParseError::UnexpectedToken {
+                expected: "/".to_string(),
+                found: self.peek().token.clone(),
+                position: self.peek().position,
+            });
+        }
+        self.advance(); // Consume '/'
+
+        let position = self.peek().position;
+        let denominator = match self.consume_number()? {
+            #[allow(clippy::cast_possible_truncation)]
+            value @ (1 | 2 | 4 | 8 | 16 | 32) => value as u8,
+            denominator => {
+                return Err(ParseError::InvalidTimeSignature {
+                    denominator,
+                    position,
+                })
+            }
+        };
+
+        Ok(TimeSignature {
+            numerator,
+            denominator,
+        })
+    }
+
+    /// 変化指定の`:<拍数>`を消費（1-128）
+    fn consume_ramp_beats(&mut self) -> Result<u16, ParseError> {
+        if !self.check_colon() {
+            return Err(ParseError::UnexpectedToken {
+                expected: ":".to_string(),
+                found: self.peek().token.clone(),
+                position: self.peek().position,
+            });
+        }
+        self.advance(); // Consume ':'
+        self.consume_number_in_range(1, 128)
+    }
+
+    /// 次のトークンが変化指定の`>`（直後に数値が続く）かチェック
+    fn check_ramp_arrow(&self) -> bool {
+        matches!(self.peek().token, Token::OctaveUp)
+            && matches!(self.peek_next().token, Token::Number(_))
+    }
+
     /// 次のトークンがSharpかチェック
     fn check_sharp(&self) -> bool {
         matches!(self.peek().token, Token::Sharp)
@@ -607,6 +1034,12 @@ impl Parser {
         }
     }
 
+    /// Peek at the token after the current one without consuming anything
+    fn peek_next(&self) -> &TokenWithPos {
+        let index = (self.current + 1).min(self.tokens.len() - 1);
+        &self.tokens[index]
+    }
+
     /// Advance to the next token and return the previous one
     pub fn advance(&mut self) -> &TokenWithPos {
         if !self.is_at_end() {
@@ -628,6 +1061,112 @@ impl Parser {
     }
 }
 
+/// 演奏時間が上限を超えないかチェックする
+///
+/// ループを遅延展開しながら演奏時間を積算し、上限を超えた時点で打ち切る。
+///
+/// # Errors
+/// Returns `ParseError::DurationTooLong` if the total duration exceeds `max_seconds`.
+pub fn check_duration(commands: &[Command], max_seconds: u32) -> Result<(), ParseError> {
+    let max = f64::from(max_seconds);
+    let mut tempo = TempoState::default();
+    let mut default_length = 4;
+    let mut beat_position = 0.0;
+    let mut seconds = 0.0;
+
+    for command in CommandIter::new(commands) {
+        match command {
+            Command::Tempo(t) => tempo.set(t.value),
+            Command::TempoRamp(ramp) => tempo.apply_ramp(ramp, beat_position),
+            Command::Swing(swing) => tempo.set_swing(*swing),
+            Command::DefaultLength(l) => default_length = l.value,
+            _ => {
+                let beats = command.beats(default_length);
+                seconds += tempo.seconds(beat_position, beats);
+                beat_position += beats;
+                if seconds > max {
+                    return Err(ParseError::DurationTooLong { max_seconds });
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// ループ展開後のコマンド数が上限以下かチェックする
+///
+/// 音量や小節線など演奏時間を持たないコマンドだけのループは演奏時間の
+/// 上限にかからないため、展開せずに数えたコマンド数で制限する。
+///
+/// # Errors
+/// Returns `ParseError::LoopExpandedTooLarge` if expanded commands exceed 1,000,000
+pub fn check_expanded_len(commands: &[Command]) -> Result<(), ParseError> {
+    let actual = expanded_len(commands);
+    if actual > MAX_EXPANDED_COMMANDS {
+        return Err(ParseError::LoopExpandedTooLarge {
+            max_commands: MAX_EXPANDED_COMMANDS,
+            actual,
+        });
+    }
+    Ok(())
+}
+
+/// 小節線を含むかどうか（ループは展開せずに中身を調べる）
+fn contains_bar_line(commands: &[Command]) -> bool {
+    commands.iter().any(|command| match command {
+        Command::BarLine => true,
+        Command::Loop {
+            commands, endings, ..
+        } => {
+            contains_bar_line(commands)
+                || endings
+                    .iter()
+                    .any(|ending| contains_bar_line(&ending.commands))
+        }
+        _ => false,
+    })
+}
+
+/// 小節線の間の拍数が拍子と一致するかチェックする
+///
+/// ループを展開した演奏順でコマンドを走査し、小節線`|`で区切られた各小節の
+/// 拍数を`Command::beats`で合計して比較する。`@M`指定がない場合は4/4拍子とみなす。
+/// 最後の小節線より後ろ（曲の終わりの不完全な小節）はチェックしない。
+/// ループで同じ小節線を何度も通る場合、警告はMML上の小節線ごとに最初の1回だけ返す。
+#[must_use]
+pub fn check_bar_lengths(commands: &[Command]) -> Vec<ParseWarning> {
+    let mut warnings = Vec::new();
+    // 警告済みの小節線（ASTのノードのアドレスで区別する）
+    let mut reported = HashSet::new();
+    let mut time_signature = TimeSignature::default();
+    let mut default_length = 4;
+    let mut bar = 1;
+    let mut beats = 0.0;
+
+    for command in CommandIter::new(commands) {
+        match command {
+            Command::DefaultLength(l) => default_length = l.value,
+            Command::TimeSignature(sig) => time_signature = *sig,
+            Command::BarLine => {
+                let expected = time_signature.beats_per_bar();
+                if (beats - expected).abs() > 1e-6 && reported.insert(std::ptr::from_ref(command)) {
+                    warnings.push(ParseWarning::BarLengthMismatch {
+                        bar,
+                        expected,
+                        actual: beats,
+                    });
+                }
+                bar += 1;
+                beats = 0.0;
+            }
+            _ => beats += command.beats(default_length),
+        }
+    }
+
+    warnings
+}
+
 /// Parses an MML string into an MML AST.
 ///
 /// # Errors
@@ -636,6 +1175,34 @@ impl Parser {
 /// - The input is empty.
 /// - The input contains invalid MML syntax.
 pub fn parse(input: &str) -> Result<Mml, ParseError> {
+    parse_with_warnings(input).map(|(mml, _)| mml)
+}
+
+/// MML文字列をパースし、ASTと警告を返す
+///
+/// 警告はパースを妨げない問題（小節の拍数の不一致など）。
+///
+/// # Errors
+///
+/// Returns `ParseError` if:
+/// - The input is empty.
+/// - The input contains invalid MML syntax.
+pub fn parse_with_warnings(input: &str) -> Result<(Mml, Vec<ParseWarning>), ParseError> {
+    parse_with_options(input, &ParseOptions::default())
+}
+
+/// 設定を指定してMML文字列をパースし、ASTと警告を返す
+///
+/// # Errors
+///
+/// Returns `ParseError` if:
+/// - The input is empty.
+/// - The input contains invalid MML syntax.
+/// - The total duration exceeds `options.max_duration_secs`.
+pub fn parse_with_options(
+    input: &str,
+    options: &ParseOptions,
+) -> Result<(Mml, Vec<ParseWarning>), ParseError> {
     if input.is_empty() {
         return Err(ParseError::EmptyInput);
     }
@@ -643,9 +1210,10 @@ pub fn parse(input: &str) -> Result<Mml, ParseError> {
     if tokens.is_empty() || (tokens.len() == 1 && matches!(tokens[0].token, Token::Eof)) {
         // Tokenize returns EOF token for empty string, but check input empty first
         // If input is whitespace only, tokens will contain only EOF
-        return Ok(Mml { commands: vec![] });
+        return Ok((Mml { commands: vec![] }, Vec::new()));
     }
 
-    let mut parser = Parser::new(tokens);
-    parser.parse()
+    let mut parser = Parser::with_options(tokens, *options);
+    let mml = parser.parse()?;
+    Ok((mml, parser.warnings))
 }
@@ -0,0 +1,579 @@
+//! 再生範囲（開始位置・終了位置）の指定モジュール
+//!
+//! `--from` / `--to`で指定された小節番号・マーカー名・時刻を、曲の先頭からの
+//! 秒数とサンプル位置に変換する。
+//!
+//! # ビジネスルール
+//! - 数字のみは小節番号（1始まり）、`m:ss`（`m:ss.s`）形式は時刻、それ以外はマーカー名
+//! - 小節は拍子（`@M`、未指定時は4/4）から求める。曲の途中の`@M`は新しい小節を開始する
+//!   （メトロノームの小節頭の強調と同じ規則）
+//! - ループ内のマーカーは最初に演奏される位置を指す
+//! - サンプル位置はシンセサイザーと同じく音符ごとに切り捨てて積算する
+//! - 終了位置に小節番号を指定した場合は、その小節の終わりまでを範囲とする
+
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use super::{Command, Mml, TempoState, TimeSignature};
+
+/// 位置計算の許容誤差（拍）
+const EPSILON: f64 = 1e-9;
+
+/// 曲中の位置の指定
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlayPosition {
+    /// 小節番号（1始まり）
+    Bar(u32),
+    /// マーカー名（`@"name"`）
+    Marker(String),
+    /// 曲の先頭からの秒数
+    Time(f64),
+}
+
+impl FromStr for PlayPosition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(
+                "位置が空です（小節番号、マーカー名、または m:ss を指定してください）".into(),
+            );
+        }
+
+        if s.chars().all(|c| c.is_ascii_digit()) {
+            return match s.parse::<u32>() {
+                Ok(bar) if bar >= 1 => Ok(Self::Bar(bar)),
+                _ => Err(format!(
+                    "小節番号 '{s}' は無効です（1以上を指定してください）"
+                )),
+            };
+        }
+
+        if let Some((minutes, seconds)) = s.split_once(':') {
+            let invalid = || format!("時刻 '{s}' は無効です（m:ss 形式で指定してください）");
+            let minutes: u32 = minutes.parse().map_err(|_| invalid())?;
+            let seconds: f64 = seconds.parse().map_err(|_| invalid())?;
+            if !(0.0..60.0).contains(&seconds) {
+                return Err(invalid());
+            }
+            return Ok(Self::Time(f64::from(minutes) * 60.0 + seconds));
+        }
+
+        if s.contains('"') {
+            return Err(format!("マーカー名 '{s}' に '\"' は使用できません"));
+        }
+        Ok(Self::Marker(s.to_string()))
+    }
+}
+
+impl fmt::Display for PlayPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bar(bar) => write!(f, "{bar}小節目"),
+            Self::Marker(name) => write!(f, "マーカー '{name}'"),
+            Self::Time(seconds) => {
+                let minutes = (seconds / 60.0).floor();
+                write!(f, "{minutes}:{:05.2}", seconds - minutes * 60.0)
+            }
+        }
+    }
+}
+
+/// 再生範囲の指定に関するエラー
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum RangeError {
+    /// 指定したマーカーがMMLにない
+    #[error("[MML-E039] マーカー '{name}' が見つかりません")]
+    MarkerNotFound { name: String },
+    /// 指定した位置が曲の長さを超えている
+    #[error("[MML-E040] {position} は曲の範囲外です")]
+    OutOfRange { position: PlayPosition },
+    /// 開始位置が終了位置より後ろ、または同じ
+    #[error("[MML-E041] 再生範囲が空です（開始位置は終了位置より前を指定してください）")]
+    EmptyRange,
+}
+
+/// 曲中の位置
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Location {
+    /// 曲の先頭からの秒数
+    pub seconds: f64,
+    /// 曲の先頭からのサンプル位置
+    pub sample: usize,
+    /// この位置でのテンポ（BPM）
+    pub bpm: f64,
+    /// この位置での拍子
+    pub time_signature: TimeSignature,
+}
+
+/// 再生範囲（開始位置を含み、終了位置を含まない）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlayRange {
+    pub start: Location,
+    pub end: Location,
+}
+
+impl PlayRange {
+    /// 範囲の長さ（秒）
+    #[must_use]
+    pub fn seconds(&self) -> f64 {
+        self.end.seconds - self.start.seconds
+    }
+}
+
+/// 位置を探しながら曲を先頭から走査する
+struct Cursor {
+    sample_rate: u32,
+    tempo: TempoState,
+    default_length: u8,
+    beat: f64,
+    seconds: f64,
+    sample: usize,
+    /// 拍子と、現在の小節の番号・開始位置（拍）
+    time_signature: TimeSignature,
+    bar: u32,
+    bar_start: f64,
+}
+
+impl Cursor {
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            tempo: TempoState::default(),
+            default_length: 4,
+            beat: 0.0,
+            seconds: 0.0,
+            sample: 0,
+            time_signature: TimeSignature::default(),
+            bar: 1,
+            bar_start: 0.0,
+        }
+    }
+
+    /// 現在位置から`beats`拍先の位置
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn location_after(&self, beats: f64) -> Location {
+        let seconds = self.tempo.seconds(self.beat, beats);
+        Location {
+            seconds: self.seconds + seconds,
+            sample: self.sample + (seconds * f64::from(self.sample_rate)) as usize,
+            bpm: self.tempo.bpm_at(self.beat + beats),
+            time_signature: self.time_signature,
+        }
+    }
+
+    /// `beats`拍進める（サンプル位置は区間ごとに切り捨て）
+    fn advance(&mut self, beats: f64) {
+        let location = self.location_after(beats);
+        self.seconds = location.seconds;
+        self.sample = location.sample;
+        self.beat += beats;
+    }
+
+    /// 指定した小節の開始位置（拍）。以降に拍子の変更がない場合に正しい
+    fn bar_beat(&self, bar: u32) -> f64 {
+        self.bar_start + f64::from(bar - self.bar) * self.time_signature.beats_per_bar()
+    }
+
+    /// 拍子を変更する（小節の途中の場合は新しい小節を開始する）
+    fn set_time_signature(&mut self, sig: TimeSignature) {
+        let length = self.time_signature.beats_per_bar();
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let completed = ((self.beat - self.bar_start + EPSILON) / length).floor() as u32;
+        self.bar += completed;
+        self.bar_start += f64::from(completed) * length;
+        if self.beat - self.bar_start > EPSILON {
+            self.bar += 1;
+        }
+        self.bar_start = self.beat;
+        self.time_signature = sig;
+    }
+
+    /// 1つのコマンドを処理する
+    ///
+    /// `target`が区間内にあればその位置を返す。
+    fn step(&mut self, command: &Command, target: Option<&PlayPosition>) -> Option<Location> {
+        match command {
+            Command::Tempo(t) => self.tempo.set(t.value),
+            Command::TempoRamp(ramp) => self.tempo.apply_ramp(ramp, self.beat),
+            Command::Swing(swing) => self.tempo.set_swing(*swing),
+            Command::DefaultLength(l) => self.default_length = l.value,
+            Command::TimeSignature(sig) => self.set_time_signature(*sig),
+            Command::Marker(name) => {
+                if matches!(target, Some(PlayPosition::Marker(target)) if target == name) {
+                    return Some(self.location_after(0.0));
+                }
+            }
+            Command::Tuplet { .. } => {
+                // シンセサイザーと同じく連符内の1音ごとにサンプル位置を切り捨てる
+                for beats in command.tuplet_element_beats(self.default_length) {
+                    if let Some(location) = self.find_in(beats, target) {
+                        return Some(location);
+                    }
+                    self.advance(beats);
+                }
+            }
+            _ => {
+                let beats = command.beats(self.default_length);
+                if let Some(location) = self.find_in(beats, target) {
+                    return Some(location);
+                }
+                self.advance(beats);
+            }
+        }
+        None
+    }
+
+    /// 現在位置から`beats`拍の区間内にある目標位置を探す
+    fn find_in(&self, beats: f64, target: Option<&PlayPosition>) -> Option<Location> {
+        if beats <= 0.0 {
+            return None;
+        }
+        match target? {
+            PlayPosition::Bar(bar) if *bar >= self.bar => {
+                let offset = self.bar_beat(*bar) - self.beat;
+                (offset > -EPSILON && offset < beats - EPSILON)
+                    .then(|| self.location_after(offset.max(0.0)))
+            }
+            PlayPosition::Time(seconds) => {
+                let end = self.seconds + self.tempo.seconds(self.beat, beats);
+                (*seconds < end).then(|| self.time_location(*seconds))
+            }
+            _ => None,
+        }
+    }
+
+    /// 指定した秒数の位置（テンポ・拍子は現在の区間のもの）
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn time_location(&self, seconds: f64) -> Location {
+        Location {
+            seconds,
+            sample: (seconds * f64::from(self.sample_rate)) as usize,
+            bpm: self.tempo.bpm_at(self.beat),
+            time_signature: self.time_signature,
+        }
+    }
+
+    /// 曲の終わりに達した時点で目標位置が曲の終わりと一致するか
+    fn at_end(&self, target: &PlayPosition) -> Option<Location> {
+        let matches = match target {
+            PlayPosition::Bar(bar) => {
+                *bar >= self.bar && (self.bar_beat(*bar) - self.beat).abs() < EPSILON
+            }
+            PlayPosition::Time(seconds) => (*seconds - self.seconds).abs() < 1e-6,
+            PlayPosition::Marker(_) => false,
+        };
+        matches.then(|| self.location_after(0.0))
+    }
+}
+
+impl Mml {
+    /// 指定した位置を探す
+    ///
+    /// 曲の終わりちょうどの位置（最終小節の次の小節など）も返す。
+    ///
+    /// # Errors
+    /// - `MarkerNotFound` - マーカーがない
+    /// - `OutOfRange` - 位置が曲の長さを超えている
+    pub fn locate(
+        &self,
+        position: &PlayPosition,
+        sample_rate: u32,
+    ) -> Result<Location, RangeError> {
+        let mut cursor = Cursor::new(sample_rate);
+        // マーカーの位置（直後のテンポ・拍子の指定を反映するため、次の音まで待つ）
+        let mut marker: Option<Location> = None;
+
+        for command in self {
+            if let Some(location) = &mut marker {
+                if command.beats(cursor.default_length) > 0.0 {
+                    return Ok(*location);
+                }
+                cursor.step(command, None);
+                location.bpm = cursor.tempo.bpm_at(cursor.beat);
+                location.time_signature = cursor.time_signature;
+                continue;
+            }
+            match cursor.step(command, Some(position)) {
+                Some(location) if matches!(command, Command::Marker(_)) => {
+                    marker = Some(location);
+                }
+                Some(location) => return Ok(location),
+                None => {}
+            }
+        }
+        if let Some(location) = marker.or_else(|| cursor.at_end(position)) {
+            return Ok(location);
+        }
+
+        Err(match position {
+            PlayPosition::Marker(name) => RangeError::MarkerNotFound { name: name.clone() },
+            _ => RangeError::OutOfRange {
+                position: position.clone(),
+            },
+        })
+    }
+
+    /// 曲の終わりの位置
+    #[must_use]
+    pub fn end_location(&self, sample_rate: u32) -> Location {
+        let mut cursor = Cursor::new(sample_rate);
+        for command in self {
+            cursor.step(command, None);
+        }
+        cursor.location_after(0.0)
+    }
+
+    /// 曲全体の拍数（テンポに依存しない長さ）
+    #[must_use]
+    pub fn total_beats(&self) -> f64 {
+        let mut cursor = Cursor::new(0);
+        for command in self {
+            cursor.step(command, None);
+        }
+        cursor.beat
+    }
+
+    /// 各小節の開始位置（1小節目から順に、曲の終わりちょうどの小節頭を含む）
+    #[must_use]
+    pub fn bar_lines(&self, sample_rate: u32) -> Vec<Location> {
+        (1..)
+            .map_while(|bar| self.locate(&PlayPosition::Bar(bar), sample_rate).ok())
+            .collect()
+    }
+
+    /// `--from` / `--to`の指定から再生範囲を求める
+    ///
+    /// 省略した場合はそれぞれ曲の先頭・終わり。終了位置の小節番号はその小節を含む。
+    ///
+    /// # Errors
+    /// - 位置が見つからない、または曲の範囲外
+    /// - `EmptyRange` - 開始位置が終了位置より前でない
+    pub fn play_range(
+        &self,
+        from: Option<&PlayPosition>,
+        to: Option<&PlayPosition>,
+        sample_rate: u32,
+    ) -> Result<PlayRange, RangeError> {
+        let start = match from {
+            Some(position) => self.locate(position, sample_rate)?,
+            // 冒頭のテンポ・拍子の指定を反映するため、最初の音の位置を使う
+            None => self
+                .locate(&PlayPosition::Time(0.0), sample_rate)
+                .unwrap_or_else(|_| Cursor::new(sample_rate).location_after(0.0)),
+        };
+        let end = match to {
+            Some(PlayPosition::Bar(bar)) => self
+                .locate(&PlayPosition::Bar(bar + 1), sample_rate)
+                .map_err(|_| RangeError::OutOfRange {
+                position: PlayPosition::Bar(*bar),
+            })?,
+            Some(position) => self.locate(position, sample_rate)?,
+            None => self.end_location(sample_rate),
+        };
+
+        if end.sample <= start.sample {
+            return Err(RangeError::EmptyRange);
+        }
+        Ok(PlayRange { start, end })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mml::parse;
+
+    const SAMPLE_RATE: u32 = 44100;
+
+    fn locate(mml: &str, position: &str) -> Result<Location, RangeError> {
+        parse(mml)
+            .unwrap()
+            .locate(&position.parse().unwrap(), SAMPLE_RATE)
+    }
+
+    #[test]
+    fn position_from_str() {
+        assert_eq!("12".parse(), Ok(PlayPosition::Bar(12)));
+        assert_eq!("1:30".parse(), Ok(PlayPosition::Time(90.0)));
+        assert_eq!("0:02.5".parse(), Ok(PlayPosition::Time(2.5)));
+        assert_eq!(
+            "chorus".parse(),
+            Ok(PlayPosition::Marker("chorus".to_string()))
+        );
+        assert!("0".parse::<PlayPosition>().is_err());
+        assert!("1:75".parse::<PlayPosition>().is_err());
+        assert!("a:b".parse::<PlayPosition>().is_err());
+        assert!("".parse::<PlayPosition>().is_err());
+    }
+
+    #[test]
+    fn position_display() {
+        assert_eq!(PlayPosition::Bar(3).to_string(), "3小節目");
+        assert_eq!(PlayPosition::Time(90.5).to_string(), "1:30.50");
+        assert_eq!(
+            PlayPosition::Marker("a".to_string()).to_string(),
+            "マーカー 'a'"
+        );
+    }
+
+    #[test]
+    fn locate_bar_at_tempo() {
+        // T120: 1小節（4拍）= 2秒
+        let location = locate("T120 C1 D1 E1", "3").unwrap();
+        assert!((location.seconds - 4.0).abs() < 1e-9);
+        assert_eq!(location.sample, 44100 * 4);
+    }
+
+    #[test]
+    fn locate_bar_inside_long_note() {
+        // 2小節目の頭は全音符2つ分のタイの途中
+        let location = locate("T120 C1&1", "2").unwrap();
+        assert!((location.seconds - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn locate_bar_follows_time_signature() {
+        // 3/4: 1小節 = 3拍 = 1.5秒
+        let location = locate("T120 @M3/4 C2. D2. E2.", "3").unwrap();
+        assert!((location.seconds - 3.0).abs() < 1e-9);
+        assert_eq!(location.time_signature.numerator, 3);
+    }
+
+    #[test]
+    fn locate_bar_after_time_signature_change() {
+        // 4/4の1小節の後に3/4: 3小節目は4+3拍目
+        let location = locate("T120 C1 @M3/4 D2. E2. F2.", "3").unwrap();
+        assert!((location.seconds - 3.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn locate_bar_after_tempo_change() {
+        // 1小節目はT120（2秒）、2小節目はT60（4秒）
+        let location = locate("T120 C1 T60 D1 E1", "3").unwrap();
+        assert!((location.seconds - 6.0).abs() < 1e-9);
+        assert!((location.bpm - 60.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn locate_marker() {
+        let location = locate(r#"T120 C1 @"chorus" D1"#, "chorus").unwrap();
+        assert!((location.seconds - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn locate_marker_in_loop_uses_first_pass() {
+        let location = locate(r#"T120 C1 [@"a" D1]3"#, "a").unwrap();
+        assert!((location.seconds - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn locate_time() {
+        let location = locate("T120 C1 D1", "0:01.5").unwrap();
+        assert_eq!(location.sample, 66150);
+    }
+
+    #[test]
+    fn locate_end_of_piece() {
+        // 2小節の曲の3小節目 = 曲の終わり
+        let location = locate("T120 C1 D1", "3").unwrap();
+        assert!((location.seconds - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn locate_errors() {
+        assert_eq!(
+            locate("T120 C1", "chorus"),
+            Err(RangeError::MarkerNotFound {
+                name: "chorus".to_string()
+            })
+        );
+        assert_eq!(
+            locate("T120 C1", "5"),
+            Err(RangeError::OutOfRange {
+                position: PlayPosition::Bar(5)
+            })
+        );
+        assert!(locate("T120 C1", "1:00").is_err());
+    }
+
+    #[test]
+    fn locate_marker_applies_following_settings() {
+        let location = locate(r#"T120 C1 @"slow" T60 @M3/4 D2."#, "slow").unwrap();
+        assert!((location.seconds - 2.0).abs() < 1e-9);
+        assert!((location.bpm - 60.0).abs() < 1e-9);
+        assert_eq!(location.time_signature.numerator, 3);
+    }
+
+    #[test]
+    fn end_location_matches_synthesizer_length() {
+        use crate::audio::synthesizer::Synthesizer;
+        use crate::audio::waveform::WaveformType;
+
+        // 切り捨てが発生するテンポで連符・付点・テンポ変化を含める
+        let mml = parse("T130 {CDE}3 {C{DE}3F}3 L8 C.D16 T97>150:4 CDEFGAB T71 @S60 CDEF").unwrap();
+        let mut synth = Synthesizer::new(SAMPLE_RATE, 50, WaveformType::Sine);
+        let samples = synth.synthesize(&mml).unwrap();
+        assert_eq!(mml.end_location(SAMPLE_RATE).sample, samples.len());
+    }
+
+    #[test]
+    fn total_beats_ignores_tempo() {
+        let mml = parse("T90 L8 CDEF T200>60:2 C4 R2").unwrap();
+        assert!((mml.total_beats() - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn bar_lines_follow_time_signature() {
+        let mml = parse("T120 @M3/4 C2. D2. @M2/4 E2").unwrap();
+        let seconds: Vec<f64> = mml
+            .bar_lines(SAMPLE_RATE)
+            .iter()
+            .map(|location| location.seconds)
+            .collect();
+        assert_eq!(seconds.len(), 4);
+        for (actual, expected) in seconds.iter().zip([0.0, 1.5, 3.0, 4.0]) {
+            assert!((actual - expected).abs() < 1e-9, "{seconds:?}");
+        }
+    }
+
+    #[test]
+    fn play_range_to_bar_is_inclusive() {
+        let mml = parse("T120 C1 D1 E1 F1").unwrap();
+        let range = mml
+            .play_range(
+                Some(&PlayPosition::Bar(2)),
+                Some(&PlayPosition::Bar(3)),
+                SAMPLE_RATE,
+            )
+            .unwrap();
+        assert!((range.start.seconds - 2.0).abs() < 1e-9);
+        assert!((range.end.seconds - 6.0).abs() < 1e-9);
+        assert!((range.seconds() - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn play_range_defaults_to_whole_piece() {
+        let mml = parse("T120 C1 D1").unwrap();
+        let range = mml.play_range(None, None, SAMPLE_RATE).unwrap();
+        assert_eq!(range.start.sample, 0);
+        assert_eq!(range.end.sample, 44100 * 4);
+    }
+
+    #[test]
+    fn play_range_rejects_empty_range() {
+        let mml = parse("T120 C1 D1 E1").unwrap();
+        let result = mml.play_range(
+            Some(&PlayPosition::Bar(3)),
+            Some(&PlayPosition::Time(1.0)),
+            SAMPLE_RATE,
+        );
+        assert_eq!(result, Err(RangeError::EmptyRange));
+        assert!(mml
+            .play_range(Some(&PlayPosition::Bar(4)), None, SAMPLE_RATE)
+            .is_err());
+    }
+}
@@ -0,0 +1,336 @@
+//! テンポ変化（アッチェレランド/リタルダンド）とスウィングを考慮した時間計算モジュール
+//!
+//! `T`コマンドによる即時のテンポ変更と、`Tn>m:b`による指定拍数かけた
+//! テンポ変化、`@S`によるスウィングを扱う。シンセサイザー、MIDIプレイヤー、
+//! テンポイベント生成、メトロノームから共通で利用し、拍位置から秒数への変換を一致させる。
+//!
+//! # ビジネスルール
+//! - テンポ変化は拍位置に対してBPMを線形補間し、終了後は終了テンポを維持する
+//! - `T`コマンドは進行中のテンポ変化を解除する（スウィングは維持）
+//! - 区間の秒数は`60 / bpm`を拍で積分して求める（閉形式）
+//! - スウィングは細分2つ分の組の中だけで拍位置をずらすため、組の境界
+//!   （ひいては小節線）の位置は変わらない
+
+use super::{Swing, TempoRamp};
+
+/// 進行中のテンポ変化
+///
+/// 開始位置（拍）からの経過拍数に応じて、`from`から`to`へBPMを線形補間する。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoCurve {
+    /// 開始テンポ（BPM）
+    pub from: f64,
+    /// 終了テンポ（BPM）
+    pub to: f64,
+    /// 変化を開始した位置（拍）
+    pub start_beat: f64,
+    /// 変化にかける拍数
+    pub beats: f64,
+}
+
+impl TempoCurve {
+    /// Creates a new `TempoCurve`
+    #[must_use]
+    pub const fn new(from: f64, to: f64, start_beat: f64, beats: f64) -> Self {
+        Self {
+            from,
+            to,
+            start_beat,
+            beats,
+        }
+    }
+
+    /// 指定位置（拍）でのテンポ（BPM）を計算
+    #[must_use]
+    pub fn bpm_at(&self, beat: f64) -> f64 {
+        let progress = if self.beats > 0.0 {
+            ((beat - self.start_beat) / self.beats).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        self.from + (self.to - self.from) * progress
+    }
+
+    /// 変化が終了する位置（拍）
+    #[must_use]
+    pub fn end_beat(&self) -> f64 {
+        self.start_beat + self.beats
+    }
+}
+
+/// テンポ状態
+///
+/// 拍位置は呼び出し側が保持し、各メソッドに渡す。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoState {
+    /// `T`コマンドで指定された固定テンポ（BPM）
+    pub bpm: f64,
+    /// 進行中のテンポ変化
+    pub curve: Option<TempoCurve>,
+    /// スウィング（`None`の場合はイーブン）
+    pub swing: Option<Swing>,
+}
+
+impl Default for TempoState {
+    fn default() -> Self {
+        Self::new(120)
+    }
+}
+
+impl TempoState {
+    /// 固定テンポで初期化
+    #[must_use]
+    pub fn new(bpm: u16) -> Self {
+        Self {
+            bpm: f64::from(bpm),
+            curve: None,
+            swing: None,
+        }
+    }
+
+    /// `T`コマンドを適用する（進行中のテンポ変化は解除）
+    pub fn set(&mut self, bpm: u16) {
+        self.bpm = f64::from(bpm);
+        self.curve = None;
+    }
+
+    /// 小数を含む固定テンポに設定する（進行中のテンポ変化は解除、スウィングは維持）
+    ///
+    /// 外部MIDIクロックに同期する場合に、MML中のテンポ指定の代わりに使う。
+    pub fn set_fixed(&mut self, bpm: f64) {
+        self.bpm = bpm;
+        self.curve = None;
+    }
+
+    /// `Tn>m:b`コマンドを適用する
+    ///
+    /// 開始テンポ省略時は現在位置でのテンポから変化する。
+    pub fn apply_ramp(&mut self, ramp: &TempoRamp, beat: f64) {
+        let from = ramp.from.map_or_else(|| self.bpm_at(beat), f64::from);
+        let to = f64::from(ramp.to);
+        self.bpm = to;
+        self.curve = Some(TempoCurve::new(from, to, beat, f64::from(ramp.beats)));
+    }
+
+    /// `@S`コマンドを適用する（割合50%はスウィングなし）
+    pub fn set_swing(&mut self, swing: Swing) {
+        self.swing = (swing.ratio != 50).then_some(swing);
+    }
+
+    /// 指定位置（拍）でのテンポ（BPM）
+    #[must_use]
+    pub fn bpm_at(&self, beat: f64) -> f64 {
+        self.curve.map_or(self.bpm, |curve| curve.bpm_at(beat))
+    }
+
+    /// 位置`beat`から`beats`拍進むのにかかる秒数
+    ///
+    /// スウィング指定時は、楽譜上の拍位置をスウィング後の位置に変換してから計算する。
+    #[must_use]
+    pub fn seconds(&self, beat: f64, beats: f64) -> f64 {
+        if beats <= 0.0 {
+            return 0.0;
+        }
+        match self.swing {
+            Some(swing) => {
+                let start = swing_position(swing, beat);
+                let end = swing_position(swing, beat + beats);
+                self.straight_seconds(start, end - start)
+            }
+            None => self.straight_seconds(beat, beats),
+        }
+    }
+
+    /// スウィングを考慮せずに秒数を計算
+    fn straight_seconds(&self, beat: f64, beats: f64) -> f64 {
+        if beats <= 0.0 {
+            return 0.0;
+        }
+        let end = beat + beats;
+        let Some(curve) = self.curve else {
+            return beats * 60.0 / self.bpm;
+        };
+
+        // 変化前・変化中・変化後の区間に分けて積分
+        let ramp_start = beat.max(curve.start_beat).min(end);
+        let ramp_end = end.min(curve.end_beat()).max(ramp_start);
+        let before = (ramp_start - beat) * 60.0 / curve.from;
+        let after = (end - ramp_end) * 60.0 / curve.to;
+        before + self.linear_seconds(ramp_start, ramp_end) + after
+    }
+
+    /// テンポが線形に変化する区間`[b0, b1]`の秒数
+    ///
+    /// `∫ 60 / bpm(b) db = 60 * (b1 - b0) / (t1 - t0) * ln(t1 / t0)`
+    fn linear_seconds(&self, b0: f64, b1: f64) -> f64 {
+        let beats = b1 - b0;
+        if beats <= 0.0 {
+            return 0.0;
+        }
+        let t0 = self.bpm_at(b0);
+        let t1 = self.bpm_at(b1);
+        if (t1 - t0).abs() < 1e-9 {
+            beats * 60.0 / t0
+        } else {
+            60.0 * beats / (t1 - t0) * (t1 / t0).ln()
+        }
+    }
+
+    /// 位置`beat`を0拍目とした状態に変換する（テンポイベント用）
+    #[must_use]
+    pub fn rebased(&self, beat: f64) -> Self {
+        Self {
+            bpm: self.bpm,
+            curve: self.curve.map(|curve| TempoCurve {
+                start_beat: curve.start_beat - beat,
+                ..curve
+            }),
+            swing: self.swing,
+        }
+    }
+}
+
+/// 楽譜上の拍位置をスウィング後の拍位置に変換
+///
+/// 細分2つ分を1組とし、組の前半を`ratio`%、後半を残りの長さに伸縮する。
+/// 組の境界は変換前後で一致する。
+#[must_use]
+pub fn swing_position(swing: Swing, beat: f64) -> f64 {
+    let pair = 8.0 / f64::from(swing.subdivision);
+    let ratio = f64::from(swing.ratio) / 100.0;
+    let pair_start = (beat / pair).floor() * pair;
+    let phase = (beat - pair_start) / pair;
+
+    let warped = if phase < 0.5 {
+        phase * 2.0 * ratio
+    } else {
+        ratio + (phase - 0.5) * 2.0 * (1.0 - ratio)
+    };
+    pair_start + warped * pair
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+    use super::*;
+
+    fn ramp(from: Option<u16>, to: u16, beats: u8) -> TempoRamp {
+        TempoRamp { from, to, beats }
+    }
+
+    fn swing(ratio: u8, subdivision: u8) -> Swing {
+        Swing { ratio, subdivision }
+    }
+
+    #[test]
+    fn constant_tempo_seconds() {
+        let tempo = TempoState::new(120);
+        assert_eq!(tempo.seconds(0.0, 1.0), 0.5);
+        assert_eq!(tempo.seconds(3.0, 4.0), 2.0);
+    }
+
+    #[test]
+    fn set_clears_curve() {
+        let mut tempo = TempoState::new(120);
+        tempo.apply_ramp(&ramp(None, 60, 4), 0.0);
+        tempo.set(90);
+        assert!(tempo.curve.is_none());
+        assert_eq!(tempo.bpm_at(2.0), 90.0);
+    }
+
+    #[test]
+    fn ramp_interpolates_bpm() {
+        let mut tempo = TempoState::new(120);
+        tempo.apply_ramp(&ramp(Some(120), 80, 8), 2.0);
+        assert_eq!(tempo.bpm_at(2.0), 120.0);
+        assert_eq!(tempo.bpm_at(6.0), 100.0);
+        assert_eq!(tempo.bpm_at(10.0), 80.0);
+        assert_eq!(tempo.bpm_at(20.0), 80.0);
+    }
+
+    #[test]
+    fn ramp_from_current_tempo() {
+        let mut tempo = TempoState::new(120);
+        tempo.apply_ramp(&ramp(Some(120), 60, 4), 0.0);
+        tempo.apply_ramp(&ramp(None, 120, 4), 2.0);
+        assert_eq!(tempo.bpm_at(2.0), 90.0);
+    }
+
+    #[test]
+    fn ramp_seconds_closed_form() {
+        let mut tempo = TempoState::new(120);
+        tempo.apply_ramp(&ramp(Some(120), 60, 8), 0.0);
+        let expected = 60.0 * 8.0 / (60.0 - 120.0) * (60.0_f64 / 120.0).ln();
+        assert!((tempo.seconds(0.0, 8.0) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ramp_seconds_are_additive() {
+        let mut tempo = TempoState::new(100);
+        tempo.apply_ramp(&ramp(Some(100), 200, 6), 1.0);
+        let whole = tempo.seconds(0.0, 10.0);
+        let parts: f64 = (0..20)
+            .map(|i| tempo.seconds(f64::from(i) * 0.5, 0.5))
+            .sum();
+        assert!((whole - parts).abs() < 1e-9);
+    }
+
+    #[test]
+    fn seconds_after_ramp_use_end_tempo() {
+        let mut tempo = TempoState::new(120);
+        tempo.apply_ramp(&ramp(Some(120), 60, 4), 0.0);
+        assert_eq!(tempo.seconds(4.0, 2.0), 2.0);
+    }
+
+    #[test]
+    fn rebased_shifts_curve_start() {
+        let mut tempo = TempoState::new(120);
+        tempo.apply_ramp(&ramp(Some(120), 60, 4), 8.0);
+        let rebased = tempo.rebased(8.0);
+        assert_eq!(rebased.curve.unwrap().start_beat, 0.0);
+        assert_eq!(rebased.seconds(0.0, 4.0), tempo.seconds(8.0, 4.0));
+    }
+
+    #[test]
+    fn swing_position_keeps_pair_boundaries() {
+        let eighths = swing(66, 8);
+        for beat in 0..8 {
+            let beat = f64::from(beat);
+            assert!((swing_position(eighths, beat) - beat).abs() < 1e-9);
+        }
+        let sixteenths = swing(60, 16);
+        assert!((swing_position(sixteenths, 2.5) - 2.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn swing_position_moves_offbeat() {
+        assert!((swing_position(swing(60, 8), 0.5) - 0.6).abs() < 1e-9);
+        assert!((swing_position(swing(75, 8), 3.5) - 3.75).abs() < 1e-9);
+        assert!((swing_position(swing(60, 16), 1.25) - 1.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn swung_eighths_seconds() {
+        let mut tempo = TempoState::new(120);
+        tempo.set_swing(swing(60, 8));
+        assert!((tempo.seconds(0.0, 0.5) - 0.3).abs() < 1e-9);
+        assert!((tempo.seconds(0.5, 0.5) - 0.2).abs() < 1e-9);
+        assert!((tempo.seconds(0.0, 4.0) - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn swing_50_is_straight() {
+        let mut tempo = TempoState::new(120);
+        tempo.set_swing(swing(60, 8));
+        tempo.set_swing(swing(50, 8));
+        assert!(tempo.swing.is_none());
+    }
+
+    #[test]
+    fn tempo_command_keeps_swing() {
+        let mut tempo = TempoState::new(120);
+        tempo.set_swing(swing(60, 8));
+        tempo.set(90);
+        assert!(tempo.swing.is_some());
+    }
+}
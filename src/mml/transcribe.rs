@@ -0,0 +1,379 @@
+//! 録音した音符をMMLに変換するモジュール（`record`サブコマンド）
+//!
+//! MIDIキーボードで演奏した音符（ノート番号と発音・消音の時刻）を、指定したテンポと
+//! グリッドでクオンタイズし、人が書くようなMML文字列にする。
+//!
+//! # ビジネスルール
+//! - 最初の音の発音位置を曲の先頭とする（それより前の無音は含めない）
+//! - 発音・消音の時刻はそれぞれ最も近いグリッドに丸める。長さが0になる音は1グリッドにする
+//! - 単音のメロディとして扱う。同じ位置で始まる音は最も高い音だけを残し、次の音と重なる音は
+//!   次の音の位置で切る
+//! - 音と音の間は休符にする。休符は小節線（4/4拍子）で分け、小節の区切りに空白を入れる
+//! - 音長は付点を含む音長の和で表し、2つ以上になる場合はタイ（`&`）でつなぐ
+//! - 音符で最も多く使う音長を既定の音長（`L`）にし、既定の音長と同じ音長は省略する
+//!   （タイでつなぐ音長は省略しない）
+//! - オクターブの変化が1の場合は`<` / `>`、それ以上の場合は`O`で指定する
+//! - オクターブ1-8の範囲外の音は、範囲内に収まるようにオクターブ単位で移す
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// 臨時記号にシャープ（`+`）を使った音名（ピッチクラス順）
+const PITCH_NAMES: [&str; 12] = [
+    "C", "C+", "D", "D+", "E", "F", "F+", "G", "G+", "A", "A+", "B",
+];
+
+/// MMLで指定できるオクターブの範囲
+const MIN_OCTAVE: u8 = 1;
+const MAX_OCTAVE: u8 = 8;
+
+/// クオンタイズに指定できるグリッド（n分音符）
+pub const GRIDS: [u8; 4] = [4, 8, 16, 32];
+
+/// 録音した1音
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordedNote {
+    /// MIDIノート番号（60 = O4のC）
+    pub note: u8,
+    /// 発音の時刻（秒）
+    pub start: f64,
+    /// 消音の時刻（秒）
+    pub end: f64,
+}
+
+/// クオンタイズの設定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TranscribeOptions {
+    /// テンポ（BPM）
+    pub bpm: u16,
+    /// グリッド（n分音符、[`GRIDS`]のいずれか）
+    pub grid: u8,
+}
+
+/// 音符または休符と、その長さ（グリッド数）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Event {
+    note: Option<u8>,
+    steps: u32,
+}
+
+/// 録音した音符をMMLに変換する
+///
+/// 音符がない場合はテンポの指定だけを返す。
+#[must_use]
+pub fn transcribe(notes: &[RecordedNote], options: TranscribeOptions) -> String {
+    let grid = u32::from(options.grid);
+    let events = split_rests(&to_events(&quantize(notes, options)), grid);
+    let default_length = default_length(&events, grid);
+
+    let mut mml = format!("T{} L{default_length}", options.bpm);
+    let mut octave: Option<u8> = None;
+    let mut position = 0_u32;
+    for event in events {
+        // 1小節（4/4拍子）は全音符 = `grid`グリッド
+        if position.is_multiple_of(grid) {
+            mml.push(' ');
+        }
+        match event.note {
+            Some(note) => {
+                let (note_octave, pitch) = split_note(note);
+                mml.push_str(&octave_change(octave, note_octave));
+                octave = Some(note_octave);
+                mml.push_str(PITCH_NAMES[usize::from(pitch)]);
+            }
+            None => mml.push('R'),
+        }
+        mml.push_str(&duration(event.steps, grid, default_length));
+        position += event.steps;
+    }
+    mml
+}
+
+/// 発音・消音の位置をグリッドに丸め、単音の（重ならない）音符列にする
+///
+/// 返り値は位置順の`(開始, 終了, ノート番号)`（グリッド単位）。
+fn quantize(notes: &[RecordedNote], options: TranscribeOptions) -> Vec<(u32, u32, u8)> {
+    let mut sorted = notes.to_vec();
+    sorted.sort_by(|a, b| a.start.total_cmp(&b.start));
+    let Some(origin) = sorted.first().map(|note| note.start) else {
+        return Vec::new();
+    };
+
+    let step_seconds = 60.0 / f64::from(options.bpm) * 4.0 / f64::from(options.grid);
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let to_step = |seconds: f64| ((seconds - origin).max(0.0) / step_seconds).round() as u32;
+
+    let mut quantized: Vec<(u32, u32, u8)> = Vec::new();
+    for note in sorted {
+        let start = to_step(note.start);
+        let end = to_step(note.end).max(start + 1);
+        match quantized.last_mut() {
+            Some(last) if last.0 == start => {
+                if note.note > last.2 {
+                    *last = (start, end, note.note);
+                }
+            }
+            Some(last) => {
+                last.1 = last.1.min(start);
+                quantized.push((start, end, note.note));
+            }
+            None => quantized.push((start, end, note.note)),
+        }
+    }
+    quantized
+}
+
+/// 音符の間に休符を入れる
+fn to_events(notes: &[(u32, u32, u8)]) -> Vec<Event> {
+    let mut events = Vec::new();
+    let mut position = 0;
+    for &(start, end, note) in notes {
+        if start > position {
+            events.push(Event {
+                note: None,
+                steps: start - position,
+            });
+        }
+        events.push(Event {
+            note: Some(note),
+            steps: end - start,
+        });
+        position = end;
+    }
+    events
+}
+
+/// 休符を小節線（`bar_steps`グリッドごと）で分ける
+fn split_rests(events: &[Event], bar_steps: u32) -> Vec<Event> {
+    let mut split = Vec::new();
+    let mut position = 0;
+    for event in events {
+        let mut remaining = event.steps;
+        while event.note.is_none() && remaining > 0 {
+            let to_bar = bar_steps - position % bar_steps;
+            let steps = remaining.min(to_bar);
+            split.push(Event { note: None, steps });
+            position += steps;
+            remaining -= steps;
+        }
+        if event.note.is_some() {
+            split.push(*event);
+            position += event.steps;
+        }
+    }
+    split
+}
+
+/// 音符で最も多く使う音長（音長の表記の最初の部分で数える。同数の場合は長い音長）
+fn default_length(events: &[Event], grid: u32) -> u32 {
+    let mut counts: HashMap<u32, usize> = HashMap::new();
+    for event in events.iter().filter(|event| event.note.is_some()) {
+        if let Some(&(length, _)) = parts(event.steps, grid).first() {
+            *counts.entry(length).or_default() += 1;
+        }
+    }
+    counts
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+        .map_or(4.min(grid), |(length, _)| length)
+}
+
+/// `steps`グリッドの長さを、長い順の音長（n分音符と付点の有無）の和に分ける
+fn parts(steps: u32, grid: u32) -> Vec<(u32, bool)> {
+    // 長い順の音長とそのグリッド数（全音符の付点から1グリッドまで）
+    let mut candidates = Vec::new();
+    let mut length = 1;
+    while length <= grid {
+        let size = grid / length;
+        if size.is_multiple_of(2) {
+            candidates.push((length, true, size / 2 * 3));
+        }
+        candidates.push((length, false, size));
+        length *= 2;
+    }
+
+    let mut parts = Vec::new();
+    let mut remaining = steps;
+    while remaining > 0 {
+        let &(length, dotted, size) = candidates
+            .iter()
+            .find(|(_, _, size)| *size <= remaining)
+            .expect("1グリッドの音長が常にある");
+        parts.push((length, dotted));
+        remaining -= size;
+    }
+    parts
+}
+
+/// 音長の表記（タイでつながない既定の音長は省略する）
+fn duration(steps: u32, grid: u32, default_length: u32) -> String {
+    let parts = parts(steps, grid);
+    let tied = parts.len() > 1;
+    let mut text = String::new();
+    for (i, (length, dotted)) in parts.into_iter().enumerate() {
+        if i > 0 {
+            text.push('&');
+        }
+        if tied || length != default_length {
+            let _ = write!(text, "{length}");
+        }
+        if dotted {
+            text.push('.');
+        }
+    }
+    text
+}
+
+/// ノート番号をオクターブ（1-8に収める）とピッチクラスに分ける
+fn split_note(note: u8) -> (u8, u8) {
+    let octave = (note / 12).saturating_sub(1).clamp(MIN_OCTAVE, MAX_OCTAVE);
+    (octave, note % 12)
+}
+
+/// オクターブを`from`から`to`に変える表記（最初の音は`O`で指定する）
+fn octave_change(from: Option<u8>, to: u8) -> String {
+    match from {
+        Some(from) if from == to => String::new(),
+        Some(from) if from + 1 == to => ">".to_string(),
+        Some(from) if from == to + 1 => "<".to_string(),
+        _ => format!("O{to}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 120 BPMの16分音符（0.125秒）
+    const STEP: f64 = 0.125;
+
+    fn note(note: u8, start: u32, end: u32) -> RecordedNote {
+        RecordedNote {
+            note,
+            start: f64::from(start) * STEP,
+            end: f64::from(end) * STEP,
+        }
+    }
+
+    fn options() -> TranscribeOptions {
+        TranscribeOptions { bpm: 120, grid: 16 }
+    }
+
+    #[test]
+    fn transcribes_melody_with_default_length() {
+        let notes = [
+            note(60, 0, 2),
+            note(62, 2, 4),
+            note(64, 4, 6),
+            note(65, 6, 10),
+        ];
+        assert_eq!(transcribe(&notes, options()), "T120 L8 O4CDEF4");
+    }
+
+    #[test]
+    fn quantizes_to_nearest_grid() {
+        // 少し早い・遅い演奏を16分音符に丸める
+        let notes = [
+            RecordedNote {
+                note: 60,
+                start: 1.0,
+                end: 1.0 + 0.26,
+            },
+            RecordedNote {
+                note: 62,
+                start: 1.0 + 0.49,
+                end: 1.0 + 0.55,
+            },
+        ];
+        assert_eq!(transcribe(&notes, options()), "T120 L8 O4CRD16");
+    }
+
+    #[test]
+    fn uses_relative_and_absolute_octave_changes() {
+        let notes = [
+            note(60, 0, 4),
+            note(72, 4, 8),
+            note(71, 8, 12),
+            note(96, 12, 16),
+        ];
+        assert_eq!(transcribe(&notes, options()), "T120 L4 O4C>C<BO7C");
+    }
+
+    #[test]
+    fn ties_lengths_that_need_several_parts() {
+        // 4分音符 + 16分音符、付点4分音符、全音符 + 4分音符
+        let notes = [note(60, 0, 5), note(61, 5, 11), note(62, 11, 31)];
+        assert_eq!(transcribe(&notes, options()), "T120 L4 O4C4&16C+.D1&4");
+    }
+
+    #[test]
+    fn splits_rests_at_bar_lines() {
+        let notes = [note(60, 0, 4), note(67, 24, 28)];
+        assert_eq!(transcribe(&notes, options()), "T120 L4 O4CR2. R2G");
+    }
+
+    #[test]
+    fn keeps_monophonic_melody() {
+        // 和音は最も高い音を残し、重なった音は次の音の位置で切る
+        let notes = [note(60, 0, 4), note(64, 0, 4), note(67, 2, 4)];
+        assert_eq!(transcribe(&notes, options()), "T120 L8 O4EG");
+    }
+
+    #[test]
+    fn gives_very_short_notes_one_grid() {
+        let notes = [RecordedNote {
+            note: 69,
+            start: 0.0,
+            end: 0.01,
+        }];
+        assert_eq!(transcribe(&notes, options()), "T120 L16 O4A");
+    }
+
+    #[test]
+    fn clamps_octave_range() {
+        let notes = [note(12, 0, 4), note(127, 4, 8)];
+        assert_eq!(transcribe(&notes, options()), "T120 L4 O1CO8G");
+    }
+
+    #[test]
+    fn empty_recording_has_only_header() {
+        assert_eq!(transcribe(&[], options()), "T120 L4");
+    }
+
+    #[test]
+    fn output_parses_back_to_same_notes() {
+        let notes = [
+            note(55, 0, 3),
+            note(57, 3, 4),
+            note(59, 4, 8),
+            note(62, 10, 17),
+            note(74, 20, 22),
+            note(50, 22, 40),
+        ];
+        let mml = crate::mml::parse(&transcribe(&notes, options())).unwrap();
+        let mut octave = 4;
+        let mut default_length = 4;
+        let mut beat = 0.0;
+        let mut parsed = Vec::new();
+        for command in &mml {
+            match command {
+                crate::mml::Command::Octave(o) => octave = o.value,
+                crate::mml::Command::OctaveUp => octave += 1,
+                crate::mml::Command::OctaveDown => octave -= 1,
+                crate::mml::Command::DefaultLength(l) => default_length = l.value,
+                crate::mml::Command::Note(n) => {
+                    let beats = n.total_beats(default_length);
+                    parsed.push((n.to_midi_note(octave), beat * 4.0, (beat + beats) * 4.0));
+                    beat += beats;
+                }
+                crate::mml::Command::Rest(r) => beat += r.total_beats(default_length),
+                _ => {}
+            }
+        }
+        let expected: Vec<_> = notes
+            .iter()
+            .map(|n| (n.note, n.start / STEP, n.end / STEP))
+            .collect();
+        assert_eq!(parsed, expected);
+    }
+}
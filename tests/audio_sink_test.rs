@@ -0,0 +1,124 @@
+//! Audio Sink Tests
+//!
+//! `AudioPlayer`を`StreamSink`で再生し、出力されたフレームを検証する。
+
+use sine_mml::audio::player::{AudioPlayer, PlaybackState};
+use sine_mml::audio::sink::StreamSink;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const SAMPLE_RATE: u32 = 44100;
+
+/// 書き込まれたバイト列を共有するライター
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn frames(&self) -> Vec<f32> {
+        self.0
+            .lock()
+            .unwrap()
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 常に失敗するライター（閉じられたパイプ）
+struct BrokenPipe;
+
+impl Write for BrokenPipe {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::BrokenPipe.into())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn wait_until_finished(player: &AudioPlayer) {
+    let state = player.playback_state().expect("not playing");
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !state.lock().unwrap().is_finished() {
+        assert!(Instant::now() < deadline, "playback did not finish");
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+fn ramp(len: u16) -> Vec<f32> {
+    (0..len).map(|i| f32::from(i) / f32::from(len)).collect()
+}
+
+#[test]
+fn test_stream_sink_plays_exact_frames() {
+    let buffer = SharedBuffer::default();
+    let mut player = AudioPlayer::with_sink(Box::new(StreamSink::new(buffer.clone(), SAMPLE_RATE)));
+    let samples = ramp(2000);
+
+    player.play(&samples, false).unwrap();
+    assert!(player.is_playing());
+    wait_until_finished(&player);
+    player.stop();
+
+    assert_eq!(buffer.frames(), samples);
+}
+
+#[test]
+fn test_stream_sink_runs_in_real_time() {
+    let mut player = AudioPlayer::with_sink(Box::new(StreamSink::null(SAMPLE_RATE)));
+    let start = Instant::now();
+    player.play(&vec![0.0; 4410], false).unwrap();
+    wait_until_finished(&player);
+    assert!(start.elapsed() >= Duration::from_millis(90));
+}
+
+#[test]
+fn test_stream_sink_starts_from_prepared_state() {
+    let buffer = SharedBuffer::default();
+    let mut player = AudioPlayer::with_sink(Box::new(StreamSink::new(buffer.clone(), SAMPLE_RATE)));
+    let samples = ramp(3000);
+    let mut state = PlaybackState::new(samples.clone(), false, 0);
+    state.seek(1000);
+
+    player.play_shared(Arc::new(Mutex::new(state))).unwrap();
+    wait_until_finished(&player);
+    player.stop();
+
+    assert_eq!(buffer.frames(), samples[1000..].to_vec());
+}
+
+#[test]
+fn test_stream_sink_can_be_restarted() {
+    let buffer = SharedBuffer::default();
+    let mut player = AudioPlayer::with_sink(Box::new(StreamSink::new(buffer.clone(), SAMPLE_RATE)));
+
+    player.play(&[0.5; 100], false).unwrap();
+    wait_until_finished(&player);
+    player.play(&[0.25; 100], false).unwrap();
+    wait_until_finished(&player);
+    player.stop();
+
+    assert_eq!(buffer.frames(), [[0.5; 100], [0.25; 100]].concat());
+}
+
+#[test]
+fn test_stream_sink_write_error_stops_playback() {
+    let mut player = AudioPlayer::with_sink(Box::new(StreamSink::new(BrokenPipe, SAMPLE_RATE)));
+    player
+        .play(&vec![0.0; SAMPLE_RATE as usize * 10], false)
+        .unwrap();
+    wait_until_finished(&player);
+}
@@ -663,6 +663,8 @@ fn test_mix_metronome_with_tempo_events_single() {
     let events = vec![TempoEvent {
         sample_position: 0,
         bpm: 120,
+        curve: None,
+        meter: None,
     }];
 
     synth.mix_metronome_with_tempo_events(&mut samples, 44100.0, &events, 4, 0.3);
@@ -680,10 +682,14 @@ fn test_mix_metronome_with_tempo_events_tempo_change() {
         TempoEvent {
             sample_position: 0,
             bpm: 60,
+            curve: None,
+            meter: None,
         },
         TempoEvent {
             sample_position: 44100,
             bpm: 120,
+            curve: None,
+            meter: None,
         },
     ];
 
@@ -704,3 +710,344 @@ fn test_mix_metronome_with_tempo_events_empty() {
 
     assert_eq!(samples, original, "Empty events should not modify samples");
 }
+
+// ===== ゲートタイム（Qコマンド） =====
+
+#[test]
+fn test_synthesize_gate_keeps_length_and_adds_silence() {
+    use sine_mml::mml::parse;
+
+    let mut synth = Synthesizer::new(44100, 100, WaveformType::Sine);
+    let full = synth.synthesize(&parse("T120 C4").unwrap()).unwrap();
+    let gated = synth.synthesize(&parse("T120 Q4 C4").unwrap()).unwrap();
+
+    // 全体の長さは変わらない
+    assert_eq!(full.len(), gated.len());
+
+    // 後半は無音、前半は発音
+    let half = gated.len() / 2;
+    assert!(gated[half + 10..].iter().all(|&s| s == 0.0));
+    assert!(gated[..half].iter().any(|&s| s.abs() > 0.001));
+}
+
+#[test]
+fn test_synthesize_gate_percent() {
+    use sine_mml::mml::parse;
+
+    let mut synth = Synthesizer::new(44100, 100, WaveformType::Sine);
+    let samples = synth.synthesize(&parse("T120 Q%25 C4").unwrap()).unwrap();
+    let last_sounding = samples.iter().rposition(|&s| s != 0.0).unwrap();
+
+    // 22050サンプル中、25%（約5512サンプル）のみ発音
+    assert!(
+        last_sounding < 5513,
+        "last sounding sample: {last_sounding}"
+    );
+    assert!(
+        last_sounding > 5000,
+        "last sounding sample: {last_sounding}"
+    );
+}
+
+#[test]
+fn test_synthesize_gate_applies_to_tuplet_notes() {
+    use sine_mml::mml::parse;
+
+    let mut synth = Synthesizer::new(44100, 100, WaveformType::Sine);
+    let full = synth.synthesize(&parse("T120 {CDE}3").unwrap()).unwrap();
+    let gated = synth.synthesize(&parse("T120 Q2 {CDE}3").unwrap()).unwrap();
+
+    assert_eq!(full.len(), gated.len());
+    let silent_full = full.iter().filter(|&&s| s == 0.0).count();
+    let silent_gated = gated.iter().filter(|&&s| s == 0.0).count();
+    assert!(silent_gated > silent_full + full.len() / 2);
+}
+
+// ===== ベロシティ（@vコマンド） =====
+
+fn peak(samples: &[f32]) -> f32 {
+    samples.iter().fold(0.0_f32, |acc, &s| acc.max(s.abs()))
+}
+
+#[test]
+fn test_synthesize_velocity_absolute_changes_level() {
+    use sine_mml::mml::parse;
+
+    let mut synth = Synthesizer::new(44100, 100, WaveformType::Sine);
+    let soft = synth.synthesize(&parse("T120 @v20 C4").unwrap()).unwrap();
+    let loud = synth.synthesize(&parse("T120 @v127 C4").unwrap()).unwrap();
+
+    assert_eq!(soft.len(), loud.len());
+    assert!(peak(&loud) > peak(&soft) * 3.0);
+}
+
+#[test]
+fn test_synthesize_velocity_ramp_crescendo() {
+    use sine_mml::mml::parse;
+
+    let mut synth = Synthesizer::new(44100, 100, WaveformType::Sine);
+    let samples = synth
+        .synthesize(&parse("T120 L4 @v10>127:4 CCCC").unwrap())
+        .unwrap();
+
+    let quarter = samples.len() / 4;
+    let peaks: Vec<f32> = samples.chunks(quarter).take(4).map(peak).collect();
+    assert!(
+        peaks.windows(2).all(|w| w[1] > w[0]),
+        "peaks should increase: {peaks:?}"
+    );
+}
+
+#[test]
+fn test_synthesize_volume_command_clears_velocity() {
+    use sine_mml::mml::parse;
+
+    let mut synth = Synthesizer::new(44100, 100, WaveformType::Sine);
+    let plain = synth.synthesize(&parse("T120 V10 C4").unwrap()).unwrap();
+    let reset = synth
+        .synthesize(&parse("T120 @v20 V10 C4").unwrap())
+        .unwrap();
+
+    assert_eq!(plain, reset);
+}
+
+// ===== テンポ変化（Tn>m:b） =====
+
+#[test]
+#[allow(clippy::cast_precision_loss)]
+fn test_synthesize_tempo_ramp_length_matches_integral() {
+    use sine_mml::mml::parse;
+
+    let mut synth = Synthesizer::new(44100, 100, WaveformType::Sine);
+    let samples = synth
+        .synthesize(&parse("T120>60:4 L4 CCCC").unwrap())
+        .unwrap();
+
+    // 120→60を4拍: 4*ln2 ≒ 2.7726秒（一定テンポ120なら2秒、60なら4秒）
+    let expected = 4.0 * 2.0_f64.ln() * 44100.0;
+    assert!(
+        (samples.len() as f64 - expected).abs() < 4.0,
+        "len {} expected {expected}",
+        samples.len()
+    );
+}
+
+#[test]
+fn test_synthesize_tempo_ramp_notes_get_longer() {
+    use sine_mml::mml::parse;
+
+    let mut synth = Synthesizer::new(44100, 100, WaveformType::Sine);
+    let first = synth.synthesize(&parse("T120>60:4 L4 C").unwrap()).unwrap();
+    let fourth = synth
+        .synthesize(&parse("T120>60:4 L4 RRR C").unwrap())
+        .unwrap();
+    let fourth_note = fourth.len()
+        - synth
+            .synthesize(&parse("T120>60:4 L4 RRR").unwrap())
+            .unwrap()
+            .len();
+
+    // リタルダンドなので後の音符ほど長い
+    assert!(first.len() > 22050);
+    assert!(fourth_note > first.len());
+    assert!(fourth_note < 44100);
+}
+
+#[test]
+fn test_tempo_events_match_synthesized_length() {
+    use sine_mml::mml::parse;
+
+    let mut synth = Synthesizer::new(44100, 100, WaveformType::Sine);
+    let head = parse("T100 L8 CDE {CDE}3 T100>180:6 CDEFGAB>C {CD{EF}2}3").unwrap();
+    let head_len = synth.synthesize(&head).unwrap().len();
+
+    let whole = parse("T100 L8 CDE {CDE}3 T100>180:6 CDEFGAB>C {CD{EF}2}3 T90 C").unwrap();
+    let events = whole.get_tempo_events(44100);
+
+    assert_eq!(events.last().unwrap().bpm, 90);
+    assert_eq!(events.last().unwrap().sample_position, head_len);
+}
+
+#[test]
+#[allow(
+    clippy::float_cmp,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+fn test_mix_metronome_with_tempo_ramp_clicks_on_beats() {
+    use sine_mml::mml::{TempoCurve, TempoState};
+
+    let synth = Synthesizer::new(44100, 100, WaveformType::Sine);
+    let curve = TempoCurve::new(120.0, 60.0, 0.0, 4.0);
+    let events = vec![TempoEvent {
+        sample_position: 0,
+        bpm: 120,
+        curve: Some(curve),
+        meter: None,
+    }];
+    let mut samples = vec![0.0; 44100 * 4];
+    synth.mix_metronome_with_tempo_events(&mut samples, 44100.0, &events, 4, 0.3);
+
+    let tempo = TempoState {
+        bpm: 60.0,
+        curve: Some(curve),
+        swing: None,
+    };
+    for beat in 1..4_u8 {
+        let position = (tempo.seconds(0.0, f64::from(beat)) * 44100.0) as usize;
+        assert_ne!(samples[position], 0.0, "Click at beat {beat}");
+        // 一定テンポ120の位置にはクリックがない
+        assert_eq!(samples[22050 * usize::from(beat)], 0.0);
+    }
+}
+
+// ===== スウィング（@S） =====
+
+#[test]
+fn test_synthesize_swing_lengthens_onbeat_eighths() {
+    use sine_mml::mml::parse;
+
+    let mut synth = Synthesizer::new(44100, 100, WaveformType::Sine);
+    let first = synth.synthesize(&parse("T120 @S60 L8 C").unwrap()).unwrap();
+    let pair = synth
+        .synthesize(&parse("T120 @S60 L8 CC").unwrap())
+        .unwrap();
+
+    // 1拍（22050サンプル）を60:40に分割
+    assert_eq!(first.len(), 13230);
+    assert_eq!(pair.len(), 22050);
+}
+
+#[test]
+fn test_synthesize_swing_keeps_bar_length() {
+    use sine_mml::mml::parse;
+
+    let mut synth = Synthesizer::new(44100, 100, WaveformType::Sine);
+    let straight = synth
+        .synthesize(&parse("T120 L8 CDEFGAB>C C4 {CDE}3:4 C.C16").unwrap())
+        .unwrap();
+    let swung = synth
+        .synthesize(&parse("T120 @S66 L8 CDEFGAB>C C4 {CDE}3:4 C.C16").unwrap())
+        .unwrap();
+
+    // 差は音符ごとのサンプル切り捨て分のみ
+    assert!(
+        straight.len().abs_diff(swung.len()) <= 16,
+        "{} vs {}",
+        straight.len(),
+        swung.len()
+    );
+}
+
+// ===== 拍子（@M）による小節頭の強調 =====
+
+fn click_peak(samples: &[f32], position: usize) -> f32 {
+    peak(&samples[position..position + 1000])
+}
+
+#[test]
+fn test_mix_metronome_accents_downbeats() {
+    use sine_mml::mml::{Meter, TimeSignature};
+
+    let synth = Synthesizer::new(44100, 100, WaveformType::Sine);
+    let events = vec![TempoEvent {
+        sample_position: 0,
+        bpm: 120,
+        curve: None,
+        meter: Some(Meter {
+            time_signature: TimeSignature {
+                numerator: 3,
+                denominator: 4,
+            },
+            beat_in_bar: 0.0,
+        }),
+    }];
+    let mut samples = vec![0.0; 44100 * 2];
+    synth.mix_metronome_with_tempo_events(&mut samples, 44100.0, &events, 4, 0.3);
+
+    let downbeat = click_peak(&samples, 0);
+    let second = click_peak(&samples, 22050);
+    let third = click_peak(&samples, 44100);
+    let next_downbeat = click_peak(&samples, 66150);
+
+    assert!(downbeat > second * 1.5);
+    assert!((second - third).abs() < 1e-6);
+    assert!((downbeat - next_downbeat).abs() < 1e-6);
+}
+
+#[test]
+fn test_mix_metronome_without_meter_has_no_accent() {
+    let synth = Synthesizer::new(44100, 100, WaveformType::Sine);
+    let events = vec![TempoEvent {
+        sample_position: 0,
+        bpm: 120,
+        curve: None,
+        meter: None,
+    }];
+    let mut samples = vec![0.0; 44100];
+    synth.mix_metronome_with_tempo_events(&mut samples, 44100.0, &events, 4, 0.3);
+
+    assert!((click_peak(&samples, 0) - click_peak(&samples, 22050)).abs() < 1e-6);
+}
+
+// ===== カウントイン・小節頭のクリック音色 =====
+
+#[test]
+fn test_generate_accent_click_differs_from_noise_click() {
+    use sine_mml::audio::synthesizer::{generate_accent_click, generate_noise_click};
+
+    let accent = generate_accent_click(44100.0, 0.5);
+    let click = generate_noise_click(44100.0, 0.5);
+
+    assert_eq!(accent.len(), 1323);
+    assert!(peak(&accent) <= 0.5);
+    assert!(peak(&accent) > 0.4);
+    assert_ne!(accent[..click.len()], click[..]);
+    // 減衰して終わる
+    assert!(peak(&accent[1200..]) < 0.01);
+}
+
+#[test]
+fn test_generate_count_in_length_follows_first_tempo_and_meter() {
+    let synth = Synthesizer::new(44100, 100, WaveformType::Sine);
+
+    let events = sine_mml::mml::parse("T120 C")
+        .unwrap()
+        .get_tempo_events(44100);
+    let count_in = synth.generate_count_in(44100.0, &events, 2, 4, 0.3);
+    assert_eq!(count_in.len(), 44100 * 4);
+
+    let events = sine_mml::mml::parse("@M3/4 T60 C")
+        .unwrap()
+        .get_tempo_events(44100);
+    let count_in = synth.generate_count_in(44100.0, &events, 1, 4, 0.3);
+    assert_eq!(count_in.len(), 44100 * 3);
+
+    // 小節の頭だけ強調される
+    let downbeat = click_peak(&count_in, 0);
+    let second = click_peak(&count_in, 44100);
+    assert!(downbeat > second);
+    assert!((second - click_peak(&count_in, 88200)).abs() < 1e-6);
+}
+
+#[test]
+fn test_generate_count_in_zero_bars_is_empty() {
+    let synth = Synthesizer::new(44100, 100, WaveformType::Sine);
+    let events = sine_mml::mml::parse("C").unwrap().get_tempo_events(44100);
+    assert!(synth
+        .generate_count_in(44100.0, &events, 0, 4, 0.3)
+        .is_empty());
+}
+
+#[test]
+fn test_metronome_accents_downbeats_by_default() {
+    let synth = Synthesizer::new(44100, 100, WaveformType::Sine);
+    let events = sine_mml::mml::parse("T120 C1 C1")
+        .unwrap()
+        .get_tempo_events(44100);
+    let mut samples = vec![0.0; 44100 * 4];
+    synth.mix_metronome_with_tempo_events(&mut samples, 44100.0, &events, 4, 0.3);
+
+    assert!(click_peak(&samples, 0) > click_peak(&samples, 22050));
+    assert!((click_peak(&samples, 0) - click_peak(&samples, 88200)).abs() < 1e-6);
+}
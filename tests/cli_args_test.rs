@@ -3,7 +3,8 @@
 //! Moved from src/cli/args.rs to reduce module size
 
 use sine_mml::cli::args::{
-    validate_note, validate_volume, Cli, Command, Waveform, MAX_NOTE_LENGTH,
+    validate_note, validate_volume, AudioSinkKind, AudioSubcommand, Cli, Command, Waveform,
+    MAX_NOTE_LENGTH,
 };
 
 #[cfg(feature = "midi-output")]
@@ -319,6 +320,200 @@ fn test_midi_combined_options() {
     assert_eq!(args.midi_channel, 10);
 }
 
+#[cfg(feature = "midi-output")]
+#[test]
+fn test_midi_clock_requires_midi_out() {
+    let result = Cli::try_parse_from(["sine-mml", "play", "CDE", "--midi-clock"]);
+    assert!(result.is_err());
+
+    let result =
+        Cli::try_parse_from(["sine-mml", "play", "CDE", "--midi-out", "0", "--midi-clock"]);
+    let Command::Play(args) = result.unwrap().command else {
+        panic!("Unexpected command")
+    };
+    assert!(args.midi_clock);
+}
+
+#[cfg(feature = "midi-output")]
+#[test]
+fn test_midi_virtual_option() {
+    let result = Cli::try_parse_from([
+        "sine-mml",
+        "play",
+        "CDE",
+        "--midi-virtual",
+        "DAW",
+        "--midi-clock",
+    ]);
+    let Command::Play(args) = result.unwrap().command else {
+        panic!("Unexpected command")
+    };
+    assert_eq!(args.midi_virtual, Some("DAW".to_string()));
+    assert_eq!(args.midi_out, None);
+    assert!(args.midi_clock);
+}
+
+#[cfg(feature = "midi-output")]
+#[test]
+fn test_midi_virtual_conflicts_with_midi_out() {
+    let result = Cli::try_parse_from([
+        "sine-mml",
+        "play",
+        "CDE",
+        "--midi-virtual",
+        "DAW",
+        "--midi-out",
+        "0",
+    ]);
+    assert!(result.is_err());
+}
+
+#[cfg(feature = "midi-output")]
+#[test]
+fn test_with_audio_and_midi_offset() {
+    let result = Cli::try_parse_from([
+        "sine-mml",
+        "play",
+        "CDE",
+        "--midi-out",
+        "0",
+        "--with-audio",
+        "--midi-offset-ms",
+        "-25",
+    ]);
+    let Command::Play(args) = result.unwrap().command else {
+        panic!("Unexpected command")
+    };
+    assert!(args.with_audio);
+    assert_eq!(args.midi_offset_ms, -25);
+}
+
+#[cfg(feature = "midi-output")]
+#[test]
+fn test_with_audio_requires_midi_output() {
+    for argv in [
+        vec!["sine-mml", "play", "CDE", "--with-audio"],
+        vec![
+            "sine-mml",
+            "play",
+            "CDE",
+            "--midi-out",
+            "0",
+            "--midi-offset-ms",
+            "10",
+        ],
+        vec![
+            "sine-mml",
+            "play",
+            "CDE",
+            "--midi-out",
+            "0",
+            "--with-audio",
+            "--midi-offset-ms",
+            "1001",
+        ],
+        vec![
+            "sine-mml",
+            "play",
+            "CDE",
+            "--midi-out",
+            "0",
+            "--with-audio",
+            "--count-in",
+            "1",
+        ],
+    ] {
+        assert!(Cli::try_parse_from(&argv).is_err(), "{argv:?}");
+    }
+}
+
+#[cfg(feature = "midi-output")]
+#[test]
+fn test_midi_clock_in_conflicts_with_positioning() {
+    for extra in [
+        ["--from", "2"],
+        ["--count-in", "1"],
+        ["--loop-play", "--metronome"],
+    ] {
+        let mut argv = vec!["sine-mml", "play", "CDE", "--midi-clock-in", "0"];
+        argv.extend(extra);
+        assert!(
+            Cli::try_parse_from(argv).is_err(),
+            "{extra:?} should conflict"
+        );
+    }
+
+    let result = Cli::try_parse_from([
+        "sine-mml",
+        "play",
+        "CDE",
+        "--midi-clock-in",
+        "0",
+        "--midi-out",
+        "1",
+    ]);
+    let Command::Play(args) = result.unwrap().command else {
+        panic!("Unexpected command")
+    };
+    assert_eq!(args.midi_clock_in.as_deref(), Some("0"));
+}
+
+#[cfg(feature = "midi-output")]
+#[test]
+fn test_record_args() {
+    let result = Cli::try_parse_from(["sine-mml", "record", "--midi-in", "0"]);
+    let Command::Record(args) = result.unwrap().command else {
+        panic!("Unexpected command")
+    };
+    assert_eq!(args.midi_in, "0");
+    assert_eq!(args.tempo, 120);
+    assert_eq!(args.grid, 16);
+    assert!(args.output.is_none());
+
+    let result = Cli::try_parse_from([
+        "sine-mml",
+        "record",
+        "--midi-in",
+        "0",
+        "-t",
+        "90",
+        "--grid",
+        "8",
+        "-o",
+        "a.mml",
+    ]);
+    let Command::Record(args) = result.unwrap().command else {
+        panic!("Unexpected command")
+    };
+    assert_eq!(args.tempo, 90);
+    assert_eq!(args.grid, 8);
+    assert_eq!(args.output.as_deref(), Some("a.mml"));
+}
+
+#[cfg(feature = "midi-output")]
+#[test]
+fn test_record_rejects_invalid_grid_and_tempo() {
+    for extra in [["--grid", "12"], ["--tempo", "20"]] {
+        let mut argv = vec!["sine-mml", "record", "--midi-in", "0"];
+        argv.extend(extra);
+        assert!(
+            Cli::try_parse_from(argv).is_err(),
+            "{extra:?} should be rejected"
+        );
+    }
+    let result = Cli::try_parse_from([
+        "sine-mml",
+        "record",
+        "--midi-in",
+        "0",
+        "-o",
+        "a.mml",
+        "--note",
+        "memo",
+    ]);
+    assert!(result.is_err());
+}
+
 #[test]
 fn test_no_history_long_flag() {
     let result = Cli::try_parse_from(["sine-mml", "play", "CDE", "--no-history"]);
@@ -372,3 +567,264 @@ fn test_no_history_with_loop_play() {
     assert!(args.no_history);
     assert!(args.loop_play);
 }
+
+#[test]
+fn test_count_in_option() {
+    let cli = Cli::try_parse_from(["sine-mml", "play", "CDE", "--count-in", "2"]).unwrap();
+    let Command::Play(args) = cli.command else {
+        panic!("Expected Play command");
+    };
+    assert_eq!(args.count_in, 2);
+
+    assert!(Cli::try_parse_from(["sine-mml", "play", "CDE", "--count-in", "17"]).is_err());
+}
+
+#[test]
+fn test_export_metronome_options() {
+    let cli = Cli::try_parse_from([
+        "sine-mml",
+        "export",
+        "--history-id",
+        "1",
+        "-o",
+        "out.wav",
+        "--metronome",
+        "--metronome-beat",
+        "8",
+        "--count-in",
+        "1",
+    ])
+    .unwrap();
+    let Command::Export(args) = cli.command else {
+        panic!("Expected Export command");
+    };
+    assert!(args.metronome);
+    assert_eq!(args.metronome_beat, 8);
+    assert!((args.metronome_volume - 0.3).abs() < f32::EPSILON);
+    assert_eq!(args.count_in, 1);
+}
+
+#[test]
+fn test_max_duration_option() {
+    let cli = Cli::try_parse_from(["sine-mml", "play", "CDE"]).unwrap();
+    let Command::Play(args) = cli.command else {
+        panic!("Expected Play command");
+    };
+    assert_eq!(args.max_duration, 3600);
+
+    let cli = Cli::try_parse_from(["sine-mml", "play", "CDE", "--max-duration", "0"]).unwrap();
+    let Command::Play(args) = cli.command else {
+        panic!("Expected Play command");
+    };
+    assert_eq!(args.max_duration, 0);
+
+    let cli = Cli::try_parse_from([
+        "sine-mml",
+        "export",
+        "--history-id",
+        "1",
+        "-o",
+        "out.wav",
+        "--max-duration",
+        "60",
+    ])
+    .unwrap();
+    let Command::Export(args) = cli.command else {
+        panic!("Expected Export command");
+    };
+    assert_eq!(args.max_duration, 60);
+}
+
+#[test]
+fn test_section_option() {
+    let cli =
+        Cli::try_parse_from(["sine-mml", "play", "-f", "song.mml", "--section", "verse"]).unwrap();
+    let Command::Play(args) = cli.command else {
+        panic!("Expected Play command");
+    };
+    assert_eq!(args.section, Some("verse".to_string()));
+
+    // --section は --file 専用
+    let result = Cli::try_parse_from(["sine-mml", "play", "CDE", "--section", "verse"]);
+    assert_eq!(
+        result.unwrap_err().kind(),
+        clap::error::ErrorKind::ArgumentConflict
+    );
+}
+
+#[test]
+fn test_export_file_option() {
+    let cli = Cli::try_parse_from([
+        "sine-mml",
+        "export",
+        "-f",
+        "song.mml",
+        "--section",
+        "intro",
+        "-o",
+        "intro.wav",
+        "-w",
+        "square",
+    ])
+    .unwrap();
+    let Command::Export(args) = cli.command else {
+        panic!("Expected Export command");
+    };
+    assert_eq!(args.history_id, None);
+    assert_eq!(args.file, Some("song.mml".to_string()));
+    assert_eq!(args.section, Some("intro".to_string()));
+    assert!(matches!(args.waveform, Some(Waveform::Square)));
+    assert_eq!(args.volume, None);
+
+    // 入力ソースはどちらか一方が必須
+    let result = Cli::try_parse_from(["sine-mml", "export", "-o", "out.wav"]);
+    assert_eq!(
+        result.unwrap_err().kind(),
+        clap::error::ErrorKind::MissingRequiredArgument
+    );
+    let result = Cli::try_parse_from([
+        "sine-mml",
+        "export",
+        "--history-id",
+        "1",
+        "-f",
+        "song.mml",
+        "-o",
+        "out.wav",
+    ]);
+    assert_eq!(
+        result.unwrap_err().kind(),
+        clap::error::ErrorKind::ArgumentConflict
+    );
+}
+
+#[test]
+fn test_from_to_options() {
+    use sine_mml::mml::PlayPosition;
+
+    let cli = Cli::try_parse_from([
+        "sine-mml", "play", "CDE", "--from", "chorus", "--to", "1:30",
+    ])
+    .unwrap();
+    let Command::Play(args) = cli.command else {
+        panic!("Expected Play command");
+    };
+    assert_eq!(args.from, Some(PlayPosition::Marker("chorus".to_string())));
+    assert_eq!(args.to, Some(PlayPosition::Time(90.0)));
+
+    let cli = Cli::try_parse_from([
+        "sine-mml",
+        "export",
+        "--history-id",
+        "1",
+        "-o",
+        "out.wav",
+        "--from",
+        "5",
+    ])
+    .unwrap();
+    let Command::Export(args) = cli.command else {
+        panic!("Expected Export command");
+    };
+    assert_eq!(args.from, Some(PlayPosition::Bar(5)));
+    assert_eq!(args.to, None);
+
+    let result = Cli::try_parse_from(["sine-mml", "play", "CDE", "--from", "0"]);
+    assert_eq!(
+        result.unwrap_err().kind(),
+        clap::error::ErrorKind::ValueValidation
+    );
+}
+
+#[test]
+fn test_watch_options() {
+    let cli = Cli::try_parse_from(["sine-mml", "play", "-f", "song.mml", "--watch"]).unwrap();
+    let Command::Play(args) = cli.command else {
+        panic!("Expected Play command");
+    };
+    assert!(args.watch);
+    assert!(!args.watch_from_start);
+
+    // --watch は --file 専用
+    let result = Cli::try_parse_from(["sine-mml", "play", "CDE", "--watch"]);
+    assert_eq!(
+        result.unwrap_err().kind(),
+        clap::error::ErrorKind::ArgumentConflict
+    );
+
+    // --watch-from-start は --watch と併用
+    let result = Cli::try_parse_from(["sine-mml", "play", "-f", "song.mml", "--watch-from-start"]);
+    assert_eq!(
+        result.unwrap_err().kind(),
+        clap::error::ErrorKind::MissingRequiredArgument
+    );
+}
+
+#[test]
+fn test_repl_command() {
+    let cli = Cli::try_parse_from(["sine-mml", "repl", "-w", "square", "-v", "0.5"]).unwrap();
+    let Command::Repl(args) = cli.command else {
+        panic!("Expected Repl command");
+    };
+    assert_eq!(args.waveform, Waveform::Square);
+    assert!((args.volume - 0.5).abs() < f32::EPSILON);
+}
+
+#[test]
+fn test_audio_device_options() {
+    let cli = Cli::try_parse_from([
+        "sine-mml",
+        "play",
+        "CDE",
+        "--audio-device",
+        "USB",
+        "--audio-host",
+        "ALSA",
+    ])
+    .unwrap();
+    let Command::Play(args) = cli.command else {
+        panic!("Expected Play command");
+    };
+    assert_eq!(args.audio_device.as_deref(), Some("USB"));
+    assert_eq!(args.audio_host.as_deref(), Some("ALSA"));
+
+    let cli = Cli::try_parse_from(["sine-mml", "audio", "list", "--audio-host", "jack"]).unwrap();
+    let Command::Audio(args) = cli.command else {
+        panic!("Expected Audio command");
+    };
+    let AudioSubcommand::List { audio_host } = args.command;
+    assert_eq!(audio_host.as_deref(), Some("jack"));
+}
+
+#[test]
+fn test_audio_sink_options() {
+    let cli = Cli::try_parse_from(["sine-mml", "play", "CDE"]).unwrap();
+    let Command::Play(args) = cli.command else {
+        panic!("Expected Play command");
+    };
+    assert_eq!(args.audio_sink, AudioSinkKind::Device);
+
+    let cli = Cli::try_parse_from(["sine-mml", "play", "CDE", "--audio-sink", "null"]).unwrap();
+    let Command::Play(args) = cli.command else {
+        panic!("Expected Play command");
+    };
+    assert_eq!(args.audio_sink, AudioSinkKind::Null);
+
+    // file には --pcm-file が必要
+    assert!(Cli::try_parse_from(["sine-mml", "play", "CDE", "--audio-sink", "file"]).is_err());
+    let cli = Cli::try_parse_from([
+        "sine-mml",
+        "play",
+        "CDE",
+        "--audio-sink",
+        "file",
+        "--pcm-file",
+        "out.pcm",
+    ])
+    .unwrap();
+    let Command::Play(args) = cli.command else {
+        panic!("Expected Play command");
+    };
+    assert_eq!(args.audio_sink, AudioSinkKind::File);
+    assert_eq!(args.pcm_file.as_deref(), Some("out.pcm"));
+}
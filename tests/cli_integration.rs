@@ -417,15 +417,15 @@ fn test_cli_nested_loop_6_levels_error() {
         .stderr(predicate::str::contains("LoopNestTooDeep"));
 }
 
-/// TC-029-E-004: ループ展開数超過エラー
+/// TC-029-E-004: ループ展開後の演奏時間超過エラー
 #[test]
-fn test_cli_loop_expanded_too_large() {
+fn test_cli_loop_duration_too_long() {
     let mut cmd = Command::new(env!("CARGO_BIN_EXE_sine-mml"));
     cmd.arg("play").arg("[ [ [ C ]99 ]99 ]99");
 
     cmd.assert()
         .failure()
-        .stderr(predicate::str::contains("LoopExpandedTooLarge"));
+        .stderr(predicate::str::contains("DurationTooLong"));
 }
 
 // ----------------------------------------------------------------------------
@@ -452,3 +452,14 @@ fn test_cli_integration_file_volume_loop() {
 
     cmd.assert().code(predicate::in_iter([0i32]));
 }
+
+/// ループ展開後の演奏時間の上限は --max-duration で変更できる
+#[test]
+fn test_cli_max_duration_option() {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_sine-mml"));
+    cmd.arg("play").arg("[C]21").arg("--max-duration").arg("10");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("DurationTooLong"));
+}
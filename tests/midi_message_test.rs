@@ -3,10 +3,11 @@
 //! Tests extracted from src/midi/message.rs for 500-line rule compliance.
 
 use sine_mml::midi::message::{
-    build_all_notes_off_message, build_note_off_message, build_note_on_message, mml_to_midi_note,
-    mml_volume_to_velocity, MidiMessage,
+    build_all_notes_off_message, build_note_off_message, build_note_on_message,
+    build_song_position_message, mml_to_midi_note, mml_volume_to_velocity, MidiMessage,
+    MidiRealtime,
 };
-use sine_mml::mml::{Accidental, Pitch};
+use sine_mml::mml::{Accidental, MidiControl, Pitch};
 
 // ============================================================
 // MidiMessage Tests (existing)
@@ -106,6 +107,131 @@ fn test_to_bytes_clamps_velocity() {
     assert_eq!(bytes, [0x90, 60, 72]);
 }
 
+// ============================================================
+// Channel Voice Message Tests
+// ============================================================
+
+#[test]
+fn test_control_change_to_bytes() {
+    let msg = MidiMessage::control_change(3, 7, 100);
+    assert_eq!(msg.to_bytes(), [0xB2, 7, 100]);
+    assert_eq!(msg.to_packets(), vec![vec![0xB2, 7, 100]]);
+}
+
+#[test]
+fn test_program_change_is_two_bytes() {
+    assert_eq!(MidiMessage::program_change(1, 5).to_bytes(), [0xC0, 5]);
+    assert_eq!(MidiMessage::program_change(16, 200).to_bytes(), [0xCF, 72]);
+}
+
+#[test]
+fn test_channel_pressure_is_two_bytes() {
+    assert_eq!(MidiMessage::channel_pressure(10, 64).to_bytes(), [0xD9, 64]);
+}
+
+#[test]
+fn test_poly_pressure_to_bytes() {
+    let msg = MidiMessage::PolyPressure {
+        channel: 2,
+        note: 60,
+        pressure: 90,
+    };
+    assert_eq!(msg.to_bytes(), [0xA1, 60, 90]);
+}
+
+#[test]
+fn test_pitch_bend_to_bytes() {
+    // 中央は0x2000（LSB, MSBの順）
+    assert_eq!(MidiMessage::pitch_bend(1, 0).to_bytes(), [0xE0, 0x00, 0x40]);
+    assert_eq!(
+        MidiMessage::pitch_bend(1, -8192).to_bytes(),
+        [0xE0, 0x00, 0x00]
+    );
+    assert_eq!(
+        MidiMessage::pitch_bend(1, 8191).to_bytes(),
+        [0xE0, 0x7F, 0x7F]
+    );
+    assert_eq!(MidiMessage::pitch_bend(2, 1).to_bytes(), [0xE1, 0x01, 0x40]);
+}
+
+#[test]
+fn test_pitch_bend_clamps_value() {
+    assert_eq!(
+        MidiMessage::pitch_bend(1, i16::MAX),
+        MidiMessage::PitchBend {
+            channel: 1,
+            value: 8191
+        }
+    );
+    assert_eq!(
+        MidiMessage::pitch_bend(1, i16::MIN).to_bytes(),
+        [0xE0, 0, 0]
+    );
+}
+
+#[test]
+fn test_bank_select_packets() {
+    let msg = MidiMessage::bank_select(1, 1, Some(2));
+    assert_eq!(msg.to_packets(), vec![vec![0xB0, 0, 1], vec![0xB0, 32, 2]]);
+    assert_eq!(msg.to_bytes(), [0xB0, 0, 1, 0xB0, 32, 2]);
+
+    let msg = MidiMessage::bank_select(5, 3, None);
+    assert_eq!(msg.to_packets(), vec![vec![0xB4, 0, 3]]);
+}
+
+#[test]
+fn test_message_channel() {
+    assert_eq!(MidiMessage::note_on(4, 60, 100).channel(), 4);
+    assert_eq!(MidiMessage::bank_select(9, 0, None).channel(), 9);
+    assert_eq!(MidiMessage::all_notes_off(16).channel(), 16);
+}
+
+#[test]
+fn test_from_control() {
+    assert_eq!(
+        MidiMessage::from_control(2, MidiControl::ProgramChange(10)),
+        MidiMessage::program_change(2, 10)
+    );
+    assert_eq!(
+        MidiMessage::from_control(
+            2,
+            MidiControl::ControlChange {
+                controller: 64,
+                value: 127
+            }
+        ),
+        MidiMessage::control_change(2, 64, 127)
+    );
+    assert_eq!(
+        MidiMessage::from_control(2, MidiControl::ChannelPressure(3)),
+        MidiMessage::channel_pressure(2, 3)
+    );
+    assert_eq!(
+        MidiMessage::from_control(2, MidiControl::PitchBend(-100)),
+        MidiMessage::pitch_bend(2, -100)
+    );
+    assert_eq!(
+        MidiMessage::from_control(2, MidiControl::BankSelect { msb: 1, lsb: None }),
+        MidiMessage::bank_select(2, 1, None)
+    );
+}
+
+#[test]
+fn test_realtime_to_byte() {
+    assert_eq!(MidiRealtime::TimingClock.to_byte(), 0xF8);
+    assert_eq!(MidiRealtime::Start.to_byte(), 0xFA);
+    assert_eq!(MidiRealtime::Continue.to_byte(), 0xFB);
+    assert_eq!(MidiRealtime::Stop.to_byte(), 0xFC);
+}
+
+#[test]
+fn test_build_song_position_message() {
+    assert_eq!(build_song_position_message(0), [0xF2, 0, 0]);
+    // 200 = 1 * 128 + 72
+    assert_eq!(build_song_position_message(200), [0xF2, 72, 1]);
+    assert_eq!(build_song_position_message(0x3FFF), [0xF2, 0x7F, 0x7F]);
+}
+
 // ============================================================
 // Build Functions Tests (TC-031-U-001)
 // ============================================================
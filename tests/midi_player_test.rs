@@ -300,3 +300,322 @@ fn test_calculate_total_duration_ms_loop_with_escape() {
         "Expected ~2500ms, got {duration}ms"
     );
 }
+
+#[test]
+#[allow(clippy::float_cmp)]
+fn test_playback_state_default_gate_is_full() {
+    let state = PlaybackState::default();
+    assert_eq!(state.gate, 1.0);
+}
+
+#[test]
+#[allow(clippy::float_cmp)]
+fn test_playback_state_update_gate() {
+    use sine_mml::mml::{Gate, GateValue};
+
+    let mut state = PlaybackState::default();
+    state.update_state(&Command::Gate(Gate {
+        value: GateValue::Eighths(6),
+    }));
+    assert_eq!(state.gate, 0.75);
+
+    state.update_state(&Command::Gate(Gate {
+        value: GateValue::Percent(30),
+    }));
+    assert_eq!(state.gate, 0.3);
+}
+
+#[test]
+fn test_calculate_total_duration_ms_gate_does_not_change_timing() {
+    use sine_mml::mml::parse;
+    let full = calculate_total_duration_ms(&parse("T120 L4 CDEF").unwrap().commands);
+    let gated = calculate_total_duration_ms(&parse("T120 L4 Q2 CDEF").unwrap().commands);
+    assert_eq!(full, gated);
+}
+
+#[test]
+fn test_playback_state_velocity_follows_volume_by_default() {
+    let mut state = PlaybackState::default();
+    state.volume = 15;
+    assert_eq!(state.velocity(), 127);
+}
+
+#[test]
+fn test_playback_state_velocity_absolute() {
+    use sine_mml::mml::{Velocity, VelocityValue};
+
+    let mut state = PlaybackState::default();
+    state.update_state(&Command::Velocity(Velocity {
+        value: VelocityValue::Absolute(100),
+    }));
+    assert_eq!(state.velocity(), 100);
+}
+
+#[test]
+fn test_playback_state_velocity_ramp_interpolates_by_beat() {
+    use sine_mml::mml::{Velocity, VelocityValue};
+
+    let mut state = PlaybackState::default();
+    state.update_state(&Command::Velocity(Velocity {
+        value: VelocityValue::Ramp {
+            from: Some(40),
+            to: 120,
+            beats: 4,
+        },
+    }));
+    assert_eq!(state.velocity(), 40);
+
+    state.beat_position = 2.0;
+    assert_eq!(state.velocity(), 80);
+
+    state.beat_position = 10.0;
+    assert_eq!(state.velocity(), 120);
+}
+
+#[test]
+fn test_playback_state_volume_clears_velocity() {
+    use sine_mml::mml::{Velocity, VelocityValue, Volume};
+
+    let mut state = PlaybackState::default();
+    state.update_state(&Command::Velocity(Velocity {
+        value: VelocityValue::Absolute(100),
+    }));
+    state.update_state(&Command::Volume(Volume {
+        value: VolumeValue::Absolute(15),
+    }));
+    assert_eq!(state.velocity(), 127);
+}
+
+#[test]
+fn test_calculate_total_duration_ms_tempo_ramp() {
+    use sine_mml::mml::parse;
+    // 120→60を4拍: 4*ln2 ≒ 2773ms
+    let duration = calculate_total_duration_ms(&parse("T120>60:4 L4 CDEF").unwrap().commands);
+    assert!(
+        (duration as i64 - 2773).abs() <= 2,
+        "Expected ~2773ms, got {duration}ms"
+    );
+}
+
+#[test]
+fn test_calculate_total_duration_ms_tempo_ramp_holds_end_tempo() {
+    use sine_mml::mml::parse;
+    let ramp_only = calculate_total_duration_ms(&parse("T120>60:4 L4 CDEF").unwrap().commands);
+    let with_tail = calculate_total_duration_ms(&parse("T120>60:4 L4 CDEF G").unwrap().commands);
+    assert!(
+        (with_tail as i64 - ramp_only as i64 - 1000).abs() <= 1,
+        "tail should be played at 60BPM"
+    );
+}
+
+#[test]
+#[allow(clippy::float_cmp)]
+fn test_playback_state_tempo_ramp_advance() {
+    use sine_mml::mml::{Tempo, TempoRamp};
+
+    let mut state = PlaybackState::default();
+    state.update_state(&Command::TempoRamp(TempoRamp {
+        from: Some(120),
+        to: 60,
+        beats: 4,
+    }));
+    assert_eq!(state.bpm, 60);
+
+    let first = state.advance(1.0);
+    let second = state.advance(1.0);
+    assert!(first > 0.5 && second > first);
+
+    state.update_state(&Command::Tempo(Tempo { value: 120 }));
+    assert_eq!(state.advance(1.0), 0.5);
+}
+
+#[test]
+fn test_calculate_total_duration_ms_swing_keeps_length() {
+    use sine_mml::mml::parse;
+    let straight = calculate_total_duration_ms(&parse("T120 L8 CDEFGAB>C").unwrap().commands);
+    let swung = calculate_total_duration_ms(&parse("T120 @S66 L8 CDEFGAB>C").unwrap().commands);
+    assert!((straight as i64 - swung as i64).abs() <= 1);
+}
+
+#[test]
+fn test_playback_state_swing_advance() {
+    use sine_mml::mml::Swing;
+
+    let mut state = PlaybackState::default();
+    state.update_state(&Command::Swing(Swing {
+        ratio: 75,
+        subdivision: 8,
+    }));
+    assert!((state.advance(0.5) - 0.375).abs() < 1e-9);
+    assert!((state.advance(0.5) - 0.125).abs() < 1e-9);
+}
+
+#[test]
+fn test_calculate_total_duration_ms_loop_with_volta_endings() {
+    use sine_mml::mml::{parse, VoltaEnding};
+    // [C :1 D :2 E2]2 → C D C E2 = 5拍
+    let commands = [
+        parse("T120 L4").unwrap().commands,
+        vec![Command::Loop {
+            commands: parse("C").unwrap().commands,
+            escape_index: None,
+            endings: vec![
+                VoltaEnding {
+                    pass: 1,
+                    commands: parse("D").unwrap().commands,
+                },
+                VoltaEnding {
+                    pass: 2,
+                    commands: parse("E2").unwrap().commands,
+                },
+            ],
+            repeat_count: 2,
+        }],
+    ]
+    .concat();
+    let duration = calculate_total_duration_ms(&commands);
+    assert!(
+        (duration as i64 - 2500).abs() < 100,
+        "Expected ~2500ms, got {duration}ms"
+    );
+}
+
+// ========================================
+// RecordingSinkによる送信メッセージの検証
+// ========================================
+
+mod recording_sink {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use sine_mml::midi::{
+        mml_to_midi_note, play_midi_stream, play_midi_stream_interruptible, play_midi_stream_range,
+        PlayOptions, RecordingSink,
+    };
+    use sine_mml::mml::{parse, Accidental, Pitch};
+
+    const ALL_NOTES_OFF: [u8; 3] = [0xB0, 123, 0];
+
+    fn note(pitch: Pitch) -> u8 {
+        mml_to_midi_note(pitch, Accidental::Natural, 4)
+    }
+
+    #[test]
+    fn sends_note_on_and_off_in_order() {
+        let mml = parse("T240 L16 CDE").unwrap();
+        let mut sink = RecordingSink::new();
+        play_midi_stream(&mut sink, &mml.commands, 1).unwrap();
+
+        let summary: Vec<(u8, u8)> = sink.bytes().iter().map(|m| (m[0], m[1])).collect();
+        assert_eq!(
+            summary,
+            [
+                (0x90, note(Pitch::C)),
+                (0x80, note(Pitch::C)),
+                (0x90, note(Pitch::D)),
+                (0x80, note(Pitch::D)),
+                (0x90, note(Pitch::E)),
+                (0x80, note(Pitch::E)),
+                (0xB0, 123),
+            ]
+        );
+    }
+
+    #[test]
+    fn sends_notes_at_their_timing() {
+        // T240 L8: 1音125ms
+        let mml = parse("T240 L8 CDE").unwrap();
+        let mut sink = RecordingSink::new();
+        play_midi_stream(&mut sink, &mml.commands, 1).unwrap();
+
+        let note_on_times: Vec<Duration> = sink
+            .messages()
+            .iter()
+            .filter(|(_, bytes)| bytes[0] == 0x90)
+            .map(|(at, _)| *at)
+            .collect();
+        assert_eq!(note_on_times.len(), 3);
+        for (at, expected) in note_on_times.iter().zip([0.0, 125.0, 250.0]) {
+            let actual = at.as_secs_f64() * 1000.0;
+            assert!(
+                (actual - expected).abs() < 30.0,
+                "expected ~{expected}ms, got {actual}ms"
+            );
+        }
+    }
+
+    /// `offset_ms`を指定して再生し、Note Onの送信時刻（ミリ秒）を返す
+    fn note_on_times_with_offset(mml: &str, offset_ms: i32) -> Vec<f64> {
+        let mml = parse(mml).unwrap();
+        let range = mml.play_range(None, None, 44100).unwrap();
+        let mut sink = RecordingSink::new();
+        let interrupt = Arc::new(AtomicBool::new(false));
+        let options = PlayOptions {
+            offset_ms,
+            ..PlayOptions::default()
+        };
+        play_midi_stream_range(&mut sink, &mml.commands, 1, &range, &interrupt, options).unwrap();
+        sink.messages()
+            .iter()
+            .filter(|(_, bytes)| bytes[0] == 0x90)
+            .map(|(at, _)| at.as_secs_f64() * 1000.0)
+            .collect()
+    }
+
+    #[test]
+    fn positive_offset_delays_messages() {
+        let times = note_on_times_with_offset("T240 L8 CD", 100);
+        for (actual, expected) in times.iter().zip([100.0, 225.0]) {
+            assert!(
+                (actual - expected).abs() < 30.0,
+                "expected ~{expected}ms, got {actual}ms"
+            );
+        }
+    }
+
+    #[test]
+    fn negative_offset_advances_messages() {
+        // 最初の100ms分は開始時にまとめて送る
+        let times = note_on_times_with_offset("T240 L8 CDE", -100);
+        for (actual, expected) in times.iter().zip([0.0, 25.0, 150.0]) {
+            assert!(
+                (actual - expected).abs() < 30.0,
+                "expected ~{expected}ms, got {actual}ms"
+            );
+        }
+    }
+
+    #[test]
+    fn interrupted_playback_sends_only_cleanup() {
+        let mml = parse("T120 C1 D1").unwrap();
+        let mut sink = RecordingSink::new();
+        let interrupt = Arc::new(AtomicBool::new(true));
+        play_midi_stream_interruptible(&mut sink, &mml.commands, 2, &interrupt).unwrap();
+
+        assert_eq!(sink.bytes(), [vec![0xB1, 123, 0]]);
+    }
+
+    #[test]
+    fn sends_clock_with_start_and_stop() {
+        // T240 C4: 1拍250msで24クロック
+        let mml = parse("T240 C4").unwrap();
+        let range = mml.play_range(None, None, 44100).unwrap();
+        let mut sink = RecordingSink::new();
+        let interrupt = Arc::new(AtomicBool::new(false));
+        let options = PlayOptions {
+            send_clock: true,
+            ..PlayOptions::default()
+        };
+        play_midi_stream_range(&mut sink, &mml.commands, 1, &range, &interrupt, options).unwrap();
+
+        let bytes = sink.bytes();
+        assert_eq!(bytes.first(), Some(&vec![0xFA]));
+        assert_eq!(
+            bytes[bytes.len() - 2..],
+            [vec![0xFC], ALL_NOTES_OFF.to_vec()]
+        );
+        let clocks = bytes.iter().filter(|m| m[..] == [0xF8]).count();
+        assert!((23..=25).contains(&clocks), "got {clocks} clocks");
+    }
+}
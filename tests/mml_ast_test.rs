@@ -1,4 +1,7 @@
-use sine_mml::mml::{Accidental, Command, Duration, Mml, Note, Pitch, Rest, Tempo, TiedDuration};
+use sine_mml::mml::{
+    Accidental, Command, Duration, GateValue, Mml, Note, Pitch, Rest, Tempo, TempoRamp,
+    TiedDuration,
+};
 
 #[test]
 fn pitch_from_char_valid() {
@@ -513,3 +516,115 @@ fn tuplet_command_clone_and_eq() {
     let cloned = tuplet.clone();
     assert_eq!(tuplet, cloned);
 }
+
+#[test]
+#[allow(clippy::float_cmp)]
+fn gate_value_ratio() {
+    assert_eq!(GateValue::Eighths(8).ratio(), 1.0);
+    assert_eq!(GateValue::Eighths(4).ratio(), 0.5);
+    assert_eq!(GateValue::Eighths(1).ratio(), 0.125);
+    assert_eq!(GateValue::Percent(75).ratio(), 0.75);
+    assert_eq!(GateValue::Percent(100).ratio(), 1.0);
+}
+
+#[test]
+#[allow(clippy::float_cmp)]
+fn gate_value_default_is_full_length() {
+    assert_eq!(GateValue::default(), GateValue::Eighths(8));
+    assert_eq!(GateValue::default().ratio(), 1.0);
+}
+
+#[test]
+fn get_tempo_with_tempo_ramp_uses_start_tempo() {
+    let mml = Mml {
+        commands: vec![Command::TempoRamp(TempoRamp {
+            from: Some(90),
+            to: 60,
+            beats: 4,
+        })],
+    };
+    assert_eq!(mml.get_tempo(), 90);
+}
+
+#[test]
+#[allow(clippy::cast_precision_loss)]
+fn get_tempo_events_tempo_ramp_has_curve() {
+    let mml = sine_mml::mml::parse("T120 L4 C T120>60:4 CCCC T90 C").unwrap();
+    let events = mml.get_tempo_events(44100);
+
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[0].sample_position, 0);
+    assert!(events[0].curve.is_none());
+
+    assert_eq!(events[1].sample_position, 22050);
+    assert_eq!(events[1].bpm, 120);
+    let curve = events[1].curve.unwrap();
+    assert!((curve.from - 120.0).abs() < 1e-9);
+    assert!((curve.to - 60.0).abs() < 1e-9);
+    assert!(curve.start_beat.abs() < 1e-9);
+
+    // 120→60を4拍: 60*4/(60-120)*ln(60/120) ≒ 2.7726秒
+    let ramp_samples = events[2].sample_position - events[1].sample_position;
+    let expected = 4.0 * 2.0_f64.ln() * 44100.0;
+    assert!(
+        (ramp_samples as f64 - expected).abs() < 4.0,
+        "{ramp_samples}"
+    );
+    assert_eq!(events[2].bpm, 90);
+    assert!(events[2].curve.is_none());
+}
+
+#[test]
+#[allow(clippy::float_cmp)]
+fn time_signature_beats_per_bar() {
+    use sine_mml::mml::TimeSignature;
+    let sig = |numerator, denominator| TimeSignature {
+        numerator,
+        denominator,
+    };
+    assert_eq!(TimeSignature::default().beats_per_bar(), 4.0);
+    assert_eq!(sig(3, 4).beats_per_bar(), 3.0);
+    assert_eq!(sig(6, 8).beats_per_bar(), 3.0);
+    assert_eq!(sig(2, 2).beats_per_bar(), 4.0);
+}
+
+#[test]
+fn command_beats() {
+    let mml = sine_mml::mml::parse("C4 R8. {CDE}3 {C8DE}3:2 T90").unwrap();
+    let beats: Vec<f64> = mml.commands.iter().map(|c| c.beats(4)).collect();
+    let expected = [1.0, 0.75, 1.0, 0.5 / 3.0 + 4.0 / 3.0, 0.0];
+    for (actual, expected) in beats.iter().zip(expected) {
+        assert!((actual - expected).abs() < 1e-9, "{beats:?}");
+    }
+}
+
+#[test]
+fn get_tempo_events_time_signature_meter() {
+    let mml = sine_mml::mml::parse("@M3/4 T120 C T90 C").unwrap();
+    let events = mml.get_tempo_events(44100);
+
+    assert_eq!(events.len(), 2);
+    let meter = events[0].meter.unwrap();
+    assert_eq!(meter.time_signature.numerator, 3);
+    assert!(meter.beat_in_bar.abs() < 1e-9);
+
+    let meter = events[1].meter.unwrap();
+    assert!((meter.beat_in_bar - 1.0).abs() < 1e-9);
+    assert!(meter.is_downbeat(2.0));
+    assert!(!meter.is_downbeat(1.0));
+}
+
+#[test]
+fn get_tempo_events_without_time_signature_defaults_to_four_four() {
+    let mml = sine_mml::mml::parse("T120 C T90 D").unwrap();
+    let events = mml.get_tempo_events(44100);
+    assert_eq!(events.len(), 2);
+    for event in &events {
+        let meter = event.meter.unwrap();
+        assert_eq!(
+            meter.time_signature,
+            sine_mml::mml::TimeSignature::default()
+        );
+    }
+    assert!((events[1].meter.unwrap().beat_in_bar - 1.0).abs() < 1e-9);
+}
@@ -3,10 +3,14 @@
 //! This module contains integration tests for the MML parser.
 //! Tests were extracted from src/mml/parser.rs for better organization.
 
-use sine_mml::mml::parser::{expand_loop, parse, Parser};
+use sine_mml::mml::parser::{
+    check_bar_lengths, expand_loop, expand_loop_with_endings, loop_pass, parse, parse_with_options,
+    parse_with_warnings, ParseOptions, Parser,
+};
 use sine_mml::mml::{
-    Accidental, Command, Duration, Note, ParseError, Pitch, TiedDuration, Token, Volume,
-    VolumeValue,
+    Accidental, Channel, Command, Duration, Gate, GateValue, MidiControl, Note, ParseError,
+    ParseWarning, Pitch, Swing, TempoRamp, TiedDuration, TimeSignature, Token, Velocity,
+    VelocityValue, VoltaEnding, Volume, VolumeValue,
 };
 
 #[test]
@@ -168,44 +172,49 @@ fn parse_octave_change_with_notes() {
 #[test]
 fn parse_basic_loop_3_times() {
     let mml = parse("[CDEF]3").unwrap();
-    assert_eq!(mml.commands.len(), 12);
+    let commands: Vec<&Command> = mml.iter().collect();
+    assert_eq!(commands.len(), 12);
     for i in 0..3 {
         let base = i * 4;
-        assert!(matches!(&mml.commands[base], Command::Note(n) if n.pitch == Pitch::C));
-        assert!(matches!(&mml.commands[base + 1], Command::Note(n) if n.pitch == Pitch::D));
-        assert!(matches!(&mml.commands[base + 2], Command::Note(n) if n.pitch == Pitch::E));
-        assert!(matches!(&mml.commands[base + 3], Command::Note(n) if n.pitch == Pitch::F));
+        assert!(matches!(&commands[base], Command::Note(n) if n.pitch == Pitch::C));
+        assert!(matches!(&commands[base + 1], Command::Note(n) if n.pitch == Pitch::D));
+        assert!(matches!(&commands[base + 2], Command::Note(n) if n.pitch == Pitch::E));
+        assert!(matches!(&commands[base + 3], Command::Note(n) if n.pitch == Pitch::F));
     }
 }
 
 #[test]
 fn parse_loop_with_escape_point() {
     let mml = parse("[CD:EF]2").unwrap();
-    assert_eq!(mml.commands.len(), 6);
-    assert!(matches!(&mml.commands[0], Command::Note(n) if n.pitch == Pitch::C));
-    assert!(matches!(&mml.commands[1], Command::Note(n) if n.pitch == Pitch::D));
-    assert!(matches!(&mml.commands[2], Command::Note(n) if n.pitch == Pitch::E));
-    assert!(matches!(&mml.commands[3], Command::Note(n) if n.pitch == Pitch::F));
-    assert!(matches!(&mml.commands[4], Command::Note(n) if n.pitch == Pitch::C));
-    assert!(matches!(&mml.commands[5], Command::Note(n) if n.pitch == Pitch::D));
+    let commands: Vec<&Command> = mml.iter().collect();
+    assert_eq!(commands.len(), 6);
+    assert!(matches!(&commands[0], Command::Note(n) if n.pitch == Pitch::C));
+    assert!(matches!(&commands[1], Command::Note(n) if n.pitch == Pitch::D));
+    assert!(matches!(&commands[2], Command::Note(n) if n.pitch == Pitch::E));
+    assert!(matches!(&commands[3], Command::Note(n) if n.pitch == Pitch::F));
+    assert!(matches!(&commands[4], Command::Note(n) if n.pitch == Pitch::C));
+    assert!(matches!(&commands[5], Command::Note(n) if n.pitch == Pitch::D));
 }
 
 #[test]
 fn parse_loop_default_count() {
     let mml = parse("[CDEF]").unwrap();
-    assert_eq!(mml.commands.len(), 4);
+    let commands: Vec<&Command> = mml.iter().collect();
+    assert_eq!(commands.len(), 4);
 }
 
 #[test]
 fn parse_loop_count_1() {
     let mml = parse("[CDEF]1").unwrap();
-    assert_eq!(mml.commands.len(), 4);
+    let commands: Vec<&Command> = mml.iter().collect();
+    assert_eq!(commands.len(), 4);
 }
 
 #[test]
 fn parse_loop_count_99() {
     let mml = parse("[C]99").unwrap();
-    assert_eq!(mml.commands.len(), 99);
+    let commands: Vec<&Command> = mml.iter().collect();
+    assert_eq!(commands.len(), 99);
 }
 
 #[test]
@@ -238,8 +247,9 @@ fn parse_loop_count_100_error() {
 fn parse_nested_loop_allowed() {
     // ネストしたループは許可されるようになった (Issue #93)
     let mml = parse("[[CDEF]2]3").unwrap();
+    let commands: Vec<&Command> = mml.iter().collect();
     // 内側: CDEF × 2 = 8, 外側: 8 × 3 = 24
-    assert_eq!(mml.commands.len(), 24);
+    assert_eq!(commands.len(), 24);
 }
 
 #[test]
@@ -269,47 +279,52 @@ fn parse_multiple_escape_points_error() {
 #[test]
 fn parse_empty_loop() {
     let mml = parse("[]").unwrap();
-    assert_eq!(mml.commands.len(), 0);
+    let commands: Vec<&Command> = mml.iter().collect();
+    assert_eq!(commands.len(), 0);
 }
 
 #[test]
 fn parse_loop_with_rest() {
     let mml = parse("[R4 C4]2").unwrap();
-    assert_eq!(mml.commands.len(), 4);
-    assert!(matches!(mml.commands[0], Command::Rest(_)));
-    assert!(matches!(mml.commands[1], Command::Note(_)));
-    assert!(matches!(mml.commands[2], Command::Rest(_)));
-    assert!(matches!(mml.commands[3], Command::Note(_)));
+    let commands: Vec<&Command> = mml.iter().collect();
+    assert_eq!(commands.len(), 4);
+    assert!(matches!(commands[0], Command::Rest(_)));
+    assert!(matches!(commands[1], Command::Note(_)));
+    assert!(matches!(commands[2], Command::Rest(_)));
+    assert!(matches!(commands[3], Command::Note(_)));
 }
 
 #[test]
 fn parse_loop_with_octave_change() {
     let mml = parse("[>C <C]2").unwrap();
-    assert_eq!(mml.commands.len(), 8);
-    assert!(matches!(mml.commands[0], Command::OctaveUp));
-    assert!(matches!(mml.commands[1], Command::Note(_)));
-    assert!(matches!(mml.commands[2], Command::OctaveDown));
-    assert!(matches!(mml.commands[3], Command::Note(_)));
-    assert!(matches!(mml.commands[4], Command::OctaveUp));
-    assert!(matches!(mml.commands[5], Command::Note(_)));
-    assert!(matches!(mml.commands[6], Command::OctaveDown));
-    assert!(matches!(mml.commands[7], Command::Note(_)));
+    let commands: Vec<&Command> = mml.iter().collect();
+    assert_eq!(commands.len(), 8);
+    assert!(matches!(commands[0], Command::OctaveUp));
+    assert!(matches!(commands[1], Command::Note(_)));
+    assert!(matches!(commands[2], Command::OctaveDown));
+    assert!(matches!(commands[3], Command::Note(_)));
+    assert!(matches!(commands[4], Command::OctaveUp));
+    assert!(matches!(commands[5], Command::Note(_)));
+    assert!(matches!(commands[6], Command::OctaveDown));
+    assert!(matches!(commands[7], Command::Note(_)));
 }
 
 #[test]
 fn parse_multiple_loops() {
     let mml = parse("[CD]2 [EF]2").unwrap();
-    assert_eq!(mml.commands.len(), 8);
+    let commands: Vec<&Command> = mml.iter().collect();
+    assert_eq!(commands.len(), 8);
 }
 
 #[test]
 fn parse_loop_with_tempo_and_volume() {
     let mml = parse("T120 [CD]2 V10").unwrap();
-    assert_eq!(mml.commands.len(), 6);
-    assert!(matches!(mml.commands[0], Command::Tempo(_)));
-    assert!(matches!(mml.commands[1], Command::Note(_)));
-    assert!(matches!(mml.commands[4], Command::Note(_)));
-    assert!(matches!(mml.commands[5], Command::Volume(_)));
+    let commands: Vec<&Command> = mml.iter().collect();
+    assert_eq!(commands.len(), 6);
+    assert!(matches!(commands[0], Command::Tempo(_)));
+    assert!(matches!(commands[1], Command::Note(_)));
+    assert!(matches!(commands[4], Command::Note(_)));
+    assert!(matches!(commands[5], Command::Volume(_)));
 }
 
 #[test]
@@ -326,7 +341,7 @@ fn test_expand_loop_basic() {
             duration: TiedDuration::new(Duration::new(None, 0)),
         }),
     ];
-    let expanded = expand_loop(&commands, None, 3).unwrap();
+    let expanded = expand_loop(&commands, None, 3);
     assert_eq!(expanded.len(), 6);
 }
 
@@ -349,14 +364,14 @@ fn test_expand_loop_with_escape() {
             duration: TiedDuration::new(Duration::new(None, 0)),
         }),
     ];
-    let expanded = expand_loop(&commands, Some(1), 2).unwrap();
+    let expanded = expand_loop(&commands, Some(1), 2);
     assert_eq!(expanded.len(), 4);
 }
 
 #[test]
 fn test_expand_loop_empty() {
     let commands: Vec<Command> = vec![];
-    let expanded = expand_loop(&commands, None, 5).unwrap();
+    let expanded = expand_loop(&commands, None, 5);
     assert_eq!(expanded.len(), 0);
 }
 
@@ -367,7 +382,7 @@ fn test_expand_loop_escape_at_start() {
         accidental: Accidental::Natural,
         duration: TiedDuration::new(Duration::new(None, 0)),
     })];
-    let expanded = expand_loop(&commands, Some(0), 3).unwrap();
+    let expanded = expand_loop(&commands, Some(0), 3);
     assert_eq!(expanded.len(), 2);
 }
 
@@ -513,32 +528,36 @@ fn parse_volume_consecutive_relative() {
 fn parse_loop_nest_2_levels() {
     // 2階層ネスト - 許可
     let mml = parse("[[C]2]2").unwrap();
+    let commands: Vec<&Command> = mml.iter().collect();
     // 展開: C C × 2 = C C C C (4コマンド)
-    assert_eq!(mml.commands.len(), 4);
+    assert_eq!(commands.len(), 4);
 }
 
 #[test]
 fn parse_loop_nest_3_levels() {
     // 3階層ネスト - 許可
     let mml = parse("[[[C]2]2]2").unwrap();
+    let commands: Vec<&Command> = mml.iter().collect();
     // 展開: 2^3 = 8コマンド
-    assert_eq!(mml.commands.len(), 8);
+    assert_eq!(commands.len(), 8);
 }
 
 #[test]
 fn parse_loop_nest_4_levels() {
     // 4階層ネスト - 許可
     let mml = parse("[[[[C]2]2]2]2").unwrap();
+    let commands: Vec<&Command> = mml.iter().collect();
     // 展開: 2^4 = 16コマンド
-    assert_eq!(mml.commands.len(), 16);
+    assert_eq!(commands.len(), 16);
 }
 
 #[test]
 fn parse_loop_nest_5_levels() {
     // 5階層ネスト - 許可（上限）
     let mml = parse("[[[[[C]2]2]2]2]2").unwrap();
+    let commands: Vec<&Command> = mml.iter().collect();
     // 展開: 2^5 = 32コマンド
-    assert_eq!(mml.commands.len(), 32);
+    assert_eq!(commands.len(), 32);
 }
 
 #[test]
@@ -565,57 +584,115 @@ fn parse_loop_nest_7_levels_error() {
 fn parse_loop_nest_with_commands() {
     // 2階層ネストに複数コマンド
     let mml = parse("[CDE[FG]2AB]2").unwrap();
+    let commands: Vec<&Command> = mml.iter().collect();
     // 内側: FG × 2 = FGFG (4)
     // 外側: CDE(3) + FGFG(4) + AB(2) = 9コマンド × 2 = 18
-    assert_eq!(mml.commands.len(), 18);
+    assert_eq!(commands.len(), 18);
 }
 
 #[test]
 fn parse_loop_nest_with_escape_point() {
     // ネスト内での脱出ポイント
     let mml = parse("[[CD:EF]2]2").unwrap();
+    let commands: Vec<&Command> = mml.iter().collect();
     // 内側: CDEF CD (6) × 2 = 12
-    assert_eq!(mml.commands.len(), 12);
+    assert_eq!(commands.len(), 12);
 }
 
-// ======== Loop Expansion Limit Tests (Issue #94) ========
+// ======== Duration Limit Tests ========
 
 #[test]
 fn parse_loop_expansion_within_limit() {
-    // 10,000コマンド以下は許可
-    // [[[C]10]10]99 = 10 × 10 × 99 = 9,900コマンド
-    let mml = parse("[[[C]10]10]99").unwrap();
-    assert_eq!(mml.commands.len(), 9_900);
+    // ループはASTに展開せずに保持し、演奏時に遅延展開する
+    // [[[C]10]10]99 = 10 × 10 × 99 = 9,900コマンド（T240 L8で約1,238秒）
+    let mml = parse("T240 L8 [[[C]10]10]99").unwrap();
+    assert_eq!(mml.commands.len(), 3);
+    assert_eq!(mml.iter().count(), 9_902);
+}
+
+#[test]
+fn parse_long_song_beyond_former_command_cap() {
+    // [[[C]50]50]5 = 12,500コマンド（T240 L16で約781秒）
+    let mml = parse("T240 L16 [[[C]50]50]5").unwrap();
+    assert_eq!(
+        mml.iter().filter(|c| matches!(c, Command::Note(_))).count(),
+        12_500
+    );
 }
 
 #[test]
 fn parse_loop_expansion_too_large() {
-    // 10,000コマンド超過でエラー
-    // [[C]99]99 × 2 = 99 × 99 × 2 = 19,602コマンド
-    let err = parse("[[[C]99]99]2").unwrap_err();
-    assert!(matches!(
-        err,
-        ParseError::LoopExpandedTooLarge {
-            max_commands: 10_000,
-            ..
+    // 演奏時間を持たないコマンドだけのループは演奏時間の上限にかからない
+    // [[[[[V1]99]99]99]99]99 = 99^5 ≒ 95億コマンド
+    let options = ParseOptions {
+        max_duration_secs: None,
+    };
+    for input in [
+        "[[[[[V1]99]99]99]99]99",
+        "[[[[|]99]99]99]99",
+        "[[[<>]99]99]99",
+    ] {
+        for result in [
+            parse(input),
+            parse_with_options(input, &options).map(|(mml, _)| mml),
+        ] {
+            assert!(
+                matches!(
+                    result.unwrap_err(),
+                    ParseError::LoopExpandedTooLarge {
+                        max_commands: 1_000_000,
+                        ..
+                    }
+                ),
+                "{input}"
+            );
         }
-    ));
+    }
+    // 上限以下なら演奏時間を持たないループも許可
+    assert!(parse("[[[V1]99]99]99").is_ok());
 }
 
 #[test]
-fn parse_loop_expansion_barely_exceeds() {
-    // ギリギリ超過
-    // [[C]50]50]5 = 50 × 50 × 5 = 12,500コマンド
-    let err = parse("[[[C]50]50]5").unwrap_err();
+fn parse_duration_too_long() {
+    // [[C]99]99 × 2 = 19,602拍（T120で9,801秒）
+    let err = parse("[[[C]99]99]2").unwrap_err();
+    assert_eq!(err, ParseError::DurationTooLong { max_seconds: 3600 });
+}
+
+#[test]
+fn parse_duration_limit_boundary() {
+    // 7,200拍（T120でちょうど3,600秒）は許可、1拍でも超えるとエラー
+    assert!(parse("[[C]80]90").is_ok());
     assert!(matches!(
-        err,
-        ParseError::LoopExpandedTooLarge {
-            max_commands: 10_000,
-            ..
-        }
+        parse("[[C]80]90 C").unwrap_err(),
+        ParseError::DurationTooLong { .. }
     ));
 }
 
+#[test]
+fn parse_with_options_custom_duration_limit() {
+    let options = ParseOptions {
+        max_duration_secs: Some(10),
+    };
+    // T120の4分音符 = 0.5秒
+    assert!(parse_with_options("[C]20", &options).is_ok());
+    assert_eq!(
+        parse_with_options("[C]21", &options).unwrap_err(),
+        ParseError::DurationTooLong { max_seconds: 10 }
+    );
+    // テンポが速ければ同じ音符数でも許可
+    assert!(parse_with_options("T240 [C]21", &options).is_ok());
+}
+
+#[test]
+fn parse_with_options_without_duration_limit() {
+    let options = ParseOptions {
+        max_duration_secs: None,
+    };
+    let (mml, _) = parse_with_options("[[[C]99]99]99", &options).unwrap();
+    assert_eq!(mml.commands.len(), 1);
+}
+
 // TC-028-U-009: 複数の相対値指定
 #[test]
 fn test_volume_multiple_relative() {
@@ -676,8 +753,9 @@ fn test_loop_depth_tracking() {
     // Total: 6 commands
     let input = "[ [ C ]2 ]2 [ D ]2";
     let mml = parse(input).unwrap();
+    let commands: Vec<&Command> = mml.iter().collect();
 
-    assert_eq!(mml.commands.len(), 6);
+    assert_eq!(commands.len(), 6);
 }
 
 #[test]
@@ -688,8 +766,9 @@ fn test_loop_depth_reset_after_sequence() {
     // Total: 16 commands
     let input = "[[[C]2]2]2 [[[D]2]2]2";
     let mml = parse(input).unwrap();
+    let commands: Vec<&Command> = mml.iter().collect();
 
-    assert_eq!(mml.commands.len(), 16);
+    assert_eq!(commands.len(), 16);
 }
 
 #[test]
@@ -1212,3 +1291,693 @@ fn parse_tuplet_individual_durations() {
         _ => panic!("Expected Tuplet"),
     }
 }
+
+// ゲートタイムテスト
+
+#[test]
+fn parse_gate_eighths() {
+    let mml = parse("Q4 C").unwrap();
+    assert_eq!(
+        mml.commands[0],
+        Command::Gate(Gate {
+            value: GateValue::Eighths(4)
+        })
+    );
+}
+
+#[test]
+fn parse_gate_percent() {
+    let mml = parse("Q%75 C").unwrap();
+    assert_eq!(
+        mml.commands[0],
+        Command::Gate(Gate {
+            value: GateValue::Percent(75)
+        })
+    );
+}
+
+#[test]
+fn parse_gate_out_of_range_error() {
+    for input in ["Q0", "Q9", "Q%0", "Q%101"] {
+        let err = parse(input).unwrap_err();
+        assert!(
+            matches!(err, ParseError::InvalidNumber { .. }),
+            "{input}: expected InvalidNumber, got {err:?}"
+        );
+    }
+}
+
+#[test]
+fn parse_gate_missing_value_error() {
+    let err = parse("Q C").unwrap_err();
+    assert!(matches!(err, ParseError::UnexpectedToken { .. }));
+}
+
+#[test]
+fn parse_velocity_absolute() {
+    let mml = parse("@v100 C").unwrap();
+    assert_eq!(
+        mml.commands[0],
+        Command::Velocity(Velocity {
+            value: VelocityValue::Absolute(100)
+        })
+    );
+}
+
+#[test]
+fn parse_velocity_ramp_with_start() {
+    let mml = parse("@V40>110:8 C").unwrap();
+    assert_eq!(
+        mml.commands[0],
+        Command::Velocity(Velocity {
+            value: VelocityValue::Ramp {
+                from: Some(40),
+                to: 110,
+                beats: 8
+            }
+        })
+    );
+}
+
+#[test]
+fn parse_velocity_ramp_from_current() {
+    let mml = parse("@v>20:4 C").unwrap();
+    assert_eq!(
+        mml.commands[0],
+        Command::Velocity(Velocity {
+            value: VelocityValue::Ramp {
+                from: None,
+                to: 20,
+                beats: 4
+            }
+        })
+    );
+}
+
+#[test]
+fn parse_velocity_followed_by_octave_up() {
+    // `>`の後に数値が続かない場合はオクターブアップとして扱う
+    let mml = parse("@v40>C").unwrap();
+    assert_eq!(mml.commands.len(), 3);
+    assert_eq!(
+        mml.commands[0],
+        Command::Velocity(Velocity {
+            value: VelocityValue::Absolute(40)
+        })
+    );
+    assert_eq!(mml.commands[1], Command::OctaveUp);
+    assert!(matches!(mml.commands[2], Command::Note(_)));
+}
+
+#[test]
+fn parse_velocity_out_of_range_error() {
+    for input in ["@v128", "@v0>128:4", "@v>10:0", "@v>10:129"] {
+        let err = parse(input).unwrap_err();
+        assert!(
+            matches!(err, ParseError::InvalidNumber { .. }),
+            "{input}: expected InvalidNumber, got {err:?}"
+        );
+    }
+}
+
+#[test]
+fn parse_velocity_incomplete_ramp_error() {
+    for input in ["@v>", "@v>20", "@v40>110", "@v40>110:"] {
+        assert!(parse(input).is_err(), "{input}: expected error");
+    }
+}
+
+#[test]
+fn parse_unknown_extended_command_error() {
+    let err = parse("@C").unwrap_err();
+    assert!(matches!(err, ParseError::UnexpectedToken { .. }));
+}
+
+#[test]
+fn parse_tempo_ramp_with_start() {
+    let mml = parse("T120>80:8 C").unwrap();
+    assert_eq!(
+        mml.commands[0],
+        Command::TempoRamp(TempoRamp {
+            from: Some(120),
+            to: 80,
+            beats: 8
+        })
+    );
+}
+
+#[test]
+fn parse_tempo_ramp_from_current() {
+    let mml = parse("t>200:16 C").unwrap();
+    assert_eq!(
+        mml.commands[0],
+        Command::TempoRamp(TempoRamp {
+            from: None,
+            to: 200,
+            beats: 16
+        })
+    );
+}
+
+#[test]
+fn parse_tempo_followed_by_octave_up() {
+    let mml = parse("T120>C").unwrap();
+    assert_eq!(mml.commands.len(), 3);
+    assert_eq!(mml.commands[1], Command::OctaveUp);
+}
+
+#[test]
+fn parse_tempo_ramp_out_of_range_error() {
+    for input in ["T120>301:4", "T20>120:4", "T120>80:0", "T>80:129"] {
+        let err = parse(input).unwrap_err();
+        assert!(
+            matches!(err, ParseError::InvalidNumber { .. }),
+            "{input}: expected InvalidNumber, got {err:?}"
+        );
+    }
+}
+
+#[test]
+fn parse_tempo_ramp_incomplete_error() {
+    for input in ["T>", "T120>80", "T120>80:"] {
+        assert!(parse(input).is_err(), "{input}: expected error");
+    }
+}
+
+#[test]
+fn parse_swing_default_subdivision() {
+    let mml = parse("@S60 C8").unwrap();
+    assert_eq!(
+        mml.commands[0],
+        Command::Swing(Swing {
+            ratio: 60,
+            subdivision: 8
+        })
+    );
+}
+
+#[test]
+fn parse_swing_with_subdivision() {
+    let mml = parse("@s66:16 C16").unwrap();
+    assert_eq!(
+        mml.commands[0],
+        Command::Swing(Swing {
+            ratio: 66,
+            subdivision: 16
+        })
+    );
+}
+
+#[test]
+fn parse_swing_before_loop_escape() {
+    // `:`の直後が数値でなければループの脱出ポイント
+    let mml = parse("[@S60 C:D]2").unwrap();
+    let commands: Vec<&Command> = mml.iter().collect();
+    assert_eq!(commands.len(), 5);
+}
+
+#[test]
+fn parse_swing_out_of_range_error() {
+    for input in ["@S49", "@S76"] {
+        let err = parse(input).unwrap_err();
+        assert!(
+            matches!(err, ParseError::InvalidNumber { .. }),
+            "{input}: expected InvalidNumber, got {err:?}"
+        );
+    }
+}
+
+#[test]
+fn parse_swing_invalid_subdivision_error() {
+    let err = parse("@S60:32").unwrap_err();
+    assert_eq!(
+        err,
+        ParseError::InvalidSwingSubdivision {
+            value: 32,
+            position: 5
+        }
+    );
+}
+
+#[test]
+fn parse_time_signature() {
+    let mml = parse("@M6/8 C").unwrap();
+    assert_eq!(
+        mml.commands[0],
+        Command::TimeSignature(TimeSignature {
+            numerator: 6,
+            denominator: 8
+        })
+    );
+}
+
+#[test]
+fn parse_bar_line() {
+    let mml = parse("CDEF|G").unwrap();
+    assert_eq!(mml.commands.len(), 6);
+    assert_eq!(mml.commands[4], Command::BarLine);
+}
+
+#[test]
+fn parse_time_signature_invalid_denominator_error() {
+    let err = parse("@M3/5").unwrap_err();
+    assert_eq!(
+        err,
+        ParseError::InvalidTimeSignature {
+            denominator: 5,
+            position: 4
+        }
+    );
+}
+
+#[test]
+fn parse_time_signature_errors() {
+    assert!(matches!(
+        parse("@M0/4").unwrap_err(),
+        ParseError::InvalidNumber { .. }
+    ));
+    assert!(matches!(
+        parse("@M33/4").unwrap_err(),
+        ParseError::InvalidNumber { .. }
+    ));
+    assert!(matches!(
+        parse("@M3 C").unwrap_err(),
+        ParseError::UnexpectedToken { .. }
+    ));
+}
+
+#[test]
+fn parse_with_warnings_bar_length_mismatch() {
+    let (_, warnings) = parse_with_warnings("@M3/4 CDE|CDEF|GAB|").unwrap();
+    assert_eq!(
+        warnings,
+        vec![ParseWarning::BarLengthMismatch {
+            bar: 2,
+            expected: 3.0,
+            actual: 4.0
+        }]
+    );
+}
+
+#[test]
+fn parse_with_warnings_matching_bars() {
+    for input in [
+        "CDEF|G2A4.B8|",
+        "@M6/8 L8 CDEFGA|C4.D4.|",
+        "{CDE}3 C2.|",
+        "L8 [CDEFGAB>C<|]4",
+        "@M3/4 C2.|@M2/4 C2|",
+        "CDEF|GAB",
+    ] {
+        let (_, warnings) = parse_with_warnings(input).unwrap();
+        assert!(warnings.is_empty(), "{input}: {warnings:?}");
+    }
+}
+
+#[test]
+fn parse_with_warnings_counts_bars_after_loop_expansion() {
+    let (_, warnings) = parse_with_warnings("[CDEF|]2 CDE|").unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert!(matches!(
+        warnings[0],
+        ParseWarning::BarLengthMismatch { bar: 3, .. }
+    ));
+}
+
+#[test]
+fn parse_with_warnings_reports_looped_bar_once() {
+    let (_, warnings) = parse_with_warnings("[CDE|]16").unwrap();
+    assert_eq!(
+        warnings,
+        vec![ParseWarning::BarLengthMismatch {
+            bar: 1,
+            expected: 4.0,
+            actual: 3.0
+        }]
+    );
+
+    // 展開後に約97万小節になっても、MML上の小節線は1つ
+    let (_, warnings) = parse_with_warnings("[[[|]99]99]99").unwrap();
+    assert_eq!(warnings.len(), 1);
+
+    // 別の小節線はそれぞれ報告する
+    let (_, warnings) = parse_with_warnings("[CDE|CD|]4").unwrap();
+    assert_eq!(warnings.len(), 2);
+}
+
+#[test]
+fn parser_warnings_accessor() {
+    let tokens = sine_mml::mml::tokenize("CDE|").unwrap();
+    let mut parser = Parser::new(tokens);
+    let mml = parser.parse().unwrap();
+    assert_eq!(parser.warnings().len(), 1);
+    assert_eq!(check_bar_lengths(&mml.commands), parser.warnings());
+}
+
+// n番カッコ（ボルタ）テスト
+
+fn pitches<'a>(commands: impl IntoIterator<Item = &'a Command>) -> String {
+    commands
+        .into_iter()
+        .filter_map(|cmd| match cmd {
+            Command::Note(note) => Some(format!("{:?}", note.pitch)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn parse_loop_with_volta_endings() {
+    let mml = parse("[CDE:1FG:2AB]2").unwrap();
+    assert_eq!(pitches(mml.iter()), "CDEFGCDEAB");
+}
+
+#[test]
+fn parse_loop_volta_passes_without_ending_play_body_only() {
+    let mml = parse("[C:1D:3E]3").unwrap();
+    assert_eq!(pitches(mml.iter()), "CDCCE");
+}
+
+#[test]
+fn parse_nested_loop_with_volta_endings() {
+    let mml = parse("[[C:1D:2E]2 F:1G:2A]2").unwrap();
+    assert_eq!(pitches(mml.iter()), "CDCEFGCDCEFA");
+}
+
+#[test]
+fn parse_volta_out_of_range_error() {
+    assert_eq!(
+        parse("[C:1D:3E]2").unwrap_err(),
+        ParseError::VoltaOutOfRange {
+            pass: 3,
+            repeat_count: 2,
+            position: 5
+        }
+    );
+    assert!(matches!(
+        parse("[C:0D]2").unwrap_err(),
+        ParseError::VoltaOutOfRange { pass: 0, .. }
+    ));
+    // 回数省略時は1回
+    assert!(matches!(
+        parse("[C:1D:2E]").unwrap_err(),
+        ParseError::VoltaOutOfRange {
+            pass: 2,
+            repeat_count: 1,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn parse_volta_order_error() {
+    assert_eq!(
+        parse("[C:2D:1E]2").unwrap_err(),
+        ParseError::InvalidVoltaOrder {
+            pass: 1,
+            position: 5
+        }
+    );
+    assert!(matches!(
+        parse("[C:1D:1E]2").unwrap_err(),
+        ParseError::InvalidVoltaOrder { pass: 1, .. }
+    ));
+}
+
+#[test]
+fn parse_mixed_escape_and_volta_error() {
+    assert_eq!(
+        parse("[C:D:1E]2").unwrap_err(),
+        ParseError::MixedEscapeAndVolta { position: 4 }
+    );
+    assert_eq!(
+        parse("[C:1D:E]2").unwrap_err(),
+        ParseError::MixedEscapeAndVolta { position: 5 }
+    );
+}
+
+#[test]
+fn parse_volta_after_tuplet() {
+    // 空白を挟んだ`:1`は連符のベース音長ではなくn番カッコ
+    let mml = parse("[{cde}3 :1 f :2 g]2").unwrap();
+    let Command::Loop {
+        commands, endings, ..
+    } = &mml.commands[0]
+    else {
+        panic!("Expected Loop");
+    };
+    assert!(matches!(
+        commands[0],
+        Command::Tuplet {
+            count: 3,
+            base_duration: None,
+            ..
+        }
+    ));
+    assert_eq!(endings.len(), 2);
+    // 連符は展開しない（1周目: 連符 F、2周目: 連符 G）
+    assert_eq!(pitches(mml.iter()), "FG");
+    assert_eq!(
+        mml.iter()
+            .filter(|cmd| matches!(cmd, Command::Tuplet { .. }))
+            .count(),
+        2
+    );
+    assert!((mml.total_beats() - 4.0).abs() < 1e-9);
+
+    // 空白を挟まなければベース音長
+    let mml = parse("[{cde}3:8 f]2").unwrap();
+    let Command::Loop { commands, .. } = &mml.commands[0] else {
+        panic!("Expected Loop");
+    };
+    assert!(matches!(
+        commands[0],
+        Command::Tuplet {
+            base_duration: Some(8),
+            ..
+        }
+    ));
+    // ループの外では空白を挟んでもベース音長
+    assert!(matches!(
+        parse("{cde}3 :8").unwrap().commands[0],
+        Command::Tuplet {
+            base_duration: Some(8),
+            ..
+        }
+    ));
+}
+
+#[test]
+fn parse_volta_after_swing() {
+    let mml = parse("[c @S60 :1 e :2 f]2").unwrap();
+    let Command::Loop {
+        commands, endings, ..
+    } = &mml.commands[0]
+    else {
+        panic!("Expected Loop");
+    };
+    assert!(matches!(
+        commands[1],
+        Command::Swing(Swing {
+            ratio: 60,
+            subdivision: 8
+        })
+    ));
+    assert_eq!(endings.len(), 2);
+    assert_eq!(pitches(mml.iter()), "CECF");
+
+    let mml = parse("[c @S60:16 e]2").unwrap();
+    let Command::Loop { commands, .. } = &mml.commands[0] else {
+        panic!("Expected Loop");
+    };
+    assert!(matches!(
+        commands[1],
+        Command::Swing(Swing {
+            ratio: 60,
+            subdivision: 16
+        })
+    ));
+}
+
+#[test]
+fn parse_volta_bar_lengths_checked_per_pass() {
+    let (_, warnings) = parse_with_warnings("[CDEF|:1GAB>C|:2<C1|]2").unwrap();
+    assert!(warnings.is_empty(), "{warnings:?}");
+
+    let (_, warnings) = parse_with_warnings("[CDEF|:1GAB|:2C1|]2").unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert!(matches!(
+        warnings[0],
+        ParseWarning::BarLengthMismatch { bar: 2, .. }
+    ));
+}
+
+#[test]
+fn test_loop_pass_selects_ending() {
+    let body = parse("CD").unwrap().commands;
+    let endings = vec![
+        VoltaEnding {
+            pass: 1,
+            commands: parse("E").unwrap().commands,
+        },
+        VoltaEnding {
+            pass: 3,
+            commands: parse("F").unwrap().commands,
+        },
+    ];
+
+    let (first, ending) = loop_pass(&body, None, &endings, 3, 0);
+    assert_eq!(pitches(first), "CD");
+    assert_eq!(pitches(ending), "E");
+
+    let (_, ending) = loop_pass(&body, None, &endings, 3, 1);
+    assert!(ending.is_empty());
+
+    let (_, ending) = loop_pass(&body, None, &endings, 3, 2);
+    assert_eq!(pitches(ending), "F");
+
+    let expanded = expand_loop_with_endings(&body, None, &endings, 3);
+    assert_eq!(pitches(&expanded), "CDECDCDF");
+}
+
+#[test]
+fn parse_marker() {
+    let mml = parse(r#"C @" chorus " D"#).unwrap();
+    assert_eq!(mml.commands.len(), 3);
+    assert_eq!(mml.commands[1], Command::Marker("chorus".to_string()));
+    assert!(mml.commands[1].beats(4).abs() < f64::EPSILON);
+}
+
+#[test]
+fn parse_marker_errors() {
+    assert_eq!(
+        parse(r#"C @"  ""#).unwrap_err(),
+        ParseError::EmptyMarkerName { position: 3 }
+    );
+    assert_eq!(
+        parse(r#"{C @"a" D}2"#).unwrap_err(),
+        ParseError::MarkerInTuplet { position: 4 }
+    );
+    // `@`なしの引用符はコマンドではない
+    assert!(matches!(
+        parse(r#""a" C"#).unwrap_err(),
+        ParseError::UnexpectedToken { .. }
+    ));
+}
+
+#[test]
+fn parse_channel() {
+    let mml = parse("@C10 C @c2 D").unwrap();
+    assert_eq!(mml.commands[0], Command::Channel(Channel { value: 10 }));
+    assert_eq!(mml.commands[2], Command::Channel(Channel { value: 2 }));
+    assert!(mml.commands[0].beats(4).abs() < f64::EPSILON);
+
+    for input in ["@C0", "@C17", "@C"] {
+        assert!(parse(input).is_err(), "{input}: expected error");
+    }
+}
+
+#[test]
+fn parse_midi_control() {
+    let mml = parse("@P5 @CC7:100 @A64 @PB-8192 @pb+8191 @PB0 @B1:2 @B3 C").unwrap();
+    let controls: Vec<_> = mml
+        .commands
+        .iter()
+        .filter_map(|command| match command {
+            Command::MidiControl(control) => Some(*control),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        controls,
+        [
+            MidiControl::ProgramChange(5),
+            MidiControl::ControlChange {
+                controller: 7,
+                value: 100
+            },
+            MidiControl::ChannelPressure(64),
+            MidiControl::PitchBend(-8192),
+            MidiControl::PitchBend(8191),
+            MidiControl::PitchBend(0),
+            MidiControl::BankSelect {
+                msb: 1,
+                lsb: Some(2)
+            },
+            MidiControl::BankSelect { msb: 3, lsb: None },
+        ]
+    );
+    assert!(mml.commands[0].beats(4).abs() < f64::EPSILON);
+    // `@C`はチャンネル、`@CC`はコントロールチェンジ
+    assert_eq!(
+        parse("@C2").unwrap().commands[0],
+        Command::Channel(Channel { value: 2 })
+    );
+}
+
+#[test]
+fn parse_bank_select_before_loop_escape() {
+    // `:`の直後が数値でなければループの脱出ポイント
+    let mml = parse("[@B1 C:D]2").unwrap();
+    let Command::Loop {
+        commands,
+        escape_index,
+        ..
+    } = &mml.commands[0]
+    else {
+        panic!("expected loop");
+    };
+    assert_eq!(
+        commands[0],
+        Command::MidiControl(MidiControl::BankSelect { msb: 1, lsb: None })
+    );
+    assert_eq!(*escape_index, Some(2));
+}
+
+#[test]
+fn parse_bank_select_before_volta() {
+    // 空白を挟んだ`:1`はLSBではなくn番カッコ
+    let mml = parse("[c @B0 :1 e :2 f]2").unwrap();
+    let Command::Loop {
+        commands, endings, ..
+    } = &mml.commands[0]
+    else {
+        panic!("expected loop");
+    };
+    assert_eq!(
+        commands[1],
+        Command::MidiControl(MidiControl::BankSelect { msb: 0, lsb: None })
+    );
+    assert_eq!(endings.len(), 2);
+    assert_eq!(pitches(mml.iter()), "CECF");
+
+    // ループの外でも`@B`と`:`の間に空白は挟めない
+    assert!(parse("@B0 :1").is_err());
+    assert_eq!(
+        parse("[c @B0:1 e]2").unwrap().commands[0],
+        Command::Loop {
+            commands: parse("c @B0:1 e").unwrap().commands,
+            escape_index: None,
+            endings: Vec::new(),
+            repeat_count: 2,
+        }
+    );
+}
+
+#[test]
+fn parse_midi_control_errors() {
+    for input in [
+        "@P128", "@P", "@CC128:0", "@CC7", "@CC7:128", "@A128", "@PB8192", "@PB-8193", "@B128",
+        "@B1:128",
+    ] {
+        assert!(parse(input).is_err(), "{input}: expected error");
+    }
+    assert_eq!(
+        parse("@PB9000").unwrap_err(),
+        ParseError::InvalidNumber {
+            value: 9000,
+            range: (0, 8191),
+            position: 3,
+        }
+    );
+}
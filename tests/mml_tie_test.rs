@@ -442,9 +442,10 @@ fn test_parse_tie_with_other_commands() {
 #[test]
 fn test_parse_tie_inside_loop() {
     let mml = parse("[C4&8 D2]2").unwrap();
+    let commands: Vec<&Command> = mml.iter().collect();
     // Loop expanded: 2 notes × 2 = 4 commands
-    assert_eq!(mml.commands.len(), 4);
-    match &mml.commands[0] {
+    assert_eq!(commands.len(), 4);
+    match &commands[0] {
         Command::Note(n) => {
             assert_eq!(n.duration.tied.len(), 1);
         }
@@ -537,8 +538,9 @@ fn test_regression_dotted_note() {
 #[test]
 fn test_regression_loop_syntax() {
     let mml = parse("[CDEF]2").unwrap();
+    let commands: Vec<&Command> = mml.iter().collect();
     // 4 notes × 2 = 8 commands
-    assert_eq!(mml.commands.len(), 8);
+    assert_eq!(commands.len(), 8);
 }
 
 /// TC-030-R-004: 相対ボリュームの動作確認
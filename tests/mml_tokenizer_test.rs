@@ -2,7 +2,7 @@
 //!
 //! Tests extracted from src/mml/mod.rs for 500-line rule compliance.
 
-use sine_mml::mml::{tokenize, Pitch, Token};
+use sine_mml::mml::{tokenize, ParseError, Pitch, Token};
 
 #[test]
 fn tokenize_simple_note() {
@@ -353,3 +353,71 @@ fn tokenize_nested_tuplet() {
     // 最後がEofであることを確認
     assert_eq!(tokens.last().unwrap().token, Token::Eof);
 }
+
+#[test]
+fn tokenize_gate() {
+    let tokens = tokenize("Q4 q%75").unwrap();
+    assert_eq!(tokens[0].token, Token::Gate);
+    assert_eq!(tokens[1].token, Token::Number(4));
+    assert_eq!(tokens[2].token, Token::Gate);
+    assert_eq!(tokens[3].token, Token::Percent);
+    assert_eq!(tokens[4].token, Token::Number(75));
+    assert_eq!(tokens[5].token, Token::Eof);
+}
+
+#[test]
+fn tokenize_velocity() {
+    let tokens = tokenize("@v40>110:8").unwrap();
+    assert_eq!(tokens[0].token, Token::At);
+    assert_eq!(tokens[1].token, Token::Volume);
+    assert_eq!(tokens[2].token, Token::Number(40));
+    assert_eq!(tokens[3].token, Token::OctaveUp);
+    assert_eq!(tokens[4].token, Token::Number(110));
+}
+
+#[test]
+fn tokenize_swing() {
+    let tokens = tokenize("@s60:16").unwrap();
+    assert_eq!(tokens[0].token, Token::At);
+    assert_eq!(tokens[1].token, Token::Swing);
+    assert_eq!(tokens[2].token, Token::Number(60));
+    assert_eq!(tokens[3].token, Token::LoopEscape);
+    assert_eq!(tokens[4].token, Token::Number(16));
+}
+
+#[test]
+fn tokenize_program() {
+    let tokens = tokenize("@p5 @PB-1").unwrap();
+    assert_eq!(tokens[1].token, Token::Program);
+    assert_eq!(tokens[2].token, Token::Number(5));
+    assert_eq!(tokens[4].token, Token::Program);
+    assert_eq!(tokens[5].token, Token::Pitch(Pitch::B));
+    assert_eq!(tokens[6].token, Token::Flat);
+}
+
+#[test]
+fn tokenize_time_signature_and_bar_line() {
+    let tokens = tokenize("@m3/4 C|").unwrap();
+    assert_eq!(tokens[0].token, Token::At);
+    assert_eq!(tokens[1].token, Token::Meter);
+    assert_eq!(tokens[2].token, Token::Number(3));
+    assert_eq!(tokens[3].token, Token::Slash);
+    assert_eq!(tokens[4].token, Token::Number(4));
+    assert_eq!(tokens[6].token, Token::BarLine);
+}
+
+#[test]
+fn tokenize_marker_name() {
+    let tokens = tokenize(r#"C @"サビ 1" D"#).unwrap();
+    assert_eq!(tokens[1].token, Token::At);
+    assert_eq!(tokens[2].token, Token::MarkerName("サビ 1".to_string()));
+    assert_eq!(tokens[2].position, 3);
+    assert_eq!(tokens[3].token, Token::Pitch(Pitch::D));
+    assert_eq!(tokens[3].position, 10);
+}
+
+#[test]
+fn tokenize_unterminated_marker_error() {
+    let err = tokenize(r#"C @"chorus D"#).unwrap_err();
+    assert_eq!(err, ParseError::UnterminatedMarker { position: 3 });
+}